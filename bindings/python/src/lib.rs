@@ -1,7 +1,6 @@
-use flate2::read::GzDecoder;
 use nlprule::{
     rule::{Example, Rule},
-    rules::{apply_suggestions, Rules},
+    rules::{apply_suggestions, CheckOptions, Rules},
     tokenizer::{finalize, tag::Tagger},
     tokenizer::{Tokenizer, TokenizerOptions},
     types::*,
@@ -9,12 +8,7 @@ use nlprule::{
 use pyo3::prelude::*;
 use pyo3::types::PyString;
 use pyo3::{exceptions::PyValueError, types::PyBytes};
-use std::{
-    fs::{self, File},
-    io::{BufReader, Cursor, Read},
-    path::PathBuf,
-    sync::Arc,
-};
+use std::{collections::HashMap, sync::Arc};
 
 fn serialize_splitter(py: Python, obj: &Option<PyObject>) -> PyResult<Vec<u8>> {
     let bytes: &PyBytes = py
@@ -30,52 +24,6 @@ fn deserialize_splitter(py: Python, bytes: Vec<u8>) -> PyResult<Option<PyObject>
     Ok(obj.extract::<Option<_>>()?)
 }
 
-fn get_resource(code: &str, name: &str) -> PyResult<impl Read> {
-    let version = env!("CARGO_PKG_VERSION");
-    let mut cache_path: Option<PathBuf> = None;
-
-    // try to find a file at which to cache the data
-    if let Some(project_dirs) = directories::ProjectDirs::from("", "", "nlprule") {
-        let cache_dir = project_dirs.cache_dir();
-
-        cache_path = Some(
-            cache_dir.join(version).join(code).join(
-                name.strip_suffix(".gz")
-                    .expect("resource name must have .gz ending."),
-            ),
-        );
-    }
-
-    // if the file can be read, the data is already cached
-    if let Some(path) = &cache_path {
-        if let Ok(bytes) = fs::read(path) {
-            return Ok(Cursor::new(bytes));
-        }
-    }
-
-    // ... otherwise, request the data from the URL ...
-    let bytes = reqwest::blocking::get(&format!(
-        "https://github.com/bminixhofer/nlprule/releases/download/{}/{}_{}",
-        env!("CARGO_PKG_VERSION"),
-        code,
-        name
-    ))
-    .and_then(|x| x.bytes())
-    .map_err(|x| PyValueError::new_err(format!("{}", x)))?;
-
-    let mut gz = GzDecoder::new(&bytes[..]);
-    let mut buffer = Vec::new();
-    gz.read_to_end(&mut buffer).expect("gunzipping failed");
-
-    // ... and then cache the data at the provided file, if one was found
-    if let Some(path) = &cache_path {
-        fs::create_dir_all(path.parent().unwrap())?;
-        fs::write(path, &buffer)?;
-    }
-
-    Ok(Cursor::new(buffer))
-}
-
 fn sentence_guard<F, O>(py: Python, sentence_or_sentences: PyObject, f: F) -> PyResult<PyObject>
 where
     F: Fn(String) -> PyResult<O>,
@@ -285,6 +233,17 @@ impl PyTagger {
     fn get_group_members(&self, lemma: &str) -> Vec<&str> {
         self.tagger.get_group_members(&lemma.to_string())
     }
+
+    /// Get the frequency rank of a word, 1 for the most frequent word.
+    ///
+    /// Arguments:
+    ///     word (str): The word.
+    ///
+    /// Returns:
+    ///     rank (Optional[int]): The rank or None if the tagger has no frequency information for the word.
+    fn frequency(&self, word: &str) -> Option<u32> {
+        self.tagger.frequency(word)
+    }
 }
 
 impl PyTagger {
@@ -374,8 +333,8 @@ impl PyToken {
     }
 
     #[getter]
-    fn chunks(&self) -> Vec<&str> {
-        self.token.chunks.iter().map(|x| x.as_str()).collect()
+    fn chunks(&self) -> Vec<String> {
+        self.token.chunks.iter().map(|x| x.to_string()).collect()
     }
 }
 
@@ -385,6 +344,12 @@ impl PyToken {
 /// * text (List[str]): A list of suggested replacements.
 /// * source (str): The ID of the rule that triggered this suggestion.
 /// * message (str): A human-readable message for this suggestion.
+/// * safe (bool): Whether this suggestion can be applied without review.
+/// * confidence (Optional[float]): How likely this suggestion is right, if the rules have precision statistics.
+/// * merged_sources (List[str]): The IDs of other rules which made the same suggestion, if duplicates are merged.
+/// * short_message (str): A compact version of the message for inline hints.
+/// * matched (str): The text between `start` and `end`.
+/// * severity (str): One of "error", "warning", "info" and "hint", see `Rules.set_severities`.
 #[pyclass(name = "Suggestion", module = "nlprule")]
 struct PySuggestion {
     suggestion: Suggestion,
@@ -420,6 +385,40 @@ impl PySuggestion {
     fn message(&self) -> &str {
         &self.suggestion.message
     }
+
+    #[getter]
+    fn safe(&self) -> bool {
+        self.suggestion.safety == FixSafety::Safe
+    }
+
+    #[getter]
+    fn confidence(&self) -> Option<f32> {
+        self.suggestion.confidence
+    }
+
+    #[getter]
+    fn short_message(&self) -> &str {
+        &self.suggestion.short_message
+    }
+
+    #[getter]
+    fn matched(&self) -> &str {
+        &self.suggestion.matched
+    }
+
+    #[getter]
+    fn severity(&self) -> &str {
+        self.suggestion.severity.as_str()
+    }
+
+    #[getter]
+    fn merged_sources(&self) -> Vec<&str> {
+        self.suggestion
+            .merged_sources
+            .iter()
+            .map(|x| x.as_str())
+            .collect()
+    }
 }
 
 impl From<Suggestion> for PySuggestion {
@@ -459,10 +458,8 @@ impl PyTokenizer {
     #[text_signature = "(code, sentence_splitter=None)"]
     #[staticmethod]
     fn load(code: &str, sentence_splitter: Option<PyObject>) -> PyResult<Self> {
-        let bytes = get_resource(code, "tokenizer.bin.gz")?;
-
-        let tokenizer: Tokenizer = bincode::deserialize_from(bytes)
-            .map_err(|x| PyValueError::new_err(format!("{}", x)))?;
+        let tokenizer =
+            Tokenizer::from_lang(code).map_err(|x| PyValueError::new_err(format!("{}", x)))?;
         Ok(PyTokenizer {
             tokenizer,
             sentence_splitter,
@@ -472,8 +469,7 @@ impl PyTokenizer {
     #[new]
     fn new(path: Option<&str>, sentence_splitter: Option<PyObject>) -> PyResult<Self> {
         let tokenizer = if let Some(path) = path {
-            let reader = BufReader::new(File::open(path).unwrap());
-            bincode::deserialize_from(reader).unwrap()
+            Tokenizer::new(path).map_err(|x| PyValueError::new_err(format!("{}", x)))?
         } else {
             Tokenizer::default()
         };
@@ -628,6 +624,8 @@ impl PyExample {
 /// * category_id (str): ID of the category this rule is in.
 /// * category_name (str): A human-readable name of the category this rule is in.
 /// * category_type (Option[str]): The type of the category this rule is in e. g. "style" or "grammar".
+/// * group_id (Option[str]): ID of the rule group this rule is in.
+/// * source (Option[Tuple[str, int]]): The XML file and the line the rule is defined at.
 #[pyclass(name = "Rule", module = "nlprule")]
 struct PyRule {
     id: String,
@@ -638,6 +636,8 @@ struct PyRule {
     category_id: String,
     category_name: String,
     category_type: Option<String>,
+    group_id: Option<String>,
+    source: Option<(String, usize)>,
 }
 
 impl PyRule {
@@ -655,6 +655,8 @@ impl PyRule {
             category_id: rule.category_id().to_owned(),
             category_name: rule.category_name().to_owned(),
             category_type: rule.category_type().map(String::from),
+            group_id: rule.group().map(|x| x.id().to_owned()),
+            source: rule.source().map(|x| (x.file().to_owned(), x.line())),
         })
     }
 }
@@ -700,6 +702,18 @@ impl PyRule {
     fn category_type(&self) -> Option<&str> {
         self.category_type.as_deref()
     }
+
+    #[getter]
+    fn group_id(&self) -> Option<&str> {
+        self.group_id.as_deref()
+    }
+
+    #[getter]
+    fn source(&self) -> Option<(&str, usize)> {
+        self.source
+            .as_ref()
+            .map(|(file, line)| (file.as_str(), *line))
+    }
 }
 
 /// The grammatical rules.
@@ -727,13 +741,14 @@ impl PyRules {
     #[text_signature = "(code, tokenizer, sentence_splitter=None)"]
     #[staticmethod]
     fn load(
+        py: Python,
         code: &str,
         tokenizer: Py<PyTokenizer>,
         sentence_splitter: Option<PyObject>,
     ) -> PyResult<Self> {
-        let bytes = get_resource(code, "rules.bin.gz")?;
-
-        let rules: Rules = bincode::deserialize_from(bytes)
+        let rules = Rules::from_lang(code).map_err(|x| PyValueError::new_err(format!("{}", x)))?;
+        rules
+            .check_compatibility(tokenizer.borrow(py).tokenizer())
             .map_err(|x| PyValueError::new_err(format!("{}", x)))?;
         Ok(PyRules {
             rules,
@@ -750,12 +765,14 @@ impl PyRules {
         sentence_splitter: Option<PyObject>,
     ) -> PyResult<Self> {
         let rules = if let Some(path) = path {
-            let reader = BufReader::new(File::open(path).unwrap());
-            bincode::deserialize_from(reader).unwrap()
+            Rules::new(path).map_err(|x| PyValueError::new_err(format!("{}", x)))?
         } else {
             Rules::default()
         };
         let tokenizer = if let Some(tokenizer) = tokenizer {
+            rules
+                .check_compatibility(tokenizer.borrow(py).tokenizer())
+                .map_err(|x| PyValueError::new_err(format!("{}", x)))?;
             tokenizer
         } else {
             Py::new(py, PyTokenizer::default())?
@@ -786,6 +803,30 @@ impl PyRules {
         }
     }
 
+    /// Sets the severity of suggestions by the ID of their rule, rule group or category.
+    /// The most specific ID wins, suggestions without one are warnings.
+    ///
+    /// Arguments:
+    ///     severities (Dict[str, str]): The severity ("error", "warning", "info" or "hint") by ID.
+    #[text_signature = "(severities)"]
+    fn set_severities(&mut self, severities: HashMap<String, String>) -> PyResult<()> {
+        let severities = severities
+            .into_iter()
+            .map(|(id, severity)| {
+                severity
+                    .parse()
+                    .map(|severity| (id, severity))
+                    .map_err(|x| PyValueError::new_err(format!("{}", x)))
+            })
+            .collect::<PyResult<_>>()?;
+
+        self.rules.set_check_options(CheckOptions {
+            severities,
+            ..self.rules.check_options().clone()
+        });
+        Ok(())
+    }
+
     /// Get suggestions for the given sentence.
     ///
     /// Arguments:
@@ -843,6 +884,43 @@ impl PyRules {
         )
     }
 
+    /// Get suggestions for many texts at once. Each text is checked as a whole like in `suggest_sentence`.
+    /// The GIL is released while the texts are checked, so other Python threads can run meanwhile.
+    ///
+    /// Arguments:
+    ///     texts (List[str]): The texts to get suggestions for.
+    ///
+    /// Returns:
+    ///     suggestions (List[Tuple[int, Suggestion]]):
+    ///         The index of the text and a suggestion for it, for all texts ordered by text.
+    #[text_signature = "(texts)"]
+    fn suggest_batch(
+        &self,
+        py: Python,
+        texts: Vec<&str>,
+    ) -> PyResult<Vec<(usize, Py<PySuggestion>)>> {
+        let mut buffer = String::with_capacity(texts.iter().map(|x| x.len()).sum());
+        let mut offsets = Vec::with_capacity(texts.len() + 1);
+        offsets.push(0);
+        for text in texts {
+            buffer.push_str(text);
+            offsets.push(buffer.len());
+        }
+
+        let tokenizer = self.tokenizer.borrow(py);
+        let tokenizer = tokenizer.tokenizer();
+        let rules = &self.rules;
+
+        let suggestions = py
+            .allow_threads(|| rules.suggest_batch(&buffer, &offsets, tokenizer))
+            .map_err(|x| PyValueError::new_err(format!("{}", x)))?;
+
+        suggestions
+            .into_iter()
+            .map(|(i, x)| Ok((i, Py::new(py, PySuggestion::from(x))?)))
+            .collect()
+    }
+
     /// Correct the given sentence.
     ///
     /// Arguments:
@@ -909,6 +987,17 @@ impl PyRules {
                     replacements: x.replacements().iter().map(|x| x.to_string()).collect(),
                     start: x.start(),
                     end: x.end(),
+                    safety: if x.safe() {
+                        FixSafety::Safe
+                    } else {
+                        FixSafety::NeedsReview
+                    },
+                    confidence: x.confidence(),
+                    merged_sources: x.suggestion.merged_sources.clone(),
+                    short_message: x.suggestion.short_message.clone(),
+                    matched: x.suggestion.matched.clone(),
+                    token_span: x.suggestion.token_span,
+                    severity: x.suggestion.severity,
                 }
             })
             .collect();
@@ -51,6 +51,21 @@ def test_suggest(tokenizer_and_rules):
     )
 
 
+def test_suggest_batch(tokenizer_and_rules):
+    (tokenizer, rules) = tokenizer_and_rules
+
+    texts = ["She was not been here.", "This is fine.", "I can due his homework."]
+
+    suggestions = rules.suggest_batch(texts)
+    assert [i for (i, _) in suggestions] == [0, 2]
+
+    (i, suggestion) = suggestions[1]
+    assert (suggestion.start, suggestion.end) == (6, 9)
+    assert suggestion.replacements == ["do"]
+
+    assert rules.suggest_batch([]) == []
+
+
 def test_rules_inspectable(tokenizer_and_rules):
     (tokenizer, rules) = tokenizer_and_rules
 
@@ -0,0 +1,110 @@
+//! Benchmarks for the individual stages of checking a text.
+//! Expects the compiled binaries in `../storage/{lang}_tokenizer.bin` and `../storage/{lang}_rules.bin` (see BUILD.md),
+//! languages without binaries are skipped.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use nlprule::{rules::CheckOptions, tokenizer::finalize, Rules, Tokenizer};
+use std::path::Path;
+
+const LANGS: &[(&str, &str)] = &[
+    ("en", include_str!("data/en.txt")),
+    ("de", include_str!("data/de.txt")),
+];
+
+const LONG_DOCUMENT_REPEATS: usize = 50;
+
+fn load(lang: &str) -> Option<(Tokenizer, Rules)> {
+    let tokenizer_path = format!("../storage/{}_tokenizer.bin", lang);
+    let rules_path = format!("../storage/{}_rules.bin", lang);
+
+    if !Path::new(&tokenizer_path).exists() || !Path::new(&rules_path).exists() {
+        eprintln!("skipping {}: binaries not found in ../storage", lang);
+        return None;
+    }
+
+    Some((
+        Tokenizer::new(tokenizer_path).unwrap(),
+        Rules::new(rules_path).unwrap(),
+    ))
+}
+
+fn bench_check(c: &mut Criterion) {
+    for (lang, corpus) in LANGS {
+        let (tokenizer, mut rules) = match load(lang) {
+            Some(x) => x,
+            None => continue,
+        };
+        let sentences: Vec<_> = corpus.lines().filter(|x| !x.is_empty()).collect();
+
+        let mut group = c.benchmark_group(*lang);
+        group.throughput(Throughput::Bytes(corpus.len() as u64));
+
+        group.bench_function(BenchmarkId::new("tokenize", sentences.len()), |b| {
+            b.iter(|| {
+                for sentence in &sentences {
+                    black_box(tokenizer.tokenize(sentence));
+                }
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("disambiguate", sentences.len()), |b| {
+            b.iter(|| {
+                for sentence in &sentences {
+                    black_box(tokenizer.disambiguate(tokenizer.tokenize(sentence)));
+                }
+            })
+        });
+
+        let tokens: Vec<_> = sentences
+            .iter()
+            .map(|sentence| finalize(tokenizer.disambiguate(tokenizer.tokenize(sentence))))
+            .collect();
+
+        group.bench_function(BenchmarkId::new("apply", sentences.len()), |b| {
+            b.iter(|| {
+                for sentence_tokens in &tokens {
+                    black_box(rules.apply(sentence_tokens, &tokenizer));
+                }
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("suggest", sentences.len()), |b| {
+            b.iter(|| {
+                for sentence in &sentences {
+                    black_box(rules.suggest(sentence, &tokenizer));
+                }
+            })
+        });
+
+        // the same with regex results shared between rules, see `CheckOptions::memoize_matches`
+        rules.set_check_options(CheckOptions {
+            memoize_matches: true,
+            ..CheckOptions::default()
+        });
+        group.bench_function(BenchmarkId::new("suggest_memoized", sentences.len()), |b| {
+            b.iter(|| {
+                for sentence in &sentences {
+                    black_box(rules.suggest(sentence, &tokenizer));
+                }
+            })
+        });
+
+        group.finish();
+
+        // one long document instead of many sentences, where the tokens of the whole text are alive at once
+        let document = corpus.repeat(LONG_DOCUMENT_REPEATS);
+        let mut group = c.benchmark_group(format!("{}_long", lang));
+        group.throughput(Throughput::Bytes(document.len() as u64));
+        group.sample_size(10);
+
+        group.bench_function(
+            BenchmarkId::new("disambiguate", LONG_DOCUMENT_REPEATS),
+            |b| b.iter(|| black_box(tokenizer.disambiguate(tokenizer.tokenize(&document)))),
+        );
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_check);
+criterion_main!(benches);
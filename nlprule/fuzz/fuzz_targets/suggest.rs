@@ -0,0 +1,30 @@
+#![no_main]
+
+use lazy_static::lazy_static;
+use libfuzzer_sys::fuzz_target;
+use nlprule::{Rules, Tokenizer};
+
+lazy_static! {
+    static ref TOKENIZER: Tokenizer = Tokenizer::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../storage/en_tokenizer.bin"
+    ))
+    .expect("fuzzing needs the compiled binaries in storage/");
+    static ref RULES: Rules = Rules::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../storage/en_rules.bin"
+    ))
+    .expect("fuzzing needs the compiled binaries in storage/");
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let char_count = text.chars().count();
+
+        for suggestion in RULES.suggest(text, &TOKENIZER) {
+            assert!(suggestion.start <= suggestion.end && suggestion.end <= char_count);
+            assert!(!suggestion.replacements.is_empty());
+        }
+        RULES.correct(text, &TOKENIZER);
+    }
+});
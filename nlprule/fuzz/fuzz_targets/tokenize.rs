@@ -0,0 +1,19 @@
+#![no_main]
+
+use lazy_static::lazy_static;
+use libfuzzer_sys::fuzz_target;
+use nlprule::{tokenizer::finalize, Tokenizer};
+
+lazy_static! {
+    static ref TOKENIZER: Tokenizer = Tokenizer::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../storage/en_tokenizer.bin"
+    ))
+    .expect("fuzzing needs the compiled binaries in storage/");
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        finalize(TOKENIZER.disambiguate(TOKENIZER.tokenize(text)));
+    }
+});
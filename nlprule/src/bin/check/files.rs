@@ -0,0 +1,281 @@
+//! Finds the files to check. Directories are walked recursively and their files are kept if they match an include
+//! glob and no exclude glob and are not ignored by a [IGNORE_FILE] in their directory or above. Hidden files and
+//! directories are skipped.
+//!
+//! Globs follow `.gitignore`: `*` and `?` do not match `/`, `**` matches any number of directories and `[a-z]`
+//! matches one char of a class. A glob with a `/` before its end is relative to the walked directory (or the one
+//! of the ignore file), other globs match the name at any depth. A glob ending with `/` only matches directories.
+//! In ignore files, a glob starting with `!` re-includes what earlier globs ignored and lines starting with `#`
+//! are comments.
+
+use regex::Regex;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The name of the file listing globs of paths which are not checked.
+pub const IGNORE_FILE: &str = ".nlpruleignore";
+
+/// The globs of the files which are checked if no include glob is given.
+pub const DEFAULT_INCLUDES: &[&str] = &["*.txt", "*.md", "*.markdown"];
+
+pub struct Glob {
+    regex: Regex,
+    directory_only: bool,
+    negated: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let (directory_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+        let mut regex = String::from("^");
+        if !anchored {
+            regex.push_str("(?:.*/)?");
+        }
+
+        let chars: Vec<_> = pattern.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    if chars.get(i + 2) == Some(&'/') {
+                        regex.push_str("(?:.*/)?");
+                        i += 1;
+                    } else {
+                        regex.push_str(".*");
+                    }
+                    i += 1;
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                '[' => match chars[i + 1..].iter().position(|x| *x == ']') {
+                    Some(length) => {
+                        let mut class: String = chars[i + 1..i + 1 + length].iter().collect();
+                        if let Some(rest) = class.strip_prefix('!') {
+                            class = format!("^{}", rest);
+                        }
+                        regex.push('[');
+                        regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                        regex.push(']');
+                        i += length + 1;
+                    }
+                    None => regex.push_str("\\["),
+                },
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+            i += 1;
+        }
+        regex.push('$');
+
+        Ok(Glob {
+            regex: Regex::new(&regex)?,
+            directory_only,
+            negated,
+        })
+    }
+
+    /// Whether the glob matches the path, which is relative and separated by `/`.
+    pub fn is_match(&self, path: &str, is_dir: bool) -> bool {
+        (is_dir || !self.directory_only) && self.regex.is_match(path)
+    }
+}
+
+// the globs of an ignore file and the relative path of its directory, empty or ending with `/`
+struct IgnoreFile {
+    base: String,
+    globs: Vec<Glob>,
+}
+
+impl IgnoreFile {
+    fn read(path: &Path, base: String) -> io::Result<Option<Self>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        let mut globs = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            globs.push(Glob::new(line).map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: invalid glob: {}", path.display(), i + 1, error),
+                )
+            })?);
+        }
+
+        Ok(Some(IgnoreFile { base, globs }))
+    }
+}
+
+pub struct Walker {
+    pub includes: Vec<Glob>,
+    pub excludes: Vec<Glob>,
+}
+
+impl Walker {
+    /// The files to check for a path given on the command line. A file is always checked, a directory is walked.
+    pub fn files(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        if path.is_dir() {
+            self.walk(path, "", &mut Vec::new(), &mut files)?;
+        } else {
+            files.push(path.to_path_buf());
+        }
+        Ok(files)
+    }
+
+    fn is_ignored(ignore_files: &[IgnoreFile], relative: &str, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for ignore_file in ignore_files {
+            if let Some(path) = relative.strip_prefix(ignore_file.base.as_str()) {
+                for glob in &ignore_file.globs {
+                    if glob.is_match(path, is_dir) {
+                        ignored = !glob.negated;
+                    }
+                }
+            }
+        }
+        ignored
+    }
+
+    fn walk(
+        &self,
+        dir: &Path,
+        relative: &str,
+        ignore_files: &mut Vec<IgnoreFile>,
+        files: &mut Vec<PathBuf>,
+    ) -> io::Result<()> {
+        let ignore_file = IgnoreFile::read(&dir.join(IGNORE_FILE), relative.to_string())?;
+        let has_ignore_file = ignore_file.is_some();
+        ignore_files.extend(ignore_file);
+
+        let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|x| x.file_name());
+
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let path = entry.path();
+            // symlinked directories are not followed, so the walk always ends
+            let is_dir = entry.file_type()?.is_dir();
+            let relative = format!("{}{}", relative, name);
+
+            if self.excludes.iter().any(|x| x.is_match(&relative, is_dir))
+                || Self::is_ignored(ignore_files, &relative, is_dir)
+            {
+                continue;
+            }
+
+            if is_dir {
+                self.walk(&path, &format!("{}/", relative), ignore_files, files)?;
+            } else if path.is_file() && self.includes.iter().any(|x| x.is_match(&relative, false)) {
+                files.push(path);
+            }
+        }
+
+        if has_ignore_file {
+            ignore_files.pop();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_globs() {
+        let matches = |pattern: &str, path: &str| Glob::new(pattern).unwrap().is_match(path, false);
+
+        assert!(matches("*.md", "README.md"));
+        assert!(matches("*.md", "docs/guide/intro.md"));
+        assert!(!matches("*.md", "README.mdx"));
+        assert!(matches("docs/*.md", "docs/intro.md"));
+        assert!(!matches("docs/*.md", "docs/guide/intro.md"));
+        assert!(!matches("docs/*.md", "src/docs/intro.md"));
+        assert!(matches("/docs/**/*.md", "docs/intro.md"));
+        assert!(matches("docs/**/*.md", "docs/guide/intro.md"));
+        assert!(matches("docs/**", "docs/guide/intro.md"));
+        assert!(matches("CHANGELOG.?d", "CHANGELOG.md"));
+        assert!(matches("[A-Z]*.txt", "LICENSE.txt"));
+        assert!(!matches("[!A-Z]*.txt", "LICENSE.txt"));
+        assert!(matches("a+b(1).txt", "a+b(1).txt"));
+
+        let directory = Glob::new("target/").unwrap();
+        assert!(directory.is_match("target", true));
+        assert!(!directory.is_match("target", false));
+    }
+
+    #[test]
+    fn walks_directories() {
+        let root = std::env::temp_dir().join(format!("nlprule-check-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for path in &[
+            "README.md",
+            "notes.txt",
+            "main.rs",
+            ".hidden/a.md",
+            "docs/intro.md",
+            "docs/draft.md",
+            "docs/generated/api.md",
+            "docs/generated/index.md",
+            "vendor/lib.md",
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        fs::write(root.join(IGNORE_FILE), "# not ours\nvendor/\n").unwrap();
+        fs::write(
+            root.join("docs").join(IGNORE_FILE),
+            "draft.md\ngenerated/*\n!generated/index.md\n",
+        )
+        .unwrap();
+
+        let walker = Walker {
+            includes: DEFAULT_INCLUDES
+                .iter()
+                .map(|x| Glob::new(x).unwrap())
+                .collect(),
+            excludes: vec![Glob::new("notes.txt").unwrap()],
+        };
+        let files: Vec<_> = walker
+            .files(&root)
+            .unwrap()
+            .into_iter()
+            .map(|x| {
+                x.strip_prefix(&root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            files,
+            vec!["README.md", "docs/generated/index.md", "docs/intro.md"]
+        );
+    }
+}
@@ -0,0 +1,401 @@
+//! Checks text files and optionally fixes them in place.
+//!
+//! Directories are walked for text and markdown files, see the [files] module for the globs of `--include` and
+//! `--exclude` and for `.nlpruleignore` files. Each file is checked with the nearest `nlprule.toml` in its
+//! directory or above, see [nlprule::config]. The tokenizer and rules given on the command line override it.
+//! The suggestions are cached in `.nlprule-cache` by the content of the file, the tokenizer, the rules and the
+//! config, see [nlprule::rules::persistent_cache], so repeated runs only check changed files. With `--watch` the
+//! files are checked again whenever they change, see the [watch] module.
+//!
+//! Prints one line per suggestion: `path:line:column: severity: message [source]`. With `--fix` the safe suggestions
+//! are applied, with `--interactive` every suggestion is shown and only applied if confirmed, like `git add -p`.
+//! Both can be combined to apply the safe suggestions and confirm the others. A changed file keeps its original
+//! content in a backup next to it unless `--no-backup` is given.
+//!
+//! Exits with 0 if there are no findings, with 1 if there are and with 2 on errors. With `--max-warnings` only
+//! errors and more warnings than allowed are findings which fail, see the [summary] module, which also describes
+//! the JSON summary written with `--summary`.
+
+use clap::Clap;
+use files::{Glob, Walker, DEFAULT_INCLUDES};
+use nlprule::{
+    config::Config,
+    rules::{apply_suggestions, parse_severities, persistent_cache::FileCache, Rules},
+    tokenizer::Tokenizer,
+    types::{FixSafety, Suggestion},
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process,
+};
+use summary::{Summary, EXIT_ERROR};
+
+mod files;
+mod summary;
+mod watch;
+
+#[derive(Clap)]
+#[clap(
+    version = env!("CARGO_PKG_VERSION"),
+    author = "Benjamin Minixhofer <bminixhofer@gmail.com>"
+)]
+struct Opts {
+    /// The files and directories to check.
+    #[clap(required = true)]
+    paths: Vec<String>,
+    /// The tokenizer binary, overrides the one of the config.
+    #[clap(long, short)]
+    tokenizer: Option<String>,
+    /// The rules binary, overrides the one of the config.
+    #[clap(long, short)]
+    rules: Option<String>,
+    /// The config for all files. By default each file uses the nearest `nlprule.toml` in its directory or above.
+    #[clap(long, short)]
+    config: Option<String>,
+    /// Checks the files again whenever they change and shows a summary of all suggestions, until interrupted.
+    #[clap(long, conflicts_with_all = &["fix", "interactive"])]
+    watch: bool,
+    /// Applies the safe suggestions and writes the files in place.
+    #[clap(long)]
+    fix: bool,
+    /// Asks for each suggestion whether to apply it and writes the files in place.
+    #[clap(long, short)]
+    interactive: bool,
+    /// The suffix of the backup of a changed file.
+    #[clap(long, default_value = ".orig")]
+    backup_suffix: String,
+    /// Changes files without keeping a backup.
+    #[clap(long)]
+    no_backup: bool,
+    /// A file mapping rule, rule group or category IDs to severities, one `ID severity` per line. Overrides the
+    /// severities of the config.
+    #[clap(long)]
+    severities: Option<String>,
+    /// The directory of the cache of suggestions. Only files whose content, tokenizer, rules or config changed
+    /// since they were last checked are checked again.
+    #[clap(long, default_value = ".nlprule-cache")]
+    cache_dir: String,
+    /// Checks all files without reading or writing the cache.
+    #[clap(long)]
+    no_cache: bool,
+    /// Fails only if there are errors or more warnings than this, infos and hints never fail. Without a limit any
+    /// finding fails.
+    #[clap(long)]
+    max_warnings: Option<usize>,
+    /// Writes the counts of the findings per severity, category and rule as JSON to this file, `-` for stdout.
+    #[clap(long)]
+    summary: Option<String>,
+    /// A glob of the files to check in directories, can be given multiple times. Text and markdown files by default.
+    #[clap(long, number_of_values = 1)]
+    include: Vec<String>,
+    /// A glob of the files and directories to skip in directories, can be given multiple times.
+    #[clap(long, number_of_values = 1)]
+    exclude: Vec<String>,
+}
+
+// what to do with the suggestions of a file
+#[derive(Clone, Copy, PartialEq)]
+enum Answer {
+    // applies the replacement with this index
+    Apply(usize),
+    Skip,
+    // applies the first replacement of this and all later suggestions in the file
+    ApplyRest,
+    SkipRest,
+    Quit,
+}
+
+const HELP: &str = "y - apply the first replacement
+1-9 - apply the replacement with this number
+n - do not apply this suggestion
+a - apply this and all later suggestions in the file
+d - do not apply this or any later suggestion in the file
+q - quit, the suggestions applied so far are written
+? - print help";
+
+// the 1-based line and column of the char index
+fn line_column(text: &str, index: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for c in text.chars().take(index) {
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+// the lines containing the suggestion
+fn context(text: &str, suggestion: &Suggestion) -> String {
+    let chars: Vec<_> = text.chars().collect();
+    let start = chars[..suggestion.start]
+        .iter()
+        .rposition(|x| *x == '\n')
+        .map_or(0, |x| x + 1);
+    let end = chars[suggestion.end..]
+        .iter()
+        .position(|x| *x == '\n')
+        .map_or(chars.len(), |x| suggestion.end + x);
+    chars[start..end].iter().collect()
+}
+
+fn ask(
+    input: &mut impl BufRead,
+    path: &str,
+    text: &str,
+    suggestion: &Suggestion,
+) -> io::Result<Answer> {
+    let (line, column) = line_column(text, suggestion.start);
+    println!("{}:{}:{}:", path, line, column);
+    println!("{}", context(text, suggestion));
+    println!(
+        "  {} -> {}",
+        suggestion.matched,
+        suggestion.replacements.join(" | ")
+    );
+    println!("{} [{}]", suggestion.message, suggestion.source);
+
+    let n = suggestion.replacements.len().min(9);
+    loop {
+        if n > 1 {
+            print!("Apply [y,n,a,d,q,1-{},?]? ", n);
+        } else {
+            print!("Apply [y,n,a,d,q,?]? ");
+        }
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(Answer::Quit);
+        }
+
+        match answer.trim() {
+            "y" => return Ok(Answer::Apply(0)),
+            "n" => return Ok(Answer::Skip),
+            "a" => return Ok(Answer::ApplyRest),
+            "d" => return Ok(Answer::SkipRest),
+            "q" => return Ok(Answer::Quit),
+            answer => match answer.parse::<usize>() {
+                Ok(i) if (1..=n).contains(&i) => return Ok(Answer::Apply(i - 1)),
+                _ => println!("{}", HELP),
+            },
+        }
+    }
+}
+
+// the suggestion with the replacement at `index` moved to the front, so it is the one which is applied
+fn choose(suggestion: &Suggestion, index: usize) -> Suggestion {
+    let mut suggestion = suggestion.clone();
+    let replacement = suggestion.replacements.remove(index);
+    suggestion.replacements.insert(0, replacement);
+    suggestion
+}
+
+// the tokenizer and rules for the files covered by a config
+fn load(opts: &Opts, mut config: Config) -> Result<(Tokenizer, Rules), Box<dyn Error>> {
+    if let Some(path) = &opts.tokenizer {
+        config.tokenizer = Some(path.into());
+    }
+    if let Some(path) = &opts.rules {
+        config.rules = Some(path.into());
+    }
+    if let Some(path) = &opts.severities {
+        config
+            .severities
+            .extend(parse_severities(&fs::read_to_string(path)?)?);
+    }
+
+    // the cache keys only identify the tagger of the tokenizer by the spaces of its words and tags, so the
+    // entries of each tokenizer file are kept apart by its content
+    let cache_dir = match &config.tokenizer {
+        Some(path) if !opts.no_cache => {
+            Path::new(&opts.cache_dir).join(format!("{:016x}", content_hash(&fs::read(path)?)))
+        }
+        _ => PathBuf::from(&opts.cache_dir),
+    };
+
+    let (tokenizer, mut rules) = config.load()?;
+    if !opts.no_cache {
+        rules.set_persistent_cache(Some(Box::new(FileCache::new(cache_dir)?)));
+    }
+    Ok((tokenizer, rules))
+}
+
+// FNV-1a, stable across runs and platforms unlike the hasher of the standard library
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn main() {
+    env_logger::init();
+    let opts = Opts::parse();
+
+    if opts.watch {
+        if let Err(error) = watch::watch(&opts) {
+            eprintln!("error: {}", error);
+            process::exit(EXIT_ERROR);
+        }
+        return;
+    }
+
+    let result = run(&opts).and_then(|mut summary| {
+        summary.exit_code = summary.exit_code(opts.max_warnings);
+        match opts.summary.as_deref() {
+            Some("-") => println!("{}", serde_json::to_string(&summary)?),
+            Some(path) => fs::write(path, serde_json::to_string(&summary)?)?,
+            None => {}
+        }
+        Ok(summary.exit_code)
+    });
+
+    match result {
+        Ok(code) => process::exit(code),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+// the files to check grouped by the config which applies to them, in the order they were found
+type Groups = Vec<(Config, Vec<PathBuf>)>;
+
+fn files_by_config(opts: &Opts) -> Result<Groups, Box<dyn Error>> {
+    let globs = |patterns: &[String]| {
+        patterns
+            .iter()
+            .map(|x| {
+                Glob::new(x).map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+            })
+            .collect::<io::Result<Vec<_>>>()
+    };
+    let includes = if opts.include.is_empty() {
+        DEFAULT_INCLUDES.iter().map(|x| x.to_string()).collect()
+    } else {
+        opts.include.clone()
+    };
+    let walker = Walker {
+        includes: globs(&includes)?,
+        excludes: globs(&opts.exclude)?,
+    };
+    let mut paths = Vec::new();
+    for path in &opts.paths {
+        paths.extend(walker.files(Path::new(path))?);
+    }
+
+    let explicit = opts.config.as_ref().map(Config::new).transpose()?;
+    // the config of each directory, so it is only discovered once
+    let mut discovered: HashMap<PathBuf, Config> = HashMap::new();
+    let mut groups: Groups = Vec::new();
+    for path in paths {
+        let config = match &explicit {
+            Some(config) => config.clone(),
+            None => {
+                let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+                match discovered.get(&dir) {
+                    Some(config) => config.clone(),
+                    None => {
+                        let config = Config::discover(&path)?.unwrap_or_default();
+                        discovered.insert(dir, config.clone());
+                        config
+                    }
+                }
+            }
+        };
+        match groups.iter_mut().find(|(x, _)| x.path() == config.path()) {
+            Some((_, paths)) => paths.push(path),
+            None => groups.push((config, vec![path])),
+        }
+    }
+    Ok(groups)
+}
+
+// the line printed for a suggestion which is not applied
+fn report(path: &str, text: &str, suggestion: &Suggestion) -> String {
+    let (line, column) = line_column(text, suggestion.start);
+    format!(
+        "{}:{}:{}: {}: {} [{}]",
+        path, line, column, suggestion.severity, suggestion.message, suggestion.source
+    )
+}
+
+fn run(opts: &Opts) -> Result<Summary, Box<dyn Error>> {
+    let groups = files_by_config(opts)?;
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut summary = Summary::default();
+    let mut quit = false;
+
+    for (config, paths) in groups {
+        let (tokenizer, rules) = load(opts, config)?;
+        for path in &paths {
+            let text = fs::read_to_string(path)?;
+            let path = &path.display().to_string();
+            let suggestions = rules.suggest(&text, &tokenizer);
+            let findings = summary.findings;
+            let mut applied = Vec::new();
+            let mut rest = None;
+
+            for suggestion in &suggestions {
+                let safe = opts.fix && suggestion.safety == FixSafety::Safe;
+                let answer = if safe {
+                    Answer::Apply(0)
+                } else if quit || !opts.interactive {
+                    Answer::Skip
+                } else if let Some(answer) = rest {
+                    answer
+                } else {
+                    match ask(&mut input, path, &text, suggestion)? {
+                        Answer::ApplyRest => {
+                            rest = Some(Answer::Apply(0));
+                            Answer::Apply(0)
+                        }
+                        Answer::SkipRest => {
+                            rest = Some(Answer::Skip);
+                            Answer::Skip
+                        }
+                        Answer::Quit => {
+                            quit = true;
+                            Answer::Skip
+                        }
+                        answer => answer,
+                    }
+                };
+
+                match answer {
+                    Answer::Apply(i) => applied.push(choose(suggestion, i)),
+                    _ => {
+                        if !opts.interactive {
+                            println!("{}", report(path, &text, suggestion));
+                        }
+                        summary.record(suggestion, &rules);
+                    }
+                }
+            }
+
+            summary.files += 1;
+            summary.files_with_findings += (summary.findings > findings) as usize;
+            summary.applied += applied.len();
+
+            if !applied.is_empty() {
+                if !opts.no_backup {
+                    fs::write(format!("{}{}", path, opts.backup_suffix), &text)?;
+                }
+                fs::write(path, apply_suggestions(&text, &applied))?;
+                eprintln!("{}: applied {} suggestions", path, applied.len());
+            }
+        }
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,93 @@
+//! The statistics of a run and the exit code derived from them. Written as JSON with `--summary`, e. g.
+//!
+//! ```json
+//! {"exit_code":1,"files":3,"files_with_findings":1,"findings":2,"applied":0,
+//!  "severities":{"error":1,"warning":1},"categories":{"GRAMMAR":2},"rules":{"ALOT":1,"DOSNT":1}}
+//! ```
+//!
+//! Findings are the suggestions which were not applied. They are counted by severity, by the category of their
+//! rule and by their source, which is the rule ID or e. g. the ID of a word list. Suggestions which are not from a
+//! rule are counted in the category of their source.
+
+use nlprule::{
+    types::{Severity, Suggestion},
+    Rules,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The exit code if there are no findings or they are within the limits.
+pub const EXIT_CLEAN: i32 = 0;
+/// The exit code if there are findings beyond the limits.
+pub const EXIT_FINDINGS: i32 = 1;
+/// The exit code if the run failed, e. g. because a binary or a file could not be read.
+pub const EXIT_ERROR: i32 = 2;
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub exit_code: i32,
+    pub files: usize,
+    pub files_with_findings: usize,
+    pub findings: usize,
+    pub applied: usize,
+    pub severities: BTreeMap<Severity, usize>,
+    pub categories: BTreeMap<String, usize>,
+    pub rules: BTreeMap<String, usize>,
+}
+
+impl Summary {
+    /// Counts a suggestion which was not applied.
+    pub fn record(&mut self, suggestion: &Suggestion, rules: &Rules) {
+        let category = rules
+            .rule(&suggestion.source)
+            .map_or(suggestion.source.as_str(), |x| x.category_id());
+
+        self.findings += 1;
+        *self.severities.entry(suggestion.severity).or_default() += 1;
+        *self.categories.entry(category.to_string()).or_default() += 1;
+        *self.rules.entry(suggestion.source.clone()).or_default() += 1;
+    }
+
+    /// The exit code for the findings. Without a limit on warnings any finding fails. With a limit, errors always
+    /// fail, warnings fail if there are more than the limit and infos and hints never fail.
+    pub fn exit_code(&self, max_warnings: Option<usize>) -> i32 {
+        let count = |severity| self.severities.get(&severity).copied().unwrap_or(0);
+        let fails = match max_warnings {
+            None => self.findings > 0,
+            Some(max) => count(Severity::Error) > 0 || count(Severity::Warning) > max,
+        };
+
+        if fails {
+            EXIT_FINDINGS
+        } else {
+            EXIT_CLEAN
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_warnings() {
+        let summary = |errors, warnings, hints| Summary {
+            findings: errors + warnings + hints,
+            severities: vec![
+                (Severity::Error, errors),
+                (Severity::Warning, warnings),
+                (Severity::Hint, hints),
+            ]
+            .into_iter()
+            .collect(),
+            ..Summary::default()
+        };
+
+        assert_eq!(summary(0, 0, 0).exit_code(None), EXIT_CLEAN);
+        assert_eq!(summary(0, 0, 1).exit_code(None), EXIT_FINDINGS);
+        assert_eq!(summary(0, 0, 3).exit_code(Some(0)), EXIT_CLEAN);
+        assert_eq!(summary(0, 2, 0).exit_code(Some(2)), EXIT_CLEAN);
+        assert_eq!(summary(0, 3, 0).exit_code(Some(2)), EXIT_FINDINGS);
+        assert_eq!(summary(1, 0, 0).exit_code(Some(10)), EXIT_FINDINGS);
+    }
+}
@@ -0,0 +1,149 @@
+//! The `--watch` mode. Every [POLL_INTERVAL] the paths are walked again and the files whose modification time or
+//! size changed are checked again, as are all files of a config which changed. The screen is redrawn with the
+//! suggestions of all files whenever something changed. Errors, e. g. in a config which is being edited, are shown
+//! in the summary instead of stopping the watch.
+
+use super::{files_by_config, load, report, Opts};
+use nlprule::{Rules, Tokenizer};
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// How often the files are checked for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// the modification time and size of a file
+type FileState = (Option<SystemTime>, u64);
+
+fn state(path: &Path) -> Option<FileState> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
+
+// the components for the files of a config, or the error loading them
+struct Loaded {
+    config_state: Option<FileState>,
+    components: Result<(Tokenizer, Rules), String>,
+}
+
+struct Checked {
+    state: FileState,
+    lines: Vec<String>,
+}
+
+fn draw(checked: &BTreeMap<PathBuf, Checked>, errors: &[String]) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    // clears the screen and moves the cursor to the top left
+    write!(stdout, "\x1b[2J\x1b[H")?;
+
+    let mut suggestions = 0;
+    let mut flagged = 0;
+    for file in checked.values() {
+        for line in &file.lines {
+            writeln!(stdout, "{}", line)?;
+        }
+        suggestions += file.lines.len();
+        flagged += !file.lines.is_empty() as usize;
+    }
+    for error in errors {
+        writeln!(stdout, "error: {}", error)?;
+    }
+
+    writeln!(
+        stdout,
+        "\n{} suggestions in {} of {} files, watching for changes (Ctrl-C to stop)",
+        suggestions,
+        flagged,
+        checked.len()
+    )?;
+    stdout.flush()
+}
+
+pub fn watch(opts: &Opts) -> Result<(), Box<dyn Error>> {
+    let mut loaded: BTreeMap<Option<PathBuf>, Loaded> = BTreeMap::new();
+    let mut checked: BTreeMap<PathBuf, Checked> = BTreeMap::new();
+    let mut last_errors = None;
+
+    loop {
+        let mut errors = Vec::new();
+        let mut changed = false;
+        let mut seen = HashSet::new();
+
+        match files_by_config(opts) {
+            Ok(groups) => {
+                for (config, paths) in groups {
+                    let key = config.path().map(Path::to_path_buf);
+                    let config_state = key.as_deref().and_then(state);
+                    let reload =
+                        !matches!(loaded.get(&key), Some(x) if x.config_state == config_state);
+                    if reload {
+                        let components = load(opts, config).map_err(|x| x.to_string());
+                        loaded.insert(
+                            key.clone(),
+                            Loaded {
+                                config_state,
+                                components,
+                            },
+                        );
+                    }
+
+                    let (tokenizer, rules) = match &loaded[&key].components {
+                        Ok(components) => components,
+                        Err(error) => {
+                            errors.push(error.clone());
+                            continue;
+                        }
+                    };
+
+                    for path in paths {
+                        let file_state = match state(&path) {
+                            Some(file_state) => file_state,
+                            None => continue,
+                        };
+                        seen.insert(path.clone());
+                        if !reload && checked.get(&path).map(|x| x.state) == Some(file_state) {
+                            continue;
+                        }
+
+                        let name = path.display().to_string();
+                        let lines = match fs::read_to_string(&path) {
+                            Ok(text) => rules
+                                .suggest(&text, tokenizer)
+                                .iter()
+                                .map(|x| report(&name, &text, x))
+                                .collect(),
+                            Err(error) => vec![format!("{}: error: {}", name, error)],
+                        };
+                        checked.insert(
+                            path,
+                            Checked {
+                                state: file_state,
+                                lines,
+                            },
+                        );
+                        changed = true;
+                    }
+                }
+
+                // files which were removed or ignored, or whose config failed to load
+                let count = checked.len();
+                checked.retain(|path, _| seen.contains(path));
+                changed |= checked.len() != count;
+            }
+            Err(error) => errors.push(error.to_string()),
+        }
+
+        if changed || last_errors.as_ref() != Some(&errors) {
+            draw(&checked, &errors)?;
+            last_errors = Some(errors);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
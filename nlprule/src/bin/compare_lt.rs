@@ -0,0 +1,275 @@
+//! Sends a corpus to a LanguageTool HTTP server and compares its matches to the suggestions of nlprule.
+//! Start a server with e. g. `java -cp languagetool-server.jar org.languagetool.server.HTTPServer --port 8081`.
+
+use clap::Clap;
+use nlprule::{rules::Rules, types::Suggestion, Tokenizer};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs};
+
+#[derive(Clap)]
+#[clap(
+    version = env!("CARGO_PKG_VERSION"),
+    author = "Benjamin Minixhofer <bminixhofer@gmail.com>"
+)]
+struct Opts {
+    #[clap(long, short)]
+    tokenizer: String,
+    #[clap(long, short)]
+    rules: String,
+    /// Path to the corpus. Each non-empty line is checked as one text.
+    #[clap(long, short)]
+    corpus: String,
+    /// The URL of the LanguageTool server.
+    #[clap(long, default_value = "http://localhost:8081")]
+    server: String,
+    /// The language code sent to the server, e. g. "en-US". Defaults to the language of the rules.
+    #[clap(long)]
+    language: Option<String>,
+    /// Only compare matches of rules which exist in nlprule.
+    #[clap(long)]
+    known_rules: bool,
+    /// The number of divergences printed per category.
+    #[clap(long, default_value = "20")]
+    examples: usize,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    matches: Vec<Match>,
+}
+
+#[derive(Deserialize)]
+struct Match {
+    offset: usize,
+    length: usize,
+    replacements: Vec<Replacement>,
+    rule: MatchRule,
+}
+
+#[derive(Deserialize)]
+struct Replacement {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct MatchRule {
+    id: String,
+}
+
+/// A match of either implementation as a char span, the rule ID without the index in its rule group and the
+/// replacements.
+#[derive(Debug, Clone, PartialEq)]
+struct Found {
+    start: usize,
+    end: usize,
+    rule_id: String,
+    replacements: Vec<String>,
+}
+
+impl Found {
+    fn from_suggestion(suggestion: &Suggestion) -> Self {
+        let rule_id = match suggestion.source.rsplit_once('.') {
+            Some((group, index)) if index.chars().all(|c| c.is_ascii_digit()) => group,
+            _ => suggestion.source.as_str(),
+        };
+
+        Found {
+            start: suggestion.start,
+            end: suggestion.end,
+            rule_id: rule_id.to_string(),
+            replacements: suggestion.replacements.clone(),
+        }
+    }
+
+    // LanguageTool offsets are in UTF-16 code units
+    fn from_match(m: Match, text: &str) -> Self {
+        let to_char = |utf16: usize| {
+            let mut units = 0;
+            text.chars()
+                .take_while(|c| {
+                    units += c.len_utf16();
+                    units <= utf16
+                })
+                .count()
+        };
+
+        Found {
+            start: to_char(m.offset),
+            end: to_char(m.offset + m.length),
+            rule_id: m.rule.id,
+            replacements: m.replacements.into_iter().map(|x| x.value).collect(),
+        }
+    }
+
+    fn describe(&self, text: &str) -> String {
+        let matched: String = text
+            .chars()
+            .skip(self.start)
+            .take(self.end - self.start)
+            .collect();
+        format!(
+            "{} {:?} -> {:?}",
+            self.rule_id,
+            matched,
+            self.replacements.iter().take(3).collect::<Vec<_>>()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Category {
+    Identical,
+    OtherReplacements,
+    OtherSpan,
+    OnlyNlprule,
+    OnlyLanguageTool,
+}
+
+impl Category {
+    fn name(&self) -> &'static str {
+        match self {
+            Category::Identical => "identical",
+            Category::OtherReplacements => "same span, other replacements",
+            Category::OtherSpan => "same rule, overlapping span",
+            Category::OnlyNlprule => "only in nlprule",
+            Category::OnlyLanguageTool => "only in LanguageTool",
+        }
+    }
+}
+
+/// Pairs the matches of both implementations and categorizes each pair or unpaired match.
+fn compare(ours: Vec<Found>, theirs: Vec<Found>) -> Vec<(Category, Option<Found>, Option<Found>)> {
+    let mut theirs: Vec<_> = theirs.into_iter().map(Some).collect();
+    let mut output = Vec::new();
+
+    for our in ours {
+        let position = |f: &dyn Fn(&Found) -> bool| {
+            theirs
+                .iter()
+                .position(|x| matches!(x, Some(x) if x.rule_id == our.rule_id && f(x)))
+        };
+
+        let (category, index) =
+            if let Some(i) = position(&|x| x.start == our.start && x.end == our.end) {
+                let category = if theirs[i].as_ref().unwrap().replacements == our.replacements {
+                    Category::Identical
+                } else {
+                    Category::OtherReplacements
+                };
+                (category, Some(i))
+            } else if let Some(i) = position(&|x| x.start < our.end && our.start < x.end) {
+                (Category::OtherSpan, Some(i))
+            } else {
+                (Category::OnlyNlprule, None)
+            };
+
+        output.push((category, Some(our), index.and_then(|i| theirs[i].take())));
+    }
+
+    output.extend(
+        theirs
+            .into_iter()
+            .flatten()
+            .map(|x| (Category::OnlyLanguageTool, None, Some(x))),
+    );
+    output
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let opts = Opts::parse();
+
+    let tokenizer = Tokenizer::new(&opts.tokenizer)?;
+    let rules = Rules::new(&opts.rules)?;
+    let language = opts
+        .language
+        .clone()
+        .unwrap_or_else(|| rules.metadata().lang_code().to_string());
+    let known_ids: Vec<_> = rules
+        .rules()
+        .iter()
+        .map(|x| x.group().map_or(x.id(), |group| group.id()).to_string())
+        .collect();
+
+    let client = reqwest::blocking::Client::new();
+    let url = format!("{}/v2/check", opts.server.trim_end_matches('/'));
+
+    let mut results: Vec<(Category, Option<Found>, Option<Found>, String)> = Vec::new();
+    let corpus = fs::read_to_string(&opts.corpus)?;
+    let texts: Vec<_> = corpus.lines().filter(|x| !x.trim().is_empty()).collect();
+
+    for &text in &texts {
+        let response: Response = serde_json::from_str(
+            &client
+                .post(&url)
+                .form(&[("text", text), ("language", language.as_str())])
+                .send()?
+                .error_for_status()?
+                .text()?,
+        )?;
+
+        let ours = rules
+            .suggest(text, &tokenizer)
+            .iter()
+            .map(Found::from_suggestion)
+            .collect();
+        let theirs = response
+            .matches
+            .into_iter()
+            .map(|x| Found::from_match(x, text))
+            .filter(|x| !opts.known_rules || known_ids.contains(&x.rule_id))
+            .collect();
+
+        for (category, our, their) in compare(ours, theirs) {
+            results.push((category, our, their, text.to_string()));
+        }
+    }
+
+    let mut by_category: BTreeMap<Category, usize> = BTreeMap::new();
+    let mut by_rule: BTreeMap<&str, BTreeMap<Category, usize>> = BTreeMap::new();
+    for (category, our, their, _) in &results {
+        *by_category.entry(*category).or_default() += 1;
+
+        let rule_id = our.as_ref().or(their.as_ref()).unwrap().rule_id.as_str();
+        *by_rule
+            .entry(rule_id)
+            .or_default()
+            .entry(*category)
+            .or_default() += 1;
+    }
+
+    println!("{} matches in {} texts", results.len(), texts.len());
+    for (category, count) in &by_category {
+        println!("{:>8} {}", count, category.name());
+    }
+
+    println!("\nDiverging rules:");
+    for (rule_id, counts) in &by_rule {
+        if counts.keys().any(|x| *x != Category::Identical) {
+            let counts: Vec<_> = counts
+                .iter()
+                .map(|(category, count)| format!("{} {}", count, category.name()))
+                .collect();
+            println!("  {}: {}", rule_id, counts.join(", "));
+        }
+    }
+
+    for category in by_category.keys().filter(|x| **x != Category::Identical) {
+        println!("\n{}:", category.name());
+        for (_, our, their, text) in results
+            .iter()
+            .filter(|(x, ..)| x == category)
+            .take(opts.examples)
+        {
+            println!("  {}", text);
+            if let Some(our) = our {
+                println!("    nlprule:      {}", our.describe(text));
+            }
+            if let Some(their) = their {
+                println!("    LanguageTool: {}", their.describe(text));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,9 +1,134 @@
 use clap::Clap;
-use nlprule::compile::{compile, BuildOptions};
+use nlprule::{
+    compile::{
+        compile_extension, compile_with_progress, read_manifest, BuildOptions, ExtensionOptions,
+        Progress, Target,
+    },
+    rules::patch::RulesPatch,
+    Component,
+};
+use std::fs::File;
+
+#[derive(Clap)]
+#[clap(
+    version = env!("CARGO_PKG_VERSION"),
+    author = "Benjamin Minixhofer <bminixhofer@gmail.com>"
+)]
+enum Command {
+    /// Compile the tokenizer and the rules.
+    All(Source),
+    /// Compile only the tokenizer.
+    Tokenizer(Source),
+    /// Compile only the rules.
+    Rules(Source),
+    /// Create a patch which updates one compiled rules binary to another.
+    Diff(Diff),
+    /// Compile a dictionary extension which can be layered over a compiled tagger.
+    Extension(ExtensionOptions),
+}
+
+#[derive(Clap)]
+#[allow(clippy::large_enum_variant)]
+enum Source {
+    /// Build one language with the options given as flags.
+    Flags(BuildOptions),
+    /// Build languages described in a JSON manifest.
+    Manifest(Manifest),
+}
+
+#[derive(Clap)]
+struct Manifest {
+    /// Path to the manifest.
+    path: String,
+    /// Codes of the languages to build. Builds all languages in the manifest if not set.
+    #[clap(long)]
+    lang: Vec<String>,
+}
+
+#[derive(Clap)]
+struct Diff {
+    /// Path to the rules binary deployments currently have.
+    old_path: String,
+    /// Path to the updated rules binary.
+    new_path: String,
+    /// Path to write the patch to.
+    #[clap(long, short)]
+    out_path: String,
+    /// Whether to gzip compress the patch.
+    #[clap(long)]
+    compress: bool,
+}
+
+fn diff(opts: &Diff) -> Result<(), nlprule::Error> {
+    let patch = RulesPatch::new(File::open(&opts.old_path)?, File::open(&opts.new_path)?)?;
+    let changed: Vec<_> = patch.changed_categories().collect();
+    eprintln!(
+        "{} changed categories: {}",
+        changed.len(),
+        changed.join(", ")
+    );
+
+    patch.write(File::create(&opts.out_path)?, opts.compress)
+}
 
 fn main() {
     env_logger::init();
-    let opts = BuildOptions::parse();
 
-    compile(&opts);
+    let (target, source) = match Command::parse() {
+        Command::All(source) => (Target::All, source),
+        Command::Tokenizer(source) => (Target::Tokenizer, source),
+        Command::Rules(source) => (Target::Rules, source),
+        Command::Diff(opts) => {
+            if let Err(error) = diff(&opts) {
+                eprintln!("could not create patch: {}", error);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Command::Extension(opts) => {
+            match compile_extension(&opts) {
+                Ok(count) => eprintln!("compiled extension with {} words", count),
+                Err(error) => {
+                    eprintln!("could not compile extension: {}", error);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+    };
+
+    let builds = match source {
+        Source::Flags(opts) => vec![opts],
+        Source::Manifest(manifest) => read_manifest(&manifest.path)
+            .unwrap_or_else(|x| panic!("invalid manifest {}: {}", manifest.path, x))
+            .into_iter()
+            .filter(|x| manifest.lang.is_empty() || manifest.lang.contains(&x.lang_code))
+            .collect(),
+    };
+
+    for opts in builds {
+        let lang_code = opts.lang_code.clone();
+        let report = compile_with_progress(&opts, target, &mut |progress: Progress| {
+            eprintln!("[{}] {}", lang_code, progress)
+        });
+
+        if !report.errors.is_empty() {
+            eprintln!("[{}] skipped {} rules:", lang_code, report.errors.len());
+            for error in &report.errors {
+                eprintln!("[{}] {}", lang_code, error);
+            }
+        }
+
+        if !report.false_positives.is_empty() {
+            eprintln!(
+                "[{}] {} rules match the negative corpus:",
+                lang_code,
+                report.false_positives.len()
+            );
+            for (rule_id, lines) in &report.false_positives {
+                let lines: Vec<_> = lines.iter().map(|x| x.to_string()).collect();
+                eprintln!("[{}] {}: lines {}", lang_code, rule_id, lines.join(", "));
+            }
+        }
+    }
 }
@@ -0,0 +1,51 @@
+//! Serves the gRPC `Checker` service defined in `proto/nlprule.proto`, see the [grpc][nlprule::grpc] module.
+
+use clap::Clap;
+use nlprule::{
+    grpc::CheckService,
+    rules::{parse_severities, CheckOptions},
+    Pipeline, Rules, Tokenizer,
+};
+use std::sync::Arc;
+
+#[derive(Clap)]
+#[clap(
+    version = env!("CARGO_PKG_VERSION"),
+    author = "Benjamin Minixhofer <bminixhofer@gmail.com>"
+)]
+struct Opts {
+    #[clap(long, short)]
+    tokenizer: String,
+    #[clap(long, short)]
+    rules: String,
+    /// The address to listen on.
+    #[clap(long, default_value = "[::1]:50051")]
+    address: String,
+    /// A file mapping rule, rule group or category IDs to severities, one `ID severity` per line.
+    #[clap(long)]
+    severities: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let opts = Opts::parse();
+
+    let mut rules = Rules::new(&opts.rules)?;
+    if let Some(path) = &opts.severities {
+        rules.set_check_options(CheckOptions {
+            severities: parse_severities(&std::fs::read_to_string(path)?)?,
+            ..CheckOptions::default()
+        });
+    }
+
+    let pipeline = Pipeline::new(Tokenizer::new(&opts.tokenizer)?, rules)?;
+    let address = opts.address.parse()?;
+    eprintln!("listening on {}", address);
+
+    tonic::transport::Server::builder()
+        .add_service(CheckService::new(Arc::new(pipeline)).into_server())
+        .serve(address)
+        .await?;
+    Ok(())
+}
@@ -15,6 +15,9 @@ struct Opts {
     tokenizer: String,
     #[clap(long, short)]
     rules: String,
+    /// Print the analysis and the changes of the disambiguation rules in the format of LanguageTool instead.
+    #[clap(long)]
+    lt_log: bool,
 }
 
 fn main() {
@@ -24,6 +27,11 @@ fn main() {
     let tokenizer = Tokenizer::new(opts.tokenizer).unwrap();
     let rules = Rules::new(opts.rules).unwrap();
 
+    if opts.lt_log {
+        print!("{}", tokenizer.lt_log(&opts.text));
+        return;
+    }
+
     let incomplete_tokens = tokenizer.disambiguate(tokenizer.tokenize(&opts.text));
 
     println!("Tokens: {:#?}", incomplete_tokens);
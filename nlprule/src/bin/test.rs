@@ -1,5 +1,8 @@
 use clap::Clap;
-use nlprule::{rules::Rules, tokenizer::Tokenizer};
+use nlprule::{
+    rules::{stats::Statistics, Rules},
+    tokenizer::Tokenizer,
+};
 
 #[derive(Clap)]
 #[clap(
@@ -13,6 +16,12 @@ struct Opts {
     rules: String,
     #[clap(long, short)]
     ids: Vec<String>,
+    /// Writes the precision statistics of the rules on their examples to this path.
+    #[clap(long)]
+    stats_path: Option<String>,
+    /// Path to a corpus of correct text with one sentence per line. Lists the rules which match any sentence.
+    #[clap(long)]
+    negative_corpus: Option<String>,
 }
 
 fn main() {
@@ -33,6 +42,27 @@ fn main() {
     }
 
     println!("Rules passing tests: {}", passes);
+
+    if let Some(path) = opts.stats_path {
+        let statistics = Statistics::from_examples(&rules_container, &tokenizer);
+        std::fs::write(path, statistics.to_text()).unwrap();
+    }
+
+    if let Some(path) = opts.negative_corpus {
+        let corpus = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<_> = corpus.lines().collect();
+        let false_positives = rules_container.false_positives(&lines, &tokenizer);
+
+        println!(
+            "Rules matching the negative corpus: {}",
+            false_positives.len()
+        );
+        for (rule, indices) in false_positives {
+            let lines: Vec<_> = indices.iter().map(|i| (i + 1).to_string()).collect();
+            println!("{}: lines {}", rule.id(), lines.join(", "));
+        }
+    }
+
     if passes == rules.len() {
         std::process::exit(0);
     } else {
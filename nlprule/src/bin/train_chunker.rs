@@ -0,0 +1,12 @@
+use clap::Clap;
+use nlprule::compile::{train_chunker, TrainOptions};
+
+fn main() {
+    env_logger::init();
+    let opts = TrainOptions::parse();
+
+    match train_chunker(&opts).unwrap() {
+        Some(accuracy) => println!("accuracy on test data: {:.2}%", accuracy * 100.),
+        None => println!("model written to {}", opts.out_path),
+    }
+}
@@ -0,0 +1,116 @@
+//! Checks texts for other processes. Reads one JSON request per line from stdin, e. g.
+//! `{"id": 1, "text": "She was not been here."}`, and writes one JSON response per line to stdout:
+//! `{"id": 1, "suggestions": [...]}`, or `{"id": 1, "error": "..."}` if the request is invalid.
+//!
+//! Requests are answered one at a time in order and each response is flushed before the next request is read,
+//! so a client which stops reading the responses also stops the worker from reading requests.
+//! The `id` can be any JSON value and is returned unchanged, it is `null` if the request does not have one.
+
+use clap::Clap;
+use nlprule::{
+    rules::{parse_severities, CheckOptions, Rules},
+    tokenizer::Tokenizer,
+    types::Suggestion,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+
+#[derive(Clap)]
+#[clap(
+    version = env!("CARGO_PKG_VERSION"),
+    author = "Benjamin Minixhofer <bminixhofer@gmail.com>"
+)]
+struct Opts {
+    #[clap(long, short)]
+    tokenizer: String,
+    #[clap(long, short)]
+    rules: String,
+    /// A file mapping rule, rule group or category IDs to severities, one `ID severity` per line.
+    #[clap(long)]
+    severities: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct Response {
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestions: Option<Vec<Suggestion>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Response {
+    fn error(id: Value, error: impl ToString) -> Self {
+        Response {
+            id,
+            suggestions: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+fn respond(line: &[u8], rules: &Rules, tokenizer: &Tokenizer) -> Response {
+    let line = match std::str::from_utf8(line) {
+        Ok(line) => line,
+        Err(error) => return Response::error(Value::Null, error),
+    };
+
+    match serde_json::from_str::<Request>(line) {
+        Ok(request) => Response {
+            id: request.id,
+            suggestions: Some(rules.suggest(&request.text, tokenizer)),
+            error: None,
+        },
+        Err(error) => {
+            // keeps the ID of requests which are valid JSON with a missing or invalid text
+            let id = serde_json::from_str::<Value>(line)
+                .ok()
+                .and_then(|x| x.get("id").cloned())
+                .unwrap_or(Value::Null);
+            Response::error(id, error)
+        }
+    }
+}
+
+fn main() -> io::Result<()> {
+    env_logger::init();
+    let opts = Opts::parse();
+
+    let tokenizer = Tokenizer::new(&opts.tokenizer).unwrap();
+    let mut rules = Rules::new(&opts.rules).unwrap();
+    if let Some(path) = &opts.severities {
+        rules.set_check_options(CheckOptions {
+            severities: parse_severities(&std::fs::read_to_string(path)?).unwrap(),
+            ..CheckOptions::default()
+        });
+    }
+
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if stdin.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        if line.iter().all(|x| x.is_ascii_whitespace()) {
+            continue;
+        }
+
+        let response = respond(&line, &rules, &tokenizer);
+        serde_json::to_writer(&mut stdout, &response)?;
+        stdout.write_all(b"\n")?;
+        stdout.flush()?;
+    }
+}
@@ -0,0 +1,230 @@
+//! Downloading and caching of the published binaries. Requires the `binaries` feature.
+//!
+//! Binaries are downloaded from the Github release matching the version of this crate and cached in the
+//! user's cache directory (e. g. `~/.cache/nlprule` on Linux), so they are only downloaded once.
+//! The binaries published for 0.3.0 predate the binary header and are migrated when loaded.
+//!
+//! ```no_run
+//! use nlprule::{Rules, Tokenizer};
+//!
+//! let tokenizer = Tokenizer::from_lang("en")?;
+//! let rules = Rules::from_lang("en")?;
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use crate::{component::Metadata, Component, Error, Rules, Tokenizer};
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
+};
+
+/// The URL binaries are downloaded from.
+pub const RELEASE_URL: &str = "https://github.com/bminixhofer/nlprule/releases/download";
+
+/// The directory binaries are cached in, if one can be determined on this platform.
+pub fn cache_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", "nlprule")
+        .map(|x| x.cache_dir().join(env!("CARGO_PKG_VERSION")))
+}
+
+/// Gets a reader over the binary of the component for the language with the given code (e. g. "en").
+/// Reads the binary from the cache if possible, otherwise downloads it and tries to store it in the cache.
+pub fn get<C: Component>(lang_code: &str) -> Result<impl Read, Error> {
+    let filename = format!("{}.gz", C::filename(lang_code));
+    let cache_path = cache_dir().map(|x| x.join(&filename));
+
+    // if the file can be read, the data is already cached
+    if let Some(path) = &cache_path {
+        if let Ok(bytes) = fs::read(path) {
+            return Ok(Cursor::new(bytes));
+        }
+    }
+
+    // ... otherwise, request the data from the URL ...
+    let url = format!("{}/{}/{}", RELEASE_URL, env!("CARGO_PKG_VERSION"), filename);
+    let bytes = reqwest::blocking::get(url)?
+        .error_for_status()?
+        .bytes()?
+        .to_vec();
+
+    // ... and then cache the data at the provided file, if one was found. Failing to cache is not an error,
+    // the data is downloaded again next time
+    if let Some(path) = &cache_path {
+        let _ = fs::create_dir_all(path.parent().unwrap()).and_then(|_| fs::write(path, &bytes));
+    }
+
+    Ok(Cursor::new(bytes))
+}
+
+/// Stores the tokenizer and rules binaries for the language with the given code in `dir` as
+/// `{lang_code}_tokenizer.bin` and `{lang_code}_rules.bin`. Intended to be called from a build script
+/// to embed the binaries with [embed_nlprule][crate::embed_nlprule].
+pub fn store<P: AsRef<Path>>(lang_code: &str, dir: P) -> Result<(), Error> {
+    fn store_component<C: Component>(lang_code: &str, dir: &Path) -> Result<(), Error> {
+        let mut file = fs::File::create(dir.join(C::filename(lang_code)))?;
+        io::copy(&mut get::<C>(lang_code)?, &mut file)?;
+        Ok(())
+    }
+
+    store_component::<Tokenizer>(lang_code, dir.as_ref())?;
+    store_component::<Rules>(lang_code, dir.as_ref())
+}
+
+// binaries of nlprule 0.3.0 do not store their language, it is known from the binary they were loaded from
+fn set_lang_code(metadata: &mut Metadata, lang_code: &str) {
+    if metadata.lang_code.is_empty() {
+        metadata.lang_code = lang_code.to_string();
+    }
+}
+
+impl Tokenizer {
+    /// Creates the tokenizer for the language with the given code (e. g. "en") from the published binaries.
+    /// See the [binaries][crate::binaries] module.
+    pub fn from_lang(lang_code: &str) -> Result<Self, Error> {
+        let mut tokenizer = Tokenizer::read(get::<Tokenizer>(lang_code)?)?;
+        set_lang_code(&mut tokenizer.metadata, lang_code);
+        Ok(tokenizer)
+    }
+}
+
+impl Rules {
+    /// Creates the rules for the language with the given code (e. g. "en") from the published binaries.
+    /// See the [binaries][crate::binaries] module.
+    pub fn from_lang(lang_code: &str) -> Result<Self, Error> {
+        let mut rules = Rules::read(get::<Rules>(lang_code)?)?;
+        set_lang_code(&mut rules.metadata, lang_code);
+        Ok(rules)
+    }
+
+    /// Creates the rules for the language with the given code from the published binaries, only loading the rules
+    /// in the given categories. See [new_with_categories][Rules::new_with_categories].
+    pub fn from_lang_with_categories(
+        lang_code: &str,
+        category_ids: &[&str],
+    ) -> Result<Self, Error> {
+        let mut rules = Rules::new_from_with_categories(get::<Rules>(lang_code)?, category_ids)?;
+        set_lang_code(&mut rules.metadata, lang_code);
+        Ok(rules)
+    }
+}
+
+/// The codes of the languages for which binaries are published with this version of the crate e. g. "en".
+/// This is a static list, it does not check which binaries can be downloaded. See [cached_languages] for the
+/// languages which can be loaded without downloading.
+pub fn available_languages() -> &'static [&'static str] {
+    &["en", "de"]
+}
+
+/// The codes of the languages for which the tokenizer and the rules binary are in the [cache directory][cache_dir],
+/// as stored in the [Metadata] of the binaries. Binaries which can not be read are skipped. Binaries of nlprule 0.3.0
+/// have no metadata, they are loaded completely to check them and their language is taken from the file name.
+pub fn cached_languages() -> Vec<String> {
+    cache_dir().map_or_else(Vec::new, |dir| languages_in(&dir))
+}
+
+/// The language of the binary of the component at `path`, if it can be read and it is named after its language.
+fn cached_language<C: Component>(path: &Path) -> Option<String> {
+    let filename = path.file_name()?.to_str()?;
+    let lang_code = filename.strip_suffix(&format!("_{}.bin.gz", C::name()))?;
+    let open = || fs::File::open(path).map(io::BufReader::new).ok();
+
+    let metadata = Metadata::read(open()?).ok()?;
+    let valid = if metadata.lang_code().is_empty() {
+        C::read(open()?).is_ok()
+    } else {
+        metadata.lang_code() == lang_code
+    };
+
+    if valid {
+        Some(lang_code.to_string())
+    } else {
+        None
+    }
+}
+
+fn languages_in(dir: &Path) -> Vec<String> {
+    let mut tokenizers = BTreeSet::new();
+    let mut rules = BTreeSet::new();
+
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        tokenizers.extend(cached_language::<Tokenizer>(&path));
+        rules.extend(cached_language::<Rules>(&path));
+    }
+
+    tokenizers.intersection(&rules).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::{DE, EN};
+
+    #[test]
+    fn finds_cached_languages() {
+        let dir = std::env::temp_dir().join(format!("nlprule-binaries-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let create =
+            |filename: String| fs::File::create(dir.join(format!("{}.gz", filename))).unwrap();
+        EN.0.write(create(Tokenizer::filename("en")), true).unwrap();
+        DE.0.write(create(Tokenizer::filename("de")), true).unwrap();
+        EN.1.write(create(Rules::filename("en")), true).unwrap();
+        fs::write(
+            dir.join(format!("{}.gz", Rules::filename("de"))),
+            "not a binary",
+        )
+        .unwrap();
+
+        let languages = languages_in(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(languages, vec!["en"]);
+    }
+
+    #[test]
+    fn finds_cached_languages_of_0_3_0() {
+        let dir =
+            std::env::temp_dir().join(format!("nlprule-binaries-0.3.0-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let copy = |from: &str, to: String| {
+            let mut encoder = flate2::write::GzEncoder::new(
+                fs::File::create(dir.join(format!("{}.gz", to))).unwrap(),
+                flate2::Compression::default(),
+            );
+            io::copy(
+                &mut fs::File::open(format!("tests/fixtures/0.3.0/{}", from)).unwrap(),
+                &mut encoder,
+            )
+            .unwrap();
+            encoder.finish().unwrap();
+        };
+        copy("en_tokenizer.bin", Tokenizer::filename("en"));
+        copy("en_rules.bin", Rules::filename("en"));
+        copy("de_tokenizer.bin", Tokenizer::filename("de"));
+        // a tokenizer stored as rules can not be loaded
+        copy("de_tokenizer.bin", Rules::filename("de"));
+
+        let languages = languages_in(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(languages, vec!["en"]);
+    }
+
+    #[test]
+    #[ignore = "downloads the published binaries"]
+    fn publishes_available_languages() {
+        for lang_code in available_languages() {
+            assert_eq!(
+                Tokenizer::from_lang(lang_code)
+                    .unwrap()
+                    .metadata()
+                    .lang_code(),
+                *lang_code
+            );
+            assert_eq!(
+                Rules::from_lang(lang_code).unwrap().metadata().lang_code(),
+                *lang_code
+            );
+        }
+    }
+}
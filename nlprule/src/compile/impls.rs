@@ -3,21 +3,26 @@ use std::hash::{Hash, Hasher};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    component::Metadata,
     rule::{
         disambiguation::POSFilter,
         engine::composition::{Matcher, PosMatcher, TextMatcher},
-        DisambiguationRule, MatchGraph, Rule,
+        DisambiguationRule, MatchGraph, Rule, RuleGroup, RuleSource,
     },
     rules::{Rules, RulesOptions},
-    tokenizer::{chunk, Tokenizer, TokenizerOptions},
+    tokenizer::{
+        chunk,
+        multiword::{Multiword, MultiwordTagger},
+        Tokenizer, TokenizerOptions,
+    },
     types::*,
     utils::parallelism::MaybeParallelIterator,
 };
 
-use super::parse_structure::BuildInfo;
+use super::{parse_structure::BuildInfo, RuleError};
 
 impl TextMatcher {
-    pub fn new(matcher: Matcher, info: &mut BuildInfo) -> Self {
+    pub fn new(matcher: Matcher, info: &BuildInfo) -> Self {
         let graph = MatchGraph::default();
 
         let set = if matcher.needs_graph() {
@@ -29,8 +34,15 @@ impl TextMatcher {
             matcher.empty_always_false.hash(&mut hasher);
             let matcher_hash = hasher.finish();
 
-            if let Some(set) = info.mut_regex_cache().get(&matcher_hash) {
-                set.clone()
+            let cached = info
+                .regex_cache()
+                .read()
+                .unwrap()
+                .get(&matcher_hash)
+                .cloned();
+
+            if let Some(set) = cached {
+                set
             } else {
                 let data: Vec<_> = info.tagger().word_store().iter().collect();
 
@@ -49,7 +61,10 @@ impl TextMatcher {
                 // this cutoff is pretty arbitrary but without any threshold the size of some sets blows up
                 // the vast majority of regexes matches less than 100 strings from manual inspection
                 let set = if set.len() > 100 { None } else { Some(set) };
-                info.mut_regex_cache().insert(matcher_hash, set.clone());
+                info.regex_cache()
+                    .write()
+                    .unwrap()
+                    .insert(matcher_hash, set.clone());
                 set
             }
         } else {
@@ -61,7 +76,7 @@ impl TextMatcher {
 }
 
 impl PosMatcher {
-    pub fn new(matcher: Matcher, info: &mut BuildInfo) -> Self {
+    pub fn new(matcher: Matcher, info: &BuildInfo) -> Self {
         let mut mask = vec![false; info.tagger().tag_store().len()];
         let graph = MatchGraph::default();
 
@@ -69,26 +84,31 @@ impl PosMatcher {
             mask[*id as usize] = matcher.is_match(word.as_str(), &graph, None);
         }
 
-        PosMatcher { mask }
+        PosMatcher::from(mask)
     }
 }
 
 impl Rules {
     pub fn from_xml<P: AsRef<std::path::Path>>(
         path: P,
-        build_info: &mut BuildInfo,
+        build_info: &BuildInfo,
         options: RulesOptions,
-    ) -> Self {
+    ) -> (Self, Vec<RuleError>) {
         use log::warn;
         use std::collections::HashMap;
 
+        let path_str = path.as_ref().display().to_string();
+        let file_name = path
+            .as_ref()
+            .file_name()
+            .map_or_else(|| path_str.clone(), |x| x.to_string_lossy().into_owned());
         let rules = super::parse_structure::read_rules(path);
-        let mut errors: HashMap<String, usize> = HashMap::new();
 
-        let rules: Vec<_> = rules
-            .into_iter()
-            .filter_map(|x| match x {
-                Ok((rule_structure, group, category)) => {
+        // rules are compiled in parallel, errors are collected afterwards to keep the order of the rules
+        let results: Vec<Result<Option<Rule>, RuleError>> = rules
+            .into_maybe_par_iter()
+            .map(|x| match x {
+                Ok((rule_structure, group, category, line)) => {
                     let id = rule_structure.id.as_ref().map_or_else(
                         || {
                             let group = group.as_ref().expect("must have group if ID not set");
@@ -127,51 +147,131 @@ impl Rules {
                                 rule.category_id = category.id;
                                 rule.category_name = category.name;
                                 rule.category_type = category.kind;
-                                Some(rule)
+                                rule.safety = rule.classify_safety();
+                                rule.group = group.map(|x| RuleGroup {
+                                    id: x.id,
+                                    name: x.name,
+                                    index: x.n,
+                                });
+                                rule.source = Some(RuleSource {
+                                    file: file_name.clone(),
+                                    line,
+                                });
+                                Ok(Some(rule))
                             } else {
-                                None
+                                Ok(None)
                             }
                         }
-                        Err(x) => {
-                            *errors.entry(format!("[Rule] {}", x)).or_insert(0) += 1;
-                            None
-                        }
+                        Err(x) => Err(RuleError {
+                            path: path_str.clone(),
+                            line,
+                            rule_id: Some(id),
+                            message: x.to_string(),
+                        }),
                     }
                 }
+                Err(x) => Err(x),
+            })
+            .collect();
+
+        let mut errors = Vec::new();
+        let rules: Vec<_> = results
+            .into_iter()
+            .filter_map(|x| match x {
+                Ok(rule) => rule,
                 Err(x) => {
-                    *errors.entry(format!("[Structure] {}", x)).or_insert(0) += 1;
+                    errors.push(x);
                     None
                 }
             })
             .collect();
+        errors.sort_by_key(|x| x.line);
 
         if !errors.is_empty() {
-            let mut errors: Vec<(String, usize)> = errors.into_iter().collect();
-            errors.sort_by_key(|x| -(x.1 as i32));
+            let mut counts: HashMap<&str, usize> = HashMap::new();
+            for error in &errors {
+                *counts.entry(&error.message).or_insert(0) += 1;
+            }
+            let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+            counts.sort_by_key(|x| -(x.1 as i32));
+
+            warn!("Errors constructing Rules: {:#?}", &counts);
+        }
+
+        (
+            Rules::from_rules(rules, build_info.tagger().fingerprint()),
+            errors,
+        )
+    }
+}
+
+impl Rules {
+    /// Adds the translated messages of the file to the rules, see [read_translations][super::parse_structure::read_translations].
+    /// A translation applies to the rule with its ID or to all rules in the rule group with its ID.
+    pub fn translate_from_xml<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        build_info: &BuildInfo,
+    ) -> Vec<RuleError> {
+        use log::warn;
+
+        let path_str = path.as_ref().display().to_string();
+        let (locale, translations) = super::parse_structure::read_translations(path);
+
+        let mut errors = Vec::new();
+        for translation in translations {
+            let translation = match translation {
+                Ok(x) => x,
+                Err(x) => {
+                    errors.push(x);
+                    continue;
+                }
+            };
+
+            let mut found = false;
+            for rule in self.rules.iter_mut().filter(|x| {
+                x.id == translation.id || x.group().map(|x| x.id()) == Some(translation.id.as_str())
+            }) {
+                found = true;
+                if let Err(x) = rule.translate(&locale, translation.message.clone(), build_info) {
+                    errors.push(RuleError {
+                        path: path_str.clone(),
+                        line: translation.line,
+                        rule_id: Some(rule.id.clone()),
+                        message: x.to_string(),
+                    });
+                }
+            }
 
-            warn!("Errors constructing Rules: {:#?}", &errors);
+            if !found {
+                warn!(
+                    "{}:{}: no rule with ID {} for the translation",
+                    path_str, translation.line, translation.id
+                );
+            }
         }
 
-        Rules { rules }
+        errors
     }
 }
 
 impl Tokenizer {
     pub fn from_xml<P: AsRef<std::path::Path>>(
         path: P,
-        build_info: &mut BuildInfo,
+        build_info: &BuildInfo,
         chunker: Option<chunk::Chunker>,
+        multiword_tagger: Option<MultiwordTagger>,
         options: TokenizerOptions,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
+    ) -> Result<(Self, Vec<RuleError>), Box<dyn std::error::Error>> {
         use log::warn;
 
+        let path_str = path.as_ref().display().to_string();
         let rules = super::parse_structure::read_disambiguation_rules(path);
-        let mut error = None;
 
-        let rules: Vec<_> = rules
-            .into_iter()
-            .filter_map(|x| match x {
-                Ok((rule_structure, group, _)) => {
+        let results: Vec<Result<Option<DisambiguationRule>, RuleError>> = rules
+            .into_maybe_par_iter()
+            .map(|x| match x {
+                Ok((rule_structure, group, _, line)) => {
                     let id = rule_structure.id.as_ref().map_or_else(
                         || {
                             let group = group.expect("must have group if ID not set");
@@ -182,31 +282,43 @@ impl Tokenizer {
 
                     match DisambiguationRule::from_rule_structure(rule_structure, build_info) {
                         Ok(mut rule) => {
-                            if error.is_none()
-                                && (options.ids.is_empty() || options.ids.contains(&id))
+                            if (options.ids.is_empty() || options.ids.contains(&id))
                                 && !options.ignore_ids.contains(&id)
                             {
                                 rule.id = id;
 
-                                Some(rule)
+                                Ok(Some(rule))
                             } else {
-                                None
+                                Ok(None)
                             }
                         }
-                        Err(x) => {
-                            error = Some(format!("[Rule] {}", x));
-                            None
-                        }
+                        Err(x) => Err(RuleError {
+                            path: path_str.clone(),
+                            line,
+                            rule_id: Some(id),
+                            message: x.to_string(),
+                        }),
                     }
                 }
+                Err(x) => Err(x),
+            })
+            .collect();
+
+        // the rules after an error are dropped since disambiguation rules depend on the ones before them
+        let mut errors = Vec::new();
+        let rules: Vec<_> = results
+            .into_iter()
+            .filter_map(|x| match x {
+                Ok(rule) => rule.filter(|_| errors.is_empty()),
                 Err(x) => {
-                    error = Some(format!("[Structure] {}", x));
+                    errors.push(x);
                     None
                 }
             })
             .collect();
+        errors.sort_by_key(|x| x.line);
 
-        if let Some(x) = error {
+        if let Some(x) = errors.last() {
             if options.allow_errors {
                 warn!("Error constructing Disambiguator: {}", x)
             } else {
@@ -214,12 +326,21 @@ impl Tokenizer {
             }
         }
 
-        Ok(Tokenizer {
+        let tokenizer = Tokenizer {
+            metadata: Metadata {
+                rule_count: rules.len(),
+                ..Metadata::default()
+            },
+            fingerprint: build_info.tagger().fingerprint(),
             tagger: build_info.tagger().clone(),
             chunker,
+            multiword_tagger,
             rules,
             options,
-        })
+            hard_wrapped: false,
+            checksum: Default::default(),
+        };
+        Ok((tokenizer, errors))
     }
 }
 
@@ -242,6 +363,58 @@ impl From<ModelData> for chunk::Model {
     }
 }
 
+impl MultiwordTagger {
+    /// Reads multi-word expressions from a file where each line contains an expression and its part-of-speech tag,
+    /// separated by a tab. Lines starting with `#` are ignored.
+    pub fn from_dump<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        use std::io::BufRead;
+
+        let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut multiwords: DefaultHashMap<String, Vec<Multiword>> = DefaultHashMap::default();
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+
+            let mut parts = line.split('\t');
+            let (phrase, tag) = match (parts.next(), parts.next()) {
+                (Some(phrase), Some(tag)) => (phrase.trim(), tag.trim()),
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("multiword line must contain a tab: {:?}", line),
+                    ))
+                }
+            };
+
+            // split the same way as the tokenizer does, so the tokens can be compared directly
+            let mut tokens = crate::tokenizer::get_token_strs(phrase)
+                .into_iter()
+                .map(|x| x.trim())
+                .filter(|x| !x.is_empty());
+
+            if let Some(first) = tokens.next() {
+                multiwords
+                    .entry(first.to_string())
+                    .or_default()
+                    .push(Multiword {
+                        rest: tokens.map(|x| x.to_string()).collect(),
+                        phrase: phrase.to_string(),
+                        tag: tag.to_string(),
+                    });
+            }
+        }
+
+        for candidates in multiwords.values_mut() {
+            candidates.sort_by_key(|x| std::cmp::Reverse(x.rest.len()));
+        }
+
+        Ok(MultiwordTagger { multiwords })
+    }
+}
+
 impl chunk::Chunker {
     pub fn from_json<R: std::io::Read>(reader: R) -> chunk::Chunker {
         #[derive(Serialize, Deserialize)]
@@ -261,9 +434,9 @@ impl chunk::Chunker {
                 model: chunk_data.pos_model.into(),
                 tagdict: chunk_data.pos_tagdict,
             },
-            chunk_model: chunk::MaxentChunker {
+            chunk_model: chunk::ChunkModel::Maxent(chunk::MaxentChunker {
                 model: chunk_data.chunk_model.into(),
-            },
+            }),
         }
     }
 }
@@ -386,15 +559,49 @@ mod composition {
                 }
             }
 
-            let can_stop_mask = (0..parts.len())
-                .map(|i| parts[i..].iter().all(|x| x.quantifier.min == 0))
-                .collect();
-
             Composition {
                 parts,
                 group_ids_to_idx,
-                can_stop_mask,
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::parse_structure::RegexCache;
+    use std::sync::Arc;
+
+    #[test]
+    fn checks_expanded_contractions() {
+        let mut tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        tokenizer.options.contractions.insert(
+            "du".into(),
+            vec![("d".into(), "de".into()), ("u".into(), "le".into())],
+        );
+
+        // no token of the text is "le", the rule only matches the parts of the contraction
+        let xml = r#"<rules lang="fr">
+<category id="GRAMMAIRE" name="Grammaire">
+<rule id="DU_FEMININ" name="du + nom féminin">
+    <pattern><marker><token>de</token><token>le</token></marker><token>table</token></pattern>
+    <message>Utilisez <suggestion>de la</suggestion>.</message>
+    <example correction="de la">Il parle <marker>du</marker> table.</example>
+</rule>
+</category>
+</rules>
+"#;
+        let path = std::env::temp_dir().join(format!("nlprule-fr-{}.xml", std::process::id()));
+        std::fs::write(&path, xml).unwrap();
+        let info = BuildInfo::new(Arc::clone(tokenizer.tagger()), RegexCache::new(0));
+        let (rules, errors) = Rules::from_xml(&path, &info, RulesOptions::default());
+        std::fs::remove_file(&path).unwrap();
+        assert!(errors.is_empty());
+
+        let suggestions = rules.suggest("Il parle du table.", &tokenizer);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!((suggestions[0].start, suggestions[0].end), (9, 11));
+        assert_eq!(suggestions[0].replacements, vec!["de la"]);
+    }
+}
@@ -1,79 +1,518 @@
 use std::{
-    collections::HashSet,
-    fs::{read_to_string, File},
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fmt,
+    fs::{self, read_to_string, File},
     hash::{Hash, Hasher},
     io::{BufReader, BufWriter},
+    path::Path,
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use clap::Clap;
+use log::info;
+use serde::Deserialize;
 
 use crate::{
+    component::Component,
     rules::{Rules, RulesOptions},
-    tokenizer::{chunk::Chunker, tag::Tagger, Tokenizer, TokenizerOptions},
+    tokenizer::{
+        chunk::{ChunkModel, Chunker, PerceptronChunker},
+        multiword::MultiwordTagger,
+        tag::{Tagger, TaggerExtension},
+        Tokenizer, TokenizerOptions,
+    },
     types::DefaultHasher,
+    utils::StableHasher,
 };
 
 use self::parse_structure::{BuildInfo, RegexCache};
 
 mod impls;
+mod opennlp;
 mod parse_structure;
+mod perceptron;
 mod structure;
 
-#[derive(Clap)]
+/// The parts of the pipeline to compile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    /// The tokenizer and the rules.
+    All,
+    /// Only the tokenizer.
+    Tokenizer,
+    /// Only the rules.
+    Rules,
+}
+
+impl Target {
+    fn tokenizer(&self) -> bool {
+        *self != Target::Rules
+    }
+
+    fn rules(&self) -> bool {
+        *self != Target::Tokenizer
+    }
+}
+
+/// The inputs and outputs of a build. Can be given as flags or as part of a [manifest][read_manifest].
+/// Paths are relative to the working directory.
+#[derive(Clap, Deserialize)]
 #[clap(
     version = env!("CARGO_PKG_VERSION"),
     author = "Benjamin Minixhofer <bminixhofer@gmail.com>"
 )]
 pub struct BuildOptions {
+    /// The code of the language the binaries are built for e. g. "en".
+    /// In a manifest, this is the key of the options.
+    #[clap(long)]
+    #[serde(default)]
+    pub lang_code: String,
+    /// The version of LanguageTool the data is taken from e. g. "5.2".
+    #[clap(long)]
+    #[serde(default)]
+    pub lt_version: Option<String>,
     #[clap(long)]
     pub tag_paths: Vec<String>,
     #[clap(long)]
+    #[serde(default)]
     pub tag_remove_paths: Vec<String>,
+    /// Required to compile the tokenizer.
     #[clap(long)]
-    pub disambiguation_path: String,
+    #[serde(default)]
+    pub disambiguation_path: Option<String>,
+    /// Required to compile the rules.
     #[clap(long)]
-    pub grammar_path: String,
+    #[serde(default)]
+    pub grammar_path: Option<String>,
     #[clap(long)]
     pub tokenizer_config_path: String,
+    /// Required to compile the rules.
     #[clap(long)]
-    pub rules_config_path: String,
+    #[serde(default)]
+    pub rules_config_path: Option<String>,
+    /// Path to a chunker exported to JSON with `serialize_chunker.py`.
     #[clap(long)]
+    #[serde(default)]
     pub chunker_path: Option<String>,
+    /// Paths to the binary OpenNLP tokenizer, part-of-speech and chunker models to create the chunker from,
+    /// in this order. Alternative to `--chunker-path`.
+    #[clap(long, number_of_values = 3)]
+    #[serde(default)]
+    pub chunker_models: Vec<String>,
+    /// Path to an averaged perceptron chunk model trained with the `train_chunker` binary.
+    /// Replaces the chunk model of the chunker, which makes chunking several times faster.
     #[clap(long)]
+    #[serde(default)]
+    pub perceptron_chunker_path: Option<String>,
+    /// Path to a file with common words, one per line from most to least frequent. Unless `--frequency-path`
+    /// is given, the frequency ranks of the tagger are taken from the order of the words.
+    #[clap(long)]
+    #[serde(default)]
     pub common_words_path: Option<String>,
+    /// Path to a file with word counts of a corpus, one word and its count per line separated by a tab.
+    /// The frequency ranks of the tagger are computed from the counts.
+    #[clap(long)]
+    #[serde(default)]
+    pub frequency_path: Option<String>,
+    /// Paths to files with translated rule messages, one file per locale, see
+    /// [read_translations][parse_structure::read_translations]. Select a locale with
+    /// [CheckOptions::message_locale][crate::rules::CheckOptions::message_locale].
+    #[clap(long)]
+    #[serde(default)]
+    pub message_paths: Vec<String>,
+    /// Path to a corpus of correct text with one sentence per line. The rules which match any sentence are listed
+    /// in the report as likely false positives, see [Rules::false_positives]. Needs the tokenizer, which is taken
+    /// from `--out-tokenizer-path` if only the rules are compiled.
+    #[clap(long)]
+    #[serde(default)]
+    pub negative_corpus_path: Option<String>,
+    /// Path to a file with multi-word expressions and their tags, one per line separated by a tab.
+    #[clap(long)]
+    #[serde(default)]
+    pub multiwords_path: Option<String>,
     #[clap(long)]
     pub regex_cache_path: String,
+    /// Required to compile the tokenizer.
+    #[clap(long)]
+    #[serde(default)]
+    pub out_tokenizer_path: Option<String>,
+    /// Required to compile the rules.
+    #[clap(long)]
+    #[serde(default)]
+    pub out_rules_path: Option<String>,
+    /// Whether to gzip compress the output binaries.
+    #[clap(long)]
+    #[serde(default)]
+    pub compress: bool,
+    /// A directory to cache intermediate results such as the tagger in. Results are reused
+    /// in later runs if their inputs did not change.
+    #[clap(long)]
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+}
+
+/// The inputs and outputs of training an averaged perceptron chunker, see [train_chunker].
+#[derive(Clap)]
+#[clap(
+    version = env!("CARGO_PKG_VERSION"),
+    author = "Benjamin Minixhofer <bminixhofer@gmail.com>"
+)]
+pub struct TrainOptions {
+    /// Path to the training data in the CoNLL-2000 format i. e. one "word tag chunk" triple per line
+    /// and an empty line after each sentence.
+    #[clap(long)]
+    pub train_path: String,
+    /// Path to held out data in the same format to compute the accuracy on.
+    #[clap(long)]
+    pub test_path: Option<String>,
+    /// The number of passes over the training data.
+    #[clap(long, default_value = "10")]
+    pub epochs: usize,
+    /// Where to write the model. Pass it to `--perceptron-chunker-path` when compiling.
+    #[clap(long)]
+    pub out_path: String,
+}
+
+/// The inputs and outputs of compiling a [TaggerExtension], see [compile_extension].
+#[derive(Clap)]
+#[clap(
+    version = env!("CARGO_PKG_VERSION"),
+    author = "Benjamin Minixhofer <bminixhofer@gmail.com>"
+)]
+pub struct ExtensionOptions {
+    /// Paths to files with the words of the extension, in the same formats as `--tag-paths`.
+    #[clap(long, required = true)]
+    pub tag_paths: Vec<String>,
+    /// Path to a tokenizer binary. If given, the extension is checked to only use tags of its tagger.
+    #[clap(long)]
+    pub tokenizer_path: Option<String>,
+    /// Where to write the extension.
     #[clap(long)]
-    pub out_tokenizer_path: String,
+    pub out_path: String,
+    /// Whether to gzip compress the output binary.
     #[clap(long)]
-    pub out_rules_path: String,
+    pub compress: bool,
+}
+
+/// Compiles a tagger extension and writes it to `opts.out_path`. Returns the number of words in the extension.
+pub fn compile_extension(opts: &ExtensionOptions) -> Result<usize, Box<dyn Error>> {
+    let extension = TaggerExtension::from_dumps(&opts.tag_paths)?;
+
+    if let Some(path) = &opts.tokenizer_path {
+        let tokenizer = Tokenizer::new(path)?;
+        if let Some(tag) = extension.tags().find(|x| !tokenizer.tagger().has_tag(x)) {
+            return Err(format!("the tagger of {} does not know the tag {}", path, tag).into());
+        }
+    }
+
+    extension.write(BufWriter::new(File::create(&opts.out_path)?), opts.compress)?;
+    Ok(extension.len())
+}
+
+/// Trains an averaged perceptron chunker and writes it to `opts.out_path`.
+/// Returns the share of correctly chunked tokens in the held out data, if there is any.
+pub fn train_chunker(opts: &TrainOptions) -> Result<Option<f32>, Box<dyn Error>> {
+    let sentences = perceptron::read_sentences(&opts.train_path)?;
+    let model = PerceptronChunker::train(&sentences, opts.epochs);
+
+    let accuracy = if let Some(path) = &opts.test_path {
+        Some(model.accuracy(&perceptron::read_sentences(path)?))
+    } else {
+        None
+    };
+
+    bincode::serialize_into(BufWriter::new(File::create(&opts.out_path)?), &model)?;
+    Ok(accuracy)
+}
+
+/// Reads a JSON build manifest mapping language codes to their [BuildOptions], e. g.
+/// `{"en": {"tag_paths": ["data/en/tags/output.dump"], ...}}`. Returns the options ordered by language code.
+pub fn read_manifest<P: AsRef<Path>>(path: P) -> Result<Vec<BuildOptions>, Box<dyn Error>> {
+    let manifest: BTreeMap<String, BuildOptions> = serde_json::from_str(&read_to_string(path)?)?;
+
+    Ok(manifest
+        .into_iter()
+        .map(|(lang_code, mut opts)| {
+            opts.lang_code = lang_code;
+            opts
+        })
+        .collect())
+}
+
+fn required<'a>(path: &'a Option<String>, flag: &str, target: &str) -> &'a str {
+    path.as_deref()
+        .unwrap_or_else(|| panic!("{} is required to compile the {}", flag, target))
+}
+
+/// A rule which could not be read or compiled. The rule is skipped, the rest of the file is still compiled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleError {
+    /// The path of the XML file.
+    pub path: String,
+    /// The line the rule starts at, counting from 1.
+    pub line: usize,
+    /// The ID of the rule or its rule group if it could be read.
+    pub rule_id: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: ", self.path, self.line)?;
+        if let Some(id) = &self.rule_id {
+            write!(f, "{}: ", id)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The result of [compile_with_progress] besides the binaries.
+#[derive(Debug, Clone, Default)]
+pub struct CompileReport {
+    /// The skipped rules, ordered by file and line.
+    pub errors: Vec<RuleError>,
+    /// The IDs of the rules which match sentences of the negative corpus together with the lines of the
+    /// sentences counting from 1, see [BuildOptions::negative_corpus_path].
+    pub false_positives: Vec<(String, Vec<usize>)>,
+}
+
+/// Progress of a compilation, reported to the callback of [compile_with_progress].
+#[derive(Debug, Clone, Copy)]
+pub enum Progress<'a> {
+    /// A phase of the compilation started.
+    Started(&'a str),
+    /// A phase of the compilation finished after the given duration.
+    Finished(&'a str, Duration),
+    /// A phase was skipped because its output was found in the cache directory.
+    Cached(&'a str),
+}
+
+impl<'a> fmt::Display for Progress<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Progress::Started(phase) => write!(f, "{}...", phase),
+            Progress::Finished(phase, duration) => {
+                write!(f, "{} done in {:.2}s", phase, duration.as_secs_f64())
+            }
+            Progress::Cached(phase) => write!(f, "{} loaded from cache", phase),
+        }
+    }
 }
 
-pub fn compile(opts: &BuildOptions) {
-    let common_words = opts
-        .common_words_path
+fn phase<T>(progress: &mut dyn FnMut(Progress), name: &str, f: impl FnOnce() -> T) -> T {
+    progress(Progress::Started(name));
+    let start = Instant::now();
+    let output = f();
+    progress(Progress::Finished(name, start.elapsed()));
+
+    output
+}
+
+/// Loads a component from `{cache_dir}/{name}_{hash}.bin` if it exists, otherwise builds it and stores it there.
+/// `hash` must identify the inputs of the component.
+fn cached<C: Component>(
+    progress: &mut dyn FnMut(Progress),
+    cache_dir: Option<&str>,
+    hash: u64,
+    build: impl FnOnce() -> C,
+) -> C {
+    let path = cache_dir.map(|dir| Path::new(dir).join(format!("{}_{:016x}.bin", C::name(), hash)));
+
+    if let Some(component) = path
         .as_ref()
-        .map_or_else(HashSet::new, |path| {
-            read_to_string(path)
-                .unwrap()
-                .lines()
-                .map(|x| x.to_string())
-                .collect()
-        });
+        .and_then(|path| File::open(path).ok())
+        .and_then(|file| C::read(BufReader::new(file)).ok())
+    {
+        progress(Progress::Cached(C::name()));
+        return component;
+    }
+
+    let component = phase(progress, C::name(), build);
+
+    if let Some(path) = path {
+        component
+            .write(BufWriter::new(create(path)), false)
+            .unwrap();
+    }
+
+    component
+}
+
+/// Reads a file of word counts and returns the words from most to least frequent.
+fn read_frequencies(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut counts = Vec::new();
+
+    for (i, line) in read_to_string(path)?.lines().enumerate() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (word, count) = line
+            .split_once('\t')
+            .ok_or_else(|| format!("{}:{}: expected a word and a count", path, i + 1))?;
+        let count: u64 = count
+            .trim()
+            .parse()
+            .map_err(|_| format!("{}:{}: invalid count {}", path, i + 1, count))?;
+        counts.push((word.to_string(), count));
+    }
+
+    // ties are ordered by word so the ranks are the same across runs
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(counts.into_iter().map(|(word, _)| word).collect())
+}
+
+fn hash_files<S: AsRef<str>>(hasher: &mut StableHasher, paths: &[S]) {
+    for path in paths {
+        hasher.write(&fs::read(path.as_ref()).unwrap());
+        // separates the contents of the files
+        hasher.write(&[0xff]);
+    }
+}
+
+fn write<C: Component>(path: &str, component: &C, compress: bool) {
+    component
+        .validate()
+        .unwrap_or_else(|x| panic!("invalid {}: {}", C::name(), x));
+
+    let f = BufWriter::new(create(path));
+    component.write(f, compress).unwrap();
+}
+
+// creates the file together with the directories it is in
+fn create<P: AsRef<Path>>(path: P) -> File {
+    if let Some(parent) = path.as_ref().parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    File::create(path).unwrap()
+}
+
+/// Compiles the tokenizer and rules binaries. Same as [compile_with_progress] but logs the progress.
+pub fn compile(opts: &BuildOptions) -> CompileReport {
+    compile_with_progress(opts, Target::All, &mut |progress| info!("{}", progress))
+}
+
+/// Compiles the binaries of the target, calling `progress` at the start and end of each phase.
+/// Rules which can not be read or compiled are skipped and listed in the returned report.
+///
+/// # Panics
+/// Panics if an input can not be read or parsed, or if an option required for the target is missing.
+pub fn compile_with_progress(
+    opts: &BuildOptions,
+    target: Target,
+    progress: &mut dyn FnMut(Progress),
+) -> CompileReport {
+    let mut report = CompileReport::default();
+    // ordered from most to least frequent
+    let common_words_list: Vec<String> =
+        opts.common_words_path
+            .as_ref()
+            .map_or_else(Vec::new, |path| {
+                read_to_string(path)
+                    .unwrap()
+                    .lines()
+                    .map(|x| x.to_string())
+                    .collect()
+            });
+    let common_words: HashSet<String> = common_words_list.iter().cloned().collect();
 
     let tokenizer_options: TokenizerOptions =
         serde_json::from_str(&read_to_string(&opts.tokenizer_config_path).unwrap()).unwrap();
-    let rules_options: RulesOptions =
-        serde_json::from_str(&read_to_string(&opts.rules_config_path).unwrap()).unwrap();
 
-    let tagger = Tagger::from_dumps(
-        &opts.tag_paths,
-        &opts.tag_remove_paths,
-        &tokenizer_options.extra_tags,
-        &common_words,
-    )
-    .unwrap();
+    let multiword_tagger = opts
+        .multiwords_path
+        .as_ref()
+        .map(|path| MultiwordTagger::from_dump(path).unwrap());
+    // the tags of multiwords are not necessarily in the tag dumps
+    let mut extra_tags = tokenizer_options.extra_tags.clone();
+    if let Some(multiword_tagger) = &multiword_tagger {
+        extra_tags.extend(multiword_tagger.tags().map(|x| x.to_string()));
+    }
+    extra_tags.sort();
+    extra_tags.dedup();
+
+    let mut tagger_hasher = StableHasher::default();
+    // cached taggers of older format versions lack data which is only computed at build time
+    tagger_hasher.write(&Tagger::format_version().to_le_bytes());
+    hash_files(&mut tagger_hasher, &opts.tag_paths);
+    hash_files(&mut tagger_hasher, &opts.tag_remove_paths);
+    hash_files(
+        &mut tagger_hasher,
+        &opts.frequency_path.iter().collect::<Vec<_>>(),
+    );
+    for word in extra_tags.iter().chain(&common_words_list) {
+        tagger_hasher.write(word.as_bytes());
+        tagger_hasher.write(&[0xff]);
+    }
+
+    let tagger = cached(
+        progress,
+        opts.cache_dir.as_deref(),
+        tagger_hasher.finish(),
+        || {
+            let mut tagger = Tagger::from_dumps(
+                &opts.tag_paths,
+                &opts.tag_remove_paths,
+                &extra_tags,
+                &common_words,
+            )
+            .unwrap();
+
+            let frequent_words = match &opts.frequency_path {
+                Some(path) => read_frequencies(path).unwrap(),
+                None => common_words_list.clone(),
+            };
+            tagger.set_frequencies(&frequent_words);
+
+            tagger
+        },
+    );
+
+    let chunker = if let Some(path) = &opts.chunker_path {
+        let mut hasher = StableHasher::default();
+        hash_files(&mut hasher, &[path]);
+
+        Some(cached(
+            progress,
+            opts.cache_dir.as_deref(),
+            hasher.finish(),
+            || {
+                let reader = BufReader::new(File::open(path).unwrap());
+                Chunker::from_json(reader)
+            },
+        ))
+    } else if let [token_path, pos_path, chunk_path] = opts.chunker_models.as_slice() {
+        let mut hasher = StableHasher::default();
+        hash_files(&mut hasher, &opts.chunker_models);
+
+        Some(cached(
+            progress,
+            opts.cache_dir.as_deref(),
+            hasher.finish(),
+            || Chunker::from_opennlp(token_path, pos_path, chunk_path).unwrap(),
+        ))
+    } else {
+        assert!(
+            opts.chunker_models.is_empty(),
+            "--chunker-models takes the tokenizer, part-of-speech and chunker model"
+        );
+        None
+    };
+
+    let chunker = match (chunker, &opts.perceptron_chunker_path) {
+        (Some(mut chunker), Some(path)) => {
+            let reader = BufReader::new(File::open(path).unwrap());
+            chunker.chunk_model =
+                ChunkModel::Perceptron(bincode::deserialize_from(reader).unwrap());
+            Some(chunker)
+        }
+        (None, Some(_)) => {
+            panic!("--perceptron-chunker-path needs --chunker-path or --chunker-models")
+        }
+        (chunker, None) => chunker,
+    };
 
     let mut hasher = DefaultHasher::default();
     let mut word_store = tagger.word_store().iter().collect::<Vec<_>>();
@@ -92,30 +531,101 @@ pub fn compile(opts: &BuildOptions) {
         RegexCache::new(word_store_hash)
     };
 
-    let mut build_info = BuildInfo::new(Arc::new(tagger), regex_cache);
+    let build_info = BuildInfo::new(Arc::new(tagger), regex_cache);
 
-    let tokenizer = Tokenizer::from_xml(
-        &opts.disambiguation_path,
-        &mut build_info,
-        if let Some(path) = &opts.chunker_path {
-            let reader = BufReader::new(File::open(path).unwrap());
-            let chunker = Chunker::from_json(reader);
-            Some(chunker)
-        } else {
-            None
-        },
-        tokenizer_options,
-    )
-    .unwrap();
+    let mut compiled_tokenizer = None;
+    if target.tokenizer() {
+        let (mut tokenizer, errors) = phase(progress, "disambiguation rules", || {
+            Tokenizer::from_xml(
+                required(
+                    &opts.disambiguation_path,
+                    "--disambiguation-path",
+                    "tokenizer",
+                ),
+                &build_info,
+                chunker,
+                multiword_tagger,
+                tokenizer_options,
+            )
+            .unwrap()
+        });
+        report.errors.extend(errors);
+        tokenizer.metadata.lang_code = opts.lang_code.clone();
+        tokenizer.metadata.lt_version = opts.lt_version.clone();
+
+        let out_path = required(
+            &opts.out_tokenizer_path,
+            "--out-tokenizer-path",
+            "tokenizer",
+        );
+        phase(progress, "writing tokenizer", || {
+            write(out_path, &tokenizer, opts.compress)
+        });
+        compiled_tokenizer = Some(tokenizer);
+    }
 
-    let f = BufWriter::new(File::create(&opts.out_tokenizer_path).unwrap());
-    bincode::serialize_into(f, &tokenizer).unwrap();
+    if target.rules() {
+        let rules_options: RulesOptions = serde_json::from_str(
+            &read_to_string(required(
+                &opts.rules_config_path,
+                "--rules-config-path",
+                "rules",
+            ))
+            .unwrap(),
+        )
+        .unwrap();
 
-    let rules = Rules::from_xml(&opts.grammar_path, &mut build_info, rules_options);
+        let (mut rules, errors) = phase(progress, "grammar rules", || {
+            Rules::from_xml(
+                required(&opts.grammar_path, "--grammar-path", "rules"),
+                &build_info,
+                rules_options,
+            )
+        });
+        report.errors.extend(errors);
+        for path in &opts.message_paths {
+            let errors = phase(progress, "translated messages", || {
+                rules.translate_from_xml(path, &build_info)
+            });
+            report.errors.extend(errors);
+        }
+        rules.metadata.lang_code = opts.lang_code.clone();
+        rules.metadata.lt_version = opts.lt_version.clone();
+
+        let out_path = required(&opts.out_rules_path, "--out-rules-path", "rules");
+        phase(progress, "writing rules", || {
+            write(out_path, &rules, opts.compress)
+        });
+
+        if let Some(path) = &opts.negative_corpus_path {
+            let tokenizer = compiled_tokenizer.unwrap_or_else(|| {
+                let path = required(
+                    &opts.out_tokenizer_path,
+                    "--out-tokenizer-path",
+                    "negative corpus",
+                );
+                Tokenizer::new(path).unwrap()
+            });
+            let corpus = read_to_string(path).unwrap();
+            let lines: Vec<_> = corpus.lines().collect();
+
+            report.false_positives = phase(progress, "negative corpus", || {
+                rules
+                    .false_positives(&lines, &tokenizer)
+                    .into_iter()
+                    .map(|(rule, indices)| {
+                        (
+                            rule.id().to_string(),
+                            indices.into_iter().map(|i| i + 1).collect(),
+                        )
+                    })
+                    .collect()
+            });
+        }
+    }
 
-    let f = BufWriter::new(File::create(&opts.regex_cache_path).unwrap());
-    bincode::serialize_into(f, build_info.mut_regex_cache()).unwrap();
+    let f = BufWriter::new(create(&opts.regex_cache_path));
+    bincode::serialize_into(f, &build_info.into_regex_cache()).unwrap();
 
-    let f = BufWriter::new(File::create(&opts.out_rules_path).unwrap());
-    bincode::serialize_into(f, &rules).unwrap();
+    report
 }
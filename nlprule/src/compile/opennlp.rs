@@ -0,0 +1,404 @@
+//! Reading of the binary OpenNLP models the chunker is ported from, so that the [Chunker] can be created
+//! directly from the models distributed by OpenNLP (e. g. `en-chunker.bin`).
+//!
+//! A model is a ZIP archive with a maximum entropy model in the binary GIS format of OpenNLP
+//! (written by a Java `DataOutputStream`) and, for the part-of-speech model, an XML tag dictionary.
+
+use std::{
+    fs,
+    io::{self, ErrorKind, Read},
+    path::Path,
+};
+
+use flate2::read::DeflateDecoder;
+
+use crate::{
+    tokenizer::chunk::{self, Chunker},
+    types::DefaultHashMap,
+};
+
+fn invalid<T>(message: impl Into<String>) -> io::Result<T> {
+    Err(io::Error::new(ErrorKind::InvalidData, message.into()))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> io::Result<usize> {
+    match bytes.get(offset..offset + 2) {
+        Some(x) => Ok(u16::from_le_bytes([x[0], x[1]]) as usize),
+        None => invalid("unexpected end of ZIP archive"),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> io::Result<usize> {
+    match bytes.get(offset..offset + 4) {
+        Some(x) => Ok(u32::from_le_bytes([x[0], x[1], x[2], x[3]]) as usize),
+        None => invalid("unexpected end of ZIP archive"),
+    }
+}
+
+/// Reads the file with the given name from a ZIP archive. Only stored and deflated files are supported, ZIP64
+/// archives are not.
+fn read_zip_entry(archive: &[u8], name: &str) -> io::Result<Vec<u8>> {
+    const END_SIGNATURE: &[u8] = &[0x50, 0x4b, 0x05, 0x06];
+    const ZIP64_LOCATOR_SIGNATURE: &[u8] = &[0x50, 0x4b, 0x06, 0x07];
+    const ENTRY_SIGNATURE: usize = 0x0201_4b50;
+    // fields of this value are stored in the ZIP64 extra fields instead
+    const ZIP64_U16: usize = 0xffff;
+    const ZIP64_U32: usize = 0xffff_ffff;
+
+    let end = match archive.windows(4).rposition(|x| x == END_SIGNATURE) {
+        Some(end) => end,
+        None => return invalid("not a ZIP archive"),
+    };
+    let n_entries = read_u16(archive, end + 10)?;
+    let mut offset = read_u32(archive, end + 16)?;
+    let has_zip64_locator =
+        end >= 20 && archive.get(end - 20..end - 16) == Some(ZIP64_LOCATOR_SIGNATURE);
+    if has_zip64_locator || n_entries == ZIP64_U16 || offset == ZIP64_U32 {
+        return invalid("ZIP64 archives are not supported");
+    }
+
+    for _ in 0..n_entries {
+        if read_u32(archive, offset)? != ENTRY_SIGNATURE {
+            return invalid("invalid ZIP central directory");
+        }
+
+        let method = read_u16(archive, offset + 10)?;
+        let compressed_size = read_u32(archive, offset + 20)?;
+        let name_length = read_u16(archive, offset + 28)?;
+        let entry_length =
+            46 + name_length + read_u16(archive, offset + 30)? + read_u16(archive, offset + 32)?;
+        let header_offset = read_u32(archive, offset + 42)?;
+        if compressed_size == ZIP64_U32 || header_offset == ZIP64_U32 {
+            return invalid("ZIP64 archives are not supported");
+        }
+
+        if archive.get(offset + 46..offset + 46 + name_length) == Some(name.as_bytes()) {
+            // the local header has its own name and extra field lengths
+            let data_start = header_offset
+                + 30
+                + read_u16(archive, header_offset + 26)?
+                + read_u16(archive, header_offset + 28)?;
+            let data = match archive.get(data_start..data_start + compressed_size) {
+                Some(data) => data,
+                None => return invalid("unexpected end of ZIP archive"),
+            };
+
+            return match method {
+                0 => Ok(data.to_vec()),
+                8 => {
+                    let mut output = Vec::new();
+                    DeflateDecoder::new(data).read_to_end(&mut output)?;
+                    Ok(output)
+                }
+                _ => invalid(format!("unsupported ZIP compression method {}", method)),
+            };
+        }
+
+        offset += entry_length;
+    }
+
+    invalid(format!("ZIP archive does not contain {}", name))
+}
+
+/// Reads the big endian primitives written by a Java `DataOutputStream`.
+struct DataInput<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> DataInput<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < n {
+            return invalid("unexpected end of model");
+        }
+
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn read_int(&mut self) -> io::Result<i32> {
+        let x = self.take(4)?;
+        Ok(i32::from_be_bytes([x[0], x[1], x[2], x[3]]))
+    }
+
+    fn read_count(&mut self) -> io::Result<usize> {
+        match self.read_int()? {
+            x if x >= 0 => Ok(x as usize),
+            x => invalid(format!("negative count {}", x)),
+        }
+    }
+
+    fn read_double(&mut self) -> io::Result<f64> {
+        let mut x = [0; 8];
+        x.copy_from_slice(self.take(8)?);
+        Ok(f64::from_be_bytes(x))
+    }
+
+    // Java uses "modified UTF-8": code units of UTF-16 encoded with one to three bytes each
+    fn read_utf(&mut self) -> io::Result<String> {
+        let length = self.take(2)?;
+        let bytes = self.take(u16::from_be_bytes([length[0], length[1]]) as usize)?;
+
+        let mut units = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let (unit, width) = match bytes[i] {
+                x if x & 0x80 == 0 => (x as u16, 1),
+                x if x & 0xe0 == 0xc0 && i + 1 < bytes.len() => {
+                    ((((x & 0x1f) as u16) << 6) | (bytes[i + 1] & 0x3f) as u16, 2)
+                }
+                x if x & 0xf0 == 0xe0 && i + 2 < bytes.len() => (
+                    (((x & 0x0f) as u16) << 12)
+                        | (((bytes[i + 1] & 0x3f) as u16) << 6)
+                        | (bytes[i + 2] & 0x3f) as u16,
+                    3,
+                ),
+                _ => return invalid("invalid modified UTF-8"),
+            };
+
+            units.push(unit);
+            i += width;
+        }
+
+        String::from_utf16(&units).or_else(|_| invalid("invalid modified UTF-8"))
+    }
+}
+
+/// Reads a maximum entropy model in the binary GIS format.
+fn read_model(bytes: &[u8]) -> io::Result<chunk::Model> {
+    let mut input = DataInput { bytes };
+
+    let model_type = input.read_utf()?;
+    if model_type != "GIS" {
+        return invalid(format!("unsupported model type {}", model_type));
+    }
+
+    // the correction constant and parameter are not used anymore
+    input.read_int()?;
+    input.read_double()?;
+
+    let outcome_labels = (0..input.read_count()?)
+        .map(|_| input.read_utf())
+        .collect::<io::Result<Vec<_>>>()?;
+
+    // a pattern is the number of predicates it applies to, followed by the indices of its outcomes
+    let outcome_patterns = (0..input.read_count()?)
+        .map(|_| {
+            input
+                .read_utf()?
+                .split(' ')
+                .map(|x| {
+                    x.parse::<usize>()
+                        .or_else(|_| invalid("invalid outcome pattern"))
+                })
+                .collect::<io::Result<Vec<_>>>()
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let pred_labels = (0..input.read_count()?)
+        .map(|_| input.read_utf())
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut pred_labels = pred_labels.into_iter();
+    let mut pmap = DefaultHashMap::default();
+
+    for pattern in &outcome_patterns {
+        let (count, outcomes) = match pattern.split_first() {
+            Some((count, outcomes)) => (*count, outcomes),
+            None => return invalid("empty outcome pattern"),
+        };
+
+        for _ in 0..count {
+            let label = match pred_labels.next() {
+                Some(label) => label,
+                None => return invalid("outcome patterns refer to more predicates than exist"),
+            };
+            let parameters = (0..outcomes.len())
+                .map(|_| input.read_double().map(|x| x as f32))
+                .collect::<io::Result<Vec<_>>>()?;
+
+            pmap.insert(
+                chunk::hash::hash_str(&label),
+                chunk::Context {
+                    parameters,
+                    outcomes: outcomes.to_vec(),
+                },
+            );
+        }
+    }
+
+    Ok(chunk::Model {
+        outcome_labels,
+        pmap,
+    })
+}
+
+/// Reads the tag dictionary of a part-of-speech model, mapping words to the tags they can have.
+fn read_tagdict(bytes: &[u8]) -> io::Result<DefaultHashMap<String, Vec<String>>> {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(_) => return invalid("tag dictionary is not valid UTF-8"),
+    };
+    let document = match roxmltree::Document::parse(text) {
+        Ok(document) => document,
+        Err(error) => return invalid(format!("invalid tag dictionary: {}", error)),
+    };
+
+    Ok(document
+        .descendants()
+        .filter(|x| x.has_tag_name("entry"))
+        .filter_map(|entry| {
+            let token = entry.children().find(|x| x.has_tag_name("token"))?.text()?;
+            let tags = entry.attribute("tags")?;
+
+            Some((
+                token.to_string(),
+                tags.split_whitespace().map(|x| x.to_string()).collect(),
+            ))
+        })
+        .collect())
+}
+
+impl Chunker {
+    /// Creates a chunker from the binary OpenNLP tokenizer, part-of-speech and chunker models
+    /// (e. g. `en-token.bin`, `en-pos-maxent.bin` and `en-chunker.bin`).
+    pub fn from_opennlp<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
+        token_model_path: P1,
+        pos_model_path: P2,
+        chunk_model_path: P3,
+    ) -> io::Result<Self> {
+        let token_archive = fs::read(token_model_path)?;
+        let pos_archive = fs::read(pos_model_path)?;
+        let chunk_archive = fs::read(chunk_model_path)?;
+
+        Ok(Chunker {
+            token_model: chunk::MaxentTokenizer {
+                model: read_model(&read_zip_entry(&token_archive, "token.model")?)?,
+            },
+            pos_model: chunk::MaxentPosTagger {
+                model: read_model(&read_zip_entry(&pos_archive, "pos.model")?)?,
+                tagdict: read_tagdict(&read_zip_entry(&pos_archive, "tags.tagdict")?)?,
+            },
+            chunk_model: chunk::ChunkModel::Maxent(chunk::MaxentChunker {
+                model: read_model(&read_zip_entry(&chunk_archive, "chunker.model")?)?,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_utf(out: &mut Vec<u8>, string: &str) {
+        out.extend(&(string.len() as u16).to_be_bytes());
+        out.extend(string.as_bytes());
+    }
+
+    #[test]
+    fn reads_gis_model() {
+        let mut bytes = Vec::new();
+        write_utf(&mut bytes, "GIS");
+        bytes.extend(&1i32.to_be_bytes());
+        bytes.extend(&0f64.to_be_bytes());
+        bytes.extend(&2i32.to_be_bytes());
+        write_utf(&mut bytes, "B-NP");
+        write_utf(&mut bytes, "O");
+        // two predicates with both outcomes, one with only "O"
+        bytes.extend(&2i32.to_be_bytes());
+        write_utf(&mut bytes, "2 0 1");
+        write_utf(&mut bytes, "1 1");
+        bytes.extend(&3i32.to_be_bytes());
+        for label in &["w=the", "w=cat", "w=."] {
+            write_utf(&mut bytes, label);
+        }
+        for parameter in &[0.5f64, -0.5, 1.0, 2.0, -1.0] {
+            bytes.extend(&parameter.to_be_bytes());
+        }
+
+        let model = read_model(&bytes).unwrap();
+        assert_eq!(model.outcome_labels, vec!["B-NP", "O"]);
+
+        let context = &model.pmap[&chunk::hash::hash_str("w=cat")];
+        assert_eq!(context.outcomes, vec![0, 1]);
+        assert_eq!(context.parameters, vec![1.0, 2.0]);
+
+        let context = &model.pmap[&chunk::hash::hash_str("w=.")];
+        assert_eq!(context.outcomes, vec![1]);
+        assert_eq!(context.parameters, vec![-1.0]);
+    }
+
+    // a ZIP archive with one file "a.txt" with the given compression method and data, without the end of central
+    // directory record which is returned separately so tests can put other records before it
+    fn build_zip(method: u16, data: &[u8], uncompressed_size: usize) -> (Vec<u8>, Vec<u8>) {
+        let mut archive = Vec::new();
+        archive.extend(&[0x50, 0x4b, 0x03, 0x04]);
+        archive.extend(&[0; 22]);
+        archive.extend(&5u16.to_le_bytes());
+        archive.extend(&0u16.to_le_bytes());
+        archive.extend(b"a.txt");
+        archive.extend(data);
+
+        let directory = archive.len();
+        archive.extend(&[0x50, 0x4b, 0x01, 0x02]);
+        archive.extend(&[0; 6]);
+        archive.extend(&method.to_le_bytes());
+        archive.extend(&[0; 8]);
+        archive.extend(&(data.len() as u32).to_le_bytes());
+        archive.extend(&(uncompressed_size as u32).to_le_bytes());
+        archive.extend(&5u16.to_le_bytes());
+        archive.extend(&[0; 12]);
+        archive.extend(&0u32.to_le_bytes());
+        archive.extend(b"a.txt");
+        let directory_size = archive.len() - directory;
+
+        let mut end = Vec::new();
+        end.extend(&[0x50, 0x4b, 0x05, 0x06]);
+        end.extend(&[0; 6]);
+        end.extend(&1u16.to_le_bytes());
+        end.extend(&(directory_size as u32).to_le_bytes());
+        end.extend(&(directory as u32).to_le_bytes());
+        end.extend(&[0; 2]);
+
+        (archive, end)
+    }
+
+    #[test]
+    fn reads_stored_zip_entries() {
+        let (mut archive, end) = build_zip(0, b"hi", 2);
+        archive.extend(end);
+
+        assert_eq!(read_zip_entry(&archive, "a.txt").unwrap(), b"hi");
+        assert!(read_zip_entry(&archive, "b.txt").is_err());
+    }
+
+    #[test]
+    fn reads_deflated_zip_entries() {
+        use flate2::{write::DeflateEncoder, Compression};
+        use std::io::Write;
+
+        let text = "the cat sat on the mat, the cat sat on the mat".repeat(10);
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let data = encoder.finish().unwrap();
+        assert!(data.len() < text.len());
+
+        let (mut archive, end) = build_zip(8, &data, text.len());
+        archive.extend(end);
+
+        assert_eq!(read_zip_entry(&archive, "a.txt").unwrap(), text.as_bytes());
+    }
+
+    #[test]
+    fn rejects_zip64_archives() {
+        // a ZIP64 end of central directory locator directly before the end of the central directory
+        let (mut archive, end) = build_zip(0, b"hi", 2);
+        archive.extend(&[0x50, 0x4b, 0x06, 0x07]);
+        archive.extend(&[0; 16]);
+        archive.extend(end);
+
+        let error = read_zip_entry(&archive, "a.txt").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "ZIP64 archives are not supported");
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use super::structure;
 use crate::{filter::get_filter, utils, utils::regex::SerializeRegex, Error};
@@ -7,14 +7,15 @@ use lazy_static::lazy_static;
 use onig::Regex;
 use serde::{Deserialize, Serialize};
 
-pub use structure::{read_disambiguation_rules, read_rules};
+pub use structure::{read_disambiguation_rules, read_rules, read_translations};
 
 use crate::rule::disambiguation::*;
 use crate::rule::engine::composition::concrete::*;
 use crate::rule::engine::composition::*;
+use crate::rule::engine::unification::{Feature, Unification};
 use crate::rule::engine::*;
 use crate::rule::grammar::*;
-use crate::rule::{DisambiguationRule, Rule};
+use crate::rule::{DisambiguationRule, Rule, RuleTier};
 
 // TODO: should be an option in config OR restricted to one sentence
 fn max_matches() -> usize {
@@ -53,16 +54,17 @@ impl RegexCache {
     }
 }
 
+/// Shared by all rules while compiling, rules are compiled in parallel.
 pub struct BuildInfo {
     tagger: Arc<Tagger>,
-    regex_cache: RegexCache,
+    regex_cache: RwLock<RegexCache>,
 }
 
 impl BuildInfo {
     pub fn new(tagger: Arc<Tagger>, regex_cache: RegexCache) -> Self {
         BuildInfo {
             tagger,
-            regex_cache,
+            regex_cache: RwLock::new(regex_cache),
         }
     }
 
@@ -70,8 +72,12 @@ impl BuildInfo {
         &self.tagger
     }
 
-    pub fn mut_regex_cache(&mut self) -> &mut RegexCache {
-        &mut self.regex_cache
+    pub fn regex_cache(&self) -> &RwLock<RegexCache> {
+        &self.regex_cache
+    }
+
+    pub fn into_regex_cache(self) -> RegexCache {
+        self.regex_cache.into_inner().unwrap()
     }
 }
 
@@ -80,7 +86,7 @@ fn parse_match_attribs(
     text: Option<&str>,
     case_sensitive: bool,
     text_match_idx: Option<usize>,
-    info: &mut BuildInfo,
+    info: &BuildInfo,
 ) -> Result<Atom, Error> {
     let mut atoms: Vec<Atom> = Vec::new();
 
@@ -194,21 +200,17 @@ fn parse_match_attribs(
 
     match (attribs.chunk(), attribs.chunk_re()) {
         (Some(chunk), None) => {
-            let chunk_atom = ChunkAtom {
-                matcher: Matcher::new_string(
-                    either::Left(chunk.trim().to_string()),
-                    false,
-                    true,
-                    true,
-                ),
-            };
+            let chunk_atom = ChunkAtom::new(Matcher::new_string(
+                either::Left(chunk.trim().to_string()),
+                false,
+                true,
+                true,
+            ));
             atoms.push(chunk_atom.into());
         }
         (None, Some(chunk_re)) => {
             let regex = SerializeRegex::new(chunk_re.trim(), true, true)?;
-            let chunk_atom = ChunkAtom {
-                matcher: Matcher::new_regex(regex, false, true),
-            };
+            let chunk_atom = ChunkAtom::new(Matcher::new_regex(regex, false, true));
             atoms.push(chunk_atom.into());
         }
         (None, None) => {}
@@ -216,9 +218,12 @@ fn parse_match_attribs(
     }
 
     if let Some(chunk) = attribs.chunk() {
-        let chunk_atom = ChunkAtom {
-            matcher: Matcher::new_string(either::Left(chunk.trim().to_string()), false, true, true),
-        };
+        let chunk_atom = ChunkAtom::new(Matcher::new_string(
+            either::Left(chunk.trim().to_string()),
+            false,
+            true,
+            true,
+        ));
 
         atoms.push(chunk_atom.into());
     }
@@ -240,7 +245,7 @@ fn get_exceptions(
     token: &structure::Token,
     case_sensitive: bool,
     only_shifted: bool,
-    info: &mut BuildInfo,
+    info: &BuildInfo,
 ) -> Result<Atom, Error> {
     if let Some(parts) = &token.parts {
         let exceptions: Vec<Atom> = parts
@@ -289,7 +294,7 @@ fn get_exceptions(
 fn parse_token(
     token: &structure::Token,
     case_sensitive: bool,
-    info: &mut BuildInfo,
+    info: &BuildInfo,
 ) -> Result<Vec<Part>, Error> {
     let mut parts = Vec::new();
     let text = if let Some(parts) = &token.parts {
@@ -369,7 +374,7 @@ fn parse_token(
 fn parse_match(
     m: structure::Match,
     composition: &Option<&Composition>,
-    info: &mut BuildInfo,
+    info: &BuildInfo,
 ) -> Result<Match, Error> {
     if m.postag.is_some()
         || m.postag_regex.is_some()
@@ -387,17 +392,11 @@ fn parse_match(
         ));
     }
 
-    let mut id =
+    let id = match_id(
         m.no.parse::<usize>()
-            .expect("no must be parsable as usize.");
-
-    if let Some(composition) = composition {
-        let last_id = get_last_id(&composition.parts) as usize - 1;
-
-        if id > last_id {
-            id = last_id;
-        }
-    }
+            .expect("no must be parsable as usize."),
+        composition,
+    );
 
     let case_conversion = if let Some(conversion) = &m.case_conversion {
         Some(conversion.as_str())
@@ -442,6 +441,7 @@ fn parse_match(
             Some("startlower") => Conversion::StartLower,
             Some("startupper") => Conversion::StartUpper,
             Some("allupper") => Conversion::AllUpper,
+            Some("preserve") => Conversion::Preserve,
             Some(x) => {
                 return Err(Error::Unimplemented(format!(
                     "case conversion {} not supported.",
@@ -455,7 +455,15 @@ fn parse_match(
     })
 }
 
-fn parse_synthesizer_text(text: &str) -> Vec<SynthesizerPart> {
+// references past the last token refer to the last token, like in LanguageTool
+fn match_id(id: usize, composition: &Option<&Composition>) -> usize {
+    match composition {
+        Some(composition) => id.min(get_last_id(&composition.parts) as usize - 1),
+        None => id,
+    }
+}
+
+fn parse_synthesizer_text(text: &str, composition: &Option<&Composition>) -> Vec<SynthesizerPart> {
     lazy_static! {
         static ref MATCH_REGEX: Regex = Regex::new(r"\\(\d)").unwrap();
     }
@@ -470,11 +478,14 @@ fn parse_synthesizer_text(text: &str) -> Vec<SynthesizerPart> {
             parts.push(SynthesizerPart::Text((&text[end_index..start]).to_string()))
         }
 
-        let index = capture
-            .at(1)
-            .unwrap()
-            .parse::<usize>()
-            .expect("match regex capture must be parsable as usize.");
+        let index = match_id(
+            capture
+                .at(1)
+                .unwrap()
+                .parse::<usize>()
+                .expect("match regex capture must be parsable as usize."),
+            composition,
+        );
 
         parts.push(SynthesizerPart::Match(Match {
             id: index,
@@ -494,13 +505,13 @@ fn parse_synthesizer_text(text: &str) -> Vec<SynthesizerPart> {
 fn parse_suggestion(
     data: structure::Suggestion,
     composition: &Option<&Composition>,
-    info: &mut BuildInfo,
+    info: &BuildInfo,
 ) -> Result<Synthesizer, Error> {
     let mut parts = Vec::new();
     for part in data.parts {
         match part {
             structure::SuggestionPart::Text(text) => {
-                parts.extend(parse_synthesizer_text(text.as_str()));
+                parts.extend(parse_synthesizer_text(text.as_str(), composition));
             }
             structure::SuggestionPart::Match(m) => {
                 parts.push(SynthesizerPart::Match(parse_match(m, composition, info)?));
@@ -519,110 +530,192 @@ fn get_last_id(parts: &[Part]) -> isize {
     parts.iter().fold(1, |a, x| a + x.visible as isize)
 }
 
+/// Parses tokens which all have to match the same token (`<and>`) or of which one has to match (`<or>`).
+/// Quantifiers and skips are taken from the first token, the other tokens must not have any.
 fn parse_parallel_tokens(
     tokens: &[structure::Token],
+    combine: fn(Vec<Atom>) -> Atom,
     case_sensitive: bool,
-    info: &mut BuildInfo,
-) -> Result<Vec<Atom>, Error> {
-    tokens
+    info: &BuildInfo,
+) -> Result<Vec<Part>, Error> {
+    let mut parsed = tokens
         .iter()
-        .map(|x| {
-            let mut parsed = parse_token(x, case_sensitive, info)?;
+        .map(|x| parse_token(x, case_sensitive, info))
+        .collect::<Result<Vec<_>, _>>()?;
 
-            if parsed.len() != 1 || parsed[0].quantifier.min != 1 || parsed[0].quantifier.max != 1 {
-                return Err(Error::Unimplemented(
-                    "control flow in parallel tokens is not implemented.".into(),
-                ));
-            }
+    if parsed.is_empty() {
+        return Err(Error::Unexpected(
+            "parallel tokens must not be empty".into(),
+        ));
+    }
 
-            Ok(parsed.remove(0).atom)
-        })
-        .collect()
+    if parsed[1..].iter().any(|parts| {
+        parts.len() != 1 || parts[0].quantifier.min != 1 || parts[0].quantifier.max != 1
+    }) {
+        return Err(Error::Unimplemented(
+            "control flow in parallel tokens other than the first is not implemented.".into(),
+        ));
+    }
+
+    let mut first = parsed.remove(0).into_iter();
+    let head = first.next().expect("token has at least one part");
+
+    let mut atoms = vec![head.atom];
+    atoms.extend(parsed.into_iter().map(|mut x| x.remove(0).atom));
+
+    let mut parts = vec![Part {
+        atom: combine(atoms),
+        quantifier: head.quantifier,
+        visible: true,
+    }];
+    // the skip part of the first token, if any
+    parts.extend(first);
+
+    Ok(parts)
 }
 
+/// A `<unify>` block in a pattern. The features are resolved once the whole pattern is parsed.
+struct UnifyBlock {
+    feature_ids: Vec<String>,
+    group_indices: Vec<usize>,
+    negate: bool,
+}
+
+/// Parses the tokens of a `<unify>` block. `offset` is the number of parts in the composition before the block.
 fn parse_unify_tokens(
-    tokens: &[structure::UnifyTokenCombination],
+    unify: &structure::Unify,
+    offset: usize,
     case_sensitive: bool,
-    info: &mut BuildInfo,
+    blocks: &mut Vec<UnifyBlock>,
+    info: &BuildInfo,
 ) -> Result<Vec<Part>, Error> {
     let mut out = Vec::new();
+    let mut block = UnifyBlock {
+        feature_ids: Vec::new(),
+        group_indices: Vec::new(),
+        negate: unify.negate.as_deref() == Some("yes"),
+    };
 
-    for token_combination in tokens {
-        out.extend(match token_combination {
+    for token_combination in &unify.tokens {
+        let parts = match token_combination {
             structure::UnifyTokenCombination::Token(token) => {
                 parse_token(token, case_sensitive, info)?
             }
             structure::UnifyTokenCombination::And(tokens) => {
-                let atom =
-                    AndAtom::and(parse_parallel_tokens(&tokens.tokens, case_sensitive, info)?);
-                vec![Part {
-                    atom,
-                    quantifier: Quantifier::new(1, 1),
-                    visible: true,
-                }]
+                parse_parallel_tokens(&tokens.tokens, AndAtom::and, case_sensitive, info)?
             }
             structure::UnifyTokenCombination::Or(tokens) => {
-                let atom = OrAtom::or(parse_parallel_tokens(&tokens.tokens, case_sensitive, info)?);
-                vec![Part {
-                    atom,
-                    quantifier: Quantifier::new(1, 1),
-                    visible: true,
-                }]
+                parse_parallel_tokens(&tokens.tokens, OrAtom::or, case_sensitive, info)?
+            }
+            structure::UnifyTokenCombination::Feature(feature) => {
+                block.feature_ids.push(feature.id.clone());
+                continue;
             }
-            structure::UnifyTokenCombination::Feature(_) => vec![],
             structure::UnifyTokenCombination::Ignore(ignore) => {
-                parse_tokens(&ignore.tokens, case_sensitive, info)?
+                out.extend(parse_tokens(
+                    &ignore.tokens,
+                    offset + out.len(),
+                    case_sensitive,
+                    blocks,
+                    info,
+                )?);
+                continue;
             }
-        });
+        };
+
+        // skipped tokens do not have to agree, group indices are offset by one because of the group for the whole match
+        for (i, part) in parts.iter().enumerate() {
+            if part.visible {
+                block.group_indices.push(offset + out.len() + i + 1);
+            }
+        }
+        out.extend(parts);
     }
 
+    blocks.push(block);
     Ok(out)
 }
 
+/// Parses a sequence of tokens. `offset` is the number of parts in the composition before the tokens.
 fn parse_tokens(
     tokens: &[structure::TokenCombination],
+    offset: usize,
     case_sensitive: bool,
-    info: &mut BuildInfo,
+    blocks: &mut Vec<UnifyBlock>,
+    info: &BuildInfo,
 ) -> Result<Vec<Part>, Error> {
     let mut out = Vec::new();
 
     for token_combination in tokens {
-        out.extend(match token_combination {
+        let parts = match token_combination {
             structure::TokenCombination::Token(token) => parse_token(token, case_sensitive, info)?,
             structure::TokenCombination::And(tokens) => {
-                let atom =
-                    AndAtom::and(parse_parallel_tokens(&tokens.tokens, case_sensitive, info)?);
-                vec![Part {
-                    atom,
-                    quantifier: Quantifier::new(1, 1),
-                    visible: true,
-                }]
+                parse_parallel_tokens(&tokens.tokens, AndAtom::and, case_sensitive, info)?
             }
             structure::TokenCombination::Or(tokens) => {
-                let atom = OrAtom::or(parse_parallel_tokens(&tokens.tokens, case_sensitive, info)?);
-                vec![Part {
-                    atom,
-                    quantifier: Quantifier::new(1, 1),
-                    visible: true,
-                }]
+                parse_parallel_tokens(&tokens.tokens, OrAtom::or, case_sensitive, info)?
             }
             structure::TokenCombination::Unify(unify) => {
-                parse_unify_tokens(&unify.tokens, case_sensitive, info)?
+                parse_unify_tokens(unify, offset + out.len(), case_sensitive, blocks, info)?
             }
-        });
+        };
+        out.extend(parts);
     }
 
     Ok(out)
 }
 
+fn parse_unification(
+    block: UnifyBlock,
+    unifications: &[structure::Unification],
+    info: &BuildInfo,
+) -> Result<Unification, Error> {
+    let features = block
+        .feature_ids
+        .into_iter()
+        .map(|id| {
+            let unification = unifications
+                .iter()
+                .find(|x| x.feature == id)
+                .ok_or_else(|| Error::Unexpected(format!("unknown unification feature {}", id)))?;
+
+            Ok(Feature {
+                values: unification
+                    .equivalences
+                    .iter()
+                    .map(|equiv| {
+                        parse_pos_filter(
+                            &equiv.token.postag,
+                            equiv.token.postag_regexp.as_deref(),
+                            info,
+                        )
+                        .matcher
+                    })
+                    .collect(),
+                id,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(Unification {
+        features,
+        group_indices: block.group_indices,
+        negate: block.negate,
+    })
+}
+
+/// Parses a pattern into a composition and the unifications its matches must satisfy.
+/// Also returns the IDs of the first and one past the last group of the marker.
 fn parse_pattern(
     pattern: structure::Pattern,
-    info: &mut BuildInfo,
-) -> Result<(Composition, usize, usize), Error> {
+    unifications: &[structure::Unification],
+    info: &BuildInfo,
+) -> Result<(Composition, Vec<Unification>, usize, usize), Error> {
     let mut start = None;
     let mut end = None;
 
     let mut composition_parts = Vec::new();
+    let mut blocks = Vec::new();
     let case_sensitive = match &pattern.case_sensitive {
         Some(string) => string == "yes",
         None => false,
@@ -636,31 +729,42 @@ fn parse_pattern(
             structure::PatternPart::Marker(marker) => {
                 start = Some(get_last_id(&composition_parts));
 
-                composition_parts.extend(parse_tokens(&marker.tokens, case_sensitive, info)?);
+                let parts = parse_tokens(
+                    &marker.tokens,
+                    composition_parts.len(),
+                    case_sensitive,
+                    &mut blocks,
+                    info,
+                )?;
+                composition_parts.extend(parts);
 
                 end = Some(get_last_id(&composition_parts));
             }
             structure::PatternPart::And(tokens) => {
-                let atom =
-                    AndAtom::and(parse_parallel_tokens(&tokens.tokens, case_sensitive, info)?);
-
-                composition_parts.push(Part {
-                    atom,
-                    quantifier: Quantifier::new(1, 1),
-                    visible: true,
-                });
+                composition_parts.extend(parse_parallel_tokens(
+                    &tokens.tokens,
+                    AndAtom::and,
+                    case_sensitive,
+                    info,
+                )?);
             }
             structure::PatternPart::Or(tokens) => {
-                let atom = OrAtom::or(parse_parallel_tokens(&tokens.tokens, case_sensitive, info)?);
-
-                composition_parts.push(Part {
-                    atom,
-                    quantifier: Quantifier::new(1, 1),
-                    visible: true,
-                });
+                composition_parts.extend(parse_parallel_tokens(
+                    &tokens.tokens,
+                    OrAtom::or,
+                    case_sensitive,
+                    info,
+                )?);
             }
             structure::PatternPart::Unify(unify) => {
-                composition_parts.extend(parse_unify_tokens(&unify.tokens, case_sensitive, info)?)
+                let parts = parse_unify_tokens(
+                    unify,
+                    composition_parts.len(),
+                    case_sensitive,
+                    &mut blocks,
+                    info,
+                )?;
+                composition_parts.extend(parts);
             }
         }
     }
@@ -669,12 +773,77 @@ fn parse_pattern(
     let end = end.unwrap_or_else(|| get_last_id(&composition_parts)) as usize;
 
     let composition = Composition::new(composition_parts);
+    let unifications = blocks
+        .into_iter()
+        .map(|block| parse_unification(block, unifications, info))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok((composition, unifications, start, end))
+}
+
+// the parts of the message and the suggesters of the suggestions in it
+fn parse_message(
+    message: structure::Message,
+    composition: &Option<&Composition>,
+    info: &BuildInfo,
+) -> Result<(Vec<SynthesizerPart>, Vec<Synthesizer>), Error> {
+    let mut message_parts = Vec::new();
+    let mut suggesters = Vec::new();
+
+    for part in message.parts {
+        match part {
+            structure::MessagePart::Suggestion(suggestion) => {
+                let suggester = parse_suggestion(suggestion.clone(), composition, info)?;
+                // simpler to just parse a second time than cloning the result
+                message_parts.extend(parse_suggestion(suggestion, composition, info)?.parts);
+                suggesters.push(suggester);
+            }
+            structure::MessagePart::Text(text) => {
+                message_parts.extend(parse_synthesizer_text(text.as_str(), composition));
+            }
+            structure::MessagePart::Match(m) => {
+                message_parts.push(SynthesizerPart::Match(parse_match(m, composition, info)?));
+            }
+        }
+    }
 
-    Ok((composition, start, end))
+    Ok((message_parts, suggesters))
 }
 
 impl Rule {
-    pub fn from_rule_structure(data: structure::Rule, info: &mut BuildInfo) -> Result<Rule, Error> {
+    /// Adds the message in the locale, replacing an earlier translation into the same locale.
+    /// Suggestions in the translated message only change the message, the replacements stay the same.
+    pub fn translate(
+        &mut self,
+        locale: &str,
+        message: structure::Message,
+        info: &BuildInfo,
+    ) -> Result<(), Error> {
+        let composition = if let Engine::Token(engine) = &self.engine {
+            Some(&engine.composition)
+        } else {
+            None
+        };
+        let (parts, _) = parse_message(message, &composition, info)?;
+        if parts.is_empty() {
+            return Err(Error::Unexpected("translated message is empty".into()));
+        }
+
+        let message = Synthesizer {
+            parts,
+            use_titlecase_adjust: true,
+        };
+        match self
+            .translations
+            .binary_search_by(|(x, _)| x.as_str().cmp(locale))
+        {
+            Ok(i) => self.translations[i].1 = message,
+            Err(i) => self.translations.insert(i, (locale.to_string(), message)),
+        }
+        Ok(())
+    }
+
+    pub fn from_rule_structure(data: structure::Rule, info: &BuildInfo) -> Result<Rule, Error> {
         if data.filter.is_some() {
             return Err(Error::Unimplemented(
                 "rules with filter are not implemented.".into(),
@@ -689,19 +858,25 @@ impl Rule {
                 "either `pattern` or `regexp` must be supplied.".into(),
             )),
             (Some(pattern), None) => {
-                let (composition, start, end) = parse_pattern(pattern, info)?;
+                let unifications = data.unifications.unwrap_or_default();
+                let (composition, pattern_unifications, start, end) =
+                    parse_pattern(pattern, &unifications, info)?;
 
                 Ok((
                     Engine::Token(TokenEngine {
                         composition,
+                        // unifications in antipatterns are not checked
                         antipatterns: if let Some(antipatterns) = data.antipatterns {
                             antipatterns
                                 .into_iter()
-                                .map(|pattern| parse_pattern(pattern, info).map(|x| x.0))
+                                .map(|pattern| {
+                                    parse_pattern(pattern, &unifications, info).map(|x| x.0)
+                                })
                                 .collect::<Result<Vec<_>, Error>>()?
                         } else {
                             Vec::new()
                         },
+                        unifications: pattern_unifications,
                     }),
                     start,
                     end,
@@ -727,30 +902,8 @@ impl Rule {
             None
         };
 
-        let mut message_parts = Vec::new();
-        let mut suggesters = Vec::new();
-
-        for part in data.message.parts {
-            match part {
-                structure::MessagePart::Suggestion(suggestion) => {
-                    let suggester = parse_suggestion(suggestion.clone(), &maybe_composition, info)?;
-                    // simpler to just parse a second time than cloning the result
-                    message_parts
-                        .extend(parse_suggestion(suggestion, &maybe_composition, info)?.parts);
-                    suggesters.push(suggester);
-                }
-                structure::MessagePart::Text(text) => {
-                    message_parts.extend(parse_synthesizer_text(text.as_str()));
-                }
-                structure::MessagePart::Match(m) => {
-                    message_parts.push(SynthesizerPart::Match(parse_match(
-                        m,
-                        &maybe_composition,
-                        info,
-                    )?));
-                }
-            }
-        }
+        let (message_parts, mut suggesters) =
+            parse_message(data.message, &maybe_composition, info)?;
 
         if let Some(suggestions) = data.suggestions {
             for suggestion in suggestions {
@@ -816,6 +969,13 @@ impl Rule {
                                 start: char_length,
                                 end: char_length + length,
                                 replacements,
+                                safety: FixSafety::NeedsReview,
+                                confidence: None,
+                                merged_sources: Vec::new(),
+                                short_message: String::new(),
+                                matched: String::new(),
+                                token_span: (0, 0),
+                                severity: Severity::default(),
                             });
                         }
 
@@ -830,7 +990,7 @@ impl Rule {
             });
         }
 
-        Ok(Rule {
+        let mut rule = Rule {
             engine,
             examples,
             start,
@@ -849,11 +1009,21 @@ impl Rule {
             category_id: String::new(),
             category_name: String::new(),
             category_type: None,
-        })
+            uses_chunks: false,
+            safety: FixSafety::NeedsReview,
+            group: None,
+            tier: RuleTier::Fast,
+            source: None,
+            translations: Vec::new(),
+        };
+        rule.uses_chunks = rule.depends_on_chunks();
+        rule.tier = rule.classify_tier();
+
+        Ok(rule)
     }
 }
 
-fn parse_tag_form(form: &str, info: &mut BuildInfo) -> owned::Word {
+fn parse_tag_form(form: &str, info: &BuildInfo) -> owned::Word {
     lazy_static! {
         static ref REGEX: Regex = Regex::new(r"(.+?)\[(.+?)\]").unwrap();
     }
@@ -889,7 +1059,7 @@ fn parse_tag_form(form: &str, info: &mut BuildInfo) -> owned::Word {
 }
 
 impl owned::WordData {
-    fn from_structure(data: structure::WordData, info: &mut BuildInfo) -> Self {
+    fn from_structure(data: structure::WordData, info: &BuildInfo) -> Self {
         owned::WordData::new(
             info.tagger
                 .id_word(data.lemma.unwrap_or_else(String::new).into())
@@ -899,7 +1069,7 @@ impl owned::WordData {
     }
 }
 
-fn parse_pos_filter(postag: &str, postag_regexp: Option<&str>, info: &mut BuildInfo) -> POSFilter {
+fn parse_pos_filter(postag: &str, postag_regexp: Option<&str>, info: &BuildInfo) -> POSFilter {
     match postag_regexp.as_deref() {
         Some("yes") => POSFilter::new(PosMatcher::new(
             Matcher::new_regex(
@@ -919,7 +1089,7 @@ fn parse_pos_filter(postag: &str, postag_regexp: Option<&str>, info: &mut BuildI
 fn parse_unify(
     unify: &structure::Unify,
     unifications: &Option<Vec<structure::Unification>>,
-    info: &mut BuildInfo,
+    info: &BuildInfo,
 ) -> (Vec<Vec<POSFilter>>, Vec<Option<POSFilter>>, Vec<bool>) {
     let mut filters = Vec::new();
     let mut disambig = Vec::new();
@@ -1001,15 +1171,18 @@ fn parse_unify(
 impl DisambiguationRule {
     pub fn from_rule_structure(
         data: structure::DisambiguationRule,
-        info: &mut BuildInfo,
+        info: &BuildInfo,
     ) -> Result<DisambiguationRule, Error> {
         // might need the pattern later so clone it here
-        let (composition, start, end) = parse_pattern(data.pattern.clone(), info)?;
+        let unification_definitions = data.unifications.clone().unwrap_or_default();
+        let (composition, unifications, start, end) =
+            parse_pattern(data.pattern.clone(), &unification_definitions, info)?;
 
+        // unifications in antipatterns are not checked
         let antipatterns = if let Some(antipatterns) = data.antipatterns {
             antipatterns
                 .into_iter()
-                .map(|pattern| parse_pattern(pattern, info).map(|x| x.0))
+                .map(|pattern| parse_pattern(pattern, &unification_definitions, info).map(|x| x.0))
                 .collect::<Result<Vec<_>, Error>>()?
         } else {
             Vec::new()
@@ -1070,7 +1243,7 @@ impl DisambiguationRule {
                 ))
             }
             Some("ignore_spelling") => Ok(Disambiguation::Nop), // ignore_spelling can be ignored since we dont check spelling
-            Some("immunize") => Ok(Disambiguation::Nop), // immunize can probably not be ignored
+            Some("immunize") => Ok(Disambiguation::Immunize),
             Some("filterall") => {
                 let mut disambig = Vec::new();
                 let mut marker_disambig = Vec::new();
@@ -1273,6 +1446,7 @@ impl DisambiguationRule {
             engine: Engine::Token(TokenEngine {
                 composition,
                 antipatterns,
+                unifications,
             }),
             filter,
             disambiguations,
@@ -1280,6 +1454,7 @@ impl DisambiguationRule {
             end,
             examples,
             id: String::new(),
+            on: true,
         })
     }
 }
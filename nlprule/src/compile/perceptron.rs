@@ -0,0 +1,216 @@
+//! Training of the averaged perceptron chunker on data in the CoNLL-2000 format i. e. one token per line
+//! with the word, the part-of-speech tag and the chunk tag separated by whitespace, and sentences separated by empty lines.
+
+use std::{
+    fs::read_to_string,
+    io::{self, Error as IoError, ErrorKind},
+    path::Path,
+};
+
+use crate::{
+    tokenizer::chunk::{MaxentChunker, PerceptronChunker},
+    types::DefaultHashMap,
+};
+
+/// A sentence of (word, part-of-speech tag, chunk tag) triples.
+pub(crate) type Sentence = Vec<(String, String, String)>;
+
+pub(crate) fn read_sentences<P: AsRef<Path>>(path: P) -> io::Result<Vec<Sentence>> {
+    let mut sentences = Vec::new();
+    let mut sentence = Vec::new();
+
+    for (i, line) in read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            if !sentence.is_empty() {
+                sentences.push(std::mem::take(&mut sentence));
+            }
+            continue;
+        }
+
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [word, tag, chunk] => {
+                sentence.push((word.to_string(), tag.to_string(), chunk.to_string()))
+            }
+            _ => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("line {} must have a word, a tag and a chunk", i + 1),
+                ))
+            }
+        }
+    }
+
+    if !sentence.is_empty() {
+        sentences.push(sentence);
+    }
+
+    Ok(sentences)
+}
+
+// the weight of an outcome for a feature, the sum of the weight over all updates so far
+// and the update at which the sum was last brought up to date
+#[derive(Clone, Copy, Default)]
+struct Weight {
+    value: f32,
+    total: f64,
+    timestamp: u64,
+}
+
+impl Weight {
+    fn update(&mut self, delta: f32, updates: u64) {
+        self.total += (updates - self.timestamp) as f64 * self.value as f64;
+        self.timestamp = updates;
+        self.value += delta;
+    }
+
+    fn average(&self, updates: u64) -> f32 {
+        let total = self.total + (updates - self.timestamp) as f64 * self.value as f64;
+        (total / updates.max(1) as f64) as f32
+    }
+}
+
+// permutes `0..n` with a xorshift generator so training is deterministic without a dependency on `rand`
+fn shuffle(order: &mut [usize], seed: &mut u64) {
+    for i in (1..order.len()).rev() {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        order.swap(i, (*seed % (i as u64 + 1)) as usize);
+    }
+}
+
+fn input(sentence: &[(String, String, String)]) -> Vec<(&str, &str)> {
+    sentence
+        .iter()
+        .map(|(word, tag, _)| (word.as_str(), tag.as_str()))
+        .collect()
+}
+
+impl PerceptronChunker {
+    /// Trains the model for the given number of passes over the sentences.
+    /// The previous chunk tags are taken from the predictions during training, like they are during chunking.
+    pub(crate) fn train(sentences: &[Sentence], epochs: usize) -> Self {
+        let mut outcome_labels: Vec<String> = sentences
+            .iter()
+            .flatten()
+            .map(|(_, _, chunk)| chunk.clone())
+            .collect();
+        outcome_labels.sort_unstable();
+        outcome_labels.dedup();
+
+        let label_to_id: DefaultHashMap<&str, usize> = outcome_labels
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (x.as_str(), i))
+            .collect();
+
+        let mut model = PerceptronChunker {
+            outcome_labels: outcome_labels.clone(),
+            ..PerceptronChunker::default()
+        };
+        let mut weights: DefaultHashMap<u64, Vec<Weight>> = DefaultHashMap::default();
+        let mut updates = 0;
+
+        let mut order: Vec<_> = (0..sentences.len()).collect();
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+
+        for _ in 0..epochs {
+            shuffle(&mut order, &mut seed);
+
+            for sentence in order.iter().map(|i| &sentences[*i]) {
+                let input = input(sentence);
+                let mut outcomes = Vec::with_capacity(input.len());
+
+                for (i, (_, _, truth)) in sentence.iter().enumerate() {
+                    let context = MaxentChunker::context(&input, &outcomes, i);
+                    let mut scores = vec![0.; outcome_labels.len()];
+                    for feature_weights in context.iter().filter_map(|x| weights.get(x)) {
+                        for (score, weight) in scores.iter_mut().zip(feature_weights) {
+                            *score += weight.value;
+                        }
+                    }
+
+                    let guess = model.predict(&scores, &outcomes);
+                    let truth = label_to_id[truth.as_str()];
+                    updates += 1;
+
+                    if guess != truth {
+                        for feature in context {
+                            let feature_weights = weights
+                                .entry(feature)
+                                .or_insert_with(|| vec![Weight::default(); outcome_labels.len()]);
+                            feature_weights[truth].update(1., updates);
+                            feature_weights[guess].update(-1., updates);
+                        }
+                    }
+
+                    outcomes.push(outcome_labels[guess].as_str());
+                }
+            }
+        }
+
+        model.weights = weights
+            .into_iter()
+            .map(|(feature, weights)| {
+                (
+                    feature,
+                    weights.iter().map(|x| x.average(updates)).collect(),
+                )
+            })
+            .filter(|(_, weights): &(u64, Vec<f32>)| weights.iter().any(|x| *x != 0.))
+            .collect();
+        model
+    }
+
+    /// The share of tokens in the sentences which get the correct chunk tag.
+    pub(crate) fn accuracy(&self, sentences: &[Sentence]) -> f32 {
+        let mut correct = 0;
+        let mut total = 0;
+
+        for sentence in sentences {
+            let outcomes = self.chunk(&input(sentence));
+
+            for (outcome, (_, _, truth)) in outcomes.iter().zip(sentence) {
+                correct += (outcome == truth) as usize;
+                total += 1;
+            }
+        }
+
+        correct as f32 / total.max(1) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sentence(text: &str) -> Sentence {
+        text.split(' ')
+            .map(|x| {
+                let parts: Vec<_> = x.split('|').collect();
+                (
+                    parts[0].to_string(),
+                    parts[1].to_string(),
+                    parts[2].to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn learns_chunks() {
+        let sentences = vec![
+            sentence("The|DT|B-NP cat|NN|I-NP sat|VBD|B-VP .|.|O"),
+            sentence("A|DT|B-NP dog|NN|I-NP ran|VBD|B-VP .|.|O"),
+            sentence("The|DT|B-NP big|JJ|I-NP dog|NN|I-NP has|VBZ|B-VP eaten|VBN|I-VP .|.|O"),
+        ];
+
+        let model = PerceptronChunker::train(&sentences, 10);
+        assert_eq!(model.accuracy(&sentences), 1.);
+
+        let unseen = sentence("A|DT|B-NP cat|NN|I-NP has|VBZ|B-VP sat|VBD|I-VP .|.|O");
+        assert_eq!(model.accuracy(&[unseen]), 1.);
+    }
+}
@@ -1,39 +1,158 @@
+use super::RuleError;
 use serde::Deserialize;
-use std::fs::File;
-use std::io::BufReader;
 use xml::reader::EventReader;
 
 mod preprocess {
-    use xml::reader::EventReader;
+    use lazy_static::lazy_static;
+    use regex::Regex;
+    use xml::common::{Position, TextPosition};
+    use xml::reader::{EventReader, XmlEvent};
     use xml::writer::EmitterConfig;
 
-    use super::Category;
+    use super::{Category, RuleError};
 
-    pub fn sanitize(input: impl std::io::Read, whitespace_sensitive_tags: &[&str]) -> String {
+    lazy_static! {
+        static ref ID: Regex = Regex::new(r#"^[^>]*\sid="([^"]*)""#).unwrap();
+    }
+
+    /// The source line of a rule container (a rule, rule group or unification) and of the rules in it.
+    pub type ContainerLines = (usize, Vec<usize>);
+
+    fn offset(source: &str, position: TextPosition) -> usize {
+        let line_start: usize = source
+            .split_inclusive('\n')
+            .take(position.row as usize)
+            .map(str::len)
+            .sum();
+
+        source[line_start..]
+            .char_indices()
+            .nth(position.column as usize)
+            .map_or(source.len(), |(i, _)| line_start + i)
+    }
+
+    fn line(source: &str, offset: usize) -> usize {
+        source[..offset].matches('\n').count() + 1
+    }
+
+    // the byte range of the rule or rule group around the offset
+    fn enclosing_rule(source: &str, offset: usize) -> Option<(usize, usize)> {
+        let before = &source[..offset];
+        let group_start = before.rfind("<rulegroup");
+        let in_group = match (group_start, before.rfind("</rulegroup>")) {
+            (Some(start), Some(end)) => start > end,
+            (start, None) => start.is_some(),
+            (None, Some(_)) => false,
+        };
+
+        let (start, close) = if in_group {
+            (group_start?, "</rulegroup>")
+        } else {
+            let start = before
+                .match_indices("<rule")
+                .map(|(i, _)| i)
+                .filter(|i| matches!(source[i + 5..].chars().next(), Some(c) if c.is_whitespace() || c == '>'))
+                .last()?;
+            (start, "</rule>")
+        };
+
+        source[start..]
+            .match_indices(close)
+            .map(|(i, _)| start + i + close.len())
+            .find(|end| *end > offset)
+            .map(|end| (start, end))
+    }
+
+    // parses the XML, skipping rules which are not well-formed until the rest of the file can be parsed
+    fn parse(
+        path: &str,
+        mut source: String,
+        errors: &mut Vec<RuleError>,
+    ) -> Vec<(XmlEvent, usize)> {
+        loop {
+            let mut events = Vec::new();
+            let error = {
+                let mut parser = EventReader::new(source.as_bytes());
+                loop {
+                    match parser.next() {
+                        Ok(XmlEvent::EndDocument) => break None,
+                        Ok(event) => events.push((event, parser.position().row as usize + 1)),
+                        Err(error) => break Some(error),
+                    }
+                }
+            };
+
+            let error = match error {
+                Some(error) => error,
+                None => return events,
+            };
+            let (start, end) = enclosing_rule(&source, offset(&source, error.position()))
+                .unwrap_or_else(|| {
+                    panic!(
+                        "{}:{}: error reading XML: {}",
+                        path,
+                        error.position().row + 1,
+                        error.msg()
+                    )
+                });
+
+            errors.push(RuleError {
+                path: path.to_string(),
+                line: line(&source, start),
+                rule_id: ID.captures(&source[start..end]).map(|x| x[1].to_string()),
+                message: error.msg().to_string(),
+            });
+            // replaced with whitespace so the lines of the following rules stay the same
+            let blank: String = source[start..end]
+                .chars()
+                .map(|c| if c == '\n' { c } else { ' ' })
+                .collect();
+            source.replace_range(start..end, &blank);
+        }
+    }
+
+    /// Sanitizes the XML for deserialization. Rules which are not well-formed are skipped and added to the errors.
+    /// Also returns the lines of the rule containers in the order of [extract_rules].
+    pub fn sanitize(
+        path: &str,
+        source: String,
+        whitespace_sensitive_tags: &[&str],
+        errors: &mut Vec<RuleError>,
+    ) -> (String, Vec<ContainerLines>) {
         let mut sanitized = Vec::new();
 
         let mut writer = EmitterConfig::new()
             .perform_indent(true)
             .create_writer(&mut sanitized);
 
-        let parser = EventReader::new(input);
-
-        let events = parser
+        let events = parse(path, source, errors)
             .into_iter()
-            .map(|x| x.expect("error reading XML"))
-            .filter(|x| {
+            .filter(|(x, _)| {
                 // processing instructions break the writer and are useless to us
-                !matches!(x, xml::reader::XmlEvent::ProcessingInstruction { .. })
+                !matches!(x, XmlEvent::ProcessingInstruction { .. })
             })
             .collect::<Vec<_>>();
 
         let mut out_events: Vec<xml::writer::XmlEvent> = Vec::new();
         let mut parents = Vec::new();
+        let mut lines: Vec<ContainerLines> = Vec::new();
 
-        for event in &events {
+        for (event, line) in &events {
             match event {
                 xml::reader::XmlEvent::StartElement { name, .. } => {
-                    parents.push(name.local_name.as_str());
+                    let name = name.local_name.as_str();
+                    match (name, parents.last()) {
+                        ("rule", Some(&"rulegroup")) => {
+                            if let Some((_, rules)) = lines.last_mut() {
+                                rules.push(*line);
+                            }
+                        }
+                        ("rule", _) | ("rulegroup", _) | ("unification", _) => {
+                            lines.push((*line, Vec::new()))
+                        }
+                        _ => {}
+                    }
+                    parents.push(name);
                 }
                 xml::reader::XmlEvent::EndElement { .. } => {
                     parents.pop();
@@ -73,12 +192,16 @@ mod preprocess {
             writer.write(event).expect("error writing to output XML");
         }
 
-        std::str::from_utf8(&sanitized)
+        let sanitized = std::str::from_utf8(&sanitized)
             .expect("invalid UTF-8")
-            .to_string()
+            .to_string();
+        (sanitized, lines)
     }
 
-    pub fn extract_rules(mut xml: impl std::io::Read) -> Vec<(String, Option<Category>)> {
+    /// The XML of the rule containers with their category and ID.
+    pub fn extract_rules(
+        mut xml: impl std::io::Read,
+    ) -> Vec<(String, Option<Category>, Option<String>)> {
         let mut string = String::new();
         xml.read_to_string(&mut string)
             .expect("error writing to string.");
@@ -114,7 +237,7 @@ mod preprocess {
                     None
                 };
 
-                (xml, category)
+                (xml, category, x.attribute("id").map(|x| x.to_owned()))
             })
             .collect()
     }
@@ -393,6 +516,7 @@ pub enum UnifyTokenCombination {
 #[derive(Debug, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Unify {
+    pub negate: Option<String>,
     #[serde(rename = "$value")]
     pub tokens: Vec<UnifyTokenCombination>,
 }
@@ -460,6 +584,8 @@ pub struct Rule {
     pub url: Option<XMLText>,
     pub default: Option<String>,
     pub filter: Option<Filter>,
+    #[serde(rename = "__unused_unifications")]
+    pub unifications: Option<Vec<Unification>>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -479,9 +605,11 @@ pub struct RuleGroup {
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "lowercase")]
 #[serde(deny_unknown_fields)]
+#[allow(clippy::large_enum_variant)]
 pub enum RuleContainer {
     Rule(Rule),
     RuleGroup(RuleGroup),
+    Unification(Unification),
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -600,7 +728,7 @@ pub enum DisambiguationRuleContainer {
 }
 
 macro_rules! flatten_group {
-    ($rulegroup:expr, $category:expr) => {{
+    ($rulegroup:expr, $category:expr, $lines:expr) => {{
         let group_antipatterns = if let Some(antipatterns) = $rulegroup.antipatterns {
             antipatterns
         } else {
@@ -613,6 +741,7 @@ macro_rules! flatten_group {
             name: $rulegroup.name,
             n: 0,
         };
+        let (group_line, rule_lines) = $lines;
 
         $rulegroup
             .rules
@@ -627,27 +756,61 @@ macro_rules! flatten_group {
 
                 let mut group = group.clone();
                 group.n = i;
-                (rule, Some(group), $category.clone())
+                let line = rule_lines.get(i).copied().unwrap_or(group_line);
+                (rule, Some(group), $category.clone(), line)
             })
             .collect::<Vec<_>>()
     }};
 }
 
-type GrammarRuleReading = (Rule, Option<Group>, Option<Category>);
-type DisambiguationRuleReading = (DisambiguationRule, Option<Group>, Option<Category>);
+/// A rule with its group, category and the line it starts at.
+type GrammarRuleReading = (Rule, Option<Group>, Option<Category>, usize);
+type DisambiguationRuleReading = (DisambiguationRule, Option<Group>, Option<Category>, usize);
 
+// reads the rule containers of the file, each container with its source line and rule lines
+fn read_containers<P: AsRef<std::path::Path>>(
+    path: P,
+    whitespace_sensitive_tags: &[&str],
+    errors: &mut Vec<RuleError>,
+) -> Vec<(
+    String,
+    Option<Category>,
+    Option<String>,
+    preprocess::ContainerLines,
+)> {
+    let path_str = path.as_ref().display().to_string();
+    let source = std::fs::read_to_string(path).unwrap();
+
+    let (sanitized, lines) =
+        preprocess::sanitize(&path_str, source, whitespace_sensitive_tags, errors);
+    let containers = preprocess::extract_rules(sanitized.as_bytes());
+    assert_eq!(
+        containers.len(),
+        lines.len(),
+        "rule containers must have been found in both passes"
+    );
+
+    containers
+        .into_iter()
+        .zip(lines)
+        .map(|((xml, category, id), lines)| (xml, category, id, lines))
+        .collect()
+}
+
+/// Reads the grammar rules of the file. Rules which can not be read are skipped with an error
+/// pointing to their location, they do not stop reading the rest of the file.
 pub fn read_rules<P: AsRef<std::path::Path>>(
     path: P,
-) -> Vec<Result<GrammarRuleReading, serde_xml_rs::Error>> {
-    let file = File::open(path).unwrap();
-    let file = BufReader::new(file);
+) -> Vec<Result<GrammarRuleReading, RuleError>> {
+    let path_str = path.as_ref().display().to_string();
+    let mut errors = Vec::new();
+    let containers = read_containers(path, &["suggestion"], &mut errors);
 
-    let sanitized = preprocess::sanitize(file, &["suggestion"]);
-    let rules = preprocess::extract_rules(sanitized.as_bytes());
+    let mut unifications = Vec::new();
 
-    rules
+    let rules: Vec<_> = containers
         .into_iter()
-        .map(|(xml, category)| {
+        .map(|(xml, category, id, lines)| {
             let mut out = Vec::new();
 
             let deseralized = RuleContainer::deserialize(&mut serde_xml_rs::Deserializer::new(
@@ -657,35 +820,66 @@ pub fn read_rules<P: AsRef<std::path::Path>>(
             out.extend(match deseralized {
                 Ok(rule_container) => match rule_container {
                     RuleContainer::Rule(rule) => {
-                        vec![Ok((rule, None, category))]
+                        vec![Ok((rule, None, category, lines.0))]
+                    }
+                    RuleContainer::RuleGroup(mut rule_group) => {
+                        // rules without a short message use the one of their group
+                        for rule in &mut rule_group.rules {
+                            if rule.short.is_none() {
+                                rule.short = rule_group.short.clone();
+                            }
+                        }
+
+                        flatten_group!(rule_group, category, lines)
+                            .into_iter()
+                            .map(Ok)
+                            .collect()
+                    }
+                    RuleContainer::Unification(unification) => {
+                        unifications.push(unification);
+
+                        vec![]
                     }
-                    RuleContainer::RuleGroup(rule_group) => flatten_group!(rule_group, category)
-                        .into_iter()
-                        .map(Ok)
-                        .collect(),
                 },
-                Err(err) => vec![Err(err)],
+                Err(err) => vec![Err(RuleError {
+                    path: path_str.clone(),
+                    line: lines.0,
+                    rule_id: id,
+                    message: err.to_string(),
+                })],
             });
             out
         })
         .flatten()
+        .collect();
+
+    errors
+        .into_iter()
+        .map(Err)
+        .chain(rules.into_iter().map(|result| match result {
+            Ok(mut x) => {
+                x.0.unifications = Some(unifications.clone());
+
+                Ok(x)
+            }
+            Err(x) => Err(x),
+        }))
         .collect()
 }
 
+/// Reads the disambiguation rules of the file, skipping rules which can not be read like [read_rules].
 pub fn read_disambiguation_rules<P: AsRef<std::path::Path>>(
     path: P,
-) -> Vec<Result<DisambiguationRuleReading, serde_xml_rs::Error>> {
-    let file = File::open(path).unwrap();
-    let file = BufReader::new(file);
-
-    let sanitized = preprocess::sanitize(file, &[]);
-    let rules = preprocess::extract_rules(sanitized.as_bytes());
+) -> Vec<Result<DisambiguationRuleReading, RuleError>> {
+    let path_str = path.as_ref().display().to_string();
+    let mut errors = Vec::new();
+    let containers = read_containers(path, &[], &mut errors);
 
     let mut unifications = Vec::new();
 
-    let rules: Vec<_> = rules
+    let rules: Vec<_> = containers
         .into_iter()
-        .map(|(xml, _)| {
+        .map(|(xml, _, id, lines)| {
             let mut out = Vec::new();
 
             let deseralized = DisambiguationRuleContainer::deserialize(
@@ -697,10 +891,10 @@ pub fn read_disambiguation_rules<P: AsRef<std::path::Path>>(
             out.extend(match deseralized {
                 Ok(rule_container) => match rule_container {
                     DisambiguationRuleContainer::Rule(rule) => {
-                        vec![Ok((rule, None, category))]
+                        vec![Ok((rule, None, category, lines.0))]
                     }
                     DisambiguationRuleContainer::RuleGroup(rule_group) => {
-                        flatten_group!(rule_group, category)
+                        flatten_group!(rule_group, category, lines)
                             .into_iter()
                             .map(Ok)
                             .collect()
@@ -711,22 +905,166 @@ pub fn read_disambiguation_rules<P: AsRef<std::path::Path>>(
                         vec![]
                     }
                 },
-                Err(err) => vec![Err(err)],
+                Err(err) => vec![Err(RuleError {
+                    path: path_str.clone(),
+                    line: lines.0,
+                    rule_id: id,
+                    message: err.to_string(),
+                })],
             });
             out
         })
         .flatten()
         .collect();
 
-    rules
+    errors
         .into_iter()
-        .map(|result| match result {
+        .map(Err)
+        .chain(rules.into_iter().map(|result| match result {
             Ok(mut x) => {
                 x.0.unifications = Some(unifications.clone());
 
                 Ok(x)
             }
             Err(x) => Err(x),
-        })
+        }))
         .collect()
 }
+
+/// A translated message of a rule or rule group.
+#[derive(Debug, Clone)]
+pub struct Translation {
+    /// The ID of the rule or rule group.
+    pub id: String,
+    /// The line the translation starts at, counting from 1.
+    pub line: usize,
+    pub message: Message,
+}
+
+/// Reads a file with translated rule messages and returns its locale together with the translations. The messages
+/// are written like in the rules and may refer to the same matches:
+///
+/// ```xml
+/// <messages locale="de">
+///     <translation id="ALOT"><message>Meinten Sie <suggestion>a lot</suggestion>?</message></translation>
+/// </messages>
+/// ```
+///
+/// The messages of the test fixtures in `tests/fixtures/en/messages` are a complete example.
+///
+/// Translations which can not be read are skipped with an error like in [read_rules].
+pub fn read_translations<P: AsRef<std::path::Path>>(
+    path: P,
+) -> (String, Vec<Result<Translation, RuleError>>) {
+    let path_str = path.as_ref().display().to_string();
+    let source = std::fs::read_to_string(path).unwrap();
+
+    // the lines are taken from the source since sanitizing reformats the XML
+    let document = roxmltree::Document::parse(&source).expect("error parsing XML");
+    let locale = document
+        .root_element()
+        .attribute("locale")
+        .unwrap_or_else(|| panic!("{}: messages must have a locale", path_str))
+        .to_string();
+    let headers: Vec<_> = document
+        .descendants()
+        .filter(|x| x.tag_name().name() == "translation")
+        .map(|x| {
+            (
+                x.attribute("id").map(|x| x.to_owned()),
+                document.text_pos_at(x.range().start).row as usize,
+            )
+        })
+        .collect();
+
+    let mut errors = Vec::new();
+    let (sanitized, _) =
+        preprocess::sanitize(&path_str, source.clone(), &["suggestion"], &mut errors);
+    let document = roxmltree::Document::parse(&sanitized).expect("error parsing XML");
+
+    let translations: Vec<Result<Translation, RuleError>> = document
+        .descendants()
+        .filter(|x| x.tag_name().name() == "translation")
+        .zip(headers)
+        .map(|(node, (id, line))| {
+            let error = |message: String| RuleError {
+                path: path_str.clone(),
+                line,
+                rule_id: id.clone(),
+                message,
+            };
+
+            let id = id
+                .clone()
+                .ok_or_else(|| error("translation must have an ID".into()))?;
+            let message = node
+                .children()
+                .find(|x| x.tag_name().name() == "message")
+                .ok_or_else(|| error("translation must have a message".into()))?;
+            let message = Message::deserialize(&mut serde_xml_rs::Deserializer::new(
+                EventReader::new(sanitized[message.range()].as_bytes()),
+            ))
+            .map_err(|x| error(x.to_string()))?;
+
+            Ok(Translation { id, line, message })
+        })
+        .collect();
+
+    (
+        locale,
+        errors.into_iter().map(Err).chain(translations).collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_malformed_rules() {
+        let xml = r#"<rules lang="en">
+<category id="C" name="Category">
+<rule id="FINE" name="fine">
+    <pattern><token>a</token></pattern>
+    <message>Message.</message>
+    <example>a</example>
+</rule>
+<rule id="UNCLOSED" name="unclosed">
+    <pattern><token>b</pattern>
+    <message>Message.</message>
+    <example>b</example>
+</rule>
+<rule id="UNKNOWN" name="unknown">
+    <pattern><token>c</token></pattern>
+    <message>Message.</message>
+    <unknown/>
+    <example>c</example>
+</rule>
+</category>
+</rules>
+"#;
+        let path = std::env::temp_dir().join(format!("nlprule-grammar-{}.xml", std::process::id()));
+        std::fs::write(&path, xml).unwrap();
+        let rules = read_rules(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let (ok, errors): (Vec<_>, Vec<_>) = rules.into_iter().partition(|x| x.is_ok());
+        let ok: Vec<_> = ok.into_iter().map(Result::unwrap).collect();
+        assert_eq!(ok.len(), 1);
+        assert_eq!(ok[0].0.id.as_deref(), Some("FINE"));
+        assert_eq!(ok[0].3, 3);
+
+        let errors: Vec<_> = errors
+            .into_iter()
+            .map(|x| x.unwrap_err())
+            .map(|x| (x.line, x.rule_id))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (8, Some("UNCLOSED".to_string())),
+                (13, Some("UNKNOWN".to_string()))
+            ]
+        );
+    }
+}
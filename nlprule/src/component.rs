@@ -0,0 +1,345 @@
+//! A common interface for the parts of the pipeline which are compiled to binaries.
+
+use crate::{
+    pipeline::WarmPipeline,
+    rules::{patch::RulesPatch, Rules},
+    tokenizer::{
+        chunk::Chunker,
+        tag::{Tagger, TaggerExtension},
+        Tokenizer,
+    },
+    utils, Error,
+};
+use bincode::Options;
+use flate2::{write::GzEncoder, Compression};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    io::{Cursor, Read, Write},
+};
+
+pub(crate) mod migrate;
+
+// Binaries start with these bytes followed by a [Header]. Binaries without header start with the length of
+// the language code (as little endian u64), which can never be this large, so they can be told apart.
+const MAGIC: [u8; 8] = *b"NLPRULE\0";
+
+/// Describes the binary following it, so binaries of other components or other format versions
+/// are reported with a clear error instead of failing somewhere in the middle of deserialization.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    component: String,
+    format_version: u32,
+}
+
+fn write_header<C: Component, W: Write>(mut writer: W) -> Result<(), Error> {
+    writer.write_all(&MAGIC)?;
+    bincode::serialize_into(
+        writer,
+        &Header {
+            component: C::name().to_string(),
+            format_version: C::format_version(),
+        },
+    )?;
+    Ok(())
+}
+
+/// Reads the header at the start of a decompressed binary. Returns `None` for binaries created
+/// before the header was introduced and the reader positioned at the start of the component.
+fn read_header<'a, R: Read + 'a>(
+    mut reader: R,
+) -> Result<(Option<Header>, Box<dyn Read + 'a>), Error> {
+    let mut start = [0; MAGIC.len()];
+    let read = utils::read_up_to(&mut reader, &mut start)?;
+
+    if read == MAGIC.len() && start == MAGIC {
+        let header = bincode::deserialize_from(&mut reader)?;
+        Ok((Some(header), Box::new(reader)))
+    } else {
+        Ok((
+            None,
+            Box::new(Cursor::new(start[..read].to_vec()).chain(reader)),
+        ))
+    }
+}
+
+/// Strips the header from a binary of the component and returns its format version, checking that
+/// the binary is of the right component. Binaries without header have version 0.
+pub(crate) fn read_versioned<'a, C: Component, R: Read + 'a>(
+    reader: R,
+) -> Result<(u32, Box<dyn Read + 'a>), Error> {
+    let (header, reader) = read_header(utils::maybe_decompress(reader)?)?;
+
+    match header {
+        Some(header) if header.component != C::name() => Err(Error::WrongComponent {
+            expected: C::name().to_string(),
+            found: header.component,
+        }),
+        Some(header) if header.format_version > C::format_version() => {
+            Err(unsupported_format::<C>(header.format_version))
+        }
+        Some(header) => Ok((header.format_version, reader)),
+        None => Ok((0, reader)),
+    }
+}
+
+pub(crate) fn unsupported_format<C: Component>(version: u32) -> Error {
+    Error::UnsupportedFormat {
+        component: C::name().to_string(),
+        found: version,
+        current: C::format_version(),
+    }
+}
+
+/// Information about the source of a [Tokenizer] or [Rules] binary.
+///
+/// The metadata is stored at the start of the binary so it can be read with [Metadata::read]
+/// without loading the whole component.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Metadata {
+    pub(crate) lang_code: String,
+    pub(crate) lt_version: Option<String>,
+    pub(crate) rule_count: usize,
+}
+
+impl Metadata {
+    /// Reads the metadata from the start of a tokenizer or rules binary. The data can optionally be gzip compressed.
+    /// Binaries of nlprule 0.3.0 have no metadata, for them (and for any other data without header) the default
+    /// metadata with an empty language code is returned.
+    pub fn read<R: Read>(reader: R) -> Result<Self, Error> {
+        let (header, reader) = read_header(utils::maybe_decompress(reader)?)?;
+        if header.is_none() {
+            return Ok(Metadata::default());
+        }
+
+        // the metadata is small, the limit keeps data which is not a binary from being read as a huge string
+        Ok(bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(1 << 20)
+            .deserialize_from(reader)?)
+    }
+
+    /// The code of the language the binary was compiled for e. g. "en".
+    pub fn lang_code(&self) -> &str {
+        &self.lang_code
+    }
+
+    /// The version of LanguageTool the rules were taken from, if it was given at compile time.
+    pub fn lt_version(&self) -> Option<&str> {
+        self.lt_version.as_deref()
+    }
+
+    /// The number of rules in the binary, including rules in categories which are not loaded.
+    /// For a tokenizer these are the disambiguation rules.
+    pub fn rule_count(&self) -> usize {
+        self.rule_count
+    }
+}
+
+/// A serializable part of the pipeline.
+pub trait Component: Serialize + DeserializeOwned {
+    /// A short name of the component e. g. "tokenizer".
+    fn name() -> &'static str;
+
+    /// The conventional file name of the binary of this component for a language e. g. `en_tokenizer.bin`.
+    fn filename(lang_code: &str) -> String {
+        format!("{}_{}.bin", lang_code, Self::name())
+    }
+
+    /// The version of the layout of the serialized component. Must be increased whenever the layout changes,
+    /// together with a way to read the previous version in [migrate][Component::migrate].
+    fn format_version() -> u32 {
+        1
+    }
+
+    /// Reads a binary with an older format version than the current one. Version 0 are binaries of nlprule 0.3.0,
+    /// which did not store the format version; by default they are read like the current version.
+    fn migrate<R: Read>(version: u32, reader: R) -> Result<Self, Error> {
+        match version {
+            0 => migrate::deserialize(reader).map_err(|_| unsupported_format::<Self>(version)),
+            _ => Err(unsupported_format::<Self>(version)),
+        }
+    }
+
+    /// Checks that the component is internally consistent.
+    fn validate(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Deserializes the component from a reader. The data can optionally be gzip compressed.
+    fn read<R: Read>(reader: R) -> Result<Self, Error> {
+        let (version, reader) = read_versioned::<Self, _>(reader)?;
+
+        if version == Self::format_version() {
+            Ok(bincode::deserialize_from(reader)?)
+        } else {
+            Self::migrate(version, reader)
+        }
+    }
+
+    /// Serializes the component into a writer, optionally gzip compressed.
+    fn write<W: Write>(&self, writer: W, compress: bool) -> Result<(), Error> {
+        if compress {
+            let mut encoder = GzEncoder::new(writer, Compression::default());
+            write_header::<Self, _>(&mut encoder)?;
+            bincode::serialize_into(&mut encoder, self)?;
+            encoder.finish()?;
+        } else {
+            let mut writer = writer;
+            write_header::<Self, _>(&mut writer)?;
+            bincode::serialize_into(writer, self)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Component for Tagger {
+    fn name() -> &'static str {
+        "tagger"
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let word_store = self.word_store();
+        let tag_store = self.tag_store();
+
+        for (word_id, inflections) in &self.tags {
+            for (inflection_id, pos_ids) in inflections {
+                if !word_store.contains_right(word_id) || !word_store.contains_right(inflection_id)
+                {
+                    return Err(Error::Unexpected(format!(
+                        "tagger references unknown word ID {}",
+                        word_id
+                    )));
+                }
+
+                if let Some(pos_id) = pos_ids.iter().find(|x| !tag_store.contains_right(x)) {
+                    return Err(Error::Unexpected(format!(
+                        "tagger references unknown tag ID {}",
+                        pos_id
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Component for TaggerExtension {
+    fn name() -> &'static str {
+        "tagger_extension"
+    }
+}
+
+impl Component for WarmPipeline {
+    fn name() -> &'static str {
+        "warm_pipeline"
+    }
+}
+
+impl Component for Chunker {
+    fn name() -> &'static str {
+        "chunker"
+    }
+}
+
+impl Component for Tokenizer {
+    fn name() -> &'static str {
+        "tokenizer"
+    }
+
+    fn migrate<R: Read>(version: u32, reader: R) -> Result<Self, Error> {
+        migrate::tokenizer(version, reader)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.fingerprint() != self.tagger().fingerprint() {
+            return Err(Error::Unexpected(
+                "tokenizer fingerprint does not match its tagger".into(),
+            ));
+        }
+
+        if let Some(multiword_tagger) = self.multiword_tagger() {
+            if let Some(tag) = multiword_tagger
+                .tags()
+                .find(|x| !self.tagger().tag_store().contains_left(*x))
+            {
+                return Err(Error::Unexpected(format!(
+                    "multiword tagger references unknown tag {}",
+                    tag
+                )));
+            }
+        }
+
+        self.tagger().validate()
+    }
+}
+
+impl Component for Rules {
+    fn name() -> &'static str {
+        "rules"
+    }
+
+    fn migrate<R: Read>(version: u32, reader: R) -> Result<Self, Error> {
+        Ok(Rules::from_fields(migrate::rules_fields(version, reader)?)?)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let mut ids = HashSet::new();
+
+        if let Some(rule) = self.rules().iter().find(|x| !ids.insert(x.id())) {
+            return Err(Error::Unexpected(format!(
+                "rules contain duplicate ID {}",
+                rule.id()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Component for RulesPatch {
+    fn name() -> &'static str {
+        "rules_patch"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_other_components_and_versions() {
+        let tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let mut bytes = Vec::new();
+        tokenizer.write(&mut bytes, true).unwrap();
+
+        assert_eq!(
+            Metadata::read(&bytes[..]).unwrap().lang_code(),
+            tokenizer.metadata().lang_code()
+        );
+        assert!(Tokenizer::read(&bytes[..]).is_ok());
+        // the first read of a streaming reader can return less than the gzip header
+        assert!(Tokenizer::read(bytes[..1].chain(&bytes[1..])).is_ok());
+        assert!(matches!(
+            Rules::read(&bytes[..]),
+            Err(Error::WrongComponent { .. })
+        ));
+
+        let mut future = MAGIC.to_vec();
+        bincode::serialize_into(
+            &mut future,
+            &Header {
+                component: "rules".into(),
+                format_version: Rules::format_version() + 1,
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            Rules::read(&future[..]),
+            Err(Error::UnsupportedFormat { found, .. }) if found == Rules::format_version() + 1
+        ));
+    }
+}
@@ -0,0 +1,600 @@
+//! Layouts of the binaries of nlprule 0.3.0 and their conversion to the current ones.
+//!
+//! Binaries of 0.3.0 have no header, so they are read as format version 0. Only the types whose layout changed
+//! since are repeated here, the others are read with the current types.
+
+use super::{unsupported_format, Metadata};
+use crate::{
+    filter::{Filter, NoDisambiguationEnglishPartialPosTagFilter},
+    rule::{
+        disambiguation::{Disambiguation, DisambiguationExample, POSFilter},
+        engine::{
+            composition::{
+                concrete::{ChunkAtom, SpaceBeforeAtom, TextAtom, WordDataAtom},
+                AndAtom, Atom, Composition, FalseAtom, Matcher, NotAtom, OffsetAtom, OrAtom, Part,
+                PosMatcher, Quantifier, TextMatcher, TrueAtom, WordDataMatcher,
+            },
+            Engine, TokenEngine,
+        },
+        grammar::{Conversion, Match, PosReplacer, Synthesizer, SynthesizerPart},
+        DisambiguationRule, Example, Rule, RuleGroup, RuleTier,
+    },
+    rules::{Rules, RulesFields, Segment},
+    tokenizer::{
+        chunk::{ChunkModel, Chunker, MaxentChunker, MaxentPosTagger, MaxentTokenizer},
+        tag::Tagger,
+        Tokenizer, TokenizerOptions,
+    },
+    types::{owned, DefaultHashMap, DefaultHashSet, FixSafety},
+    utils::regex::SerializeRegex,
+    Error,
+};
+use bimap::BiMap;
+use bincode::Options;
+use either::Either;
+use indexmap::IndexMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{io::Read, sync::Arc};
+
+/// Regexes before the engine was stored. All regexes were executed with Oniguruma.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RegexV0 {
+    pub(crate) regex_str: String,
+    pub(crate) case_sensitive: bool,
+}
+
+impl From<RegexV0> for SerializeRegex {
+    fn from(regex: RegexV0) -> Self {
+        SerializeRegex::onig(regex.regex_str, regex.case_sensitive)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct MatcherV0 {
+    pub(crate) matcher: Either<Either<String, usize>, RegexV0>,
+    pub(crate) negate: bool,
+    pub(crate) case_sensitive: bool,
+    pub(crate) empty_always_false: bool,
+}
+
+impl From<MatcherV0> for Matcher {
+    fn from(matcher: MatcherV0) -> Self {
+        Matcher {
+            matcher: matcher.matcher.map_right(SerializeRegex::from),
+            negate: matcher.negate,
+            case_sensitive: matcher.case_sensitive,
+            empty_always_false: matcher.empty_always_false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct TextMatcherV0 {
+    pub(crate) matcher: MatcherV0,
+    pub(crate) set: Option<DefaultHashSet<u32>>,
+}
+
+impl From<TextMatcherV0> for TextMatcher {
+    fn from(matcher: TextMatcherV0) -> Self {
+        TextMatcher {
+            matcher: matcher.matcher.into(),
+            set: matcher.set,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct WordDataMatcherV0 {
+    pub(crate) pos_matcher: Option<PosMatcher>,
+    pub(crate) inflect_matcher: Option<TextMatcherV0>,
+}
+
+impl From<WordDataMatcherV0> for WordDataMatcher {
+    fn from(matcher: WordDataMatcherV0) -> Self {
+        WordDataMatcher {
+            pos_matcher: matcher.pos_matcher,
+            inflect_matcher: matcher.inflect_matcher.map(TextMatcher::from),
+        }
+    }
+}
+
+/// The atoms are stored as variants with the fields of the atom in the same order, which is the same layout.
+#[allow(clippy::enum_variant_names)]
+#[derive(Serialize, Deserialize)]
+pub(crate) enum AtomV0 {
+    ChunkAtom(MatcherV0),
+    SpaceBeforeAtom(SpaceBeforeAtom),
+    TextAtom(TextMatcherV0),
+    WordDataAtom(WordDataMatcherV0, bool),
+    TrueAtom,
+    FalseAtom,
+    AndAtom(Vec<AtomV0>),
+    OrAtom(Vec<AtomV0>),
+    NotAtom(Box<AtomV0>),
+    OffsetAtom(Box<AtomV0>, isize),
+}
+
+fn atoms(atoms: Vec<AtomV0>) -> Vec<Atom> {
+    atoms.into_iter().map(Atom::from).collect()
+}
+
+impl From<AtomV0> for Atom {
+    fn from(atom: AtomV0) -> Self {
+        match atom {
+            AtomV0::ChunkAtom(matcher) => ChunkAtom::new(matcher.into()).into(),
+            AtomV0::SpaceBeforeAtom(atom) => atom.into(),
+            AtomV0::TextAtom(matcher) => TextAtom {
+                matcher: matcher.into(),
+            }
+            .into(),
+            AtomV0::WordDataAtom(matcher, case_sensitive) => WordDataAtom {
+                matcher: matcher.into(),
+                case_sensitive,
+            }
+            .into(),
+            AtomV0::TrueAtom => TrueAtom {}.into(),
+            AtomV0::FalseAtom => FalseAtom {}.into(),
+            AtomV0::AndAtom(x) => AndAtom { atoms: atoms(x) }.into(),
+            AtomV0::OrAtom(x) => OrAtom { atoms: atoms(x) }.into(),
+            AtomV0::NotAtom(atom) => NotAtom {
+                atom: Box::new((*atom).into()),
+            }
+            .into(),
+            AtomV0::OffsetAtom(atom, offset) => OffsetAtom {
+                atom: Box::new((*atom).into()),
+                offset,
+            }
+            .into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PartV0 {
+    pub(crate) atom: AtomV0,
+    pub(crate) quantifier: Quantifier,
+    pub(crate) visible: bool,
+}
+
+/// Compositions before the mask of parts at which a match can stop was removed.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CompositionV0 {
+    pub(crate) parts: Vec<PartV0>,
+    pub(crate) group_ids_to_idx: DefaultHashMap<usize, usize>,
+    pub(crate) can_stop_mask: Vec<bool>,
+}
+
+impl From<CompositionV0> for Composition {
+    fn from(composition: CompositionV0) -> Self {
+        Composition {
+            parts: composition
+                .parts
+                .into_iter()
+                .map(|x| Part {
+                    atom: x.atom.into(),
+                    quantifier: x.quantifier,
+                    visible: x.visible,
+                })
+                .collect(),
+            group_ids_to_idx: composition.group_ids_to_idx,
+        }
+    }
+}
+
+/// Engines before unifications were added.
+#[derive(Serialize, Deserialize)]
+pub(crate) enum EngineV0 {
+    Token(CompositionV0, Vec<CompositionV0>),
+    Text(RegexV0, DefaultHashMap<usize, usize>),
+}
+
+impl From<EngineV0> for Engine {
+    fn from(engine: EngineV0) -> Self {
+        match engine {
+            EngineV0::Token(composition, antipatterns) => Engine::Token(TokenEngine {
+                composition: composition.into(),
+                antipatterns: antipatterns.into_iter().map(Composition::from).collect(),
+                unifications: Vec::new(),
+            }),
+            EngineV0::Text(regex, id_to_idx) => Engine::Text(regex.into(), id_to_idx),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct MatchV0 {
+    pub(crate) id: usize,
+    pub(crate) conversion: Conversion,
+    pub(crate) pos_replacer: Option<PosReplacer>,
+    pub(crate) regex_replacer: Option<(RegexV0, String)>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum SynthesizerPartV0 {
+    Text(String),
+    Match(MatchV0),
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SynthesizerV0 {
+    pub(crate) use_titlecase_adjust: bool,
+    pub(crate) parts: Vec<SynthesizerPartV0>,
+}
+
+impl From<SynthesizerV0> for Synthesizer {
+    fn from(synthesizer: SynthesizerV0) -> Self {
+        Synthesizer {
+            use_titlecase_adjust: synthesizer.use_titlecase_adjust,
+            parts: synthesizer
+                .parts
+                .into_iter()
+                .map(|part| match part {
+                    SynthesizerPartV0::Text(text) => SynthesizerPart::Text(text),
+                    SynthesizerPartV0::Match(m) => SynthesizerPart::Match(Match {
+                        id: m.id,
+                        conversion: m.conversion,
+                        pos_replacer: m.pos_replacer,
+                        regex_replacer: m
+                            .regex_replacer
+                            .map(|(regex, replacement)| (regex.into(), replacement)),
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RuleV0 {
+    pub(crate) id: String,
+    pub(crate) engine: EngineV0,
+    pub(crate) examples: Vec<Example>,
+    pub(crate) suggesters: Vec<SynthesizerV0>,
+    pub(crate) message: SynthesizerV0,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) on: bool,
+    pub(crate) url: Option<String>,
+    pub(crate) short: Option<String>,
+    pub(crate) name: String,
+    pub(crate) category_id: String,
+    pub(crate) category_name: String,
+    pub(crate) category_type: Option<String>,
+}
+
+// rules in a group without an ID of their own have the ID of the group followed by their index
+fn group_from_id(id: &str, name: &str) -> Option<RuleGroup> {
+    let (group_id, index) = id.rsplit_once('.')?;
+
+    Some(RuleGroup {
+        id: group_id.to_string(),
+        name: name.to_string(),
+        index: index.parse().ok()?,
+    })
+}
+
+/// The properties which are derived at compile time are derived while migrating, the group is recovered from IDs
+/// like `CAN_DUE.0`.
+impl From<RuleV0> for Rule {
+    fn from(rule: RuleV0) -> Self {
+        let mut rule = Rule {
+            group: group_from_id(&rule.id, &rule.name),
+            id: rule.id,
+            engine: rule.engine.into(),
+            examples: rule.examples,
+            suggesters: rule.suggesters.into_iter().map(Synthesizer::from).collect(),
+            message: rule.message.into(),
+            start: rule.start,
+            end: rule.end,
+            on: rule.on,
+            url: rule.url,
+            short: rule.short,
+            name: rule.name,
+            category_id: rule.category_id,
+            category_name: rule.category_name,
+            category_type: rule.category_type,
+            uses_chunks: false,
+            safety: FixSafety::NeedsReview,
+            tier: RuleTier::Fast,
+            source: None,
+            translations: Vec::new(),
+        };
+        rule.uses_chunks = rule.depends_on_chunks();
+        rule.safety = rule.classify_safety();
+        rule.tier = rule.classify_tier();
+        rule
+    }
+}
+
+/// Disambiguations before the immunization was added in front of `Nop`.
+#[derive(Serialize, Deserialize)]
+pub(crate) enum DisambiguationV0 {
+    Remove(Vec<Either<owned::WordData, POSFilter>>),
+    Add(Vec<owned::WordData>),
+    Replace(Vec<owned::WordData>),
+    Filter(Vec<Option<Either<owned::WordData, POSFilter>>>),
+    Unify(Vec<Vec<POSFilter>>, Vec<Option<POSFilter>>, Vec<bool>),
+    Nop,
+}
+
+impl From<DisambiguationV0> for Disambiguation {
+    fn from(disambiguation: DisambiguationV0) -> Self {
+        match disambiguation {
+            DisambiguationV0::Remove(x) => Disambiguation::Remove(x),
+            DisambiguationV0::Add(x) => Disambiguation::Add(x),
+            DisambiguationV0::Replace(x) => Disambiguation::Replace(x),
+            DisambiguationV0::Filter(x) => Disambiguation::Filter(x),
+            DisambiguationV0::Unify(x, y, z) => Disambiguation::Unify(x, y, z),
+            DisambiguationV0::Nop => Disambiguation::Nop,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum FilterV0 {
+    NoDisambiguationEnglishPartialPosTagFilter(usize, RegexV0, RegexV0, bool),
+}
+
+impl From<FilterV0> for Filter {
+    fn from(filter: FilterV0) -> Self {
+        match filter {
+            FilterV0::NoDisambiguationEnglishPartialPosTagFilter(
+                index,
+                regexp,
+                postag_regexp,
+                negate_postag,
+            ) => NoDisambiguationEnglishPartialPosTagFilter {
+                index,
+                regexp: regexp.into(),
+                postag_regexp: postag_regexp.into(),
+                negate_postag,
+            }
+            .into(),
+        }
+    }
+}
+
+/// Disambiguation rules before they could be turned off.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct DisambiguationRuleV0 {
+    pub(crate) id: String,
+    pub(crate) engine: EngineV0,
+    pub(crate) disambiguations: DisambiguationV0,
+    pub(crate) filter: Option<FilterV0>,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) examples: Vec<DisambiguationExample>,
+}
+
+impl From<DisambiguationRuleV0> for DisambiguationRule {
+    fn from(rule: DisambiguationRuleV0) -> Self {
+        DisambiguationRule {
+            id: rule.id,
+            engine: rule.engine.into(),
+            disambiguations: rule.disambiguations.into(),
+            filter: rule.filter.map(Filter::from),
+            start: rule.start,
+            end: rule.end,
+            examples: rule.examples,
+            on: true,
+        }
+    }
+}
+
+/// Chunkers before the perceptron chunk model was added.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ChunkerV0 {
+    pub(crate) token_model: MaxentTokenizer,
+    pub(crate) pos_model: MaxentPosTagger,
+    pub(crate) chunk_model: MaxentChunker,
+}
+
+impl From<ChunkerV0> for Chunker {
+    fn from(chunker: ChunkerV0) -> Self {
+        Chunker {
+            token_model: chunker.token_model,
+            pos_model: chunker.pos_model,
+            chunk_model: ChunkModel::Maxent(chunker.chunk_model),
+        }
+    }
+}
+
+/// Taggers before frequency ranks were added.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct TaggerV0 {
+    pub(crate) tags: DefaultHashMap<u32, IndexMap<u32, Vec<u16>>>,
+    pub(crate) tag_store: BiMap<String, u16>,
+    pub(crate) word_store: BiMap<String, u32>,
+    pub(crate) groups: DefaultHashMap<u32, Vec<u32>>,
+}
+
+impl From<TaggerV0> for Tagger {
+    fn from(tagger: TaggerV0) -> Self {
+        Tagger {
+            tags: tagger.tags,
+            tag_store: tagger.tag_store,
+            word_store: tagger.word_store,
+            groups: tagger.groups,
+            ..Tagger::default()
+        }
+    }
+}
+
+/// Tokenizer options before the options for elisions, contractions and sentences were added.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct TokenizerOptionsV0 {
+    pub(crate) allow_errors: bool,
+    pub(crate) retain_last: bool,
+    pub(crate) use_compound_split_heuristic: bool,
+    pub(crate) always_add_lower_tags: bool,
+    pub(crate) ids: Vec<String>,
+    pub(crate) ignore_ids: Vec<String>,
+    pub(crate) known_failures: Vec<String>,
+    pub(crate) extra_tags: Vec<String>,
+}
+
+impl From<TokenizerOptionsV0> for TokenizerOptions {
+    fn from(options: TokenizerOptionsV0) -> Self {
+        TokenizerOptions {
+            allow_errors: options.allow_errors,
+            retain_last: options.retain_last,
+            use_compound_split_heuristic: options.use_compound_split_heuristic,
+            always_add_lower_tags: options.always_add_lower_tags,
+            ids: options.ids,
+            ignore_ids: options.ignore_ids,
+            known_failures: options.known_failures,
+            extra_tags: options.extra_tags,
+            ..TokenizerOptions::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct TokenizerV0 {
+    pub(crate) rules: Vec<DisambiguationRuleV0>,
+    pub(crate) chunker: Option<ChunkerV0>,
+    pub(crate) tagger: TaggerV0,
+    pub(crate) options: TokenizerOptionsV0,
+}
+
+/// The language of the tokenizer was not stored, it has to be set by the caller if it is needed.
+impl From<TokenizerV0> for Tokenizer {
+    fn from(tokenizer: TokenizerV0) -> Self {
+        let tagger = Tagger::from(tokenizer.tagger);
+
+        Tokenizer {
+            metadata: Metadata {
+                rule_count: tokenizer.rules.len(),
+                ..Metadata::default()
+            },
+            rules: tokenizer
+                .rules
+                .into_iter()
+                .map(DisambiguationRule::from)
+                .collect(),
+            chunker: tokenizer.chunker.map(Chunker::from),
+            multiword_tagger: None,
+            fingerprint: tagger.fingerprint(),
+            tagger: Arc::new(tagger),
+            options: tokenizer.options.into(),
+            hard_wrapped: false,
+            checksum: Default::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RulesV0 {
+    pub(crate) rules: Vec<RuleV0>,
+}
+
+/// Deserializes data without header. It can be anything, the limit keeps it from being read as huge strings.
+pub(crate) fn deserialize<T: DeserializeOwned, R: Read>(reader: R) -> bincode::Result<T> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(1 << 30)
+        .deserialize_from(reader)
+}
+
+/// Binaries without format version are tokenizers of nlprule 0.3.0.
+pub(crate) fn tokenizer<R: Read>(version: u32, reader: R) -> Result<Tokenizer, Error> {
+    match version {
+        0 => deserialize::<TokenizerV0, _>(reader)
+            .map(Tokenizer::from)
+            .map_err(|_| unsupported_format::<Tokenizer>(version)),
+        _ => Err(unsupported_format::<Tokenizer>(version)),
+    }
+}
+
+/// Binaries without format version are rules of nlprule 0.3.0. They do not store the fingerprint of the tagger they
+/// were compiled with, so the migrated rules have fingerprint 0, see [Rules::check_compatibility].
+pub(crate) fn rules_fields<R: Read>(version: u32, reader: R) -> Result<RulesFields, Error> {
+    if version != 0 {
+        return Err(unsupported_format::<Rules>(version));
+    }
+
+    let rules: Vec<Rule> = deserialize::<RulesV0, _>(reader)
+        .map_err(|_| unsupported_format::<Rules>(version))?
+        .rules
+        .into_iter()
+        .map(Rule::from)
+        .collect();
+
+    Ok(RulesFields {
+        metadata: Metadata {
+            rule_count: rules.len(),
+            ..Metadata::default()
+        },
+        segments: Segment::from_rules(rules.iter().enumerate())?,
+        fingerprint: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pipeline::Pipeline,
+        rules::tests::{DE, EN},
+        Component,
+    };
+
+    // compiled from the rules in `tests/fixtures` with nlprule 0.3.0
+    const DIR: &str = "tests/fixtures/0.3.0";
+
+    #[test]
+    fn migrates_binaries_of_0_3_0() {
+        for (lang_code, (current_tokenizer, current_rules)) in &[("en", &*EN), ("de", &*DE)] {
+            let tokenizer = Tokenizer::new(format!("{}/{}_tokenizer.bin", DIR, lang_code)).unwrap();
+            let rules = Rules::new(format!("{}/{}_rules.bin", DIR, lang_code)).unwrap();
+            assert_eq!(tokenizer.fingerprint(), tokenizer.tagger().fingerprint());
+            assert_eq!(rules.fingerprint(), 0);
+
+            for rule in rules.rules() {
+                let current = current_rules.rule(rule.id()).unwrap();
+                assert_eq!(
+                    (rule.safety(), rule.tier(), rule.uses_chunks()),
+                    (current.safety(), current.tier(), current.uses_chunks())
+                );
+
+                for example in rule.examples() {
+                    let suggest = |rules: &Rules, tokenizer: &Tokenizer| -> Vec<_> {
+                        rules
+                            .suggest(example.text(), tokenizer)
+                            .into_iter()
+                            .map(|x| (x.source, x.replacements, x.start, x.end))
+                            .collect()
+                    };
+                    assert_eq!(
+                        suggest(&rules, &tokenizer),
+                        suggest(current_rules, current_tokenizer)
+                    );
+                }
+            }
+
+            assert!(Pipeline::new(tokenizer, rules).is_ok());
+        }
+
+        let rules = Rules::new(format!("{}/en_rules.bin", DIR)).unwrap();
+        assert_eq!(
+            rules.rule("CAN_DUE.1").unwrap().group().map(|x| x.id()),
+            Some("CAN_DUE")
+        );
+        assert!(rules.rule("WAS_BEEN").unwrap().group().is_none());
+    }
+
+    #[test]
+    fn rejects_other_binaries_without_header() {
+        let bytes = std::fs::read(format!("{}/en_tokenizer.bin", DIR)).unwrap();
+
+        assert!(matches!(
+            Tokenizer::read(&bytes[..bytes.len() / 2]),
+            Err(Error::UnsupportedFormat { found: 0, .. })
+        ));
+        assert!(matches!(
+            Rules::read(&bytes[..]),
+            Err(Error::UnsupportedFormat { found: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,241 @@
+//! Project configuration in a `nlprule.toml` file, so a team can commit the settings of its prose linting next to
+//! the documents they apply to. Requires the `config` feature.
+//!
+//! ```toml
+//! language = "en"
+//! tokenizer = "storage/en_tokenizer.bin"
+//! rules = "storage/en_rules.bin"
+//! enabled_rules = ["CAPITAL_AFTER_DOT"]
+//! disabled_rules = ["ALOT"]
+//! dictionaries = ["terms.txt"]
+//!
+//! [severities]
+//! GRAMMAR = "error"
+//! TYPOGRAPHY = "hint"
+//! ```
+//!
+//! All keys are optional. Relative paths are relative to the directory of the file. Rules are enabled and disabled
+//! by rule or rule group ID, see [Rules::enable_rules]. The severities map rule, rule group or category IDs, see
+//! [CheckOptions::severities]. Dictionaries add words to the tagger and are in the format of
+//! [TaggerExtension::from_dumps]. Without `tokenizer` and `rules`, the binaries of the language are used, which
+//! needs the `binaries` feature.
+//!
+//! ```no_run
+//! use nlprule::config::Config;
+//!
+//! let config = Config::discover("docs/guide.md")?.unwrap_or_default();
+//! let (tokenizer, rules) = config.load()?;
+//!
+//! let suggestions = rules.suggest("She was not been here.", &tokenizer);
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use crate::{
+    rules::CheckOptions, tokenizer::tag::TaggerExtension, types::Severity, Error, Rules, Tokenizer,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The name of the configuration file.
+pub const CONFIG_FILE: &str = "nlprule.toml";
+
+/// The settings of a `nlprule.toml` file, see the [module][self] documentation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The language code, e. g. `en`.
+    pub language: Option<String>,
+    pub tokenizer: Option<PathBuf>,
+    pub rules: Option<PathBuf>,
+    pub enabled_rules: Vec<String>,
+    pub disabled_rules: Vec<String>,
+    pub severities: BTreeMap<String, Severity>,
+    pub dictionaries: Vec<PathBuf>,
+    // the file the config was read from
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Reads the config from a file and resolves its relative paths.
+    pub fn new<P: AsRef<Path>>(p: P) -> Result<Self, Error> {
+        let path = p.as_ref();
+        let mut config: Config = toml::from_str(&fs::read_to_string(path)?)
+            .map_err(|error| Error::InvalidConfig(format!("{}: {}", path.display(), error)))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for path in config
+            .tokenizer
+            .iter_mut()
+            .chain(config.rules.iter_mut())
+            .chain(config.dictionaries.iter_mut())
+        {
+            *path = dir.join(&*path);
+        }
+        config.path = Some(path.to_path_buf());
+
+        Ok(config)
+    }
+
+    /// Parses a config in the TOML format. Relative paths are kept as they are.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        toml::from_str(text).map_err(|error| Error::InvalidConfig(error.to_string()))
+    }
+
+    /// The nearest config which applies to the path: the [CONFIG_FILE] in its directory (or in the path itself if it
+    /// is a directory) or in the closest ancestor which has one. `None` if there is none.
+    pub fn discover<P: AsRef<Path>>(p: P) -> Result<Option<Self>, Error> {
+        let path = p.as_ref();
+        let path = if path.is_relative() {
+            std::env::current_dir()?.join(path)
+        } else {
+            path.to_path_buf()
+        };
+        let start = if path.is_dir() {
+            path.as_path()
+        } else {
+            path.parent().unwrap_or(&path)
+        };
+
+        for dir in start.ancestors() {
+            let candidate = dir.join(CONFIG_FILE);
+            if candidate.is_file() {
+                return Self::new(candidate).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The file the config was read from, `None` if it was parsed or created otherwise.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Loads the tokenizer and the rules and [applies][Config::apply] the config to them.
+    pub fn load(&self) -> Result<(Tokenizer, Rules), Error> {
+        let mut tokenizer = match (&self.tokenizer, &self.language) {
+            (Some(path), _) => Tokenizer::new(path)?,
+            #[cfg(feature = "binaries")]
+            (None, Some(lang_code)) => Tokenizer::from_lang(lang_code)?,
+            _ => return Err(self.missing("tokenizer")),
+        };
+        let mut rules = match (&self.rules, &self.language) {
+            (Some(path), _) => Rules::new(path)?,
+            #[cfg(feature = "binaries")]
+            (None, Some(lang_code)) => Rules::from_lang(lang_code)?,
+            _ => return Err(self.missing("rules")),
+        };
+
+        self.apply(&mut tokenizer, &mut rules)?;
+        Ok((tokenizer, rules))
+    }
+
+    fn missing(&self, key: &str) -> Error {
+        let reason = if cfg!(feature = "binaries") {
+            format!("neither `{}` nor `language` is set", key)
+        } else {
+            format!(
+                "`{}` is not set, loading by `language` needs the `binaries` feature",
+                key
+            )
+        };
+        match &self.path {
+            Some(path) => Error::InvalidConfig(format!("{}: {}", path.display(), reason)),
+            None => Error::InvalidConfig(reason),
+        }
+    }
+
+    /// Applies the config to a loaded tokenizer and rules: checks that their language (if they store it) is the configured one,
+    /// extends the tagger with the dictionaries, enables and then disables the rules and adds the severities to the
+    /// check options.
+    pub fn apply(&self, tokenizer: &mut Tokenizer, rules: &mut Rules) -> Result<(), Error> {
+        if let Some(language) = &self.language {
+            for lang_code in &[
+                tokenizer.metadata().lang_code(),
+                rules.metadata().lang_code(),
+            ] {
+                // binaries of nlprule 0.3.0 do not know their language
+                if !lang_code.is_empty() && lang_code != language {
+                    return Err(Error::InvalidConfig(format!(
+                        "the language is {}, but the binaries are for {}",
+                        language, lang_code
+                    )));
+                }
+            }
+        }
+
+        if !self.dictionaries.is_empty() {
+            let paths: Vec<_> = self
+                .dictionaries
+                .iter()
+                .map(|x| x.to_string_lossy().into_owned())
+                .collect();
+            tokenizer.extend_tagger(&TaggerExtension::from_dumps(&paths)?)?;
+        }
+
+        rules.enable_rules(&self.enabled_rules);
+        rules.disable_rules(&self.disabled_rules);
+        if !self.severities.is_empty() {
+            let mut severities = rules.check_options().severities.clone();
+            severities.extend(self.severities.iter().map(|(id, x)| (id.clone(), *x)));
+            rules.set_check_options(CheckOptions {
+                severities,
+                ..rules.check_options().clone()
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discovers_and_applies_configs() {
+        let root = std::env::temp_dir().join(format!("nlprule-config-{}", std::process::id()));
+        let docs = root.join("docs").join("guide");
+        fs::create_dir_all(&docs).unwrap();
+        let storage = fs::canonicalize("../storage/fixtures").unwrap();
+        fs::write(
+            root.join(CONFIG_FILE),
+            format!(
+                "language = \"en\"\n\
+                 tokenizer = {:?}\n\
+                 rules = {:?}\n\
+                 enabled_rules = [\"CAPITAL_AFTER_DOT\"]\n\
+                 disabled_rules = [\"ALOT\"]\n\
+                 \n\
+                 [severities]\n\
+                 GRAMMAR = \"error\"\n",
+                storage.join("en_tokenizer.bin"),
+                storage.join("en_rules.bin"),
+            ),
+        )
+        .unwrap();
+
+        let config = Config::discover(docs.join("intro.md")).unwrap().unwrap();
+        assert_eq!(config.path(), Some(root.join(CONFIG_FILE).as_path()));
+        let (tokenizer, rules) = config.load().unwrap();
+        assert!(Config::discover(std::env::temp_dir().join("nlprule.md"))
+            .unwrap()
+            .is_none());
+
+        let invalid = Config::parse("disabled_rule = [\"ALOT\"]");
+        fs::remove_dir_all(&root).unwrap();
+
+        let suggestions = rules.suggest("He knows alot. I ate a apple today.", &tokenizer);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].source, "CAPITAL_AFTER_DOT");
+        assert_eq!(
+            rules.suggest("He dosn't know.", &tokenizer)[0].severity,
+            Severity::Error
+        );
+        assert!(matches!(invalid, Err(Error::InvalidConfig(_))));
+    }
+}
@@ -1,6 +1,9 @@
 use crate::tokenizer::Tokenizer;
 use crate::utils::regex::SerializeRegex;
-use crate::{rule::MatchGraph, Error};
+use crate::{
+    rule::{engine::composition::Leaf, MatchGraph},
+    Error,
+};
 use enum_dispatch::enum_dispatch;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -14,6 +17,9 @@ pub enum Filter {
 #[enum_dispatch(Filter)]
 pub trait Filterable {
     fn keep(&self, graph: &MatchGraph, tokenizer: &Tokenizer) -> bool;
+
+    /// Calls `f` with all leaves of this filter.
+    fn visit<'a>(&'a self, _f: &mut dyn FnMut(Leaf<'a>)) {}
 }
 
 trait FromArgs: Sized {
@@ -22,11 +28,11 @@ trait FromArgs: Sized {
 
 #[derive(Serialize, Deserialize)]
 pub struct NoDisambiguationEnglishPartialPosTagFilter {
-    index: usize,
-    regexp: SerializeRegex,
-    postag_regexp: SerializeRegex,
+    pub(crate) index: usize,
+    pub(crate) regexp: SerializeRegex,
+    pub(crate) postag_regexp: SerializeRegex,
     #[allow(dead_code)]
-    negate_postag: bool,
+    pub(crate) negate_postag: bool,
 }
 
 impl FromArgs for NoDisambiguationEnglishPartialPosTagFilter {
@@ -45,6 +51,11 @@ impl FromArgs for NoDisambiguationEnglishPartialPosTagFilter {
 }
 
 impl Filterable for NoDisambiguationEnglishPartialPosTagFilter {
+    fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        f(Leaf::Regex(&self.regexp));
+        f(Leaf::Regex(&self.postag_regexp));
+    }
+
     fn keep(&self, graph: &MatchGraph, tokenizer: &Tokenizer) -> bool {
         if let Some(group) = graph.by_id(self.index) {
             let tokens = &group.tokens(graph.tokens());
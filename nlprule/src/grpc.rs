@@ -0,0 +1,252 @@
+//! A gRPC service checking texts with a [Pipeline]. Requires the `grpc` feature.
+//!
+//! The service is defined in `proto/nlprule.proto`, the `grpc_server` binary serves it. The messages and traits in
+//! [proto] are generated from the definition with `tonic-build` and checked in, so building does not need `protoc`.
+//! Regenerate them after changing the definition, e. g. with `protox` and
+//! `tonic_build::configure().out_dir("src/grpc").compile_fds(fds)`, and import `std::convert::TryInto` in the
+//! client module since this crate uses the 2018 edition.
+//!
+//! ```no_run
+//! use nlprule::{grpc::CheckService, Pipeline};
+//! use std::sync::Arc;
+//!
+//! # async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+//! let pipeline = Pipeline::new(
+//!     nlprule::Tokenizer::new("path/to/en_tokenizer.bin")?,
+//!     nlprule::Rules::new("path/to/en_rules.bin")?,
+//! )?;
+//!
+//! tonic::transport::Server::builder()
+//!     .add_service(CheckService::new(Arc::new(pipeline)).into_server())
+//!     .serve("[::1]:50051".parse()?)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{types::FixSafety, Error, Pipeline};
+use proto::{
+    checker_server::{Checker, CheckerServer},
+    CheckRequest, CheckResponse,
+};
+use std::{
+    io::{self, Read},
+    sync::Arc,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+/// The messages and the client and server of the service, generated from `proto/nlprule.proto`.
+#[allow(clippy::all, missing_docs)]
+pub mod proto {
+    include!("grpc/nlprule.v1.rs");
+}
+
+/// The number of chunks and suggestions of a stream which are buffered. A client which sends chunks faster
+/// than they are checked or does not read the suggestions has to wait.
+const STREAM_BUFFER: usize = 16;
+
+impl From<crate::types::Suggestion> for proto::Suggestion {
+    fn from(suggestion: crate::types::Suggestion) -> Self {
+        proto::Suggestion {
+            source: suggestion.source,
+            message: suggestion.message,
+            start: suggestion.start as u64,
+            end: suggestion.end as u64,
+            replacements: suggestion.replacements,
+            safe: suggestion.safety == FixSafety::Safe,
+            confidence: suggestion.confidence,
+            merged_sources: suggestion.merged_sources,
+            short_message: suggestion.short_message,
+            matched: suggestion.matched,
+            severity: suggestion.severity.to_string(),
+        }
+    }
+}
+
+// reads the chunks of a streamed document on a blocking thread
+struct ChunkReader {
+    receiver: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk?;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.chunk.len() - self.position);
+        buf[..n].copy_from_slice(&self.chunk[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Implements the `Checker` service. Texts are checked on the blocking threads of the runtime.
+pub struct CheckService {
+    pipeline: Arc<Pipeline>,
+}
+
+impl CheckService {
+    pub fn new(pipeline: Arc<Pipeline>) -> Self {
+        CheckService { pipeline }
+    }
+
+    /// The service to add to a [tonic::transport::Server].
+    pub fn into_server(self) -> CheckerServer<Self> {
+        CheckerServer::new(self)
+    }
+}
+
+#[tonic::async_trait]
+impl Checker for CheckService {
+    async fn check(
+        &self,
+        request: Request<CheckRequest>,
+    ) -> Result<Response<CheckResponse>, Status> {
+        let pipeline = self.pipeline.clone();
+        let text = request.into_inner().text;
+
+        let suggestions = tokio::task::spawn_blocking(move || pipeline.check(&text))
+            .await
+            .map_err(|x| Status::internal(x.to_string()))?;
+
+        Ok(Response::new(CheckResponse {
+            suggestions: suggestions.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    type CheckStreamStream = ReceiverStream<Result<proto::Suggestion, Status>>;
+
+    async fn check_stream(
+        &self,
+        request: Request<Streaming<CheckRequest>>,
+    ) -> Result<Response<Self::CheckStreamStream>, Status> {
+        let mut requests = request.into_inner();
+        let (chunk_sender, chunk_receiver) = mpsc::channel(STREAM_BUFFER);
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+
+        tokio::spawn(async move {
+            loop {
+                let chunk = match requests.message().await {
+                    Ok(Some(request)) => Ok(request.text.into_bytes()),
+                    Ok(None) => break,
+                    Err(status) => Err(io::Error::other(status.to_string())),
+                };
+                let failed = chunk.is_err();
+
+                if chunk_sender.send(chunk).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let pipeline = self.pipeline.clone();
+        tokio::task::spawn_blocking(move || {
+            let reader = ChunkReader {
+                receiver: chunk_receiver,
+                chunk: Vec::new(),
+                position: 0,
+            };
+
+            // sending fails if the client went away, the rest of the document is still read to the end
+            let result = pipeline.check_from_reader(reader, |suggestion| {
+                let _ = sender.blocking_send(Ok(suggestion.into()));
+            });
+            if let Err(error) = result {
+                let status = match error {
+                    Error::Io(error) if error.kind() == io::ErrorKind::InvalidData => {
+                        Status::invalid_argument(error.to_string())
+                    }
+                    error => Status::aborted(error.to_string()),
+                };
+                let _ = sender.blocking_send(Err(status));
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Rules, Tokenizer};
+    use tokio_stream::StreamExt;
+
+    fn service() -> CheckService {
+        let pipeline = Pipeline::new(
+            Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap(),
+            Rules::new("../storage/fixtures/en_rules.bin").unwrap(),
+        )
+        .unwrap();
+        CheckService::new(Arc::new(pipeline))
+    }
+
+    #[tokio::test]
+    async fn checks_texts() {
+        let service = service();
+        let text = "She was not been here. He dosn't know.";
+
+        let response = service
+            .check(Request::new(CheckRequest { text: text.into() }))
+            .await
+            .unwrap()
+            .into_inner();
+        let expected: Vec<proto::Suggestion> = service
+            .pipeline
+            .check(text)
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        assert_eq!(response.suggestions, expected);
+        assert_eq!(response.suggestions.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn checks_streamed_documents() {
+        let service = service();
+        let pipeline = service.pipeline.clone();
+        let server = tonic::transport::Server::builder().add_service(service.into_server());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            server.serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let mut client =
+            proto::checker_client::CheckerClient::connect(format!("http://{}", address))
+                .await
+                .unwrap();
+        let text = "She was not been here. He dosn't know.";
+        // splits sentences and words
+        let chunks: Vec<_> = ["She was not be", "en here. He dosn", "'t know."]
+            .iter()
+            .map(|x| CheckRequest {
+                text: x.to_string(),
+            })
+            .collect();
+
+        let suggestions: Vec<_> = client
+            .check_stream(tokio_stream::iter(chunks))
+            .await
+            .unwrap()
+            .into_inner()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        let expected: Vec<proto::Suggestion> =
+            pipeline.check(text).into_iter().map(Into::into).collect();
+        assert_eq!(suggestions, expected);
+    }
+}
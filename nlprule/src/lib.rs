@@ -4,6 +4,7 @@
 //! NLPRule has the following core abstractions:
 //! - A [Tokenizer][tokenizer::Tokenizer] to split a text into tokens and analyze it by chunking, lemmatizing and part-of-speech tagging. Can also be used independently of the grammatical rules.
 //! - A [Rules][rules::Rules] structure containing a set of grammatical error correction rules.
+//! - A [Pipeline][pipeline::Pipeline] owning both to check texts with one call.
 //!
 //! # Example: correct a text
 //!
@@ -17,13 +18,13 @@
 //!     rules.correct("She was not been here since Monday.", &tokenizer),
 //!     String::from("She was not here since Monday.")
 //! );
-//! # Ok::<(), bincode::Error>(())
+//! # Ok::<(), nlprule::Error>(())
 //! ```
 //!
 //! # Example: get suggestions and correct a text
 //!
 //! ```no_run
-//! use nlprule::{Tokenizer, Rules, types::Suggestion, rules::apply_suggestions};
+//! use nlprule::{Tokenizer, Rules, rules::apply_suggestions};
 //!
 //! let tokenizer = Tokenizer::new("path/to/en_tokenizer.bin")?;
 //! let rules = Rules::new("path/to/en_rules.bin")?;
@@ -31,46 +32,151 @@
 //! let text = "She was not been here since Monday.";
 //!
 //! let suggestions = rules.suggest(text, &tokenizer);
-//! assert_eq!(
-//!     suggestions,
-//!     vec![Suggestion {
-//!         start: 4, // these are character indices!
-//!         end: 16,
-//!         replacements: vec!["was not".into(), "has not been".into()],
-//!         source: "WAS_BEEN.1".into(),
-//!         message: "Did you mean was not or has not been?".into()
-//!     }]
-//! );
+//! assert_eq!(suggestions.len(), 1);
+//!
+//! let suggestion = &suggestions[0];
+//! assert_eq!((suggestion.start, suggestion.end), (4, 16)); // these are character indices!
+//! assert_eq!(suggestion.matched, "was not been");
+//! assert_eq!(suggestion.replacements, vec!["was not", "has not been"]);
+//! assert_eq!(suggestion.source, "WAS_BEEN.1");
+//! assert_eq!(suggestion.message, "Did you mean was not or has not been?");
 //!
 //! let corrected = apply_suggestions(text, &suggestions);
 //!
 //! assert_eq!(corrected, "She was not here since Monday.");
-//! # Ok::<(), bincode::Error>(())
+//! # Ok::<(), nlprule::Error>(())
 //! ```
 //!
 //! Binaries are distributed with [Github releases](https://github.com/bminixhofer/nlprule/releases).
+//! With the `binaries` feature, `Tokenizer::from_lang` and `Rules::from_lang` download and cache them automatically.
 //!
 //! # The 't lifetime
 //! By convention the lifetime `'t` in this crate is the lifetime of the input text.
 //! Almost all structures with a lifetime are bound to this lifetime.
+//!
+//! # Thread safety
+//! [Tokenizer], [Rules] and [Pipeline] are `Send + Sync`, so one instance can be shared between threads e. g. in an
+//! `Arc` and used to check texts concurrently. The state they change while checking (the suggestion cache, the
+//! statistics of skipped rules and lazily compiled regexes) is behind locks or `OnceCell`s. This is checked at
+//! compile time.
 use thiserror::Error;
 
+#[cfg(feature = "binaries")]
+pub mod binaries;
 #[cfg(feature = "compile")]
 pub mod compile;
+pub mod component;
+#[cfg(feature = "config")]
+pub mod config;
 mod filter;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod markup;
+pub mod pipeline;
 pub mod rule;
 pub mod rules;
+pub mod search;
 pub mod tokenizer;
 pub mod types;
 pub(crate) mod utils;
 
+pub use component::Component;
+pub use pipeline::Pipeline;
 pub use rules::Rules;
 pub use tokenizer::Tokenizer;
 
+// fails to compile if one of the types is not shareable between threads anymore
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Tokenizer>();
+    assert_send_sync::<Rules>();
+    assert_send_sync::<Pipeline>();
+    assert_send_sync::<pipeline::PipelineBuilder>();
+    assert_send_sync::<types::Suggestion>();
+    assert_send_sync::<types::Token<'static>>();
+    assert_send_sync::<Error>();
+};
+
+/// Embeds the binaries for a language in the executable and loads them. Evaluates to a `(Tokenizer, Rules)` tuple.
+///
+/// The binaries are read from `$OUT_DIR/{lang_code}_tokenizer.bin` and `$OUT_DIR/{lang_code}_rules.bin` at compile time,
+/// so they have to be stored there by a build script e. g. with [binaries::store][crate::binaries::store]:
+///
+/// ```ignore
+/// // build.rs
+/// fn main() {
+///     nlprule::binaries::store("en", std::env::var("OUT_DIR").unwrap()).unwrap();
+/// }
+///
+/// // main.rs
+/// let (tokenizer, rules) = nlprule::embed_nlprule!("en");
+/// ```
+///
+/// A directory other than `$OUT_DIR` can be passed as second argument. It must be a string literal.
+#[macro_export]
+macro_rules! embed_nlprule {
+    ($lang_code:literal) => {
+        $crate::embed_nlprule!($lang_code, env!("OUT_DIR"))
+    };
+    ($lang_code:literal, $dir:expr) => {
+        (
+            $crate::Tokenizer::from_reader(
+                &include_bytes!(concat!($dir, "/", $lang_code, "_tokenizer.bin"))[..],
+            )
+            .expect("embedded tokenizer binary must be valid"),
+            $crate::Rules::from_reader(
+                &include_bytes!(concat!($dir, "/", $lang_code, "_rules.bin"))[..],
+            )
+            .expect("embedded rules binary must be valid"),
+        )
+    };
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("unexpected condition: {0}")]
     Unexpected(String),
     #[error("feature not implemented: {0}")]
     Unimplemented(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Deserialization(#[from] bincode::Error),
+    /// The rules were compiled with a different tagger than the tokenizer e. g. for another language or with another version.
+    #[error("incompatible components: tokenizer has fingerprint {tokenizer:016x}, rules have fingerprint {rules:016x}")]
+    IncompatibleComponents { tokenizer: u64, rules: u64 },
+    /// Rules compiled with different taggers were [merged][Rules::merge].
+    #[error("incompatible rules: rules have fingerprint {expected:016x}, merged rules have fingerprint {found:016x}")]
+    IncompatibleRules { expected: u64, found: u64 },
+    /// Rules with an ID which is already used were [merged][Rules::merge] with [DuplicateRules::Error][rules::DuplicateRules::Error].
+    #[error("duplicate rule ID: {0}")]
+    DuplicateRule(String),
+    /// A [RulesPatch][rules::patch::RulesPatch] was applied to other rules than the ones it was created from.
+    #[error(
+        "patch does not apply: it expects rules with checksum {expected:016x}, found {found:016x}"
+    )]
+    PatchMismatch { expected: u64, found: u64 },
+    /// The binary was created with a format version this version of nlprule can not read.
+    #[error("unsupported format: {component} binary has format version {found}, the current version is {current}. Recompile the binary or use a matching version of nlprule")]
+    UnsupportedFormat {
+        component: String,
+        found: u32,
+        current: u32,
+    },
+    /// The binary is of another component e. g. a tokenizer binary was loaded as rules.
+    #[error("wrong component: expected a {expected} binary, found a {found} binary")]
+    WrongComponent { expected: String, found: String },
+    /// A line of a [WordList][rules::word_list::WordList] is not valid.
+    #[error("invalid word list: line {line}: {reason}")]
+    InvalidWordList { line: usize, reason: String },
+    /// A `nlprule.toml` file can not be parsed or does not fit the binaries, see the `config` module.
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+    /// A query of the [search][crate::search] module is not valid.
+    #[error("invalid query: {0}")]
+    InvalidQuery(String),
+    #[cfg(feature = "binaries")]
+    #[error(transparent)]
+    Download(#[from] reqwest::Error),
 }
@@ -0,0 +1,153 @@
+//! Checking text embedded in markup. A [Markup] handler extracts the text to check from a document,
+//! [Extracted] maps the suggestions for it back to the source. There are handlers for
+//! [LaTeX][latex::Latex], [reStructuredText][rst::Rst], [AsciiDoc][asciidoc::AsciiDoc] and for the
+//! [comments in source code][code::Code].
+//!
+//! ```no_run
+//! use nlprule::{markup::latex::Latex, Pipeline};
+//!
+//! let pipeline = Pipeline::builder()
+//!     .tokenizer_path("path/to/en_tokenizer.bin")
+//!     .rules_path("path/to/en_rules.bin")
+//!     .build()?;
+//!
+//! let source = r"\section{Introduction} She was not been here since \emph{Monday}.";
+//! for suggestion in pipeline.check_markup(source, &Latex::default()) {
+//!     // `start` and `end` are char indices in `source`
+//!     println!("{:?}", suggestion);
+//! }
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use crate::types::Suggestion;
+
+pub mod asciidoc;
+pub mod code;
+pub mod latex;
+pub mod rst;
+
+/// Extracts the text which should be checked from a marked-up document.
+pub trait Markup {
+    fn extract(&self, source: &str) -> Extracted;
+}
+
+/// Text extracted from a marked-up source together with the origin of each of its characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extracted {
+    text: String,
+    // the char index in the source each char of the text comes from and whether it is a copy of that char
+    origins: Vec<(usize, bool)>,
+    // the origin of the end of the text
+    source_len: usize,
+}
+
+impl Extracted {
+    pub(crate) fn new(source_len: usize) -> Self {
+        Extracted {
+            text: String::new(),
+            origins: Vec::new(),
+            source_len,
+        }
+    }
+
+    /// Appends a char copied from the source at the given char index.
+    pub(crate) fn push(&mut self, c: char, source_index: usize) {
+        self.text.push(c);
+        self.origins.push((source_index, true));
+    }
+
+    /// Appends the chars in `source[start..end]`, where `source` are the chars of the source.
+    pub(crate) fn push_copy(&mut self, source: &[char], start: usize, end: usize) {
+        for (i, c) in source.iter().enumerate().take(end).skip(start) {
+            self.push(*c, i);
+        }
+    }
+
+    /// Appends text standing in for markup at the given char index, e. g. a space for `~` in LaTeX.
+    pub(crate) fn push_replacement(&mut self, text: &str, source_index: usize) {
+        for c in text.chars() {
+            self.text.push(c);
+            self.origins.push((source_index, false));
+        }
+    }
+
+    /// The text to check.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The char span in the source of a char span in the text. `None` if the span is not a plain copy of
+    /// the source, i. e. if it contains replaced characters or skipped markup lies between its characters.
+    pub fn source_span(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        if start == end {
+            let index = self.origins.get(start).map_or(self.source_len, |x| x.0);
+            return Some((index, index));
+        }
+
+        let origins = self.origins.get(start..end)?;
+        let is_copy =
+            origins.iter().all(|x| x.1) && origins.windows(2).all(|x| x[1].0 == x[0].0 + 1);
+
+        if is_copy {
+            Some((origins[0].0, origins[origins.len() - 1].0 + 1))
+        } else {
+            None
+        }
+    }
+
+    /// Maps the spans of suggestions for the text to the source. Suggestions whose span is not a plain copy
+    /// of the source are dropped, since applying them would change the markup.
+    pub fn map_suggestions(&self, suggestions: Vec<Suggestion>) -> Vec<Suggestion> {
+        suggestions
+            .into_iter()
+            .filter_map(|mut suggestion| {
+                let (start, end) = self.source_span(suggestion.start, suggestion.end)?;
+                suggestion.start = start;
+                suggestion.end = end;
+                Some(suggestion)
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn starts_with(chars: &[char], i: usize, pattern: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    chars[i..].starts_with(&pattern)
+}
+
+/// The index of the first occurrence of the pattern in `chars[start..end]`.
+pub(crate) fn find(chars: &[char], start: usize, end: usize, pattern: &str) -> Option<usize> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    (start..end).find(|&i| chars[i..end].starts_with(&pattern))
+}
+
+/// The char spans of the lines, without the line break.
+pub(crate) fn lines(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '\n' {
+            lines.push((start, i));
+            start = i + 1;
+        }
+    }
+    lines.push((start, chars.len()));
+    lines
+}
+
+/// If the run of the char at `i` in `chars[start..end]` is an emphasis mark like the `*` in `*word*`,
+/// the end of the run. A run is a mark if a word is on exactly one side of it.
+pub(crate) fn emphasis_mark(chars: &[char], i: usize, start: usize, end: usize) -> Option<usize> {
+    let mut run_end = i;
+    while run_end < end && chars[run_end] == chars[i] {
+        run_end += 1;
+    }
+    let left = i > start && chars[i - 1].is_alphanumeric();
+    let right = run_end < end && chars[run_end].is_alphanumeric();
+
+    if left != right {
+        Some(run_end)
+    } else {
+        None
+    }
+}
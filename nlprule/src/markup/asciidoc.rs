@@ -0,0 +1,385 @@
+//! AsciiDoc, see [AsciiDoc].
+
+use super::{emphasis_mark, find, lines, starts_with, Extracted, Markup};
+
+// block styles whose block is not text
+const SKIPPED_STYLES: &[&str] = &[
+    "asciimath",
+    "comment",
+    "ditaa",
+    "graphviz",
+    "latexmath",
+    "listing",
+    "literal",
+    "mermaid",
+    "pass",
+    "plantuml",
+    "source",
+    "stem",
+];
+
+const ADMONITION_LABELS: &[&str] = &["NOTE: ", "TIP: ", "IMPORTANT: ", "WARNING: ", "CAUTION: "];
+
+// inline macros which are not rendered as text
+const HIDDEN_MACROS: &[&str] = &["anchor", "image", "indexterm", "indexterm2"];
+
+// inline macros which are replaced with the placeholder
+const PLACEHOLDER_MACROS: &[&str] = &[
+    "asciimath",
+    "btn",
+    "kbd",
+    "latexmath",
+    "menu",
+    "pass",
+    "stem",
+];
+
+/// Extracts the text of an AsciiDoc document. Skips comments, attribute entries, listing, literal and
+/// passthrough blocks as well as blocks and paragraphs with a style like `[source]`. Inline code, attribute
+/// references and macros like `kbd:[]` are replaced with a placeholder, the text of links and cross
+/// references is kept. Section and block titles are separate paragraphs.
+#[derive(Debug, Clone)]
+pub struct AsciiDoc {
+    /// The text inline markup which is not text is replaced with, so that the sentence around it stays intact.
+    pub placeholder: String,
+}
+
+impl Default for AsciiDoc {
+    fn default() -> Self {
+        AsciiDoc {
+            placeholder: "X".into(),
+        }
+    }
+}
+
+fn is_blank(line: &[char]) -> bool {
+    line.iter().all(|c| c.is_whitespace())
+}
+
+fn trim_end(chars: &[char], start: usize, mut end: usize) -> usize {
+    while end > start && chars[end - 1].is_whitespace() {
+        end -= 1;
+    }
+    end
+}
+
+fn is_delimiter(line: &[char]) -> bool {
+    line == ['-', '-']
+        || starts_with(line, 0, "```")
+        || (line.len() >= 4
+            && (line.iter().all(|c| *c == line[0]) && "-./+=*_".contains(line[0])
+                || line[0] == '|' && line[1..].iter().all(|c| *c == '=')))
+}
+
+// whether the content of a block with this delimiter is not text
+fn is_verbatim(delimiter: &[char]) -> bool {
+    delimiter != ['-', '-'] && "-./+`".contains(delimiter[0])
+}
+
+impl AsciiDoc {
+    // the index after the macro like `link:target[text]` or the URL at `i`, if there is one
+    fn inline_macro(
+        &self,
+        chars: &[char],
+        i: usize,
+        end: usize,
+        extracted: &mut Extracted,
+    ) -> Option<usize> {
+        let mut target_end = i;
+        while target_end < end && !chars[target_end].is_whitespace() && chars[target_end] != '[' {
+            target_end += 1;
+        }
+        let colon = find(chars, i, target_end, ":")?;
+        let name: String = chars[i..colon].iter().collect();
+        if name.is_empty() || !name.chars().all(char::is_alphanumeric) {
+            return None;
+        }
+
+        if target_end < end && chars[target_end] == '[' {
+            let close = find(chars, target_end + 1, end, "]")?;
+
+            if PLACEHOLDER_MACROS.contains(&name.as_str()) {
+                extracted.push_replacement(&self.placeholder, i);
+            } else if !HIDDEN_MACROS.contains(&name.as_str()) {
+                // attributes like `window=_blank` after the text
+                let text_end = find(chars, target_end + 1, close, ",")
+                    .filter(|comma| find(chars, *comma, close, "=").is_some())
+                    .unwrap_or(close);
+
+                if text_end == target_end + 1 {
+                    extracted.push_replacement(&self.placeholder, i);
+                }
+                extracted.push_copy(chars, target_end + 1, text_end);
+            }
+            Some(close + 1)
+        } else if starts_with(&chars[..end], colon + 1, "//") {
+            // punctuation after bare URLs belongs to the sentence
+            while target_end > colon && ".,;:!?)".contains(chars[target_end - 1]) {
+                target_end -= 1;
+            }
+            extracted.push_replacement(&self.placeholder, i);
+            Some(target_end)
+        } else {
+            None
+        }
+    }
+
+    fn inline(
+        &self,
+        chars: &[char],
+        start: usize,
+        end: usize,
+        in_table: bool,
+        extracted: &mut Extracted,
+    ) {
+        let line = &chars[..end];
+        let mut i = start;
+
+        while i < end {
+            let c = chars[i];
+
+            if c == '\\' && i + 1 < end {
+                extracted.push(chars[i + 1], i + 1);
+                i += 2;
+                continue;
+            }
+            if c == '`' {
+                let delimiter = if starts_with(line, i, "``") {
+                    "``"
+                } else {
+                    "`"
+                };
+                if let Some(close) = find(line, i + delimiter.len(), end, delimiter) {
+                    extracted.push_replacement(&self.placeholder, i);
+                    i = close + delimiter.len();
+                    continue;
+                }
+            }
+            // attribute references like {version}
+            if c == '{' {
+                if let Some(close) = find(line, i + 1, end, "}") {
+                    if close > i + 1
+                        && chars[i + 1..close]
+                            .iter()
+                            .all(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+                    {
+                        extracted.push_replacement(&self.placeholder, i);
+                        i = close + 1;
+                        continue;
+                    }
+                }
+            }
+            // cross references like <<id,text>>
+            if starts_with(line, i, "<<") {
+                if let Some(close) = find(line, i + 2, end, ">>") {
+                    match find(line, i + 2, close, ",") {
+                        Some(comma) => {
+                            let text_start = (comma + 1..close)
+                                .find(|k| !chars[*k].is_whitespace())
+                                .unwrap_or(close);
+                            extracted.push_copy(chars, text_start, close);
+                        }
+                        None => extracted.push_replacement(&self.placeholder, i),
+                    }
+                    i = close + 2;
+                    continue;
+                }
+            }
+            if c == '|' && in_table {
+                extracted.push_replacement("\n\n", i);
+                i += 1;
+                continue;
+            }
+            if i == start || chars[i - 1].is_whitespace() || chars[i - 1] == '(' {
+                if let Some(next) = self.inline_macro(chars, i, end, extracted) {
+                    i = next;
+                    continue;
+                }
+            }
+            if c == '*' || c == '_' {
+                if let Some(run_end) = emphasis_mark(chars, i, start, end) {
+                    i = run_end;
+                    continue;
+                }
+            }
+
+            extracted.push(c, i);
+            i += 1;
+        }
+    }
+}
+
+impl Markup for AsciiDoc {
+    fn extract(&self, source: &str) -> Extracted {
+        let chars: Vec<char> = source.chars().collect();
+        let lines = lines(&chars);
+        let mut extracted = Extracted::new(chars.len());
+        let newline = |extracted: &mut Extracted, end: usize| {
+            if end < chars.len() {
+                extracted.push('\n', end);
+            }
+        };
+
+        // whether the line is the first of a paragraph
+        let mut paragraph_start = true;
+        // whether the next block or paragraph has a style which is not text
+        let mut skip_next = false;
+        let mut in_table = false;
+
+        let mut i = 0;
+        while i < lines.len() {
+            let (start, end) = lines[i];
+            let line = &chars[start..end];
+            let content_end = trim_end(&chars, start, end);
+            let content = &chars[start..content_end];
+
+            if is_blank(line) {
+                newline(&mut extracted, end);
+                paragraph_start = true;
+                i += 1;
+                continue;
+            }
+
+            if is_delimiter(content) {
+                if is_verbatim(content) || skip_next {
+                    let fenced = content[0] == '`';
+                    i += 1;
+                    while i < lines.len() {
+                        let (start, end) = lines[i];
+                        let other = &chars[start..trim_end(&chars, start, end)];
+                        i += 1;
+                        if other == content || (fenced && starts_with(other, 0, "```")) {
+                            break;
+                        }
+                    }
+                } else {
+                    if content[0] == '|' {
+                        in_table = !in_table;
+                    }
+                    i += 1;
+                }
+                extracted.push_replacement("\n\n", start);
+                paragraph_start = true;
+                skip_next = false;
+                continue;
+            }
+
+            // literal paragraphs are indented, styled paragraphs are skipped like their blocks
+            if paragraph_start && (skip_next || line[0].is_whitespace()) {
+                while i < lines.len() && !is_blank(&chars[lines[i].0..lines[i].1]) {
+                    i += 1;
+                }
+                extracted.push_replacement("\n\n", start);
+                skip_next = false;
+                continue;
+            }
+
+            // comments, list continuations and attribute entries like `:toc:`
+            let is_attribute_entry = content[0] == ':'
+                && find(content, 1, content.len(), ":")
+                    .filter(|colon| {
+                        *colon > 1
+                            && content[1..*colon]
+                                .iter()
+                                .all(|c| c.is_alphanumeric() || "-_!".contains(*c))
+                    })
+                    .is_some();
+            if starts_with(content, 0, "//") || content == ['+'] || is_attribute_entry {
+                i += 1;
+                continue;
+            }
+
+            // block attributes like `[source,rust]` or anchors like `[[id]]`
+            if content[0] == '[' && content[content.len() - 1] == ']' {
+                let style: String = content[1..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric())
+                    .collect();
+                skip_next = SKIPPED_STYLES.contains(&style.as_str());
+                i += 1;
+                continue;
+            }
+
+            let mut text_start = start;
+            let mut text_end = content_end;
+            let mut separate = false;
+
+            let marks = content.iter().take_while(|c| **c == content[0]).count();
+            if content[0] == '=' && content.get(marks) == Some(&' ') {
+                // section titles
+                text_start += marks + 1;
+                separate = true;
+            } else if content[0] == '.'
+                && content.len() > 1
+                && !content[1].is_whitespace()
+                && content[1] != '.'
+            {
+                // block titles
+                text_start += 1;
+                separate = true;
+            } else if "*-.".contains(content[0]) && content.get(marks) == Some(&' ') {
+                // list items
+                text_start += marks + 1;
+            } else if let Some(label) = ADMONITION_LABELS
+                .iter()
+                .find(|label| paragraph_start && starts_with(content, 0, label))
+            {
+                text_start += label.len();
+            } else {
+                let digits = content.iter().take_while(|c| c.is_ascii_digit()).count();
+                if digits > 0 && starts_with(content, digits, ". ") {
+                    text_start += digits + 2;
+                }
+            }
+
+            // hard line breaks
+            if content.ends_with(&[' ', '+']) {
+                text_end = trim_end(&chars, text_start, text_end - 1);
+            }
+
+            self.inline(&chars, text_start, text_end, in_table, &mut extracted);
+            if separate {
+                extracted.push_replacement("\n\n", end);
+            } else {
+                newline(&mut extracted, end);
+            }
+            paragraph_start = separate;
+            i += 1;
+        }
+
+        extracted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_text() {
+        let source = "= Title
+:toc:
+
+This is *really* `code` in {product}, see link:https://example.com[the docs] and <<intro,the intro>>.
+// a comment
+
+[source,rust]
+----
+fn main() {}
+----
+
+NOTE: Press kbd:[Ctrl+C] to stop.
+
+* A list item.";
+
+        let extracted = AsciiDoc::default().extract(source);
+        assert_eq!(
+            extracted.text(),
+            "Title\n\n\nThis is really X in X, see the docs and the intro.\n\n\n\n\nPress X to stop.\n\nA list item.",
+        );
+
+        let start = extracted.text().find("docs").unwrap();
+        let (source_start, source_end) = extracted.source_span(start, start + 4).unwrap();
+        assert_eq!(&source[source_start..source_end], "docs");
+    }
+}
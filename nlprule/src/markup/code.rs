@@ -0,0 +1,207 @@
+//! Comments in source code, see [Code].
+
+use super::{starts_with, Extracted, Markup};
+
+/// A syntax for comments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommentSyntax {
+    /// A comment from the marker to the end of the line, e. g. `//` in Rust.
+    Line(String),
+    /// A comment between a start and an end marker, e. g. `/*` and `*/` in C.
+    Block { start: String, end: String },
+}
+
+impl CommentSyntax {
+    fn line(marker: &str) -> Self {
+        CommentSyntax::Line(marker.into())
+    }
+
+    fn block(start: &str, end: &str) -> Self {
+        CommentSyntax::Block {
+            start: start.into(),
+            end: end.into(),
+        }
+    }
+}
+
+/// Extracts the comments and docstrings of source code. Everything else, including string literals, is skipped.
+///
+/// Comment markers are stripped. Doc comment markers like `///` or `//!` are stripped as well as the
+/// leading `*` of continuation lines in block comments. Consecutive line comments form one paragraph,
+/// all other comments are separate paragraphs.
+#[derive(Debug, Clone)]
+pub struct Code {
+    /// The comment syntaxes, tried in order. Docstrings (like Python's `"""`) are block comments.
+    pub comments: Vec<CommentSyntax>,
+    /// The chars delimiting string literals. A backslash escapes the next char.
+    pub string_delimiters: Vec<char>,
+}
+
+impl Code {
+    /// Rust, with `//` and `/* */` comments including doc comments.
+    pub fn rust() -> Self {
+        Code {
+            comments: vec![CommentSyntax::line("//"), CommentSyntax::block("/*", "*/")],
+            string_delimiters: vec!['"'],
+        }
+    }
+
+    /// C and languages with the same comment syntax like C++, Java, JavaScript or Go.
+    pub fn c() -> Self {
+        Code {
+            comments: vec![CommentSyntax::line("//"), CommentSyntax::block("/*", "*/")],
+            string_delimiters: vec!['"', '\''],
+        }
+    }
+
+    /// Python, with `#` comments and docstrings.
+    pub fn python() -> Self {
+        Code {
+            comments: vec![
+                CommentSyntax::line("#"),
+                CommentSyntax::block("\"\"\"", "\"\"\""),
+                CommentSyntax::block("'''", "'''"),
+            ],
+            string_delimiters: vec!['"', '\''],
+        }
+    }
+
+    /// Shell scripts and other languages with `#` comments like Ruby, Perl or YAML.
+    pub fn shell() -> Self {
+        Code {
+            comments: vec![CommentSyntax::line("#")],
+            string_delimiters: vec!['"', '\''],
+        }
+    }
+
+    /// The handler for a file extension (without the dot), if the language is known.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Some(match extension {
+            "rs" => Code::rust(),
+            "c" | "h" | "cc" | "cpp" | "hpp" | "cxx" | "java" | "js" | "jsx" | "ts" | "tsx"
+            | "go" | "kt" | "swift" | "scala" | "cs" => Code::c(),
+            "py" | "pyi" => Code::python(),
+            "sh" | "bash" | "zsh" | "rb" | "pl" | "yml" | "yaml" | "toml" => Code::shell(),
+            _ => return None,
+        })
+    }
+}
+
+// skips spaces and tabs, not newlines
+fn skip_blank(chars: &[char], mut i: usize) -> usize {
+    while matches!(chars.get(i), Some(' ') | Some('\t')) {
+        i += 1;
+    }
+    i
+}
+
+impl Code {
+    fn comment_at(&self, chars: &[char], i: usize) -> Option<&CommentSyntax> {
+        self.comments.iter().find(|syntax| match syntax {
+            CommentSyntax::Line(marker) => starts_with(chars, i, marker),
+            CommentSyntax::Block { start, .. } => starts_with(chars, i, start),
+        })
+    }
+
+    // the index of the text of a comment starting at `i` after stripping the marker
+    fn strip_marker(chars: &[char], i: usize, marker: &str) -> usize {
+        let mut i = i + marker.chars().count();
+        // doc comment markers like `///`, `//!` or `/**`
+        if let Some(last) = marker.chars().last() {
+            while chars.get(i) == Some(&last) || chars.get(i) == Some(&'!') {
+                i += 1;
+            }
+        }
+        if chars.get(i) == Some(&' ') {
+            i += 1;
+        }
+        i
+    }
+}
+
+impl Markup for Code {
+    fn extract(&self, source: &str) -> Extracted {
+        let chars: Vec<char> = source.chars().collect();
+        let mut extracted = Extracted::new(chars.len());
+        let mut i = 0;
+
+        while i < chars.len() {
+            match self.comment_at(&chars, i) {
+                Some(CommentSyntax::Line(marker)) => {
+                    i = Self::strip_marker(&chars, i, marker);
+                    while i < chars.len() && chars[i] != '\n' {
+                        extracted.push(chars[i], i);
+                        i += 1;
+                    }
+
+                    // a line comment on the next line continues the paragraph
+                    let next = skip_blank(&chars, i + 1);
+                    if i < chars.len() && starts_with(&chars, next, marker) {
+                        extracted.push('\n', i);
+                        i = next;
+                    } else {
+                        extracted.push_replacement("\n\n", i);
+                    }
+                }
+                Some(CommentSyntax::Block { start, end }) => {
+                    i = Self::strip_marker(&chars, i, start);
+                    while i < chars.len() && !starts_with(&chars, i, end) {
+                        extracted.push(chars[i], i);
+
+                        if chars[i] == '\n' {
+                            i = skip_blank(&chars, i + 1);
+                            if chars.get(i) == Some(&'*') && !starts_with(&chars, i, end) {
+                                i = skip_blank(&chars, i + 1);
+                            }
+                        } else {
+                            i += 1;
+                        }
+                    }
+                    extracted.push_replacement("\n\n", i);
+                    i = (i + end.chars().count()).min(chars.len());
+                }
+                None if self.string_delimiters.contains(&chars[i]) => {
+                    let delimiter = chars[i];
+                    i += 1;
+                    while i < chars.len() && chars[i] != delimiter {
+                        i += if chars[i] == '\\' { 2 } else { 1 };
+                    }
+                    i += 1;
+                }
+                None => i += 1,
+            }
+        }
+
+        extracted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_comments() {
+        let source = r#"/// Returns teh sum.
+/// Panics on overflow.
+fn add(a: u8, b: u8) -> u8 {
+    let s = "// not a comment";
+    a + b /* adds them
+           * together */
+}"#;
+
+        let extracted = Code::rust().extract(source);
+        assert_eq!(
+            extracted.text(),
+            "Returns teh sum.\nPanics on overflow.\n\nadds them\ntogether \n\n"
+        );
+
+        let start = extracted.text().find("teh").unwrap();
+        let (source_start, source_end) = extracted.source_span(start, start + 3).unwrap();
+        assert_eq!(&source[source_start..source_end], "teh");
+
+        let extracted =
+            Code::python().extract("x = 'a # b'  # a comment\n\"\"\"A docstring.\"\"\"");
+        assert_eq!(extracted.text(), "a comment\n\nA docstring.\n\n");
+    }
+}
@@ -0,0 +1,369 @@
+//! LaTeX source, see [Latex].
+
+use super::{Extracted, Markup};
+
+const MATH_ENVIRONMENTS: &[&str] = &[
+    "equation",
+    "equation*",
+    "align",
+    "align*",
+    "alignat",
+    "alignat*",
+    "flalign",
+    "flalign*",
+    "gather",
+    "gather*",
+    "multline",
+    "multline*",
+    "eqnarray",
+    "eqnarray*",
+    "math",
+    "displaymath",
+];
+
+const SKIPPED_ENVIRONMENTS: &[&str] = &[
+    "verbatim",
+    "verbatim*",
+    "lstlisting",
+    "minted",
+    "comment",
+    "tikzpicture",
+    "thebibliography",
+];
+
+// commands whose arguments are not text
+const SKIPPED_COMMANDS: &[&str] = &[
+    "addbibresource",
+    "addtolength",
+    "autoref",
+    "bibliography",
+    "bibliographystyle",
+    "cite",
+    "citep",
+    "citet",
+    "Cref",
+    "cref",
+    "DeclareMathOperator",
+    "documentclass",
+    "eqref",
+    "graphicspath",
+    "hspace",
+    "hypersetup",
+    "include",
+    "includegraphics",
+    "input",
+    "label",
+    "newcommand",
+    "newenvironment",
+    "newtheorem",
+    "pageref",
+    "pagestyle",
+    "providecommand",
+    "ref",
+    "renewcommand",
+    "renewenvironment",
+    "setcounter",
+    "setlength",
+    "thispagestyle",
+    "url",
+    "usepackage",
+    "vspace",
+];
+
+// commands whose argument is a heading or caption, which is checked as a paragraph of its own
+const PARAGRAPH_COMMANDS: &[&str] = &[
+    "part",
+    "chapter",
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+    "subparagraph",
+    "caption",
+    "title",
+    "footnote",
+];
+
+/// Extracts the text of a LaTeX document. Skips the preamble, comments, math, verbatim-like environments
+/// and commands which do not take text (like `\cite` or `\label`). The arguments of other commands are kept,
+/// so `\emph{word}` is checked as `word`. Headings, captions and footnotes are separate paragraphs.
+///
+/// Line breaks are kept, so hard-wrapped sources should be checked with
+/// [Tokenizer::set_hard_wrapped][crate::Tokenizer::set_hard_wrapped].
+#[derive(Debug, Clone)]
+pub struct Latex {
+    /// The text inline math like `$x$` is replaced with, so that the sentence around it stays intact.
+    pub math_placeholder: String,
+}
+
+impl Default for Latex {
+    fn default() -> Self {
+        Latex {
+            math_placeholder: "X".into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Group {
+    Plain,
+    Paragraph,
+}
+
+struct Scanner<'a> {
+    chars: &'a [char],
+    i: usize,
+    end: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<char> {
+        if self.i < self.end {
+            Some(self.chars[self.i])
+        } else {
+            None
+        }
+    }
+
+    fn starts_with(&self, pattern: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        self.chars[self.i..self.end].starts_with(&pattern)
+    }
+
+    fn find(&self, pattern: &str) -> Option<usize> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        (self.i..self.end).find(|&i| self.chars[i..self.end].starts_with(&pattern))
+    }
+
+    // skips to the end of the pattern, or to the end if it does not occur
+    fn skip_past(&mut self, pattern: &str) {
+        self.i = self
+            .find(pattern)
+            .map_or(self.end, |i| i + pattern.chars().count());
+    }
+
+    fn command_name(&mut self) -> String {
+        let start = self.i;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphabetic()) {
+            self.i += 1;
+        }
+        if start < self.i && self.peek() == Some('*') {
+            self.i += 1;
+        }
+        self.chars[start..self.i].iter().collect()
+    }
+
+    // skips a group delimited by `open` and `close` starting at the current position, respecting nesting
+    fn skip_group(&mut self, open: char, close: char) {
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            self.i += 1;
+            if c == '\\' {
+                self.i += 1;
+            } else if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn skip_optional_arguments(&mut self) {
+        while self.peek() == Some('[') {
+            self.skip_group('[', ']');
+        }
+    }
+
+    fn skip_arguments(&mut self) {
+        loop {
+            match self.peek() {
+                Some('[') => self.skip_group('[', ']'),
+                Some('{') => self.skip_group('{', '}'),
+                _ => break,
+            }
+        }
+    }
+
+    fn group_text(&mut self) -> Option<String> {
+        if self.peek() != Some('{') {
+            return None;
+        }
+        let start = self.i + 1;
+        self.skip_group('{', '}');
+        Some(
+            self.chars[start..self.i.saturating_sub(1).max(start)]
+                .iter()
+                .collect(),
+        )
+    }
+}
+
+impl Markup for Latex {
+    fn extract(&self, source: &str) -> Extracted {
+        let chars: Vec<char> = source.chars().collect();
+        let mut extracted = Extracted::new(chars.len());
+        let mut scanner = Scanner {
+            chars: &chars,
+            i: 0,
+            end: chars.len(),
+        };
+
+        if let Some(start) = scanner.find("\\begin{document}") {
+            scanner.i = start + "\\begin{document}".len();
+        }
+        if let Some(end) = scanner.find("\\end{document}") {
+            scanner.end = end;
+        }
+
+        let mut groups = Vec::new();
+
+        while let Some(c) = scanner.peek() {
+            let start = scanner.i;
+
+            match c {
+                '%' => {
+                    while !matches!(scanner.peek(), Some('\n') | None) {
+                        scanner.i += 1;
+                    }
+                }
+                '$' if scanner.starts_with("$$") => {
+                    scanner.i += 2;
+                    scanner.skip_past("$$");
+                }
+                '$' => {
+                    scanner.i += 1;
+                    while let Some(c) = scanner.peek() {
+                        scanner.i += 1;
+                        match c {
+                            '\\' => scanner.i += 1,
+                            '$' => break,
+                            _ => {}
+                        }
+                    }
+                    extracted.push_replacement(&self.math_placeholder, start);
+                }
+                '{' => {
+                    scanner.i += 1;
+                    groups.push(Group::Plain);
+                }
+                '}' => {
+                    scanner.i += 1;
+                    if groups.pop() == Some(Group::Paragraph) {
+                        extracted.push_replacement("\n\n", start);
+                    }
+                }
+                '~' => {
+                    scanner.i += 1;
+                    extracted.push_replacement(" ", start);
+                }
+                '`' | '\'' if scanner.starts_with("``") || scanner.starts_with("''") => {
+                    scanner.i += 2;
+                    extracted.push_replacement("\"", start);
+                }
+                '\\' => {
+                    scanner.i += 1;
+                    let name = scanner.command_name();
+
+                    match name.as_str() {
+                        "" => match scanner.peek() {
+                            Some(c @ ('%' | '$' | '&' | '#' | '_' | '{' | '}')) => {
+                                extracted.push(c, scanner.i);
+                                scanner.i += 1;
+                            }
+                            Some('(') => {
+                                scanner.skip_past("\\)");
+                                extracted.push_replacement(&self.math_placeholder, start);
+                            }
+                            Some('[') => scanner.skip_past("\\]"),
+                            Some('\\') | Some(' ') => {
+                                scanner.i += 1;
+                                extracted.push_replacement(" ", start);
+                            }
+                            // accents and spacing commands like `\'` or `\,`
+                            Some(_) => scanner.i += 1,
+                            None => {}
+                        },
+                        "begin" => {
+                            let environment = scanner.group_text().unwrap_or_default();
+
+                            if MATH_ENVIRONMENTS.contains(&environment.as_str())
+                                || SKIPPED_ENVIRONMENTS.contains(&environment.as_str())
+                            {
+                                scanner.skip_past(&format!("\\end{{{}}}", environment));
+                            } else {
+                                scanner.skip_arguments();
+                            }
+                        }
+                        "end" => {
+                            scanner.group_text();
+                        }
+                        "href" => {
+                            scanner.skip_group('{', '}');
+                        }
+                        "item" => scanner.skip_optional_arguments(),
+                        name if SKIPPED_COMMANDS.contains(&name.trim_end_matches('*')) => {
+                            scanner.skip_arguments();
+                        }
+                        name if PARAGRAPH_COMMANDS.contains(&name.trim_end_matches('*')) => {
+                            scanner.skip_optional_arguments();
+                            if scanner.peek() == Some('{') {
+                                scanner.i += 1;
+                                groups.push(Group::Paragraph);
+                                extracted.push_replacement("\n\n", start);
+                            }
+                        }
+                        _ => scanner.skip_optional_arguments(),
+                    }
+                }
+                c => {
+                    scanner.i += 1;
+                    extracted.push(c, start);
+                }
+            }
+        }
+
+        extracted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_text() {
+        let source = r"\documentclass{article}
+\usepackage[utf8]{inputenc}
+\begin{document}
+\section{Intro}
+We show~\cite{foo} that $x^2$ is \emph{not} negative. % a comment
+\begin{equation}
+  x^2 \geq 0
+\end{equation}
+It costs 5\% less.
+\end{document}";
+
+        let extracted = Latex::default().extract(source);
+        assert_eq!(
+            extracted.text(),
+            "\n\n\nIntro\n\n\nWe show  that X is not negative. \n\nIt costs 5% less.\n"
+        );
+
+        let start = extracted.text().find("not").unwrap();
+        let start = extracted.text()[..start].chars().count();
+        let (source_start, source_end) = extracted.source_span(start, start + 3).unwrap();
+        let not: String = source
+            .chars()
+            .skip(source_start)
+            .take(source_end - source_start)
+            .collect();
+        assert_eq!(not, "not");
+
+        // spans over skipped markup can not be mapped
+        let start = extracted.text().find("show").unwrap();
+        assert_eq!(extracted.source_span(start, start + 8), None);
+    }
+}
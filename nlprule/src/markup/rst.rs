@@ -0,0 +1,322 @@
+//! reStructuredText, see [Rst].
+
+use super::{emphasis_mark, find, lines, starts_with, Extracted, Markup};
+
+// directives whose content is text
+const ADMONITIONS: &[&str] = &[
+    "admonition",
+    "attention",
+    "caution",
+    "danger",
+    "deprecated",
+    "error",
+    "hint",
+    "important",
+    "note",
+    "rubric",
+    "seealso",
+    "sidebar",
+    "tip",
+    "topic",
+    "versionadded",
+    "versionchanged",
+    "warning",
+];
+
+/// Extracts the text of a reStructuredText document. Skips literal blocks, comments, section adornments
+/// and directives except admonitions like `.. note::`, whose content is checked. Inline literals, roles
+/// like ``:ref:`target` `` and substitutions are replaced with a placeholder, the text of hyperlink
+/// references is kept.
+#[derive(Debug, Clone)]
+pub struct Rst {
+    /// The text inline markup which is not text is replaced with, so that the sentence around it stays intact.
+    pub placeholder: String,
+}
+
+impl Default for Rst {
+    fn default() -> Self {
+        Rst {
+            placeholder: "X".into(),
+        }
+    }
+}
+
+fn indentation(line: &[char]) -> usize {
+    line.iter().take_while(|c| c.is_whitespace()).count()
+}
+
+fn is_blank(line: &[char]) -> bool {
+    line.iter().all(|c| c.is_whitespace())
+}
+
+fn is_adornment(line: &[char]) -> bool {
+    line.len() >= 3 && line.iter().all(|c| *c == line[0]) && "=-`:'\"~^_*+#<>.".contains(line[0])
+}
+
+// the index of the line after the block starting at line `i`, i. e. after the following lines which are
+// blank or indented more than `indent`, without trailing blank lines
+fn block_end(chars: &[char], lines: &[(usize, usize)], i: usize, indent: usize) -> usize {
+    let mut end = i + 1;
+    for (k, (start, line_end)) in lines.iter().enumerate().skip(i + 1) {
+        let line = &chars[*start..*line_end];
+        if is_blank(line) {
+            continue;
+        }
+        if indentation(line) <= indent {
+            break;
+        }
+        end = k + 1;
+    }
+    end
+}
+
+// the name of the directive and the start of its arguments in an explicit markup block like `.. note:: text`
+fn directive(chars: &[char], start: usize, end: usize) -> Option<(String, usize)> {
+    let separator = find(chars, start, end, "::")?;
+    let name: String = chars[start..separator].iter().collect();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let mut arguments = separator + 2;
+    while arguments < end && chars[arguments].is_whitespace() {
+        arguments += 1;
+    }
+    Some((name, arguments))
+}
+
+// the index of the backquote if a role like `:ref:` starts at `i`
+fn role(chars: &[char], i: usize) -> Option<usize> {
+    let mut k = i + 1;
+    while k < chars.len() && (chars[k].is_alphanumeric() || "-_.+:".contains(chars[k])) {
+        k += 1;
+    }
+
+    if k > i + 2 && chars[k - 1] == ':' && chars.get(k) == Some(&'`') {
+        Some(k)
+    } else {
+        None
+    }
+}
+
+impl Rst {
+    fn inline(&self, chars: &[char], start: usize, end: usize, extracted: &mut Extracted) {
+        let line = &chars[..end];
+        let mut i = start;
+
+        while i < end {
+            let c = chars[i];
+
+            if c == '\\' && i + 1 < end {
+                extracted.push(chars[i + 1], i + 1);
+                i += 2;
+                continue;
+            }
+            if starts_with(line, i, "``") {
+                if let Some(close) = find(line, i + 2, end, "``") {
+                    extracted.push_replacement(&self.placeholder, i);
+                    i = close + 2;
+                    continue;
+                }
+            }
+            if let Some(tick) = role(line, i).filter(|_| c == ':') {
+                if let Some(close) = find(line, tick + 1, end, "`") {
+                    extracted.push_replacement(&self.placeholder, i);
+                    i = close + 1;
+                    continue;
+                }
+            }
+            if c == '`' {
+                if let Some(close) = find(line, i + 1, end, "`") {
+                    // hyperlink references like `text <https://example.com>`_
+                    let text_end = find(line, i + 1, close, " <")
+                        .filter(|_| chars[close - 1] == '>')
+                        .unwrap_or(close);
+                    extracted.push_copy(chars, i + 1, text_end);
+                    i = close + 1;
+                    while i < end && chars[i] == '_' {
+                        i += 1;
+                    }
+                    continue;
+                }
+            }
+            if c == '|' {
+                if let Some(close) = find(line, i + 1, end, "|") {
+                    if close > i + 1 && !chars[i + 1].is_whitespace() {
+                        extracted.push_replacement(&self.placeholder, i);
+                        i = close + 1;
+                        continue;
+                    }
+                }
+            }
+            // footnote and citation references like [1]_
+            if c == '[' {
+                if let Some(close) = find(line, i + 1, end, "]_") {
+                    i = close + 2;
+                    continue;
+                }
+            }
+            if c == '*' {
+                if let Some(run_end) = emphasis_mark(chars, i, start, end) {
+                    i = run_end;
+                    continue;
+                }
+            }
+            // references like word_
+            if c == '_'
+                && i > start
+                && chars[i - 1].is_alphanumeric()
+                && (i + 1 == end || !chars[i + 1].is_alphanumeric())
+            {
+                i += 1;
+                continue;
+            }
+
+            extracted.push(c, i);
+            i += 1;
+        }
+    }
+}
+
+impl Markup for Rst {
+    fn extract(&self, source: &str) -> Extracted {
+        let chars: Vec<char> = source.chars().collect();
+        let lines = lines(&chars);
+        let mut extracted = Extracted::new(chars.len());
+        let newline = |extracted: &mut Extracted, end: usize| {
+            if end < chars.len() {
+                extracted.push('\n', end);
+            }
+        };
+
+        let mut i = 0;
+        while i < lines.len() {
+            let (start, end) = lines[i];
+            let line = &chars[start..end];
+            let indent = indentation(line);
+            let content_start = start + indent;
+            let mut content_end = end;
+            while content_end > content_start && chars[content_end - 1].is_whitespace() {
+                content_end -= 1;
+            }
+
+            if is_blank(line) {
+                newline(&mut extracted, end);
+                i += 1;
+                continue;
+            }
+
+            // explicit markup: directives, comments, targets and footnotes
+            if starts_with(&chars[..end], content_start, "..")
+                && (content_start + 2 == end || chars[content_start + 2] == ' ')
+            {
+                let block_end = block_end(&chars, &lines, i, indent);
+                let directive = directive(&chars, (content_start + 3).min(end), end);
+
+                if let Some((_, arguments)) =
+                    directive.filter(|(name, _)| ADMONITIONS.contains(&name.as_str()))
+                {
+                    extracted.push_replacement("\n\n", start);
+                    self.inline(&chars, arguments, content_end, &mut extracted);
+                    newline(&mut extracted, end);
+
+                    // options like `:class: tip` come before the content
+                    let mut options = true;
+                    for (start, end) in &lines[i + 1..block_end] {
+                        let line = &chars[*start..*end];
+                        let content_start = start + indentation(line);
+                        if is_blank(line) {
+                            options = false;
+                        } else if !(options && chars[content_start] == ':') {
+                            options = false;
+                            self.inline(&chars, content_start, *end, &mut extracted);
+                        }
+                        newline(&mut extracted, *end);
+                    }
+                } else {
+                    extracted.push_replacement("\n\n", start);
+                }
+
+                i = block_end;
+                continue;
+            }
+
+            if indent == 0 && is_adornment(line) {
+                extracted.push_replacement("\n\n", start);
+                i += 1;
+                continue;
+            }
+
+            // a paragraph ending with `::` introduces a literal block, the marker is rendered as `:` or not at all
+            let literal = content_end - content_start >= 2
+                && chars[content_end - 2..content_end] == [':', ':'];
+            if literal {
+                if content_end - content_start == 2 || chars[content_end - 3].is_whitespace() {
+                    content_end -= 2;
+                    while content_end > content_start && chars[content_end - 1].is_whitespace() {
+                        content_end -= 1;
+                    }
+                } else {
+                    content_end -= 1;
+                }
+            }
+
+            // bullet list markers
+            let mut text_start = content_start;
+            if ["- ", "* ", "+ ", "• "]
+                .iter()
+                .any(|marker| starts_with(&chars[..end], content_start, marker))
+            {
+                text_start += 2;
+            }
+
+            self.inline(&chars, text_start, content_end, &mut extracted);
+            newline(&mut extracted, end);
+
+            i = if literal {
+                block_end(&chars, &lines, i, indent)
+            } else {
+                i + 1
+            };
+        }
+
+        extracted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_text() {
+        let source = "Title
+=====
+
+This is *really* ``code`` and `a link <https://example.com>`_ see :ref:`intro`.
+
+.. code-block:: python
+
+   print('skipped')
+
+.. note:: Be careful.
+
+   It is hot.
+
+For example::
+
+   literal text
+
+Done [1]_.";
+
+        let extracted = Rst::default().extract(source);
+        assert_eq!(
+            extracted.text(),
+            "Title\n\n\n\nThis is really X and a link see X.\n\n\n\n\n\n\nBe careful.\n\nIt is hot.\n\nFor example:\n\nDone .",
+        );
+
+        let start = extracted.text().find("hot").unwrap();
+        let (source_start, source_end) = extracted.source_span(start, start + 3).unwrap();
+        assert_eq!(&source[source_start..source_end], "hot");
+    }
+}
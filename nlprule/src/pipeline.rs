@@ -0,0 +1,736 @@
+//! A [Pipeline] combines a [Tokenizer] and [Rules] to check texts with one call.
+//!
+//! ```no_run
+//! use nlprule::Pipeline;
+//!
+//! let pipeline = Pipeline::builder()
+//!     .tokenizer_path("path/to/en_tokenizer.bin")
+//!     .rules_path("path/to/en_rules.bin")
+//!     .build()?;
+//!
+//! assert_eq!(
+//!     pipeline.correct("She was not been here since Monday."),
+//!     String::from("She was not here since Monday.")
+//! );
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use crate::{
+    component::Component,
+    markup::Markup,
+    rules::{
+        apply_suggestions, fill_suggestion_cache, group_by_sentence,
+        persistent_cache::PersistentCache, sensitive::SensitiveOptions, stats::Statistics,
+        suggestion_cache_entries, word_list::WordList, CacheEntries, CacheStats, CheckOptions,
+        Rules, SuggestionCache,
+    },
+    tokenizer::{finalize, Tokenizer},
+    types::*,
+    utils::{cache::LruCache, parallelism::MaybeParallelRefIterator},
+    Error,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// Owns a tokenizer and the rules using it.
+///
+/// Cloning is cheap: clones share the tokenizer and the rules instead of copying them, so e. g. web frameworks can
+/// clone the pipeline for each worker. By default clones also share the suggestion cache of the rules,
+/// [with_own_cache][Pipeline::with_own_cache] creates a clone with a separate one.
+#[derive(Clone)]
+pub struct Pipeline {
+    tokenizer: Arc<Tokenizer>,
+    rules: Arc<Rules>,
+    disabled_rules: Vec<String>,
+    cache: PipelineCache,
+}
+
+// which suggestion cache a pipeline uses
+#[derive(Clone)]
+enum PipelineCache {
+    // the one of the rules, see `Rules::set_cache_size`
+    Rules,
+    // shared with the clones of the pipeline created after `with_own_cache`, `None` if there is no cache
+    Own(Option<Arc<SuggestionCache>>),
+}
+
+impl Pipeline {
+    /// Creates a new pipeline from a tokenizer and rules.
+    /// Fails with [Error::IncompatibleComponents] if they were not compiled with the same tagger.
+    /// If the tokenizer has no chunker, the rules which need one are turned off (see [Rules::disable_chunk_rules]).
+    pub fn new(tokenizer: Tokenizer, mut rules: Rules) -> Result<Self, Error> {
+        rules.check_compatibility(&tokenizer)?;
+
+        let disabled_rules = rules.disable_chunk_rules(&tokenizer);
+        if !disabled_rules.is_empty() {
+            warn!(
+                "tokenizer has no chunker, turned off {} rules which use chunk tags",
+                disabled_rules.len()
+            );
+        }
+
+        Ok(Pipeline {
+            tokenizer: Arc::new(tokenizer),
+            rules: Arc::new(rules),
+            disabled_rules,
+            cache: PipelineCache::Rules,
+        })
+    }
+
+    /// Creates a builder to configure a pipeline.
+    pub fn builder() -> PipelineBuilder {
+        PipelineBuilder::default()
+    }
+
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// The rules to change them, `None` if they are shared with a clone of the pipeline.
+    pub fn rules_mut(&mut self) -> Option<&mut Rules> {
+        Arc::get_mut(&mut self.rules)
+    }
+
+    /// A clone of the pipeline which shares the tokenizer and the rules but caches the suggestions for up to `size`
+    /// texts in its own cache instead of the cache of the rules, e. g. for workers which check different texts.
+    /// Clones of the returned pipeline share its cache. A size of zero turns caching off for the returned pipeline.
+    pub fn with_own_cache(&self, size: usize) -> Pipeline {
+        let cache = if size > 0 {
+            Some(Arc::new(Mutex::new(LruCache::new(size))))
+        } else {
+            None
+        };
+
+        Pipeline {
+            cache: PipelineCache::Own(cache),
+            ..self.clone()
+        }
+    }
+
+    /// Hit and miss counts of the suggestion cache this pipeline uses, `None` if it does not use one.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        match &self.cache {
+            PipelineCache::Rules => self.rules.cache_stats(),
+            PipelineCache::Own(cache) => cache.as_deref().map(CacheStats::of),
+        }
+    }
+
+    /// IDs of the rules which were turned off when creating the pipeline because the tokenizer has no chunker.
+    pub fn disabled_rules(&self) -> &[String] {
+        &self.disabled_rules
+    }
+
+    /// Splits the pipeline into its tokenizer and rules. Returns the pipeline if they are shared with a clone.
+    pub fn into_parts(self) -> Result<(Tokenizer, Rules), Pipeline> {
+        match (Arc::try_unwrap(self.tokenizer), Arc::try_unwrap(self.rules)) {
+            (Ok(tokenizer), Ok(rules)) => Ok((tokenizer, rules)),
+            (tokenizer, rules) => Err(Pipeline {
+                tokenizer: tokenizer.map_or_else(|x| x, Arc::new),
+                rules: rules.map_or_else(|x| x, Arc::new),
+                disabled_rules: self.disabled_rules,
+                cache: self.cache,
+            }),
+        }
+    }
+
+    /// Tokenizes and disambiguates the text. The first token is the special sentence start token.
+    pub fn analyze<'t>(&'t self, text: &'t str) -> Vec<Token<'t>> {
+        finalize(self.tokenizer.disambiguate(self.tokenizer.tokenize(text)))
+    }
+
+    /// Computes the suggestions for the text by checking all rules.
+    /// Uses the cache of the rules if one is set with [Rules::set_cache_size], or the own cache of the pipeline
+    /// if it was created with [with_own_cache][Pipeline::with_own_cache].
+    pub fn check(&self, text: &str) -> Vec<Suggestion> {
+        match &self.cache {
+            PipelineCache::Rules => self.rules.suggest(text, &self.tokenizer),
+            PipelineCache::Own(cache) => {
+                self.rules
+                    .suggest_with_cache(text, &self.tokenizer, cache.as_deref())
+            }
+        }
+    }
+
+    /// Checks the text and groups the suggestions by sentence, see [Rules::suggest_by_sentence].
+    /// Uses the same cache as [check][Pipeline::check].
+    pub fn check_by_sentence(&self, text: &str) -> Vec<SentenceSuggestions> {
+        group_by_sentence(text, self.check(text))
+    }
+
+    /// Checks the text from the reader sentence by sentence, see [Rules::suggest_from_reader].
+    /// Uses the same cache as [check][Pipeline::check].
+    pub fn check_from_reader<R: Read>(
+        &self,
+        reader: R,
+        sink: impl FnMut(Suggestion),
+    ) -> Result<(), Error> {
+        match &self.cache {
+            PipelineCache::Rules => self
+                .rules
+                .suggest_from_reader(reader, &self.tokenizer, sink),
+            PipelineCache::Own(cache) => self.rules.suggest_from_reader_with_cache(
+                reader,
+                &self.tokenizer,
+                cache.as_deref(),
+                sink,
+            ),
+        }
+    }
+
+    /// Corrects the text by choosing the first replacement of each suggestion.
+    pub fn correct(&self, text: &str) -> String {
+        apply_suggestions(text, &self.check(text))
+    }
+
+    /// Writes the pipeline together with the state it built up at runtime: the rule configuration, the word lists,
+    /// the sensitive data options, the statistics, the contents of the suggestion cache and which rules have compiled
+    /// their regexes. [deserialize_warm][Pipeline::deserialize_warm] restores it, so e. g. worker processes and
+    /// short-lived functions start with the state of a process which already checked many texts.
+    ///
+    /// Both the cache of the rules and the own cache of a pipeline created with
+    /// [with_own_cache][Pipeline::with_own_cache] are stored, the restored pipeline uses the same one. Compiled
+    /// regexes can not be stored, they are compiled again when the pipeline is restored. The persistent cache and
+    /// [tagger extensions][crate::tokenizer::tag::TaggerExtension] are not stored either.
+    pub fn serialize_warm<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let warm_ids = |ids: Vec<&str>| ids.into_iter().map(|x| x.to_string()).collect();
+
+        WarmPipeline {
+            tokenizer: bincode::serialize(&self.tokenizer)?,
+            hard_wrapped: self.tokenizer.hard_wrapped(),
+            rules: bincode::serialize(&self.rules)?,
+            disabled_rules: self.disabled_rules.clone(),
+            word_lists: self.rules.word_lists().to_vec(),
+            sensitive: self.rules.sensitive().cloned(),
+            statistics: self.rules.statistics().map(|x| x.to_text()),
+            check_options: self.rules.check_options().clone(),
+            cache: self.rules.cache_entries(),
+            own_cache: match &self.cache {
+                PipelineCache::Rules => None,
+                PipelineCache::Own(cache) => Some(cache.as_deref().map(suggestion_cache_entries)),
+            },
+            warm_rules: warm_ids(
+                self.rules
+                    .rules()
+                    .iter()
+                    .filter(|x| x.is_warm())
+                    .map(|x| x.id())
+                    .collect(),
+            ),
+            warm_disambiguation_rules: warm_ids(
+                self.tokenizer
+                    .rules()
+                    .iter()
+                    .filter(|x| x.is_warm())
+                    .map(|x| x.id())
+                    .collect(),
+            ),
+        }
+        .write(writer, false)
+    }
+
+    /// Restores a pipeline written by [serialize_warm][Pipeline::serialize_warm]. The regexes of the rules which had
+    /// compiled them are compiled before the pipeline is returned, so the first texts are checked as fast as later ones.
+    pub fn deserialize_warm<R: Read>(reader: R) -> Result<Pipeline, Error> {
+        let warm = WarmPipeline::read(reader)?;
+        let (warm_rules, warm_disambiguation_rules) =
+            (warm.warm_rules, warm.warm_disambiguation_rules);
+
+        let mut tokenizer: Tokenizer = bincode::deserialize(&warm.tokenizer)?;
+        tokenizer.set_hard_wrapped(warm.hard_wrapped);
+        let mut rules: Rules = bincode::deserialize(&warm.rules)?;
+        for list in warm.word_lists {
+            rules.add_word_list(list);
+        }
+        rules.set_sensitive(warm.sensitive);
+        rules.set_statistics(warm.statistics.map(|x| Statistics::parse(&x)).transpose()?);
+        rules.set_check_options(warm.check_options);
+        if let Some((capacity, entries)) = warm.cache {
+            rules.set_cache_size(capacity);
+            rules.fill_cache(entries);
+        }
+        let cache = match warm.own_cache {
+            None => PipelineCache::Rules,
+            Some(cache) => PipelineCache::Own(cache.map(|(capacity, entries)| {
+                let mut cache = Mutex::new(LruCache::new(capacity));
+                fill_suggestion_cache(&mut cache, entries);
+                Arc::new(cache)
+            })),
+        };
+
+        rules
+            .rules()
+            .maybe_par_iter()
+            .filter(|x| warm_rules.iter().any(|id| id == x.id()))
+            .for_each(|x| x.warm_up());
+        tokenizer
+            .rules()
+            .maybe_par_iter()
+            .filter(|x| warm_disambiguation_rules.iter().any(|id| id == x.id()))
+            .for_each(|x| x.warm_up());
+
+        Ok(Pipeline {
+            tokenizer: Arc::new(tokenizer),
+            rules: Arc::new(rules),
+            disabled_rules: warm.disabled_rules,
+            cache,
+        })
+    }
+
+    /// Computes the suggestions for the text in a marked-up document, e. g. [LaTeX][crate::markup::latex::Latex].
+    /// The spans of the suggestions are char indices in the source. See [Extracted::map_suggestions][crate::markup::Extracted::map_suggestions].
+    pub fn check_markup<M: Markup>(&self, source: &str, markup: &M) -> Vec<Suggestion> {
+        let extracted = markup.extract(source);
+        extracted.map_suggestions(self.check(extracted.text()))
+    }
+}
+
+/// The state of a pipeline, see [Pipeline::serialize_warm].
+#[derive(Serialize, Deserialize)]
+pub(crate) struct WarmPipeline {
+    // the tokenizer and the rules are stored serialized like in their binaries
+    tokenizer: Vec<u8>,
+    hard_wrapped: bool,
+    rules: Vec<u8>,
+    disabled_rules: Vec<String>,
+    word_lists: Vec<WordList>,
+    sensitive: Option<SensitiveOptions>,
+    // in the text format since the statistics are not serializable
+    statistics: Option<String>,
+    check_options: CheckOptions,
+    cache: Option<(usize, CacheEntries)>,
+    // the cache of a pipeline created with `with_own_cache`, `None` if it uses the cache of the rules
+    own_cache: Option<Option<(usize, CacheEntries)>>,
+    // IDs of the rules which had compiled their regexes
+    warm_rules: Vec<String>,
+    warm_disambiguation_rules: Vec<String>,
+}
+
+/// How much of the binaries a [Pipeline] loads, see [PipelineBuilder::profile].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    /// Loads the tokenizer and the rules completely.
+    #[default]
+    Full,
+    /// For deployments with little memory such as mobile apps and WASM. Drops the chunker of the tokenizer, which
+    /// turns off the rules using chunk tags, and only loads the smallest rule categories which together make up at
+    /// most [LITE_RULES_SHARE] of the rules, see [Rules::load_smallest_categories]. The other categories are
+    /// freed. If categories are set with [PipelineBuilder::categories] only those are loaded, rules passed to
+    /// [PipelineBuilder::rules] keep the categories they already loaded. Regexes are compiled
+    /// lazily like in the full profile, so turning on [validation][PipelineBuilder::validate] should be avoided.
+    Lite,
+}
+
+/// The share of the serialized size of all rules the categories loaded by [Profile::Lite] may take.
+pub const LITE_RULES_SHARE: f32 = 0.5;
+
+enum Source<T> {
+    Value(T),
+    Path(PathBuf),
+    #[cfg(feature = "binaries")]
+    Lang(String),
+}
+
+/// Configures and creates a [Pipeline].
+/// The tokenizer and rules must be set, either directly, by path or (with the `binaries` feature) by language code.
+#[derive(Default)]
+pub struct PipelineBuilder {
+    tokenizer: Option<Source<Tokenizer>>,
+    rules: Option<Source<Rules>>,
+    category_ids: Option<Vec<String>>,
+    validate: bool,
+    hard_wrapped: bool,
+    word_lists: Vec<WordList>,
+    sensitive: Option<SensitiveOptions>,
+    persistent_cache: Option<Box<dyn PersistentCache>>,
+    profile: Profile,
+}
+
+impl PipelineBuilder {
+    pub fn tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+        self.tokenizer = Some(Source::Value(tokenizer));
+        self
+    }
+
+    pub fn tokenizer_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.tokenizer = Some(Source::Path(path.into()));
+        self
+    }
+
+    pub fn rules(mut self, rules: Rules) -> Self {
+        self.rules = Some(Source::Value(rules));
+        self
+    }
+
+    pub fn rules_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.rules = Some(Source::Path(path.into()));
+        self
+    }
+
+    /// Uses the published binaries for the language with the given code (e. g. "en").
+    /// See the [binaries][crate::binaries] module.
+    #[cfg(feature = "binaries")]
+    pub fn lang(mut self, lang_code: &str) -> Self {
+        self.tokenizer = Some(Source::Lang(lang_code.to_string()));
+        self.rules = Some(Source::Lang(lang_code.to_string()));
+        self
+    }
+
+    /// Only loads the rules in the given categories when the rules are loaded by the builder.
+    /// Other categories can later be loaded with [Rules::load_category].
+    pub fn categories<S: AsRef<str>>(mut self, category_ids: &[S]) -> Self {
+        self.category_ids = Some(
+            category_ids
+                .iter()
+                .map(|x| x.as_ref().to_string())
+                .collect(),
+        );
+        self
+    }
+
+    /// Whether to check that the rules can be used with the tokenizer with [Rules::validate] when building.
+    /// Building fails if there are any issues. Off by default since this compiles all regexes.
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Whether the checked texts are hard-wrapped, see [Tokenizer::set_hard_wrapped]. Off by default.
+    pub fn hard_wrapped(mut self, hard_wrapped: bool) -> Self {
+        self.hard_wrapped = hard_wrapped;
+        self
+    }
+
+    /// Adds a word list to the rules, see [Rules::add_word_list]. Can be called multiple times.
+    pub fn word_list(mut self, list: WordList) -> Self {
+        self.word_lists.push(list);
+        self
+    }
+
+    /// Turns on detection of profanity and personal information, see [Rules::set_sensitive].
+    pub fn sensitive(mut self, options: SensitiveOptions) -> Self {
+        self.sensitive = Some(options);
+        self
+    }
+
+    /// Stores suggestions in a cache which persists between runs, see [Rules::set_persistent_cache].
+    pub fn persistent_cache<C: PersistentCache + 'static>(mut self, cache: C) -> Self {
+        self.persistent_cache = Some(Box::new(cache));
+        self
+    }
+
+    /// How much of the binaries to load, see [Profile]. [Profile::Full] by default.
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Loads the components and creates the pipeline.
+    pub fn build(self) -> Result<Pipeline, Error> {
+        let mut tokenizer = match self.tokenizer {
+            Some(Source::Value(tokenizer)) => tokenizer,
+            Some(Source::Path(path)) => Tokenizer::new(path)?,
+            #[cfg(feature = "binaries")]
+            Some(Source::Lang(lang_code)) => Tokenizer::from_lang(&lang_code)?,
+            None => return Err(Error::Unexpected("pipeline needs a tokenizer".into())),
+        };
+        if self.hard_wrapped {
+            tokenizer.set_hard_wrapped(true);
+        }
+        let lite = self.profile == Profile::Lite;
+        if lite {
+            tokenizer.chunker = None;
+            tokenizer.checksum = Default::default();
+        }
+
+        // the lite profile loads no category upfront and picks them by size below
+        let category_ids: Option<Vec<&str>> = match &self.category_ids {
+            Some(ids) => Some(ids.iter().map(|x| x.as_str()).collect()),
+            None if lite => Some(Vec::new()),
+            None => None,
+        };
+
+        let mut rules = match (self.rules, category_ids) {
+            (Some(Source::Value(rules)), _) => rules,
+            (Some(Source::Path(path)), None) => Rules::new(path)?,
+            (Some(Source::Path(path)), Some(ids)) => Rules::new_with_categories(path, &ids)?,
+            #[cfg(feature = "binaries")]
+            (Some(Source::Lang(lang_code)), None) => Rules::from_lang(&lang_code)?,
+            #[cfg(feature = "binaries")]
+            (Some(Source::Lang(lang_code)), Some(ids)) => {
+                Rules::from_lang_with_categories(&lang_code, &ids)?
+            }
+            (None, _) => return Err(Error::Unexpected("pipeline needs rules".into())),
+        };
+        if lite {
+            if self.category_ids.is_none() {
+                let unloaded = rules.memory_stats().get("unloaded_categories").unwrap_or(0);
+                rules.load_smallest_categories((unloaded as f32 * LITE_RULES_SHARE) as usize)?;
+            }
+            rules.discard_unloaded_categories();
+        }
+
+        for list in self.word_lists {
+            rules.add_word_list(list);
+        }
+        if self.sensitive.is_some() {
+            rules.set_sensitive(self.sensitive);
+        }
+        if self.persistent_cache.is_some() {
+            rules.set_persistent_cache(self.persistent_cache);
+        }
+        let pipeline = Pipeline::new(tokenizer, rules)?;
+
+        if self.validate {
+            let issues = pipeline.rules.validate(&pipeline.tokenizer);
+
+            if let Some(issue) = issues.first() {
+                return Err(Error::Unexpected(format!(
+                    "rules are not valid for the tokenizer ({} issues), first issue: {}",
+                    issues.len(),
+                    issue
+                )));
+            }
+        }
+
+        Ok(pipeline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pipeline, PipelineBuilder, Profile};
+    use crate::{
+        markup::latex::Latex,
+        rules::{tests::EN, word_list::WordList},
+        Error, Rules, Tokenizer,
+    };
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref PIPELINE: Pipeline = builder().build().unwrap();
+    }
+
+    // for the tests which configure or change the pipeline
+    fn builder() -> PipelineBuilder {
+        Pipeline::builder()
+            .tokenizer_path("../storage/fixtures/en_tokenizer.bin")
+            .rules_path("../storage/fixtures/en_rules.bin")
+    }
+
+    #[test]
+    fn check_matches_rules_suggest() {
+        let pipeline = &*PIPELINE;
+        let (tokenizer, rules) = &*EN;
+
+        let text = "She was not been here since Monday. I can due his homework.";
+        assert_eq!(pipeline.check(text), rules.suggest(text, tokenizer));
+        assert_eq!(pipeline.correct(text), rules.correct(text, tokenizer));
+    }
+
+    #[test]
+    fn clones_share_rules_and_caches() {
+        let mut pipeline = builder().build().unwrap();
+        pipeline.rules_mut().unwrap().set_cache_size(10);
+        let text = "She was not been here since Monday.";
+
+        let mut clone = pipeline.clone();
+        assert!(std::ptr::eq(clone.rules(), pipeline.rules()));
+        assert!(clone.rules_mut().is_none());
+        assert_eq!(clone.check(text), pipeline.check(text));
+        // the second check was a hit in the shared cache
+        assert_eq!(pipeline.cache_stats().unwrap().hits, 1);
+
+        let own = pipeline.with_own_cache(5);
+        let own_clone = own.clone();
+        assert_eq!(own.check(text), pipeline.check(text));
+        own_clone.check(text);
+        let stats = own.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.capacity), (1, 1, 5));
+        assert_eq!(pipeline.cache_stats().unwrap().hits, 2);
+        assert!(pipeline.with_own_cache(0).cache_stats().is_none());
+
+        drop((clone, own, own_clone));
+        assert!(pipeline.into_parts().is_ok());
+    }
+
+    #[test]
+    fn checks_sentences_and_readers_with_own_cache() {
+        let mut pipeline = builder().build().unwrap();
+        pipeline.rules_mut().unwrap().set_cache_size(10);
+        let own = pipeline.with_own_cache(5);
+        let text = "She was not been here since Monday. He knows alot.";
+
+        let by_sentence = own.check_by_sentence(text);
+        assert_eq!(own.check_by_sentence(text), by_sentence);
+        // the sentences are checked separately and cached, the last one once the reader is exhausted
+        let from_reader = || {
+            let mut suggestions = Vec::new();
+            own.check_from_reader(text.as_bytes(), |x| suggestions.push(x))
+                .unwrap();
+            suggestions
+        };
+        assert_eq!(from_reader(), from_reader());
+
+        let stats = own.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses), (3, 3));
+        assert_eq!(pipeline.cache_stats().unwrap().len, 0);
+        assert_eq!(from_reader(), pipeline.check(text));
+        assert_eq!(
+            by_sentence
+                .into_iter()
+                .flat_map(|x| x.suggestions)
+                .collect::<Vec<_>>(),
+            pipeline.check(text)
+        );
+    }
+
+    #[test]
+    fn lite_profile_loads_less() {
+        let full = &*PIPELINE;
+        let lite = builder().profile(Profile::Lite).build().unwrap();
+
+        assert!(lite.tokenizer().chunker().is_none());
+        assert_eq!(lite.rules().unloaded_categories().count(), 0);
+        assert!(!lite.rules().rules().is_empty());
+        assert!(lite.rules().rules().len() < full.rules().rules().len());
+        assert!(lite.rules().memory_stats().total() < full.rules().memory_stats().total());
+
+        // still checks the loaded rules
+        for rule in lite.rules().rules().iter().filter(|x| x.on()) {
+            assert!(rule.test(lite.tokenizer()), "{}", rule.id());
+        }
+    }
+
+    #[test]
+    fn checks_concurrently() {
+        let pipeline = &*PIPELINE;
+        let texts = [
+            "She was not been here since Monday.",
+            "I can due his homework.",
+            "The cat sit on the mat. He knows alot.",
+        ];
+        let expected: Vec<_> = texts.iter().map(|x| pipeline.check(x)).collect();
+
+        // one pipeline is shared by reference, the threads check the texts many times at once
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        let found: Vec<_> = texts.iter().map(|x| pipeline.check(x)).collect();
+                        assert_eq!(found, expected);
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn maps_markup_suggestions_to_source() {
+        let pipeline = &*PIPELINE;
+
+        let source = r"\section{Intro} She was not been here since \emph{Monday}.";
+        let suggestions = pipeline.check_markup(source, &Latex::default());
+        assert_eq!(suggestions.len(), 1);
+
+        let span: String = source
+            .chars()
+            .skip(suggestions[0].start)
+            .take(suggestions[0].end - suggestions[0].start)
+            .collect();
+        assert_eq!(span, "was not been");
+    }
+
+    #[test]
+    fn disables_chunk_rules_without_chunker() {
+        let tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        assert!(tokenizer.chunker().is_none());
+        assert!(rules.rule("VERY_NOUN_PHRASE").unwrap().uses_chunks());
+        assert!(!rules.rule("VERY_GOOD").unwrap().uses_chunks());
+
+        let pipeline = Pipeline::new(tokenizer, rules).unwrap();
+
+        assert_eq!(pipeline.disabled_rules(), ["VERY_NOUN_PHRASE"]);
+        assert!(!pipeline.rules().rule("VERY_NOUN_PHRASE").unwrap().on());
+    }
+
+    #[test]
+    fn rejects_incompatible_components() {
+        let rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+
+        assert!(matches!(
+            Pipeline::new(Tokenizer::default(), rules),
+            Err(Error::IncompatibleComponents { .. })
+        ));
+    }
+
+    #[test]
+    fn restores_warm_pipeline() {
+        let mut pipeline = builder()
+            .word_list(WordList::parse("LIST", "chairman\tchair").unwrap())
+            .build()
+            .unwrap();
+        pipeline.rules_mut().unwrap().set_cache_size(10);
+        pipeline.rules_mut().unwrap().disable_rules(&["ALOT"]);
+
+        let text = "The chairman knows alot. She was not been here.";
+        let suggestions = pipeline.check(text);
+
+        let mut bytes = Vec::new();
+        pipeline.serialize_warm(&mut bytes).unwrap();
+        let restored = Pipeline::deserialize_warm(&bytes[..]).unwrap();
+
+        assert_eq!(restored.rules().cache_stats().unwrap().len, 1);
+        assert_eq!(restored.check(text), suggestions);
+        assert_eq!(restored.rules().cache_stats().unwrap().hits, 1);
+        assert!(!restored.rules().rule("ALOT").unwrap().on());
+        assert_eq!(restored.rules().word_lists().len(), 1);
+        assert_eq!(
+            restored
+                .rules()
+                .rules()
+                .iter()
+                .map(|x| x.is_warm())
+                .collect::<Vec<_>>(),
+            pipeline
+                .rules()
+                .rules()
+                .iter()
+                .map(|x| x.is_warm())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn restores_own_cache() {
+        let own = PIPELINE.with_own_cache(5);
+        let text = "She was not been here.";
+        let suggestions = own.check(text);
+
+        let mut bytes = Vec::new();
+        own.serialize_warm(&mut bytes).unwrap();
+        let restored = Pipeline::deserialize_warm(&bytes[..]).unwrap();
+
+        assert!(restored.rules().cache_stats().is_none());
+        assert_eq!(restored.check(text), suggestions);
+        let stats = restored.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.len, stats.capacity), (1, 1, 5));
+
+        let mut bytes = Vec::new();
+        PIPELINE
+            .with_own_cache(0)
+            .serialize_warm(&mut bytes)
+            .unwrap();
+        let restored = Pipeline::deserialize_warm(&bytes[..]).unwrap();
+        assert!(restored.cache_stats().is_none());
+    }
+}
@@ -2,7 +2,7 @@ use crate::types::*;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
-use super::engine::composition::PosMatcher;
+use super::engine::composition::{Leaf, PosMatcher};
 
 #[derive(Serialize, Deserialize)]
 pub struct POSFilter {
@@ -44,10 +44,38 @@ pub enum Disambiguation {
     Replace(Vec<owned::WordData>),
     Filter(Vec<Option<either::Either<owned::WordData, POSFilter>>>),
     Unify(Vec<Vec<POSFilter>>, Vec<Option<POSFilter>>, Vec<bool>),
+    /// Marks the tokens as immune so grammar rules do not match them.
+    Immunize,
     Nop,
 }
 
 impl Disambiguation {
+    pub fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        let filters: Vec<&POSFilter> = match self {
+            Disambiguation::Remove(data_or_filters) => data_or_filters
+                .iter()
+                .filter_map(|x| x.as_ref().right())
+                .collect(),
+            Disambiguation::Filter(data_or_filters) => data_or_filters
+                .iter()
+                .filter_map(|x| x.as_ref().and_then(|x| x.as_ref().right()))
+                .collect(),
+            Disambiguation::Unify(filters, disambig, _) => filters
+                .iter()
+                .flatten()
+                .chain(disambig.iter().flatten())
+                .collect(),
+            Disambiguation::Add(_)
+            | Disambiguation::Replace(_)
+            | Disambiguation::Immunize
+            | Disambiguation::Nop => Vec::new(),
+        };
+
+        for filter in filters {
+            f(Leaf::PosMatcher(&filter.matcher));
+        }
+    }
+
     pub fn apply<'t>(&'t self, groups: Vec<Vec<&mut IncompleteToken<'t>>>, retain_last: bool) {
         match self {
             Disambiguation::Remove(data_or_filters) => {
@@ -136,6 +164,11 @@ impl Disambiguation {
                     }
                 }
             }
+            Disambiguation::Immunize => {
+                for token in groups.into_iter().flatten() {
+                    token.immune = true;
+                }
+            }
             Disambiguation::Unify(filters, disambigs, mask) => {
                 let filters: Vec<_> = filters.iter().multi_cartesian_product().collect();
 
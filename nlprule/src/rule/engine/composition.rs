@@ -4,6 +4,15 @@ use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use unicase::UniCase;
 
+/// The parts of rules which depend on external state: regexes are compiled lazily and
+/// part-of-speech matchers depend on the tag set of the tagger.
+pub enum Leaf<'a> {
+    Regex(&'a SerializeRegex),
+    PosMatcher(&'a PosMatcher),
+    /// Marks a match on the chunk tags, which are only set if the tokenizer has a chunker.
+    Chunk,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Matcher {
     pub matcher: either::Either<either::Either<String, usize>, SerializeRegex>,
@@ -13,18 +22,25 @@ pub struct Matcher {
 }
 
 impl Matcher {
-    pub fn is_slice_match<S: AsRef<str>>(
+    pub fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        if let either::Right(regex) = &self.matcher {
+            f(Leaf::Regex(regex));
+        }
+    }
+
+    pub fn is_match(&self, input: &str, graph: &MatchGraph, case_sensitive: Option<bool>) -> bool {
+        self.is_match_at(input, graph, case_sensitive, None)
+    }
+
+    /// Like [is_match][Matcher::is_match]. If `input` is the text of the token at `position`, regex matches are
+    /// memoized, see [memo][super::memo].
+    pub fn is_match_at(
         &self,
-        input: &[S],
+        input: &str,
         graph: &MatchGraph,
         case_sensitive: Option<bool>,
+        position: Option<usize>,
     ) -> bool {
-        input
-            .iter()
-            .any(|x| self.is_match(x.as_ref(), graph, case_sensitive))
-    }
-
-    pub fn is_match(&self, input: &str, graph: &MatchGraph, case_sensitive: Option<bool>) -> bool {
         if input.is_empty() {
             return if self.empty_always_false {
                 false
@@ -53,7 +69,10 @@ impl Matcher {
                     })
                 }),
             },
-            either::Right(regex) => regex.is_match(input),
+            either::Right(regex) => match position {
+                Some(position) => super::memo::regex_match(regex, position, input),
+                None => regex.is_match(input),
+            },
         };
 
         if self.negate {
@@ -67,39 +86,92 @@ impl Matcher {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TextMatcher {
     pub matcher: Matcher,
+    #[serde(serialize_with = "crate::utils::serialize_sorted_set")]
     pub set: Option<DefaultHashSet<u32>>,
 }
 
 impl TextMatcher {
+    pub fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        self.matcher.visit(f);
+    }
+
     pub fn is_match(
         &self,
         word_id: &WordId,
         graph: &MatchGraph,
         case_sensitive: Option<bool>,
+    ) -> bool {
+        self.is_match_at(word_id, graph, case_sensitive, None)
+    }
+
+    /// Like [is_match][TextMatcher::is_match], see [Matcher::is_match_at].
+    pub fn is_match_at(
+        &self,
+        word_id: &WordId,
+        graph: &MatchGraph,
+        case_sensitive: Option<bool>,
+        position: Option<usize>,
     ) -> bool {
         if self.set.is_none() {
             return self
                 .matcher
-                .is_match(word_id.as_ref(), graph, case_sensitive);
+                .is_match_at(word_id.as_ref(), graph, case_sensitive, position);
         }
 
         if let Some(id) = word_id.id() {
             self.set.as_ref().unwrap().contains(id)
         } else {
             self.matcher
-                .is_match(word_id.as_ref(), graph, case_sensitive)
+                .is_match_at(word_id.as_ref(), graph, case_sensitive, position)
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Matches part-of-speech tags by their ID. Whether each tag of the tagset matches is evaluated when the rules are
+/// compiled and kept as a bitset over the tag IDs, so matching a tag is a single bit test.
+/// It is serialized as one `bool` per tag.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<bool>", into = "Vec<bool>")]
 pub struct PosMatcher {
-    pub mask: Vec<bool>,
+    bits: Vec<u64>,
+    len: usize,
 }
 
 impl PosMatcher {
     pub fn is_match(&self, pos: &PosId) -> bool {
-        self.mask[*pos.id() as usize]
+        let id = *pos.id() as usize;
+        id < self.len && (self.bits[id / 64] >> (id % 64)) & 1 == 1
+    }
+
+    /// The number of tags in the tagset the matcher was created for.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl From<Vec<bool>> for PosMatcher {
+    fn from(mask: Vec<bool>) -> Self {
+        let mut bits = vec![0u64; mask.len().div_ceil(64)];
+        for (i, _) in mask.iter().enumerate().filter(|(_, x)| **x) {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+
+        PosMatcher {
+            bits,
+            len: mask.len(),
+        }
+    }
+}
+
+impl From<PosMatcher> for Vec<bool> {
+    fn from(matcher: PosMatcher) -> Self {
+        (0..matcher.len)
+            .map(|i| (matcher.bits[i / 64] >> (i % 64)) & 1 == 1)
+            .collect()
     }
 }
 
@@ -110,6 +182,15 @@ pub struct WordDataMatcher {
 }
 
 impl WordDataMatcher {
+    pub fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        if let Some(matcher) = &self.pos_matcher {
+            f(Leaf::PosMatcher(matcher));
+        }
+        if let Some(matcher) = &self.inflect_matcher {
+            matcher.visit(f);
+        }
+    }
+
     pub fn is_match(
         &self,
         input: &[WordData],
@@ -146,6 +227,9 @@ pub struct Quantifier {
 #[enum_dispatch]
 pub trait Atomable: Send + Sync {
     fn is_match(&self, input: &[&Token], graph: &MatchGraph, position: usize) -> bool;
+
+    /// Calls `f` with all leaves of this atom.
+    fn visit<'a>(&'a self, _f: &mut dyn FnMut(Leaf<'a>)) {}
 }
 
 #[enum_dispatch(Atomable)]
@@ -164,7 +248,8 @@ pub enum Atom {
 }
 
 pub mod concrete {
-    use super::{Atomable, MatchGraph, Matcher, TextMatcher, Token, WordDataMatcher};
+    use super::{Atomable, Leaf, MatchGraph, Matcher, TextMatcher, Token, WordDataMatcher};
+    use crate::types::ChunkTag;
     use serde::{Deserialize, Serialize};
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -175,19 +260,64 @@ pub mod concrete {
     impl Atomable for TextAtom {
         fn is_match(&self, input: &[&Token], graph: &MatchGraph, position: usize) -> bool {
             self.matcher
-                .is_match(&input[position].word.text, graph, None)
+                .is_match_at(&input[position].word.text, graph, None, Some(position))
+        }
+
+        fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+            self.matcher.visit(f);
         }
     }
 
     #[derive(Debug, Serialize, Deserialize)]
+    #[serde(from = "ChunkAtomFields")]
     pub struct ChunkAtom {
         pub(crate) matcher: Matcher,
+        // set if the matcher only matches this tag, so tags can be compared without converting them to strings
+        #[serde(skip)]
+        tag: Option<ChunkTag>,
+    }
+
+    #[derive(Deserialize)]
+    struct ChunkAtomFields {
+        matcher: Matcher,
+    }
+
+    impl From<ChunkAtomFields> for ChunkAtom {
+        fn from(fields: ChunkAtomFields) -> Self {
+            ChunkAtom::new(fields.matcher)
+        }
+    }
+
+    impl ChunkAtom {
+        pub(crate) fn new(matcher: Matcher) -> Self {
+            let tag = match &matcher.matcher {
+                either::Left(either::Left(tag))
+                    if !tag.is_empty() && !matcher.negate && matcher.case_sensitive =>
+                {
+                    Some(ChunkTag::from(tag.as_str()))
+                }
+                _ => None,
+            };
+
+            ChunkAtom { matcher, tag }
+        }
     }
 
     impl Atomable for ChunkAtom {
         fn is_match(&self, input: &[&Token], graph: &MatchGraph, position: usize) -> bool {
-            self.matcher
-                .is_slice_match(&input[position].chunks, graph, None)
+            let chunks = &input[position].chunks;
+
+            match &self.tag {
+                Some(tag) => chunks.contains(tag),
+                None => chunks
+                    .iter()
+                    .any(|x| self.matcher.is_match(&x.to_string(), graph, None)),
+            }
+        }
+
+        fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+            f(Leaf::Chunk);
+            self.matcher.visit(f);
         }
     }
 
@@ -215,6 +345,10 @@ pub mod concrete {
             self.matcher
                 .is_match(&tags, graph, Some(self.case_sensitive))
         }
+
+        fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+            self.matcher.visit(f);
+        }
     }
 }
 
@@ -247,6 +381,12 @@ impl Atomable for AndAtom {
             .iter()
             .all(|x| x.is_match(input, graph, position))
     }
+
+    fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        for atom in &self.atoms {
+            atom.visit(f);
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -260,6 +400,12 @@ impl Atomable for OrAtom {
             .iter()
             .any(|x| x.is_match(input, graph, position))
     }
+
+    fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        for atom in &self.atoms {
+            atom.visit(f);
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -271,6 +417,10 @@ impl Atomable for NotAtom {
     fn is_match(&self, input: &[&Token], graph: &MatchGraph, position: usize) -> bool {
         !self.atom.is_match(input, graph, position)
     }
+
+    fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        self.atom.visit(f);
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -289,6 +439,10 @@ impl Atomable for OffsetAtom {
             self.atom.is_match(input, graph, new_position as usize)
         }
     }
+
+    fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        self.atom.visit(f);
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -436,6 +590,10 @@ impl<'t> MatchGraph<'t> {
     }
 }
 
+/// The maximum number of states visited when matching a composition at one position.
+/// Bounds the cost of backtracking for patterns with many quantifiers.
+const MAX_SEARCH_STEPS: usize = 10_000;
+
 #[derive(Serialize, Deserialize)]
 pub struct Part {
     pub atom: Atom,
@@ -446,11 +604,17 @@ pub struct Part {
 #[derive(Serialize, Deserialize)]
 pub struct Composition {
     pub(crate) parts: Vec<Part>,
+    #[serde(serialize_with = "crate::utils::serialize_sorted_map")]
     pub(crate) group_ids_to_idx: DefaultHashMap<usize, usize>,
-    pub(crate) can_stop_mask: Vec<bool>,
 }
 
 impl Composition {
+    pub fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        for part in &self.parts {
+            part.atom.visit(f);
+        }
+    }
+
     fn next_can_match<'t>(
         &self,
         tokens: &'t [&'t Token<'t>],
@@ -475,10 +639,75 @@ impl Composition {
             .any(|x| x.atom.is_match(tokens, graph, position))
     }
 
+    /// Tries to match the part at `index` which already matched `count` tokens at `position`, backtracking if needed.
+    /// The first path tried is to move on to the next part as soon as it can match and to match the current part otherwise.
+    fn search<'t>(
+        &'t self,
+        tokens: &'t [&'t Token<'t>],
+        graph: &mut MatchGraph<'t>,
+        position: usize,
+        index: usize,
+        count: usize,
+        steps: &mut usize,
+    ) -> bool {
+        if index >= self.parts.len() {
+            return true;
+        }
+
+        *steps += 1;
+        if *steps > MAX_SEARCH_STEPS {
+            return false;
+        }
+
+        let part = &self.parts[index];
+        let can_advance = count >= part.quantifier.min;
+        let prefer_advance = can_advance
+            && position < tokens.len()
+            && self.next_can_match(tokens, graph, position, index);
+
+        if prefer_advance && self.search(tokens, graph, position, index + 1, 0, steps) {
+            return true;
+        }
+
+        if count < part.quantifier.max
+            && position < tokens.len()
+            // the first part has already been checked at the start position
+            && ((index == 0 && count == 0 && part.quantifier.min > 0)
+                || part.atom.is_match(tokens, graph, position))
+        {
+            let previous = graph.groups[index + 1].char_span;
+            let group = &mut graph.groups[index + 1];
+
+            // set the group beginning if the char end was zero (i. e. the group was empty)
+            if group.char_span.1 == 0 {
+                group.char_span.0 = tokens[position].char_span.0;
+            }
+            group.char_span.1 = tokens[position].char_span.1;
+
+            if self.search(tokens, graph, position + 1, index, count + 1, steps) {
+                return true;
+            }
+
+            graph.groups[index + 1].char_span = previous;
+        }
+
+        !prefer_advance && can_advance && self.search(tokens, graph, position, index + 1, 0, steps)
+    }
+
     pub fn apply<'t>(
         &'t self,
         tokens: &'t [&'t Token<'t>],
         start: usize,
+    ) -> Option<MatchGraph<'t>> {
+        self.apply_counted(tokens, start, &mut 0)
+    }
+
+    /// Like [apply][Composition::apply], but adds the number of states visited while searching to `total_steps`.
+    pub fn apply_counted<'t>(
+        &'t self,
+        tokens: &'t [&'t Token<'t>],
+        start: usize,
+        total_steps: &mut usize,
     ) -> Option<MatchGraph<'t>> {
         // this path is extremely hot so more optimizations are done
 
@@ -493,61 +722,15 @@ impl Composition {
             return None;
         }
 
-        let mut position = start;
-
-        let mut cur_count = 0;
-        let mut cur_atom_idx = 0;
-
         let mut graph = MatchGraph::new(
             vec![Group::default(); self.parts.len() + 1],
             &self.group_ids_to_idx,
             tokens,
         );
+        let mut steps = 0;
 
-        let mut is_match = loop {
-            if cur_atom_idx >= self.parts.len() {
-                break true;
-            }
-
-            let part = &self.parts[cur_atom_idx];
-
-            if cur_count >= part.quantifier.max {
-                cur_atom_idx += 1;
-                cur_count = 0;
-                if cur_atom_idx >= self.parts.len() {
-                    break false;
-                }
-                continue;
-            }
-
-            if position >= tokens.len() {
-                break false;
-            }
-
-            if cur_count >= part.quantifier.min
-                && self.next_can_match(&tokens, &graph, position, cur_atom_idx)
-            {
-                cur_atom_idx += 1;
-                cur_count = 0;
-            } else if (first_must_match && position == start && cur_atom_idx == 0) // we already know this must have matched, otherwise it would have early exited above
-                || part.atom.is_match(tokens, &graph, position)
-            {
-                let mut group = &mut graph.groups[cur_atom_idx + 1];
-
-                // set the group beginning if the char end was zero (i. e. the group was empty)
-                if group.char_span.1 == 0 {
-                    group.char_span.0 = tokens[position].char_span.0;
-                }
-                group.char_span.1 = tokens[position].char_span.1;
-
-                position += 1;
-                cur_count += 1;
-            } else {
-                break false;
-            }
-        };
-
-        is_match = is_match || cur_atom_idx == self.parts.len() || self.can_stop_mask[cur_atom_idx];
+        let is_match = self.search(tokens, &mut graph, start, 0, 0, &mut steps);
+        *total_steps += steps;
 
         if is_match {
             graph.fill_empty();
@@ -557,3 +740,23 @@ impl Composition {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_pos_matcher_like_mask() {
+        let mask: Vec<bool> = (0..130).map(|i| i % 3 == 0 || i == 129).collect();
+        let matcher = PosMatcher::from(mask.clone());
+
+        assert_eq!(
+            bincode::serialize(&matcher).unwrap(),
+            bincode::serialize(&mask).unwrap()
+        );
+        for (i, expected) in mask.iter().enumerate() {
+            assert_eq!(matcher.is_match(&PosId("", i as u16)), *expected);
+        }
+        assert!(!matcher.is_match(&PosId("", 200)));
+    }
+}
@@ -0,0 +1,71 @@
+//! Memoization of regex matches on the text of tokens while the rules check one sentence.
+//!
+//! Many rules share the same regexes (identical patterns are compiled once, see [SerializeRegex]) and test them on
+//! the same tokens, so the result is stored by the address of the compiled regex and the position of the token.
+//! The memo is thread local and only used inside [with_scope]; a new scope starts with an empty table, so results
+//! never leak between different token slices.
+
+use crate::{types::DefaultHashMap, utils::regex::SerializeRegex};
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static SCOPES: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(Default)]
+struct Memo {
+    scope: usize,
+    active: bool,
+    results: DefaultHashMap<(usize, usize), bool>,
+}
+
+thread_local! {
+    static MEMO: RefCell<Memo> = RefCell::new(Memo::default());
+}
+
+/// A new scope ID. All calls of [with_scope] with the same ID must check the same tokens.
+pub fn new_scope() -> usize {
+    SCOPES.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Runs `f` with memoization in the scope. The table is cleared if the thread was last used for another scope.
+pub fn with_scope<T>(scope: usize, f: impl FnOnce() -> T) -> T {
+    let was_active = MEMO.with(|memo| {
+        let mut memo = memo.borrow_mut();
+        if memo.scope != scope {
+            memo.scope = scope;
+            memo.results.clear();
+        }
+        std::mem::replace(&mut memo.active, true)
+    });
+
+    let output = f();
+
+    MEMO.with(|memo| memo.borrow_mut().active = was_active);
+    output
+}
+
+/// Whether the regex matches the text of the token at `position`, memoized if inside [with_scope].
+pub fn regex_match(regex: &SerializeRegex, position: usize, text: &str) -> bool {
+    let key = (regex.id(), position);
+
+    let cached = MEMO.with(|memo| {
+        let memo = memo.borrow();
+        if memo.active {
+            Some(memo.results.get(&key).copied())
+        } else {
+            None
+        }
+    });
+
+    match cached {
+        Some(Some(result)) => result,
+        Some(None) => {
+            let result = regex.is_match(text);
+            MEMO.with(|memo| memo.borrow_mut().results.insert(key, result));
+            result
+        }
+        None => regex.is_match(text),
+    }
+}
@@ -1,89 +1,190 @@
 use crate::{types::*, utils::regex::SerializeRegex};
 use serde::{Deserialize, Serialize};
 pub mod composition;
+pub mod memo;
+pub mod prefilter;
+pub mod unification;
 
-use composition::{Composition, Group, MatchGraph};
+use composition::{Composition, Group, Leaf, MatchGraph};
+use unification::Unification;
+
+use super::RuleComplexity;
 
 #[derive(Serialize, Deserialize)]
 pub struct TokenEngine {
     pub(crate) composition: Composition,
     pub(crate) antipatterns: Vec<Composition>,
+    pub(crate) unifications: Vec<Unification>,
 }
 
 impl TokenEngine {
-    fn get_match<'t>(&'t self, tokens: &'t [&'t Token], i: usize) -> Option<MatchGraph<'t>> {
-        if let Some(graph) = self.composition.apply(tokens, i) {
-            let mut blocked = false;
-
-            // TODO: cache / move to outer loop
-            for i in 0..tokens.len() {
-                for antipattern in &self.antipatterns {
-                    if let Some(anti_graph) = antipattern.apply(tokens, i) {
-                        let anti_start = anti_graph.by_index(0).char_span.0;
-                        let anti_end = anti_graph
-                            .by_index(anti_graph.groups().len() - 1)
-                            .char_span
-                            .1;
-
-                        let rule_start = graph.by_index(0).char_span.0;
-                        let rule_end = graph.by_index(graph.groups().len() - 1).char_span.1;
-
-                        if anti_start <= rule_end && rule_start <= anti_end {
-                            blocked = true;
-                            break;
-                        }
-                    }
-                }
-                if blocked {
-                    break;
+    fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        self.composition.visit(f);
+        for antipattern in &self.antipatterns {
+            antipattern.visit(f);
+        }
+        for unification in &self.unifications {
+            unification.visit(f);
+        }
+    }
+
+    /// The char spans of all matches of the antipatterns in the tokens.
+    fn antipattern_spans(&self, tokens: &[&Token], steps: &mut usize) -> Vec<(usize, usize)> {
+        let mut spans = Vec::new();
+
+        for i in 0..tokens.len() {
+            for antipattern in &self.antipatterns {
+                if let Some(graph) = antipattern.apply_counted(tokens, i, steps) {
+                    spans.push(span(&graph));
                 }
             }
+        }
 
-            if !blocked {
-                return Some(graph);
-            }
+        spans
+    }
+
+    fn get_match<'t>(
+        &'t self,
+        tokens: &'t [&'t Token],
+        i: usize,
+        antipattern_spans: &[(usize, usize)],
+        steps: &mut usize,
+    ) -> Option<MatchGraph<'t>> {
+        let graph = self.composition.apply_counted(tokens, i, steps)?;
+        if !self.unifications.iter().all(|x| x.is_match(&graph)) {
+            return None;
         }
 
-        None
+        let (start, end) = span(&graph);
+
+        // like in LanguageTool, a match is blocked if any of its tokens is part of an antipattern match
+        if antipattern_spans
+            .iter()
+            .any(|(anti_start, anti_end)| *anti_start < end && start < *anti_end)
+        {
+            None
+        } else {
+            Some(graph)
+        }
     }
 }
 
+/// The char span from the first to the last token of a match.
+pub(crate) fn span(graph: &MatchGraph) -> (usize, usize) {
+    (
+        graph.by_index(0).char_span.0,
+        graph.by_index(graph.groups().len() - 1).char_span.1,
+    )
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum Engine {
     Token(TokenEngine),
-    Text(SerializeRegex, DefaultHashMap<usize, usize>),
+    Text(
+        SerializeRegex,
+        #[serde(serialize_with = "crate::utils::serialize_sorted_map")]
+        DefaultHashMap<usize, usize>,
+    ),
 }
 
 impl Engine {
+    /// Whether the matches of this engine contain a group with the given ID.
+    pub fn has_group(&self, id: usize) -> bool {
+        match &self {
+            Engine::Token(engine) => engine.composition.group_ids_to_idx.contains_key(&id),
+            Engine::Text(_, id_to_idx) => id_to_idx.contains_key(&id),
+        }
+    }
+
+    pub fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        match &self {
+            Engine::Token(engine) => engine.visit(f),
+            Engine::Text(regex, _) => f(Leaf::Regex(regex)),
+        }
+    }
+
+    pub fn complexity(&self) -> RuleComplexity {
+        let mut complexity = RuleComplexity::default();
+
+        match &self {
+            Engine::Token(engine) => {
+                for part in &engine.composition.parts {
+                    if part.visible {
+                        complexity.tokens += 1;
+                    } else {
+                        // skips are invisible parts after the token they belong to
+                        complexity.skips += 1;
+                        complexity.skipped_tokens += part.quantifier.max;
+                    }
+                }
+                complexity.antipatterns = engine.antipatterns.len();
+            }
+            Engine::Text(..) => complexity.text = true,
+        }
+        self.visit(&mut |leaf| {
+            if let Leaf::Regex(_) = leaf {
+                complexity.regexes += 1;
+            }
+        });
+
+        complexity
+    }
+
     pub fn get_matches<'t>(
         &'t self,
         tokens: &'t [&'t Token],
         start: usize,
         end: usize,
     ) -> Vec<MatchGraph<'t>> {
+        self.get_matches_limited(tokens, start, end, None)
+            .expect("matching without a limit always finishes")
+    }
+
+    /// Like [get_matches][Engine::get_matches], but gives up and returns `None` once the pattern and the
+    /// antipatterns visited more than `max_steps` states while searching, summed over all positions.
+    /// Regexes on the text are not limited.
+    pub fn get_matches_limited<'t>(
+        &'t self,
+        tokens: &'t [&'t Token],
+        start: usize,
+        end: usize,
+        max_steps: Option<usize>,
+    ) -> Option<Vec<MatchGraph<'t>>> {
         let mut graphs = Vec::new();
 
+        // an empty text has no tokens, not even the sentence start
+        if tokens.is_empty() {
+            return Some(graphs);
+        }
+
         match &self {
             Engine::Token(engine) => {
-                let mut graph_info: Vec<_> = (0..tokens.len())
-                    .into_iter()
-                    .filter_map(|i| {
-                        if let Some(graph) = engine.get_match(&tokens, i) {
-                            let start_group = graph
-                                .by_id(start)
-                                .unwrap_or_else(|| panic!("group must exist in graph: {}", start));
-                            let end_group = graph.by_id(end - 1).unwrap_or_else(|| {
-                                panic!("group must exist in graph: {}", end - 1)
-                            });
-
-                            let start = start_group.char_span.0;
-                            let end = end_group.char_span.1;
-                            Some((graph, start, end))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
+                let max_steps = max_steps.unwrap_or(usize::MAX);
+                let mut steps = 0;
+                let antipattern_spans = engine.antipattern_spans(tokens, &mut steps);
+
+                let mut graph_info = Vec::new();
+                for i in 0..tokens.len() {
+                    if steps > max_steps {
+                        return None;
+                    }
+
+                    if let Some(graph) = engine.get_match(tokens, i, &antipattern_spans, &mut steps)
+                    {
+                        let start_group = graph
+                            .by_id(start)
+                            .unwrap_or_else(|| panic!("group must exist in graph: {}", start));
+                        let end_group = graph
+                            .by_id(end - 1)
+                            .unwrap_or_else(|| panic!("group must exist in graph: {}", end - 1));
+
+                        let (start, end) = (start_group.char_span.0, end_group.char_span.1);
+                        graph_info.push((graph, start, end));
+                    }
+                }
+                if steps > max_steps {
+                    return None;
+                }
 
                 graph_info.sort_by(|(_, start, _), (_, end, _)| start.cmp(end));
                 let mut mask = vec![false; tokens[0].text.chars().count()];
@@ -124,6 +225,6 @@ impl Engine {
             }
         }
 
-        graphs
+        Some(graphs)
     }
 }
@@ -0,0 +1,447 @@
+//! A cheap check whether a rule can possibly match a text.
+//!
+//! Many rules require some token to be exactly equal to a literal string. The literals of all rules in a set are
+//! deduplicated into one shared pool when the set is built or loaded, so the rules which can not match a sentence are
+//! found by looking up the words of its tokens in the pool instead of running their token patterns. The words are
+//! looked up rather than the text, since they can differ from it, e. g. the parts of a contraction. The pool is also
+//! compiled into Aho-Corasick automata to find where the literals occur in a text.
+//!
+//! Rules anchored on a lemma instead of a surface form are not covered by the automata. They are indexed by the
+//! required word or lemma of their first required token, so the rules which can match a sentence are found by
+//! looking up the words and lemmas of its tokens, independently of the number of rules.
+
+use super::{
+    composition::{Atom, Composition},
+    Engine,
+};
+use crate::types::{DefaultHashMap, Token};
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
+use unicase::UniCase;
+
+/// An automaton over a subset of the literal pool.
+struct Automaton {
+    automaton: AhoCorasick,
+    // maps automaton pattern IDs to literal IDs
+    ids: Vec<usize>,
+}
+
+impl Automaton {
+    fn new(literals: &[(String, bool)], case_sensitive: bool) -> Self {
+        let (ids, patterns): (Vec<_>, Vec<_>) = literals
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, x))| *x == case_sensitive)
+            .map(|(i, (literal, _))| (i, literal.as_str()))
+            .unzip();
+
+        Automaton {
+            automaton: AhoCorasickBuilder::new().build(patterns),
+            ids,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    // the literal IDs with the byte spans of their occurrences in the text
+    fn find<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (usize, usize, usize)> + 'a {
+        self.automaton
+            .find_overlapping_iter(text)
+            .map(move |m| (self.ids[m.pattern()], m.start(), m.end()))
+    }
+}
+
+// the case folded text and for each of its bytes the byte index of the char in the text it was folded from
+fn fold_with_offsets(text: &str) -> (String, Vec<usize>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len());
+
+    for (i, c) in text.char_indices() {
+        folded.push_str(&UniCase::new(c.encode_utf8(&mut [0; 4]) as &str).to_folded_case());
+        offsets.resize(folded.len(), i);
+    }
+
+    (folded, offsets)
+}
+
+/// Literals required by a set of engines.
+/// Case sensitive literals are looked up as they are, case insensitive literals are stored case folded
+/// and looked up case folded.
+pub struct Prefilter {
+    literals: Vec<(String, bool)>,
+    // the literal IDs by literal, separately for case sensitive and case insensitive literals
+    sensitive_ids: DefaultHashMap<String, usize>,
+    insensitive_ids: DefaultHashMap<String, usize>,
+    requirements: Vec<Vec<usize>>,
+    sensitive: Automaton,
+    insensitive: Automaton,
+    first_tokens: FirstTokenIndex,
+}
+
+/// What the first required token of an engine must be, case folded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Anchor {
+    Word(String),
+    Lemma(String),
+}
+
+/// The engines by the anchor of their first required token. Engines without an anchor can match any sentence.
+#[derive(Default)]
+struct FirstTokenIndex {
+    len: usize,
+    anchored: DefaultHashMap<Anchor, Vec<usize>>,
+    unanchored: Vec<usize>,
+}
+
+fn literal(matcher: &super::composition::Matcher) -> Option<String> {
+    match &matcher.matcher {
+        either::Left(either::Left(string)) if !matcher.negate && !string.is_empty() => {
+            Some(UniCase::new(string).to_folded_case())
+        }
+        _ => None,
+    }
+}
+
+fn anchor(atom: &Atom) -> Option<Anchor> {
+    match atom {
+        Atom::TextAtom(atom) => literal(&atom.matcher.matcher).map(Anchor::Word),
+        // the POS tag only narrows the readings down further
+        Atom::WordDataAtom(atom) => atom
+            .matcher
+            .inflect_matcher
+            .as_ref()
+            .and_then(|x| literal(&x.matcher))
+            .map(Anchor::Lemma),
+        Atom::AndAtom(atom) => atom.atoms.iter().find_map(anchor),
+        _ => None,
+    }
+}
+
+impl FirstTokenIndex {
+    fn new<'a>(engines: impl Iterator<Item = &'a Engine>) -> Self {
+        let mut index = FirstTokenIndex::default();
+
+        for (i, engine) in engines.enumerate() {
+            let anchor = match engine {
+                Engine::Token(engine) => engine
+                    .composition
+                    .parts
+                    .iter()
+                    .find(|x| x.quantifier.min > 0)
+                    .and_then(|x| anchor(&x.atom)),
+                Engine::Text(_, _) => None,
+            };
+
+            match anchor {
+                Some(anchor) => index.anchored.entry(anchor).or_default().push(i),
+                None => index.unanchored.push(i),
+            }
+            index.len += 1;
+        }
+
+        index
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.anchored
+            .iter()
+            .map(|(anchor, ids)| match anchor {
+                Anchor::Word(x) | Anchor::Lemma(x) => x.len() + ids.len() * 8,
+            })
+            .sum::<usize>()
+            + self.unanchored.len() * 8
+    }
+
+    fn candidates(&self, tokens: &[Token]) -> Vec<bool> {
+        let mut candidates = vec![false; self.len];
+        for i in &self.unanchored {
+            candidates[*i] = true;
+        }
+
+        let mut mark = |anchor: Anchor| {
+            for i in self.anchored.get(&anchor).into_iter().flatten() {
+                candidates[*i] = true;
+            }
+        };
+
+        for token in tokens {
+            let text = token.word.text.as_ref();
+            if !text.is_empty() {
+                mark(Anchor::Word(UniCase::new(text).to_folded_case()));
+            }
+            for data in &token.word.tags {
+                let lemma = data.lemma.as_ref();
+                if !lemma.is_empty() {
+                    mark(Anchor::Lemma(UniCase::new(lemma).to_folded_case()));
+                }
+            }
+        }
+
+        candidates
+    }
+}
+
+impl Default for Prefilter {
+    fn default() -> Self {
+        Prefilter::new(std::iter::empty())
+    }
+}
+
+fn collect_literals(atom: &Atom, literals: &mut Vec<(String, bool)>) {
+    match atom {
+        Atom::TextAtom(atom) => {
+            let matcher = &atom.matcher.matcher;
+
+            if let either::Left(either::Left(string)) = &matcher.matcher {
+                if !matcher.negate && !string.is_empty() {
+                    literals.push(if matcher.case_sensitive {
+                        (string.clone(), true)
+                    } else {
+                        (UniCase::new(string).to_folded_case(), false)
+                    });
+                }
+            }
+        }
+        Atom::AndAtom(atom) => {
+            for atom in &atom.atoms {
+                collect_literals(atom, literals);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn required_literals(composition: &Composition) -> Vec<(String, bool)> {
+    let mut literals = Vec::new();
+
+    for part in composition.parts.iter().filter(|x| x.quantifier.min > 0) {
+        collect_literals(&part.atom, &mut literals);
+    }
+
+    literals
+}
+
+impl Prefilter {
+    /// Creates a prefilter for the given engines. The order of the engines determines the order of the result of [candidates][Prefilter::candidates].
+    pub fn new<'a>(engines: impl Iterator<Item = &'a Engine> + Clone) -> Self {
+        let required = engines.clone().map(|engine| match engine {
+            Engine::Token(engine) => required_literals(&engine.composition),
+            Engine::Text(_, _) => Vec::new(),
+        });
+
+        Self::from_literals(required, FirstTokenIndex::new(engines))
+    }
+
+    // the required literals are case folded if they are case insensitive
+    fn from_literals(
+        required: impl Iterator<Item = Vec<(String, bool)>>,
+        first_tokens: FirstTokenIndex,
+    ) -> Self {
+        let mut literals = Vec::new();
+        let mut literal_ids = DefaultHashMap::default();
+
+        let requirements = required
+            .map(|required| {
+                let mut ids: Vec<_> = required
+                    .into_iter()
+                    .map(|literal| {
+                        *literal_ids.entry(literal.clone()).or_insert_with(|| {
+                            literals.push(literal);
+                            literals.len() - 1
+                        })
+                    })
+                    .collect();
+                ids.sort_unstable();
+                ids.dedup();
+                ids
+            })
+            .collect();
+
+        let mut sensitive_ids = DefaultHashMap::default();
+        let mut insensitive_ids = DefaultHashMap::default();
+        for ((literal, case_sensitive), id) in literal_ids {
+            if case_sensitive {
+                sensitive_ids.insert(literal, id);
+            } else {
+                insensitive_ids.insert(literal, id);
+            }
+        }
+
+        Prefilter {
+            first_tokens,
+            sensitive: Automaton::new(&literals, true),
+            insensitive: Automaton::new(&literals, false),
+            literals,
+            sensitive_ids,
+            insensitive_ids,
+            requirements,
+        }
+    }
+
+    /// The approximate memory used by the literals and the automata in bytes.
+    pub fn heap_bytes(&self) -> usize {
+        self.literals
+            .iter()
+            .map(|(x, _)| x.len() * 2 + 8)
+            .sum::<usize>()
+            + self.requirements.iter().map(|x| x.len() * 8).sum::<usize>()
+            + self.sensitive.automaton.heap_bytes()
+            + self.insensitive.automaton.heap_bytes()
+            + self.first_tokens.heap_bytes()
+    }
+
+    /// Checks for each engine whether it can possibly match the tokens of one sentence, by the literals it requires
+    /// some token to be. If this is `false`, the engine is guaranteed to not match.
+    /// Returns one entry per engine this prefilter was created with.
+    pub fn candidates(&self, tokens: &[Token]) -> Vec<bool> {
+        let mut present = vec![false; self.literals.len()];
+
+        for token in tokens {
+            let text = token.word.text.as_ref();
+            if text.is_empty() {
+                continue;
+            }
+
+            if let Some(id) = self.sensitive_ids.get(text) {
+                present[*id] = true;
+            }
+            if !self.insensitive_ids.is_empty() {
+                if let Some(id) = self
+                    .insensitive_ids
+                    .get(UniCase::new(text).to_folded_case().as_str())
+                {
+                    present[*id] = true;
+                }
+            }
+        }
+
+        self.requirements
+            .iter()
+            .map(|ids| ids.iter().all(|id| present[*id]))
+            .collect()
+    }
+
+    /// Checks for each engine whether it can possibly match the tokens of one sentence, by the word or lemma its first
+    /// required token must have. Like [candidates][Prefilter::candidates], `false` guarantees that the engine does not match.
+    pub fn token_candidates(&self, tokens: &[Token]) -> Vec<bool> {
+        self.first_tokens.candidates(tokens)
+    }
+
+    /// Finds the occurrences of the literals required by the engines which can possibly match the text and for which
+    /// `include` returns `true`. Returns their byte spans in the text, sorted by start. Engines without required literals
+    /// have no anchors.
+    pub fn anchors(&self, text: &str, include: impl Fn(usize) -> bool) -> Vec<(usize, usize)> {
+        let mut found: Vec<_> = self.sensitive.find(text).collect();
+        if !self.insensitive.is_empty() {
+            let (folded, offsets) = fold_with_offsets(text);
+
+            found.extend(self.insensitive.find(&folded).map(|(id, start, end)| {
+                // a match can end inside the folding of a char, it then spans the whole char
+                let last = offsets[end - 1];
+                let end = last + text[last..].chars().next().map_or(0, char::len_utf8);
+                (id, offsets[start], end)
+            }));
+        }
+
+        let mut present = vec![false; self.literals.len()];
+        for (id, _, _) in &found {
+            present[*id] = true;
+        }
+
+        let mut anchored = vec![false; self.literals.len()];
+        for (i, ids) in self.requirements.iter().enumerate() {
+            if include(i) && ids.iter().all(|id| present[*id]) {
+                for id in ids {
+                    anchored[*id] = true;
+                }
+            }
+        }
+
+        let mut spans: Vec<_> = found
+            .into_iter()
+            .filter(|(id, _, _)| anchored[*id])
+            .map(|(_, start, end)| (start, end))
+            .collect();
+        spans.sort_unstable();
+        spans.dedup();
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::tests::{DE, EN},
+        Rules,
+    };
+
+    fn prefilter(required: &[&[(&str, bool)]]) -> Prefilter {
+        Prefilter::from_literals(
+            required.iter().map(|literals| {
+                literals
+                    .iter()
+                    .map(|(literal, case_sensitive)| (literal.to_string(), *case_sensitive))
+                    .collect()
+            }),
+            FirstTokenIndex::default(),
+        )
+    }
+
+    #[test]
+    fn does_not_change_suggestions() {
+        for (lang_code, (tokenizer, filtered)) in &[("en", &*EN), ("de", &*DE)] {
+            let mut unfiltered =
+                Rules::new(format!("../storage/fixtures/{}_rules.bin", lang_code)).unwrap();
+            // without engines every rule is a candidate
+            unfiltered.prefilter = Prefilter::default();
+
+            for example in filtered.rules().iter().flat_map(|x| x.examples()) {
+                let suggest = |rules: &Rules| -> Vec<_> {
+                    rules
+                        .suggest(example.text(), tokenizer)
+                        .into_iter()
+                        .map(|x| (x.source, x.start, x.end, x.replacements))
+                        .collect()
+                };
+                assert_eq!(
+                    suggest(filtered),
+                    suggest(&unfiltered),
+                    "{}",
+                    example.text()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn folds_with_offsets() {
+        // "ẞ" has three bytes and folds to "ss", "İ" has two and folds to three
+        assert_eq!(
+            fold_with_offsets("Aẞİ"),
+            ("assi\u{307}".to_string(), vec![0, 1, 1, 4, 4, 4])
+        );
+    }
+
+    #[test]
+    fn finds_anchors() {
+        let prefilter = prefilter(&[
+            &[("Alot", true)],
+            &[("grosse", false)],
+            &[("gros", false), ("stadt", false)],
+            &[("fehlt", false), ("stadt", false)],
+        ]);
+        let text = "alot Alot, die GROẞE Stadt";
+
+        // the case sensitive literal only occurs once, "gros" ends inside the folding of "ẞ" and spans all of it
+        assert_eq!(
+            prefilter.anchors(text, |_| true),
+            vec![(5, 9), (15, 21), (15, 22), (23, 28)]
+        );
+        assert_eq!(&text[15..22], "GROẞE");
+        assert_eq!(prefilter.anchors(text, |i| i == 0), vec![(5, 9)]);
+        // "fehlt" does not occur, so the last engine has no anchors
+        assert!(prefilter.anchors(text, |i| i == 3).is_empty());
+    }
+}
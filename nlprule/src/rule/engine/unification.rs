@@ -0,0 +1,69 @@
+//! Agreement of tokens in morphological features such as gender, number or case (`<unify>` in LanguageTool).
+//!
+//! Matching a whole tag string with a regex is brittle for richly inflected languages where the tags encode many features at once.
+//! Instead, each feature is decomposed into its values, each described by a [PosMatcher], and tokens agree in a feature
+//! if they have a reading with the same value.
+
+use super::composition::{Leaf, MatchGraph, PosMatcher};
+use crate::types::*;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// A morphological feature e. g. "number" with the values "singular" and "plural".
+#[derive(Serialize, Deserialize)]
+pub struct Feature {
+    pub(crate) id: String,
+    pub(crate) values: Vec<PosMatcher>,
+}
+
+/// Requires the tokens in some groups of a match to agree in a set of features.
+#[derive(Serialize, Deserialize)]
+pub struct Unification {
+    pub(crate) features: Vec<Feature>,
+    // indices of the groups in the match graph whose tokens must agree
+    pub(crate) group_indices: Vec<usize>,
+    // whether the tokens must *not* agree
+    pub(crate) negate: bool,
+}
+
+impl Unification {
+    pub fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        for feature in &self.features {
+            for value in &feature.values {
+                f(Leaf::PosMatcher(value));
+            }
+        }
+    }
+
+    fn agree(&self, tokens: &[&Token]) -> bool {
+        if self.features.is_empty() {
+            return true;
+        }
+
+        // the values must be found in the same reading of a token, so all combinations of values are tried
+        self.features
+            .iter()
+            .map(|x| x.values.iter())
+            .multi_cartesian_product()
+            .any(|values| {
+                tokens.iter().all(|token| {
+                    token
+                        .word
+                        .tags
+                        .iter()
+                        .any(|data| values.iter().all(|value| value.is_match(&data.pos)))
+                })
+            })
+    }
+
+    /// Checks whether the tokens of the match agree (or disagree, if negated).
+    pub fn is_match(&self, graph: &MatchGraph) -> bool {
+        let tokens: Vec<&Token> = self
+            .group_indices
+            .iter()
+            .flat_map(|i| graph.by_index(*i).tokens(graph.tokens()))
+            .collect();
+
+        self.agree(&tokens) != self.negate
+    }
+}
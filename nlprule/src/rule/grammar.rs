@@ -1,10 +1,12 @@
-use super::engine::composition::{MatchGraph, PosMatcher};
+use super::engine::composition::{Leaf, MatchGraph, PosMatcher};
 use crate::types::*;
 use crate::{
     tokenizer::Tokenizer,
-    utils::{self, regex::SerializeRegex},
+    utils::{
+        self,
+        regex::{Captures, SerializeRegex},
+    },
 };
-use onig::Captures;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
@@ -24,12 +26,28 @@ pub enum Conversion {
     StartLower,
     AllUpper,
     StartUpper,
+    /// Takes the case of the matched text: all uppercase, uppercase first letter or unchanged.
+    Preserve,
 }
 
 impl Conversion {
-    fn convert(&self, input: &str) -> String {
+    fn convert(&self, input: &str, matched: &str) -> String {
         match &self {
             Conversion::Nop => input.to_string(),
+            Conversion::Preserve => {
+                let mut letters = matched.chars().filter(|c| c.is_alphabetic());
+
+                match letters.next() {
+                    Some(first) if first.is_uppercase() => {
+                        if matched.chars().count() > 1 && letters.all(char::is_uppercase) {
+                            input.to_uppercase()
+                        } else {
+                            utils::apply_to_first(input, |c| c.to_uppercase().collect())
+                        }
+                    }
+                    _ => input.to_string(),
+                }
+            }
             Conversion::AllLower => input.to_lowercase(),
             Conversion::StartLower => utils::apply_to_first(input, |c| c.to_lowercase().collect()),
             Conversion::AllUpper => input.to_uppercase(),
@@ -42,9 +60,62 @@ impl Conversion {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Example {
     pub(crate) text: String,
+    #[serde(with = "stored_suggestion")]
     pub(crate) suggestion: Option<Suggestion>,
 }
 
+// examples store suggestions without confidence, so binaries do not change with it
+mod stored_suggestion {
+    use crate::types::{FixSafety, Severity, Suggestion};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct StoredSuggestion {
+        source: String,
+        message: String,
+        start: usize,
+        end: usize,
+        replacements: Vec<String>,
+    }
+
+    pub fn serialize<S: Serializer>(
+        suggestion: &Option<Suggestion>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        suggestion
+            .as_ref()
+            .map(|x| StoredSuggestion {
+                source: x.source.clone(),
+                message: x.message.clone(),
+                start: x.start,
+                end: x.end,
+                replacements: x.replacements.clone(),
+            })
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Suggestion>, D::Error> {
+        let suggestion: Option<StoredSuggestion> = Deserialize::deserialize(deserializer)?;
+
+        Ok(suggestion.map(|x| Suggestion {
+            source: x.source,
+            message: x.message,
+            start: x.start,
+            end: x.end,
+            replacements: x.replacements,
+            safety: FixSafety::NeedsReview,
+            confidence: None,
+            merged_sources: Vec::new(),
+            short_message: String::new(),
+            matched: String::new(),
+            token_span: (0, 0),
+            severity: Severity::default(),
+        }))
+    }
+}
+
 impl Example {
     /// Gets the text of this example.
     pub fn text(&self) -> &str {
@@ -116,16 +187,25 @@ pub struct Match {
 }
 
 impl Match {
+    fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        if let Some(replacer) = &self.pos_replacer {
+            f(Leaf::PosMatcher(&replacer.matcher));
+        }
+        if let Some((regex, _)) = &self.regex_replacer {
+            f(Leaf::Regex(regex));
+        }
+    }
+
     fn apply(&self, graph: &MatchGraph, tokenizer: &Tokenizer) -> Option<String> {
-        let text = graph
+        let matched = graph
             .by_id(self.id)
             .unwrap_or_else(|| panic!("group must exist in graph: {}", self.id))
             .text(graph.tokens()[0].text);
 
         let mut text = if let Some(replacer) = &self.pos_replacer {
-            replacer.apply(text, tokenizer)?
+            replacer.apply(matched, tokenizer)?
         } else {
-            text.to_string()
+            matched.to_string()
         };
 
         text = if let Some((regex, replacement)) = &self.regex_replacer {
@@ -137,7 +217,7 @@ impl Match {
         };
 
         // TODO: maybe return a vector here and propagate accordingly
-        Some(self.conversion.convert(&text))
+        Some(self.conversion.convert(&text, matched))
     }
 
     fn has_conversion(&self) -> bool {
@@ -151,6 +231,27 @@ pub enum SynthesizerPart {
     Match(Match),
 }
 
+/// A part of a synthesized text with the matches resolved, so that callers can tell the text
+/// taken from the matched tokens apart from the fixed text.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ResolvedPart {
+    Text(String),
+    /// The text of the group with the ID after the transforms of the match were applied.
+    Match {
+        id: usize,
+        text: String,
+    },
+}
+
+impl ResolvedPart {
+    pub(crate) fn text(&self) -> &str {
+        match self {
+            ResolvedPart::Text(text) => text,
+            ResolvedPart::Match { text, .. } => text,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Synthesizer {
     pub(crate) use_titlecase_adjust: bool,
@@ -158,6 +259,41 @@ pub struct Synthesizer {
 }
 
 impl Synthesizer {
+    /// IDs of the groups referenced by this synthesizer.
+    pub fn match_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.parts.iter().filter_map(|x| match x {
+            SynthesizerPart::Match(m) => Some(m.id),
+            SynthesizerPart::Text(_) => None,
+        })
+    }
+
+    pub fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        for part in &self.parts {
+            if let SynthesizerPart::Match(m) = part {
+                m.visit(f);
+            }
+        }
+    }
+
+    /// Resolves the matches of this synthesizer. `None` if a match can not be resolved e. g. because
+    /// no word with the requested POS tag exists.
+    pub(crate) fn resolve(
+        &self,
+        graph: &MatchGraph,
+        tokenizer: &Tokenizer,
+    ) -> Option<Vec<ResolvedPart>> {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                SynthesizerPart::Text(t) => Some(ResolvedPart::Text(t.clone())),
+                SynthesizerPart::Match(m) => Some(ResolvedPart::Match {
+                    id: m.id,
+                    text: m.apply(graph, tokenizer)?,
+                }),
+            })
+            .collect()
+    }
+
     pub fn apply(
         &self,
         graph: &MatchGraph,
@@ -165,23 +301,18 @@ impl Synthesizer {
         start: usize,
         _end: usize,
     ) -> Option<String> {
-        let mut output = Vec::new();
-
         let starts_with_conversion = match &self.parts[..] {
             [SynthesizerPart::Match(m), ..] => m.has_conversion(),
             _ => false,
         };
 
-        for part in &self.parts {
-            match part {
-                SynthesizerPart::Text(t) => output.push(t.clone()),
-                SynthesizerPart::Match(m) => {
-                    output.push(m.apply(graph, tokenizer)?);
-                }
-            }
-        }
+        let output: String = self
+            .resolve(graph, tokenizer)?
+            .iter()
+            .map(ResolvedPart::text)
+            .collect();
 
-        let suggestion = utils::normalize_whitespace(&output.join(""));
+        let suggestion = utils::normalize_whitespace(&output);
 
         // if the suggestion does not start with a case conversion match, make it title case if:
         // * at sentence start
@@ -214,3 +345,16 @@ impl Synthesizer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preserves_case_of_match() {
+        assert_eq!(Conversion::Preserve.convert("a lot", "ALOT"), "A LOT");
+        assert_eq!(Conversion::Preserve.convert("a lot", "Alot"), "A lot");
+        assert_eq!(Conversion::Preserve.convert("a lot", "alot"), "a lot");
+        assert_eq!(Conversion::Preserve.convert("ice", "I"), "Ice");
+    }
+}
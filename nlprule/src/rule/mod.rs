@@ -14,7 +14,9 @@ pub(crate) mod disambiguation;
 pub(crate) mod engine;
 pub(crate) mod grammar;
 
-use engine::Engine;
+use engine::{composition::Leaf, Engine};
+
+use crate::rules::ValidationIssue;
 
 pub(crate) use engine::composition::MatchGraph;
 pub use grammar::Example;
@@ -46,6 +48,7 @@ pub struct DisambiguationRule {
     pub(crate) start: usize,
     pub(crate) end: usize,
     pub(crate) examples: Vec<disambiguation::DisambiguationExample>,
+    pub(crate) on: bool,
 }
 
 #[derive(Default)]
@@ -63,6 +66,39 @@ impl DisambiguationRule {
         self.id.as_str()
     }
 
+    /// Whether this rule is applied during disambiguation.
+    pub fn on(&self) -> bool {
+        self.on
+    }
+
+    /// Enables or disables this rule. Disabled rules are skipped during disambiguation.
+    pub fn set_on(&mut self, on: bool) {
+        self.on = on;
+    }
+
+    pub(crate) fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        self.engine.visit(f);
+        self.disambiguations.visit(f);
+        if let Some(filter) = &self.filter {
+            filter.visit(f);
+        }
+    }
+
+    pub(crate) fn warm_up(&self) {
+        self.visit(&mut |leaf| {
+            if let Leaf::Regex(regex) = leaf {
+                regex.warm_up();
+            }
+        });
+    }
+
+    // whether any regex of the rule has been compiled
+    pub(crate) fn is_warm(&self) -> bool {
+        let mut warm = false;
+        self.visit(&mut |leaf| warm |= matches!(leaf, Leaf::Regex(regex) if regex.is_compiled()));
+        warm
+    }
+
     pub(crate) fn apply<'t>(&'t self, tokens: &[Token<'t>], tokenizer: &Tokenizer) -> Changes {
         if matches!(self.disambiguations, disambiguation::Disambiguation::Nop) {
             return Changes::default();
@@ -245,8 +281,101 @@ pub struct Rule {
     pub(crate) category_id: String,
     pub(crate) category_name: String,
     pub(crate) category_type: Option<String>,
+    pub(crate) uses_chunks: bool,
+    pub(crate) safety: FixSafety,
+    pub(crate) group: Option<RuleGroup>,
+    pub(crate) tier: RuleTier,
+    pub(crate) source: Option<RuleSource>,
+    // translated messages by locale, sorted by locale
+    pub(crate) translations: Vec<(String, grammar::Synthesizer)>,
+}
+
+/// Where a [Rule] is defined in the LanguageTool rule files it was compiled from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleSource {
+    pub(crate) file: String,
+    pub(crate) line: usize,
 }
 
+impl RuleSource {
+    /// Gets the name of the XML file, e. g. `grammar.xml`. The directory is not stored since it depends on
+    /// the machine the rules were compiled on.
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Gets the line in the file at which the rule starts.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+/// The rule group a [Rule] is in. Rules in a group share an ID, in LanguageTool they are enabled and disabled together.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleGroup {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) index: usize,
+}
+
+impl RuleGroup {
+    /// Gets the ID of the group.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Gets a human-readable name of the group.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the position of the rule in the group, starting at zero.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// How expensive a rule is to check, computed from its pattern. See [Rule::complexity].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleComplexity {
+    /// The number of tokens in the pattern.
+    pub tokens: usize,
+    /// The number of regexes in the pattern, the antipatterns and the unifications.
+    pub regexes: usize,
+    /// The number of tokens which are followed by a skip.
+    pub skips: usize,
+    /// How many tokens the skips can cover together at most.
+    pub skipped_tokens: usize,
+    pub antipatterns: usize,
+    /// Whether the rule matches a regex on the text instead of a pattern on the tokens.
+    pub text: bool,
+}
+
+impl RuleComplexity {
+    /// A rough estimate of the work to check the rule: the tokens of the pattern, the tokens the skips can
+    /// cover, the antipatterns and twice the regexes, since regexes are the most expensive matchers.
+    pub fn cost(&self) -> usize {
+        self.tokens + self.skipped_tokens + self.antipatterns + 2 * self.regexes
+    }
+}
+
+/// Whether a rule is checked by [Rules::suggest_fast][crate::Rules::suggest_fast]. See [Rule::tier].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum RuleTier {
+    /// The rule is cheap to check.
+    #[default]
+    Fast,
+    /// The rule is only checked by [Rules::suggest_full][crate::Rules::suggest_full].
+    Slow,
+}
+
+// rules with a higher cost are slow, see `Rule::classify_tier`
+const MAX_FAST_COST: usize = 8;
+
+// category types and IDs of rules which only fix the form of a text, not its meaning
+const SAFE_CATEGORY_TYPES: &[&str] = &["duplication", "typographical", "whitespace"];
+const SAFE_CATEGORY_IDS: &[&str] = &["CASING", "PUNCTUATION", "TYPOGRAPHY", "WHITESPACE"];
+
 impl Rule {
     /// Get a unique identifier of this rule.
     pub fn id(&self) -> &str {
@@ -298,11 +427,194 @@ impl Rule {
         self.category_type.as_deref()
     }
 
+    /// Whether the suggestions of this rule can be applied without review. Rules are classified when they are
+    /// compiled: they are safe if they have a single suggestion and are in a category which only fixes
+    /// typography, punctuation, casing, whitespace or duplicated words. Suggestions of safe rules are only safe
+    /// if they have a single replacement.
+    pub fn safety(&self) -> FixSafety {
+        self.safety
+    }
+
+    pub(crate) fn classify_safety(&self) -> FixSafety {
+        let safe_category = SAFE_CATEGORY_IDS.contains(&self.category_id.as_str())
+            || matches!(self.category_type.as_deref(), Some(x) if SAFE_CATEGORY_TYPES.contains(&x));
+
+        if self.suggesters.len() == 1 && safe_category {
+            FixSafety::Safe
+        } else {
+            FixSafety::NeedsReview
+        }
+    }
+
+    /// How expensive this rule is to check, classified from its [complexity][Rule::complexity] when it is compiled.
+    /// Rules which match a regex on the text, skip tokens or have a [cost][RuleComplexity::cost] above 8 are slow.
+    pub fn tier(&self) -> RuleTier {
+        self.tier
+    }
+
+    pub(crate) fn classify_tier(&self) -> RuleTier {
+        let complexity = self.complexity();
+
+        if complexity.text || complexity.skips > 0 || complexity.cost() > MAX_FAST_COST {
+            RuleTier::Slow
+        } else {
+            RuleTier::Fast
+        }
+    }
+
+    /// Gets the rule group this rule is in if there is one. Rules in a group without an ID of their own
+    /// have the ID of the group followed by their index e. g. `CAN_DUE.0`.
+    pub fn group(&self) -> Option<&RuleGroup> {
+        self.group.as_ref()
+    }
+
+    /// Gets the locales this rule has a translated message for, e. g. `de` or `pt-BR`.
+    pub fn message_locales(&self) -> impl Iterator<Item = &str> {
+        self.translations.iter().map(|(locale, _)| locale.as_str())
+    }
+
+    // the message in the locale, falling back to the language of the locale and then to the message in the
+    // language of the rules
+    fn message_for(&self, locale: Option<&str>) -> &grammar::Synthesizer {
+        let locale = match locale {
+            Some(locale) => locale,
+            None => return &self.message,
+        };
+        let find = |locale: &str| {
+            self.translations
+                .iter()
+                .find(|(x, _)| x.eq_ignore_ascii_case(locale))
+                .map(|(_, message)| message)
+        };
+
+        find(locale)
+            .or_else(|| locale.split(['-', '_']).next().and_then(find))
+            .unwrap_or(&self.message)
+    }
+
+    /// Gets the location of this rule in the rule files. `None` for rules from binaries before the location was
+    /// stored.
+    pub fn source(&self) -> Option<&RuleSource> {
+        self.source.as_ref()
+    }
+
+    /// How expensive this rule is to check.
+    pub fn complexity(&self) -> RuleComplexity {
+        self.engine.complexity()
+    }
+
+    /// Whether this rule matches on chunk tags, so it only works with a tokenizer which has a chunker.
+    pub fn uses_chunks(&self) -> bool {
+        self.uses_chunks
+    }
+
+    pub(crate) fn depends_on_chunks(&self) -> bool {
+        let mut uses_chunks = false;
+        self.visit(&mut |leaf| uses_chunks |= matches!(leaf, Leaf::Chunk));
+        uses_chunks
+    }
+
+    pub(crate) fn visit<'a>(&'a self, f: &mut dyn FnMut(Leaf<'a>)) {
+        self.engine.visit(f);
+        self.message.visit(f);
+        for (_, message) in &self.translations {
+            message.visit(f);
+        }
+        for suggester in &self.suggesters {
+            suggester.visit(f);
+        }
+    }
+
+    pub(crate) fn warm_up(&self) {
+        self.visit(&mut |leaf| {
+            if let Leaf::Regex(regex) = leaf {
+                regex.warm_up();
+            }
+        });
+    }
+
+    // whether any regex of the rule has been compiled
+    pub(crate) fn is_warm(&self) -> bool {
+        let mut warm = false;
+        self.visit(&mut |leaf| warm |= matches!(leaf, Leaf::Regex(regex) if regex.is_compiled()));
+        warm
+    }
+
+    /// Checks that this rule can be used with the tokenizer.
+    pub(crate) fn validate(&self, tokenizer: &Tokenizer) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let n_tags = tokenizer.tagger().tag_store().len();
+
+        self.visit(&mut |leaf| match leaf {
+            Leaf::Regex(regex) => {
+                if let Err(error) = regex.check() {
+                    issues.push(ValidationIssue::InvalidRegex {
+                        rule_id: self.id.clone(),
+                        regex: regex.as_str().to_string(),
+                        error,
+                    });
+                }
+            }
+            Leaf::PosMatcher(matcher) => {
+                if matcher.len() != n_tags {
+                    issues.push(ValidationIssue::TagSetMismatch {
+                        rule_id: self.id.clone(),
+                        expected: n_tags,
+                        found: matcher.len(),
+                    });
+                }
+            }
+            Leaf::Chunk => {}
+        });
+
+        let ids = std::iter::once(self.start)
+            .chain(std::iter::once(self.end - 1))
+            .chain(self.message.match_ids())
+            .chain(self.translations.iter().flat_map(|(_, x)| x.match_ids()))
+            .chain(self.suggesters.iter().flat_map(|x| x.match_ids()));
+
+        for id in ids {
+            if !self.engine.has_group(id) {
+                issues.push(ValidationIssue::InvalidMatch {
+                    rule_id: self.id.clone(),
+                    id,
+                });
+            }
+        }
+
+        issues.dedup();
+        issues
+    }
+
     pub(crate) fn apply(&self, tokens: &[Token], tokenizer: &Tokenizer) -> Vec<Suggestion> {
+        self.apply_limited(tokens, tokenizer, None, None)
+            .expect("checking without a limit always finishes")
+    }
+
+    // `None` if matching the pattern took more than `max_steps` steps, see `Engine::get_matches_limited`.
+    // The messages are in the locale if the rule has a translation, see `CheckOptions::message_locale`.
+    pub(crate) fn apply_limited(
+        &self,
+        tokens: &[Token],
+        tokenizer: &Tokenizer,
+        max_steps: Option<usize>,
+        locale: Option<&str>,
+    ) -> Option<Vec<Suggestion>> {
+        let message = self.message_for(locale);
         let refs: Vec<&Token> = tokens.iter().collect();
         let mut suggestions = Vec::new();
 
-        for graph in self.engine.get_matches(&refs, self.start, self.end) {
+        for graph in self
+            .engine
+            .get_matches_limited(&refs, self.start, self.end, max_steps)?
+        {
+            if (self.start..self.end)
+                .filter_map(|id| graph.by_id(id))
+                .any(|group| group.tokens(graph.tokens()).iter().any(|x| x.immune))
+            {
+                continue;
+            }
+
             let start_group = graph
                 .by_id(self.start)
                 .unwrap_or_else(|| panic!("{} group must exist in graph: {}", self.id, self.start));
@@ -349,19 +661,29 @@ impl Rule {
 
             if !replacements.is_empty() {
                 suggestions.push(Suggestion {
-                    message: self
-                        .message
+                    message: message
                         .apply(&graph, tokenizer, self.start, self.end)
                         .expect("Rules must have a message."),
                     source: self.id.to_string(),
                     start,
                     end,
+                    safety: if self.safety == FixSafety::Safe && replacements.len() == 1 {
+                        FixSafety::Safe
+                    } else {
+                        FixSafety::NeedsReview
+                    },
                     replacements,
+                    confidence: None,
+                    merged_sources: Vec::new(),
+                    short_message: self.short.clone().unwrap_or_default(),
+                    matched: String::new(),
+                    token_span: (0, 0),
+                    severity: Severity::default(),
                 });
             }
         }
 
-        suggestions
+        Some(suggestions)
     }
 
     /// Grammar rules always have at least one example associated with them.
@@ -2,14 +2,59 @@
 
 use crate::tokenizer::Tokenizer;
 use crate::types::*;
-use crate::utils::parallelism::MaybeParallelRefIterator;
-use crate::{rule::Rule, tokenizer::finalize};
+use crate::utils::{self, parallelism::MaybeParallelRefIterator};
+use crate::{
+    component::{Component, Metadata},
+    rule::{
+        engine::{composition::Leaf, memo, prefilter::Prefilter},
+        Rule, RuleTier,
+    },
+    tokenizer::{finalize, guard_ranges},
+    Error,
+};
+use log::debug;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::File,
-    io::{BufReader, Read},
-    path::Path,
+    collections::BTreeMap, fmt, fs::File, io::Read, num::NonZeroUsize, path::Path, sync::Mutex,
+};
+
+pub mod patch;
+pub mod persistent_cache;
+pub mod sensitive;
+pub mod stats;
+pub mod text_index;
+pub mod word_list;
+
+mod cache;
+mod correct;
+mod ignore;
+mod merge;
+mod reader;
+mod segments;
+mod sentences;
+mod severity;
+mod span_map;
+mod spelling;
+mod subset;
+
+pub use cache::CacheStats;
+pub(crate) use cache::{
+    fill_suggestion_cache, suggestion_cache_entries, CacheEntries, SuggestionCache,
 };
+pub use ignore::TokenPredicate;
+pub use merge::DuplicateRules;
+pub use reader::{STREAM_CHUNK_BYTES, STREAM_MAX_PENDING_BYTES};
+pub(crate) use segments::{RulesFields, Segment};
+pub use sentences::group_by_sentence;
+pub use severity::parse_severities;
+pub use span_map::SpanMap;
+pub use spelling::{merge_spelling, SpellingMerge};
+
+use persistent_cache::PersistentCache;
+use sensitive::{SensitiveDetector, SensitiveOptions};
+use sentences::limit_per_sentence;
+use stats::Statistics;
+use word_list::WordList;
 
 /// Options for a rule set.
 #[derive(Serialize, Deserialize, Clone)]
@@ -34,22 +79,347 @@ impl Default for RulesOptions {
     }
 }
 
+/// Options for checking texts with a rule set, see [Rules::set_check_options].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CheckOptions {
+    /// The maximum number of replacements of a suggestion, the first ones are kept. No limit if `None`.
+    /// Suggestions always have at least one replacement, so the limit can not be zero.
+    pub max_replacements: Option<NonZeroUsize>,
+    /// The maximum number of suggestions in a sentence. The suggestions of the rules with the highest
+    /// priority are kept. No limit if `None`. A limit of zero would drop all suggestions, so it can not be zero.
+    pub max_sentence_suggestions: Option<NonZeroUsize>,
+    /// Whether suggestions of different rules with the same span and replacements are merged into the one of the
+    /// rule with the highest priority, which records the other rules in
+    /// [merged_sources][Suggestion::merged_sources]. Otherwise only the suggestion with the highest priority is kept.
+    pub merge_duplicates: bool,
+    /// The maximum number of states the pattern matcher visits for a rule in one checked text, summed over the
+    /// pattern and antipatterns at all positions. A rule which needs more steps is skipped in the text, which is
+    /// recorded in [skipped_rules][Rules::skipped_rules]. Bounds the latency for rules with many optional or
+    /// skipped tokens. No limit if `None`.
+    pub max_match_steps: Option<usize>,
+    /// Whether the results of regexes on the text of a token are shared between the rules checking a sentence.
+    /// The suggestions are the same either way; compare the `suggest` and `suggest_memoized` benchmarks to see
+    /// whether it pays off for a rule set.
+    pub memoize_matches: bool,
+    /// The locale of the messages, e. g. `de` or `pt-BR`. Rules compiled with translated messages use the one of
+    /// the locale or else of its language, the others keep the message in the language of the rules. Messages in
+    /// the language of the rules if `None`.
+    pub message_locale: Option<String>,
+    /// Tokens for which the predicate is true are treated like tokens marked as immune by the disambiguation
+    /// rules: grammar rules never match them. Exempts e. g. product names or tracked changes without editing the
+    /// rules. Not serialized.
+    #[serde(skip)]
+    pub ignore_token: Option<TokenPredicate>,
+    /// The [severity][Suggestion::severity] of suggestions by the ID of their rule, rule group or category, or by
+    /// their source for word lists and sensitive text. The most specific ID wins, suggestions without one are
+    /// [warnings][Severity::Warning]. See [parse_severities] for a text format.
+    #[serde(default)]
+    pub severities: BTreeMap<String, Severity>,
+}
+
+/// A problem found by [Rules::validate] which would lead to wrong results or panics when checking a text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    /// A part-of-speech matcher was built for a tag set of a different size than the one of the tagger.
+    /// Usually means that the rules were compiled for a different tokenizer.
+    TagSetMismatch {
+        rule_id: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A regex can not be compiled.
+    InvalidRegex {
+        rule_id: String,
+        regex: String,
+        error: String,
+    },
+    /// A group is referenced (e. g. by a suggestion) which does not exist in the pattern.
+    InvalidMatch { rule_id: String, id: usize },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::TagSetMismatch {
+                rule_id,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: expected {} part-of-speech tags, found {}",
+                rule_id, expected, found
+            ),
+            ValidationIssue::InvalidRegex {
+                rule_id,
+                regex,
+                error,
+            } => write!(f, "{}: invalid regex {:?}: {}", rule_id, regex, error),
+            ValidationIssue::InvalidMatch { rule_id, id } => {
+                write!(f, "{}: reference to nonexistent group {}", rule_id, id)
+            }
+        }
+    }
+}
+
 /// A set of grammatical error correction rules.
-#[derive(Serialize, Deserialize, Default)]
+///
+/// Rules are serialized in one segment per category. By default all segments are loaded,
+/// [new_with_categories][Rules::new_with_categories] only loads some categories and allows loading the others
+/// later with [load_category][Rules::load_category].
+#[derive(Default)]
 pub struct Rules {
+    pub(crate) metadata: Metadata,
     pub(crate) rules: Vec<Rule>,
+    pub(crate) prefilter: Prefilter,
+    // positions of the loaded rules in the complete rule set
+    indices: Vec<usize>,
+    unloaded: Vec<Segment>,
+    // fingerprint of the tagger these rules were compiled with
+    fingerprint: u64,
+    // checksum of the binary these rules were loaded from
+    checksum: u64,
+    cache: Option<SuggestionCache>,
+    // not serialized, word lists are loaded at runtime
+    word_lists: Vec<WordList>,
+    sensitive: Option<SensitiveDetector>,
+    statistics: Option<Statistics>,
+    check_options: CheckOptions,
+    // how often each rule was skipped because it exceeded `max_match_steps`
+    skipped: Mutex<BTreeMap<String, usize>>,
+    // the cache together with the hash of the configuration, updated whenever the configuration changes
+    persistent_cache: Option<(Box<dyn PersistentCache>, u64)>,
 }
 
 impl Rules {
-    /// Creates a new rules set from a file.
-    pub fn new<P: AsRef<Path>>(p: P) -> bincode::Result<Self> {
-        let reader = BufReader::new(File::open(p).unwrap());
-        bincode::deserialize_from(reader)
+    #[cfg(feature = "compile")]
+    pub(crate) fn from_rules(rules: Vec<Rule>, fingerprint: u64) -> Self {
+        Rules {
+            metadata: Metadata {
+                rule_count: rules.len(),
+                ..Metadata::default()
+            },
+            fingerprint,
+            checksum: 0,
+            prefilter: Prefilter::new(rules.iter().map(|x| &x.engine)),
+            indices: (0..rules.len()).collect(),
+            rules,
+            unloaded: Vec::new(),
+            cache: None,
+            word_lists: Vec::new(),
+            sensitive: None,
+            statistics: None,
+            check_options: CheckOptions::default(),
+            skipped: Mutex::default(),
+            persistent_cache: None,
+        }
+    }
+
+    /// Creates a new rules set from a file. The file can optionally be gzip compressed.
+    pub fn new<P: AsRef<Path>>(p: P) -> Result<Self, Error> {
+        Self::new_from(File::open(p)?)
+    }
+
+    /// Creates a new rules set from a reader. The data can optionally be gzip compressed.
+    /// Binaries of older format versions are migrated if possible, see [Component::migrate].
+    pub fn new_from<R: Read>(reader: R) -> Result<Self, Error> {
+        Self::read(reader)
+    }
+
+    /// Creates a new rules set from a reader. Same as [new_from][Self::new_from].
+    /// Useful to load binaries embedded with [embed_nlprule][crate::embed_nlprule].
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Error> {
+        Self::new_from(reader)
+    }
+
+    /// Compiles all regexes used by the rules. Regexes are otherwise compiled lazily on first use,
+    /// so calling this is only needed if the cost should be paid upfront.
+    pub fn warm_up(&self) {
+        self.rules.maybe_par_iter().for_each(|x| x.warm_up());
+    }
+
+    /// Checks that the rules can be used with the tokenizer, returning all issues found.
+    /// Compiles all regexes, so this has the same cost as [warm_up][Rules::warm_up].
+    pub fn validate(&self, tokenizer: &Tokenizer) -> Vec<ValidationIssue> {
+        self.rules
+            .maybe_par_iter()
+            .flat_map(|x| x.validate(tokenizer))
+            .collect()
+    }
+
+    /// The IDs of the rules which were skipped because they exceeded
+    /// [max_match_steps][CheckOptions::max_match_steps], with how often they were skipped. Suggestions which were
+    /// cached before are not counted again.
+    pub fn skipped_rules(&self) -> Vec<(String, usize)> {
+        self.skipped
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, count)| (id.clone(), *count))
+            .collect()
+    }
+
+    /// The approximate memory used by the loaded rules, the categories which are not loaded yet, the prefilter,
+    /// the word lists and the suggestion cache. See [MemoryStats].
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        for rule in &self.rules {
+            rule.visit(&mut |leaf| {
+                if let Leaf::Regex(regex) = leaf {
+                    stats.count_regex(regex);
+                }
+            });
+        }
+
+        stats.parts = vec![
+            ("rules", utils::serialized_size(&self.rules)),
+            (
+                "unloaded_categories",
+                self.unloaded.iter().map(|x| x.rules.len()).sum(),
+            ),
+            ("prefilter", self.prefilter.heap_bytes()),
+            ("word_lists", utils::serialized_size(&self.word_lists)),
+            (
+                "cache",
+                self.cache_entries()
+                    .map_or(0, |(_, entries)| utils::serialized_size(&entries)),
+            ),
+        ];
+        stats
+    }
+
+    /// Information about the source of these rules.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// The fingerprint of the tagger these rules were compiled with. See [Tagger::fingerprint][crate::tokenizer::tag::Tagger::fingerprint].
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// A checksum of the binary the rules were loaded from. A [RulesPatch][patch::RulesPatch] can only be applied to rules
+    /// with the checksum it was created for.
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
+    /// Checks that the rules were compiled for the tokenizer i. e. that both have the same fingerprint.
+    /// Using rules with a tokenizer they were not compiled for gives wrong results or panics.
+    /// Rules from nlprule 0.3.0 binaries do not know their tagger, they have fingerprint 0 and are not checked.
+    pub fn check_compatibility(&self, tokenizer: &Tokenizer) -> Result<(), Error> {
+        if self.fingerprint == 0 || self.fingerprint == tokenizer.fingerprint() {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleComponents {
+                tokenizer: tokenizer.fingerprint(),
+                rules: self.fingerprint,
+            })
+        }
+    }
+
+    /// Turns off the rules which match on chunk tags if the tokenizer has no chunker.
+    /// Without a chunker no token has chunk tags, so these rules would give wrong results.
+    /// Returns the IDs of the rules which were turned off.
+    pub fn disable_chunk_rules(&mut self, tokenizer: &Tokenizer) -> Vec<String> {
+        if tokenizer.chunker().is_some() {
+            return Vec::new();
+        }
+
+        let disabled: Vec<_> = self
+            .rules
+            .iter_mut()
+            .filter(|x| x.on() && x.uses_chunks())
+            .map(|x| {
+                x.set_on(false);
+                x.id().to_string()
+            })
+            .collect();
+
+        if !disabled.is_empty() {
+            self.clear_cache();
+        }
+        disabled
+    }
+
+    /// Turns off the rules whose [cost][crate::rule::RuleComplexity::cost] is larger than `max_cost`, e. g. for
+    /// deployments where latency matters more than catching every error. Returns the IDs of the rules which
+    /// were turned off.
+    pub fn disable_complex_rules(&mut self, max_cost: usize) -> Vec<String> {
+        let disabled: Vec<_> = self
+            .rules
+            .iter_mut()
+            .filter(|x| x.on() && x.complexity().cost() > max_cost)
+            .map(|x| {
+                x.set_on(false);
+                x.id().to_string()
+            })
+            .collect();
+
+        if !disabled.is_empty() {
+            self.clear_cache();
+        }
+        disabled
+    }
+
+    /// Stores the suggestions of [suggest][Rules::suggest] in a cache which persists between runs, see
+    /// [persistent_cache]. It is checked after the in-memory cache set with [set_cache_size][Rules::set_cache_size].
+    pub fn set_persistent_cache(&mut self, cache: Option<Box<dyn PersistentCache>>) {
+        self.persistent_cache = cache.map(|cache| (cache, persistent_cache::config_hash(self)));
     }
 
-    /// Creates a new rules set from a reader.
-    pub fn new_from<R: Read>(reader: R) -> bincode::Result<Self> {
-        bincode::deserialize_from(reader)
+    /// Adds a word list which is checked after the rules, see [word_list]. Replaces the list with the same ID if there is one.
+    /// Word lists are not part of the binary when the rules are written.
+    pub fn add_word_list(&mut self, list: WordList) {
+        self.remove_word_list(&list.id);
+        self.word_lists.push(list);
+        self.clear_cache();
+    }
+
+    /// Removes the word list with the given ID and returns it.
+    pub fn remove_word_list(&mut self, id: &str) -> Option<WordList> {
+        let index = self.word_lists.iter().position(|x| x.id == id)?;
+        self.clear_cache();
+        Some(self.word_lists.remove(index))
+    }
+
+    /// The word lists added with [add_word_list][Rules::add_word_list].
+    pub fn word_lists(&self) -> &[WordList] {
+        &self.word_lists
+    }
+
+    /// Turns detection of profanity and personal information on or off, see [sensitive]. Off by default.
+    /// Like word lists, the options are not part of the binary when the rules are written.
+    pub fn set_sensitive(&mut self, options: Option<SensitiveOptions>) {
+        self.sensitive = options.map(SensitiveDetector::new);
+        self.clear_cache();
+    }
+
+    /// The options for detecting sensitive text, `None` if it is turned off.
+    pub fn sensitive(&self) -> Option<&SensitiveOptions> {
+        self.sensitive.as_ref().map(|x| x.options())
+    }
+
+    /// Sets the precision statistics the [confidence][Suggestion::confidence] of suggestions is computed from,
+    /// see [stats]. Suggestions from sources without statistics have no confidence.
+    pub fn set_statistics(&mut self, statistics: Option<Statistics>) {
+        self.statistics = statistics;
+        self.clear_cache();
+    }
+
+    /// The precision statistics of the rules if they have been set.
+    pub fn statistics(&self) -> Option<&Statistics> {
+        self.statistics.as_ref()
+    }
+
+    /// Sets the options for checking texts, e. g. limits on the number of suggestions.
+    pub fn set_check_options(&mut self, options: CheckOptions) {
+        self.check_options = options;
+        self.clear_cache();
+    }
+
+    /// The options for checking texts.
+    pub fn check_options(&self) -> &CheckOptions {
+        &self.check_options
     }
 
     /// All rules ordered by priority.
@@ -62,52 +432,370 @@ impl Rules {
         self.rules.iter().find(|x| x.id() == id)
     }
 
+    /// The rules in the rule group with the given ID, ordered by their index in the group.
+    pub fn rules_in_group<'a>(&'a self, group_id: &'a str) -> impl Iterator<Item = &'a Rule> {
+        self.rules
+            .iter()
+            .filter(move |x| matches!(x.group(), Some(group) if group.id() == group_id))
+    }
+
+    /// Disables the rules with the given IDs. Like in LanguageTool, an ID can also be the ID of a rule group
+    /// to disable all rules in the group. Returns how many rules were disabled.
+    pub fn disable_rules<S: AsRef<str>>(&mut self, ids: &[S]) -> usize {
+        self.set_rules_on(ids, false)
+    }
+
+    /// Enables the rules with the given IDs or in the rule groups with the given IDs.
+    /// Returns how many rules were enabled.
+    pub fn enable_rules<S: AsRef<str>>(&mut self, ids: &[S]) -> usize {
+        self.set_rules_on(ids, true)
+    }
+
+    fn set_rules_on<S: AsRef<str>>(&mut self, ids: &[S], on: bool) -> usize {
+        let mut count = 0;
+
+        for rule in self.rules.iter_mut().filter(|x| {
+            ids.iter().any(|id| {
+                id.as_ref() == x.id()
+                    || matches!(x.group(), Some(group) if group.id() == id.as_ref())
+            })
+        }) {
+            rule.set_on(on);
+            count += 1;
+        }
+
+        if count > 0 {
+            self.clear_cache();
+        }
+        count
+    }
+
+    /// The char spans of the text where the anchors of enabled rules occur, merged where they overlap or touch.
+    /// An anchor is a literal word a rule requires, found with the same cheap pass over the text which skips rules
+    /// before checking them, so no rule is evaluated. A rule only has anchors in a text if all its required words occur.
+    ///
+    /// Rules can only match around their anchors, so a UI can compare the spans before and after an edit to
+    /// decide whether the text has to be checked again. Rules which do not require any literal word, word lists and the
+    /// sensitive data detector have no anchors and are not covered.
+    pub fn spans_of_interest(&self, text: &str) -> Vec<(usize, usize)> {
+        let anchors = self.prefilter.anchors(text, |i| self.rules[i].on());
+
+        // anchors start and end on char boundaries
+        let boundaries: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(Some(text.len()))
+            .collect();
+        let char_index = |byte: usize| boundaries.binary_search(&byte).unwrap_or_else(|x| x);
+
+        let mut spans: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in anchors {
+            let (start, end) = (char_index(start), char_index(end));
+
+            match spans.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => spans.push((start, end)),
+            }
+        }
+
+        spans
+    }
+
     /// Compute the suggestions for the given tokens by checking all rules.
+    /// Parts of sentences split because of [max_sentence_tokens][crate::tokenizer::TokenizerOptions::max_sentence_tokens]
+    /// are checked independently and skipped sentences are not checked.
     pub fn apply(&self, tokens: &[Token], tokenizer: &Tokenizer) -> Vec<Suggestion> {
+        self.apply_tier(tokens, tokenizer, None)
+    }
+
+    // checks only the rules of the tier if there is one
+    fn apply_tier(
+        &self,
+        tokens: &[Token],
+        tokenizer: &Tokenizer,
+        tier: Option<RuleTier>,
+    ) -> Vec<Suggestion> {
         if tokens.is_empty() {
             return Vec::new();
         }
 
+        let ignored = self.ignore_tokens(tokens);
+        let tokens = ignored.as_deref().unwrap_or(tokens);
+
+        let mut suggestions = if tokens.iter().all(|x| x.guard.is_none()) {
+            self.apply_range(tokens, tokenizer, tier)
+        } else {
+            // the first token is the sentence start token, each part gets its own
+            guard_ranges(tokens[1..].iter().map(|x| x.guard))
+                .into_iter()
+                .flat_map(|range| {
+                    let mut part = vec![Token::sent_start(tokens[0].text, tokens[0].tagger)];
+                    part.extend(tokens[range.start + 1..range.end + 1].iter().cloned());
+
+                    self.apply_range(&part, tokenizer, tier)
+                })
+                .collect()
+        };
+
+        if let Some(max) = self.check_options.max_sentence_suggestions {
+            suggestions = limit_per_sentence(tokens, suggestions, max.get());
+        }
+        let mut suggestions: Vec<_> = suggestions
+            .into_iter()
+            .map(|(i, mut x)| {
+                x.severity = self.severity(i, &x.source);
+                x
+            })
+            .collect();
+
+        if let Some(max) = self.check_options.max_replacements {
+            for suggestion in &mut suggestions {
+                suggestion.replacements.truncate(max.get());
+            }
+        }
+        if tokenizer.hard_wrapped() {
+            for suggestion in &mut suggestions {
+                suggestion.reflow(tokens[0].text);
+            }
+        }
+        if let Some(statistics) = &self.statistics {
+            for suggestion in &mut suggestions {
+                suggestion.confidence = statistics.confidence(&suggestion.source);
+            }
+        }
+        annotate(tokens, &mut suggestions);
+        suggestions
+    }
+
+    // the suggestions together with the priority of their source, higher is more important
+    fn apply_range(
+        &self,
+        tokens: &[Token],
+        tokenizer: &Tokenizer,
+        tier: Option<RuleTier>,
+    ) -> Vec<(usize, Suggestion)> {
+        let candidates = self.prefilter.candidates(tokens);
+        let token_candidates = self.prefilter.token_candidates(tokens);
+        let scope = memo::new_scope();
+
         let mut output: Vec<(usize, Suggestion)> = self
             .rules
             .maybe_par_iter()
             .enumerate()
-            .filter(|(_, x)| x.on())
+            .filter(|(i, x)| {
+                x.on()
+                    && (tier.is_none() || tier == Some(x.tier()))
+                    && candidates.get(*i).copied().unwrap_or(true)
+                    && token_candidates.get(*i).copied().unwrap_or(true)
+            })
             .map(|(i, rule)| {
-                let mut output = Vec::new();
+                let max_steps = self.check_options.max_match_steps;
+                let locale = self.check_options.message_locale.as_deref();
+                let apply = || rule.apply_limited(tokens, tokenizer, max_steps, locale);
+                let suggestions = if self.check_options.memoize_matches {
+                    memo::with_scope(scope, apply)
+                } else {
+                    apply()
+                };
 
-                for suggestion in rule.apply(tokens, tokenizer) {
-                    output.push((i, suggestion));
+                match suggestions {
+                    Some(suggestions) => suggestions.into_iter().map(|x| (i, x)).collect(),
+                    None => {
+                        debug!(
+                            "skipped {}: more than {:?} match steps",
+                            rule.id(),
+                            max_steps
+                        );
+                        *self
+                            .skipped
+                            .lock()
+                            .unwrap()
+                            .entry(rule.id().to_string())
+                            .or_default() += 1;
+                        Vec::new()
+                    }
                 }
-
-                output
             })
             .flatten()
             .collect();
 
+        for (i, list) in self.word_lists.iter().enumerate() {
+            output.extend(
+                list.apply(tokens)
+                    .into_iter()
+                    .map(|suggestion| (self.rules.len() + i, suggestion)),
+            );
+        }
+        if let Some(detector) = &self.sensitive {
+            let index = self.rules.len() + self.word_lists.len();
+            output.extend(
+                detector
+                    .apply(tokens)
+                    .into_iter()
+                    .map(|suggestion| (index, suggestion)),
+            );
+        }
+
         output.sort_by(|(ia, a), (ib, b)| a.start.cmp(&b.start).then_with(|| ib.cmp(ia)));
 
         let mut mask = vec![false; tokens[0].text.chars().count()];
+        let mut kept: Vec<(usize, Suggestion)> = Vec::new();
+
+        for (i, suggestion) in output {
+            if mask[suggestion.start..suggestion.end].iter().all(|x| !x) {
+                mask[suggestion.start..suggestion.end]
+                    .iter_mut()
+                    .for_each(|x| *x = true);
+                kept.push((i, suggestion));
+            } else if self.check_options.merge_duplicates {
+                // suggestions with the same start are next to each other, the kept one has the higher priority
+                let duplicate = kept
+                    .iter_mut()
+                    .rev()
+                    .take_while(|(_, x)| x.start == suggestion.start)
+                    .find(|(_, x)| {
+                        x.end == suggestion.end && x.replacements == suggestion.replacements
+                    });
+
+                if let Some((_, duplicate)) = duplicate {
+                    if duplicate.source != suggestion.source
+                        && !duplicate.merged_sources.contains(&suggestion.source)
+                    {
+                        duplicate.merged_sources.push(suggestion.source);
+                    }
+                }
+            }
+        }
+
+        kept
+    }
+
+    /// Compute the suggestions for a text by checking all rules.
+    /// Does not panic on any input, which is tested by the fuzz targets in `fuzz/`.
+    /// Uses the caches if they are set with [set_cache_size][Rules::set_cache_size] and
+    /// [set_persistent_cache][Rules::set_persistent_cache].
+    pub fn suggest(&self, text: &str, tokenizer: &Tokenizer) -> Vec<Suggestion> {
+        self.suggest_with_cache(text, tokenizer, self.cache.as_ref())
+    }
+
+    /// Computes the suggestions of the [fast][RuleTier::Fast] rules, the word lists and the sensitive data detector,
+    /// so interactive tools can show first results quickly and the rest from [suggest_full][Rules::suggest_full]
+    /// later. Suggestions of slow rules can take precedence over these, so the full suggestions replace them.
+    /// The caches are not used.
+    pub fn suggest_fast(&self, text: &str, tokenizer: &Tokenizer) -> Vec<Suggestion> {
+        let tokens = tokenizer.disambiguate(tokenizer.tokenize(text));
+        self.apply_tier(&finalize(tokens), tokenizer, Some(RuleTier::Fast))
+    }
+
+    /// Computes the suggestions of all rules like [suggest][Rules::suggest], the second phase after
+    /// [suggest_fast][Rules::suggest_fast].
+    pub fn suggest_full(&self, text: &str, tokenizer: &Tokenizer) -> Vec<Suggestion> {
+        self.suggest(text, tokenizer)
+    }
+
+    /// Checks many texts in one call, meant for bindings which want to cross the FFI boundary and release their
+    /// interpreter lock once per batch instead of once per text. The texts are stored one after another in `buffer`,
+    /// text `i` is `buffer[offsets[i]..offsets[i + 1]]` so there is one more byte offset than texts.
+    ///
+    /// Returns the suggestions of all texts in one flat list of pairs of the text index and a suggestion, ordered by
+    /// text. The char indices of a suggestion are relative to its text. The texts are checked in parallel
+    /// like [suggest][Rules::suggest] would check them one by one.
+    ///
+    /// # Errors
+    /// Fails if the offsets decrease, are out of bounds or are not on char boundaries.
+    pub fn suggest_batch(
+        &self,
+        buffer: &str,
+        offsets: &[usize],
+        tokenizer: &Tokenizer,
+    ) -> Result<Vec<(usize, Suggestion)>, Error> {
+        let texts = offsets
+            .windows(2)
+            .map(|window| {
+                buffer
+                    .get(window[0]..window[1])
+                    .filter(|_| window[0] <= window[1])
+                    .ok_or_else(|| {
+                        Error::Unexpected(format!(
+                            "invalid text offsets {}..{} in a buffer of {} bytes",
+                            window[0],
+                            window[1],
+                            buffer.len()
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let suggestions: Vec<Vec<Suggestion>> = texts
+            .maybe_par_iter()
+            .map(|text| self.suggest(text, tokenizer))
+            .collect();
 
-        output
+        Ok(suggestions
             .into_iter()
-            .filter_map(|(_, suggestion)| {
-                if mask[suggestion.start..suggestion.end].iter().all(|x| !x) {
-                    mask[suggestion.start..suggestion.end]
-                        .iter_mut()
-                        .for_each(|x| *x = true);
-                    Some(suggestion)
-                } else {
+            .enumerate()
+            .flat_map(|(i, suggestions)| suggestions.into_iter().map(move |x| (i, x)))
+            .collect())
+    }
+
+    /// The rules which match the text together with the suggestions each of them makes, ordered like
+    /// [rules][Rules::rules]. Answers "which rule flagged this?" for a snippet: disabled rules are checked too and
+    /// the suggestions of different rules are not resolved against each other, so rules whose suggestions are hidden
+    /// by others in [suggest][Rules::suggest] are found as well. The rule has the ID and the category.
+    pub fn find_matching_rules(
+        &self,
+        incorrect_example: &str,
+        tokenizer: &Tokenizer,
+    ) -> Vec<(&Rule, Vec<Suggestion>)> {
+        let tokens = finalize(tokenizer.disambiguate(tokenizer.tokenize(incorrect_example)));
+        if tokens.is_empty() {
+            return Vec::new();
+        }
+        let locale = self.check_options.message_locale.as_deref();
+
+        self.rules
+            .maybe_par_iter()
+            .filter_map(|rule| {
+                let mut suggestions = rule
+                    .apply_limited(&tokens, tokenizer, None, locale)
+                    .expect("checking without a limit always finishes");
+                annotate(&tokens, &mut suggestions);
+
+                if suggestions.is_empty() {
                     None
+                } else {
+                    Some((rule, suggestions))
                 }
             })
             .collect()
     }
 
-    /// Compute the suggestions for a text by checking all rules.
-    pub fn suggest(&self, text: &str, tokenizer: &Tokenizer) -> Vec<Suggestion> {
-        let tokens = tokenizer.disambiguate(tokenizer.tokenize(text));
-        self.apply(&finalize(tokens), tokenizer)
+    /// The rules which match any text of a corpus of correct text, e. g. one sentence per item, together with the
+    /// indices of the texts they match, ordered like [rules][Rules::rules]. Each text is checked with
+    /// [find_matching_rules][Rules::find_matching_rules], so disabled rules are included. Rules matching correct text
+    /// are false positives and candidates for exclusion from a build.
+    pub fn false_positives<S: AsRef<str>>(
+        &self,
+        corpus: &[S],
+        tokenizer: &Tokenizer,
+    ) -> Vec<(&Rule, Vec<usize>)> {
+        let mut matches: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+
+        for (i, text) in corpus.iter().enumerate() {
+            for (rule, _) in self.find_matching_rules(text.as_ref(), tokenizer) {
+                let index = self
+                    .rules
+                    .iter()
+                    .position(|x| std::ptr::eq(x, rule))
+                    .expect("matching rules are in the rule set");
+                matches.entry(index).or_default().push(i);
+            }
+        }
+
+        matches
+            .into_iter()
+            .map(|(index, texts)| (&self.rules[index], texts))
+            .collect()
     }
 
     /// Correct a text by first tokenizing, then finding all suggestions and choosing the first replacement of each suggestion.
@@ -117,6 +805,27 @@ impl Rules {
     }
 }
 
+// sets the matched text, the covered tokens and missing short messages of the suggestions
+fn annotate(tokens: &[Token], suggestions: &mut [Suggestion]) {
+    let text = tokens[0].text;
+    let mut byte_starts: Vec<_> = text.char_indices().map(|(i, _)| i).collect();
+    byte_starts.push(text.len());
+
+    for suggestion in suggestions {
+        if suggestion.short_message.is_empty() {
+            suggestion.short_message = shorten_message(&suggestion.message);
+        }
+
+        let (start, end) = (suggestion.start, suggestion.end);
+        suggestion.matched = text[byte_starts[start]..byte_starts[end]].to_string();
+
+        // the tokens are sorted by their spans, the sentence start token has an empty span at 0 and is never covered
+        let first = tokens.partition_point(|x| x.char_span.1 <= start);
+        let last = tokens.partition_point(|x| x.char_span.0 < end).max(first);
+        suggestion.token_span = (first, last);
+    }
+}
+
 /// Correct a text by applying suggestions to it.
 /// In the case of multiple possible replacements, always chooses the first one.
 pub fn apply_suggestions(text: &str, suggestions: &[Suggestion]) -> String {
@@ -135,3 +844,711 @@ pub fn apply_suggestions(text: &str, suggestions: &[Suggestion]) -> String {
 
     chars.into_iter().collect()
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{
+        apply_suggestions,
+        sensitive::{Redaction, SensitiveOptions},
+        stats::{RuleStats, Statistics},
+        word_list::WordList,
+        CheckOptions, Rules, ValidationIssue,
+    };
+    use crate::{
+        component::Metadata,
+        rule::RuleTier,
+        tokenizer::{LongSentences, SentenceGuard},
+        Tokenizer,
+    };
+    use lazy_static::lazy_static;
+    use quickcheck::{Arbitrary, Gen};
+    use quickcheck_macros::quickcheck;
+    use std::{fs::File, num::NonZeroUsize};
+
+    lazy_static! {
+        // shared by the tests which do not change the tokenizer or the rules, also in other modules
+        pub(crate) static ref EN: (Tokenizer, Rules) = (
+            Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap(),
+            Rules::new("../storage/fixtures/en_rules.bin").unwrap()
+        );
+        pub(crate) static ref DE: (Tokenizer, Rules) = (
+            Tokenizer::new("../storage/fixtures/de_tokenizer.bin").unwrap(),
+            Rules::new("../storage/fixtures/de_rules.bin").unwrap()
+        );
+        static ref EXAMPLES: Vec<String> =
+            EN.1.rules()
+                .iter()
+                .chain(DE.1.rules())
+                .flat_map(|x| x.examples())
+                .map(|x| x.text().to_string())
+                .collect();
+    }
+
+    /// Text made of rule examples, arbitrary strings and unusual characters, so that rules match
+    /// and the spans of their suggestions end up next to multi-byte characters.
+    #[derive(Debug, Clone)]
+    struct MixedText(String);
+
+    impl Arbitrary for MixedText {
+        fn arbitrary(g: &mut Gen) -> Self {
+            const SEPARATORS: &[&str] = &[" ", "  ", "\n", "\u{301}", "\u{200f}", "👍🏽", "ß", "\t"];
+
+            let mut text = String::new();
+            for _ in 0..usize::arbitrary(g) % 5 + 1 {
+                match u8::arbitrary(g) % 3 {
+                    0 => text.push_str(g.choose(&EXAMPLES).unwrap()),
+                    1 => text.push_str(&String::arbitrary(g)),
+                    _ => text.push_str(g.choose(SEPARATORS).unwrap()),
+                }
+            }
+            MixedText(text)
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            Box::new(self.0.shrink().map(MixedText))
+        }
+    }
+
+    // the invariants editor integrations rely on
+    fn has_valid_suggestions(text: &str, (tokenizer, rules): &(Tokenizer, Rules)) -> bool {
+        let suggestions = rules.suggest(text, tokenizer);
+        let char_count = text.chars().count();
+
+        let valid_spans = suggestions
+            .iter()
+            .all(|x| x.start <= x.end && x.end <= char_count && !x.replacements.is_empty());
+        let ordered = suggestions.windows(2).all(|x| x[0].end <= x[1].start);
+        if !valid_spans || !ordered {
+            return false;
+        }
+
+        // replacing the byte ranges of the char spans gives the same text as `apply_suggestions`
+        let byte_index = |i: usize| text.char_indices().nth(i).map_or(text.len(), |x| x.0);
+        let mut corrected = String::new();
+        let mut end = 0;
+        for suggestion in &suggestions {
+            corrected.push_str(&text[end..byte_index(suggestion.start)]);
+            corrected.push_str(&suggestion.replacements[0]);
+            end = byte_index(suggestion.end);
+        }
+        corrected.push_str(&text[end..]);
+
+        corrected == apply_suggestions(text, &suggestions)
+    }
+
+    #[quickcheck]
+    fn can_check_anything(text: String) -> bool {
+        has_valid_suggestions(&text, &EN)
+    }
+
+    #[quickcheck]
+    fn suggestion_spans_are_valid(text: MixedText) -> bool {
+        has_valid_suggestions(&text.0, &EN) && has_valid_suggestions(&text.0, &DE)
+    }
+
+    #[test]
+    fn reflows_hard_wrapped_text() {
+        let rules = &EN.1;
+        let mut tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        tokenizer.set_hard_wrapped(true);
+
+        let text = "She was not\nbeen here since Monday.";
+        let suggestions = rules.suggest(text, &tokenizer);
+        assert_eq!(
+            suggestions[0].replacements,
+            vec!["was\nnot", "has not\nbeen"]
+        );
+        assert_eq!(
+            rules.correct(text, &tokenizer),
+            "She was\nnot here since Monday."
+        );
+
+        tokenizer.set_hard_wrapped(false);
+        assert_eq!(
+            rules.suggest(text, &tokenizer)[0].replacements,
+            vec!["was not", "has not been"]
+        );
+    }
+
+    #[test]
+    fn backtracks_over_skipped_tokens() {
+        let (tokenizer, rules) = &*EN;
+
+        let suggestions = rules.suggest("The cat mat sit here.", tokenizer);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].source, "THE_NN_SIT");
+        assert_eq!(suggestions[0].replacements, vec!["sits".to_string()]);
+    }
+
+    #[test]
+    fn disables_complex_rules() {
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+
+        let complexity = rules.rule("THE_NN_SIT").unwrap().complexity();
+        assert_eq!((complexity.tokens, complexity.skips), (3, 1));
+        assert!(complexity.cost() > rules.rule("WAS_BEEN").unwrap().complexity().cost());
+
+        let disabled = rules.disable_complex_rules(complexity.cost() - 1);
+        assert!(disabled.contains(&"THE_NN_SIT".to_string()));
+        assert!(!disabled.contains(&"WAS_BEEN".to_string()));
+        assert!(!rules.rule("THE_NN_SIT").unwrap().on());
+    }
+
+    #[test]
+    fn checks_fast_rules_first() {
+        let (tokenizer, rules) = &*EN;
+
+        assert_eq!(rules.rule("THE_NN_SIT").unwrap().tier(), RuleTier::Slow);
+        assert_eq!(rules.rule("ALOT").unwrap().tier(), RuleTier::Fast);
+
+        let text = "The cat sit on the mat. He knows alot.";
+        let fast = rules.suggest_fast(text, tokenizer);
+        assert_eq!(fast.len(), 1);
+        assert_eq!(fast[0].source, "ALOT");
+
+        let full = rules.suggest_full(text, tokenizer);
+        assert_eq!(full.len(), 2);
+        assert_eq!(full[0].source, "THE_NN_SIT");
+    }
+
+    #[test]
+    fn skips_rules_over_step_limit() {
+        let tokenizer = &EN.0;
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let text = "The cat sit on the mat.";
+        assert_eq!(rules.suggest(text, tokenizer)[0].source, "THE_NN_SIT");
+
+        rules.set_check_options(CheckOptions {
+            max_match_steps: Some(2),
+            ..CheckOptions::default()
+        });
+        let limited = rules.suggest(text, tokenizer);
+        assert!(limited.iter().all(|x| x.source != "THE_NN_SIT"));
+        assert!(rules
+            .skipped_rules()
+            .contains(&("THE_NN_SIT".to_string(), 1)));
+    }
+
+    #[test]
+    fn memoizes_matches_without_changing_suggestions() {
+        let tokenizer = &EN.0;
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let texts = [
+            "The cat sit on the mat. I go alot.",
+            "He dosn't know. She was not been here. I can due it.",
+            "This is a very good idea.  The end.",
+        ];
+
+        let plain: Vec<_> = texts.iter().map(|x| rules.suggest(x, tokenizer)).collect();
+        assert!(plain.iter().all(|x| !x.is_empty()));
+
+        rules.set_check_options(CheckOptions {
+            memoize_matches: true,
+            ..CheckOptions::default()
+        });
+        let memoized: Vec<_> = texts.iter().map(|x| rules.suggest(x, tokenizer)).collect();
+        assert_eq!(memoized, plain);
+    }
+
+    #[test]
+    fn reports_memory_stats() {
+        let tokenizer = &EN.0;
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+
+        let stats = tokenizer.memory_stats();
+        assert!(stats.get("tagger").unwrap() > 0);
+        assert_eq!(stats.get("tagger_extension"), Some(0));
+        assert_eq!(
+            stats.total(),
+            stats.parts.iter().map(|(_, x)| x).sum::<usize>()
+        );
+
+        let stats = rules.memory_stats();
+        assert!(stats.get("rules").unwrap() > 0);
+        assert_eq!(stats.get("cache"), Some(0));
+        assert!(stats.regexes > 0);
+
+        rules.warm_up();
+        rules.set_cache_size(1);
+        rules.suggest("She was not been here.", tokenizer);
+        let warm = rules.memory_stats();
+        assert_eq!(warm.compiled_regexes, warm.regexes);
+        assert!(warm.get("cache").unwrap() > 0);
+    }
+
+    #[test]
+    fn guards_long_sentences() {
+        let rules = &EN.1;
+        let mut tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let text = "She was not been here since Monday. I can due it.";
+        let sources = |tokenizer: &Tokenizer| {
+            rules
+                .suggest(text, tokenizer)
+                .into_iter()
+                .map(|x| x.source)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sources(&tokenizer), vec!["WAS_BEEN", "CAN_DUE.0"]);
+
+        tokenizer.options.max_sentence_tokens = Some(5);
+        tokenizer.options.long_sentences = LongSentences::Skip;
+        let tokens = tokenizer.tokenize(text);
+        assert!(tokens[..8]
+            .iter()
+            .all(|x| x.immune && x.guard == Some(SentenceGuard::Skipped)));
+        assert!(tokens[8..].iter().all(|x| x.guard.is_none()));
+        assert_eq!(sources(&tokenizer), vec!["CAN_DUE.0"]);
+
+        // "was" and "not been" end up in different parts
+        tokenizer.options.max_sentence_tokens = Some(2);
+        tokenizer.options.long_sentences = LongSentences::Split;
+        let tokens = tokenizer.tokenize(text);
+        assert_eq!(tokens[1].guard, Some(SentenceGuard::Break));
+        assert!(tokens[1].is_sentence_end);
+        assert_eq!(sources(&tokenizer), Vec::<String>::new());
+    }
+
+    #[test]
+    fn corrects_bidi_text() {
+        let (tokenizer, rules) = &*EN;
+
+        let text = "שלום \u{200F}عالم\u{200F} I saw the the cat.";
+        let suggestions = rules.suggest(text, tokenizer);
+
+        assert_eq!(suggestions.len(), 1);
+        let chars: Vec<_> = text.chars().collect();
+        assert_eq!(
+            chars[suggestions[0].start..suggestions[0].end]
+                .iter()
+                .collect::<String>(),
+            "the the"
+        );
+        assert_eq!(
+            rules.correct(text, tokenizer),
+            "שלום \u{200F}عالم\u{200F} I saw the cat."
+        );
+    }
+
+    #[test]
+    fn stores_metadata() {
+        let rules = &EN.1;
+        let metadata =
+            Metadata::read(File::open("../storage/fixtures/en_rules.bin").unwrap()).unwrap();
+
+        assert_eq!(&metadata, rules.metadata());
+        assert_eq!(metadata.lang_code(), "en");
+        assert_eq!(metadata.rule_count(), rules.rules().len());
+
+        let tokenizer = &DE.0;
+        let metadata =
+            Metadata::read(File::open("../storage/fixtures/de_tokenizer.bin").unwrap()).unwrap();
+
+        assert_eq!(metadata.lang_code(), "de");
+        assert_eq!(metadata.rule_count(), tokenizer.rules().len());
+    }
+
+    #[test]
+    fn corrects_german() {
+        let (tokenizer, rules) = &*DE;
+
+        assert_eq!(
+            rules.correct("Die Häuser ist groß. Er sagt, das er kommt.", tokenizer),
+            "Die Häuser sind groß. Er sagt, dass er kommt."
+        );
+        assert!(rules
+            .suggest(
+                "Das Haus ist groß. Der Haustürschlüssel ist z. B. alt.",
+                tokenizer
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn checks_agreement() {
+        let (tokenizer, rules) = &*EN;
+
+        assert_eq!(
+            rules.correct("The cats sits here.", tokenizer),
+            "The cats sit here."
+        );
+        assert!(rules.suggest("The cat sits here.", tokenizer).is_empty());
+    }
+
+    #[test]
+    fn respects_antipatterns() {
+        let (tokenizer, rules) = &*EN;
+
+        assert_eq!(rules.suggest("He is very good.", tokenizer).len(), 1);
+        assert!(rules
+            .suggest("He is very good at it.", tokenizer)
+            .is_empty());
+    }
+
+    #[test]
+    fn ignores_immune_tokens() {
+        let (tokenizer, rules) = &*EN;
+
+        assert_eq!(rules.suggest("This is very good.", tokenizer).len(), 1);
+        assert!(rules
+            .suggest("This is a very good question.", tokenizer)
+            .is_empty());
+    }
+
+    #[test]
+    fn validates_against_tokenizer() {
+        let (tokenizer, rules) = &*EN;
+
+        assert_eq!(rules.validate(tokenizer), Vec::new());
+        assert!(rules
+            .validate(&Tokenizer::default())
+            .iter()
+            .any(|x| matches!(x, ValidationIssue::TagSetMismatch { .. })));
+    }
+
+    #[test]
+    fn checks_word_lists() {
+        let tokenizer = &EN.0;
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+
+        rules.add_word_list(
+            WordList::parse(
+                "INCLUSIVE",
+                "master\tmain|primary\t\tmaster's degree\nchairman\tchair",
+            )
+            .unwrap(),
+        );
+
+        let text = "Master branches and master's degrees. She was not been the chairman.";
+        let suggestions = rules.suggest(text, tokenizer);
+        let sources: Vec<_> = suggestions.iter().map(|x| x.source.as_str()).collect();
+        assert_eq!(sources, ["INCLUSIVE", "WAS_BEEN", "INCLUSIVE"]);
+        assert_eq!(suggestions[0].replacements, ["Main", "Primary"]);
+        assert_eq!(
+            rules.correct(text, tokenizer),
+            "Main branches and master's degrees. She was not the chair."
+        );
+
+        assert!(rules.remove_word_list("INCLUSIVE").is_some());
+        assert_eq!(rules.suggest(text, tokenizer).len(), 1);
+    }
+
+    #[test]
+    fn redacts_sensitive_text() {
+        let tokenizer = &EN.0;
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let text = "Darn, call 555 1234 or mail jane@example.com.";
+        assert!(rules.suggest(text, tokenizer).is_empty());
+
+        rules.set_sensitive(Some(SensitiveOptions {
+            profanity: vec!["darn".into()],
+            redaction: Redaction::Label,
+            ..SensitiveOptions::default()
+        }));
+
+        let sources: Vec<_> = rules
+            .suggest(text, tokenizer)
+            .into_iter()
+            .map(|x| x.source)
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                "SENSITIVE_PROFANITY",
+                "SENSITIVE_PHONE_NUMBER",
+                "SENSITIVE_EMAIL"
+            ]
+        );
+        assert_eq!(
+            rules.correct(text, tokenizer),
+            "[profanity], call [phone number] or mail [email]."
+        );
+    }
+
+    #[test]
+    fn sets_confidence_from_statistics() {
+        let tokenizer = &EN.0;
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let text = "She was not been here since Monday.";
+        assert_eq!(rules.suggest(text, tokenizer)[0].confidence, None);
+
+        let mut statistics = Statistics::from_examples(&rules, tokenizer);
+        let stats = *statistics.get("WAS_BEEN").unwrap();
+        assert!(stats.true_positives > 0);
+
+        statistics.record("WAS_BEEN", false);
+        rules.set_statistics(Some(statistics));
+        assert_eq!(
+            rules.suggest(text, tokenizer)[0].confidence,
+            Some(
+                RuleStats {
+                    false_positives: stats.false_positives + 1,
+                    ..stats
+                }
+                .precision()
+            )
+        );
+    }
+
+    #[test]
+    fn disables_rule_groups() {
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let group: Vec<_> = rules
+            .rules_in_group("CAN_DUE")
+            .map(|x| (x.id(), x.group().unwrap().index()))
+            .collect();
+        assert_eq!(group, [("CAN_DUE.0", 0), ("CAN_DUE.1", 1)]);
+
+        assert_eq!(rules.disable_rules(&["CAN_DUE", "WAS_BEEN"]), 3);
+        assert!(!rules.rule("CAN_DUE.1").unwrap().on());
+        assert_eq!(rules.enable_rules(&["CAN_DUE.1"]), 1);
+        assert!(rules.rule("CAN_DUE.1").unwrap().on());
+        assert!(!rules.rule("CAN_DUE.0").unwrap().on());
+    }
+
+    #[test]
+    fn limits_replacements() {
+        let tokenizer = &EN.0;
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let text = "She was not been here. I saw the the cat. He dosn't know alot.";
+        let all = rules.suggest(text, tokenizer);
+        assert!(all.iter().any(|x| x.replacements.len() > 1));
+
+        // a limit of zero would leave suggestions without replacements, so it is not accepted
+        let zero = bincode::serialize(&Some(0usize)).unwrap();
+        assert!(bincode::deserialize::<Option<NonZeroUsize>>(&zero).is_err());
+        rules.set_check_options(CheckOptions {
+            max_replacements: NonZeroUsize::new(1),
+            ..CheckOptions::default()
+        });
+        let limited = rules.suggest(text, tokenizer);
+        assert_eq!(limited.len(), all.len());
+        assert!(limited.iter().all(|x| x.replacements.len() == 1));
+    }
+
+    #[test]
+    fn finds_spans_of_interest() {
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let text = "I go alot.";
+        assert_eq!(rules.spans_of_interest(text), [(5, 9)]);
+
+        rules.disable_rules(&["ALOT"]);
+        assert!(rules
+            .spans_of_interest(text)
+            .iter()
+            .all(|(start, _)| *start != 5));
+        assert!(rules.spans_of_interest("").is_empty());
+    }
+
+    #[test]
+    fn records_rule_sources() {
+        let rules = &EN.1;
+        let source = rules.rule("ALOT").unwrap().source().unwrap();
+        assert_eq!(source.file(), "grammar.xml");
+
+        let grammar = std::fs::read_to_string("tests/fixtures/en/grammar.xml").unwrap();
+        let line = grammar.lines().nth(source.line() - 1).unwrap();
+        assert!(line.contains("id=\"ALOT\""));
+    }
+
+    #[test]
+    fn selects_message_by_locale() {
+        let tokenizer = &EN.0;
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let locales: Vec<_> = rules.rule("ALOT").unwrap().message_locales().collect();
+        assert_eq!(locales, vec!["de"]);
+
+        let message = |rules: &mut Rules, locale: Option<&str>| {
+            rules.set_check_options(CheckOptions {
+                message_locale: locale.map(|x| x.to_string()),
+                ..CheckOptions::default()
+            });
+            rules.suggest("He knows alot.", tokenizer)[0]
+                .message
+                .clone()
+        };
+        assert_eq!(message(&mut rules, None), "Did you mean a lot?");
+        assert_eq!(message(&mut rules, Some("fr")), "Did you mean a lot?");
+        assert_eq!(message(&mut rules, Some("de")), "Meinten Sie a lot?");
+        assert_eq!(message(&mut rules, Some("de-AT")), "Meinten Sie a lot?");
+    }
+
+    #[test]
+    fn selects_rules_by_first_token() {
+        let (tokenizer, rules) = &*EN;
+
+        let tokens =
+            crate::tokenizer::finalize(tokenizer.disambiguate(tokenizer.tokenize("I go ALOT.")));
+
+        let candidates = rules.prefilter.token_candidates(&tokens);
+        let candidate =
+            |id: &str| candidates[rules.rules.iter().position(|x| x.id() == id).unwrap()];
+        assert!(candidate("ALOT"));
+        // anchored on other words
+        assert!(!candidate("WAS_BEEN"));
+        assert!(!candidate("VERY_GOOD"));
+        // only requires POS tags
+        assert!(candidate("NOUN_VERB_AGREEMENT"));
+    }
+
+    #[test]
+    fn finds_matching_rules() {
+        let (tokenizer, rules) = &*EN;
+
+        let found = rules.find_matching_rules("He knows alot.", tokenizer);
+        assert_eq!(found.len(), 1);
+        let (rule, suggestions) = &found[0];
+        assert_eq!((rule.id(), rule.category_id()), ("ALOT", "GRAMMAR"));
+        assert_eq!(suggestions[0].replacements, vec!["a lot"]);
+
+        // disabled by default
+        let found = rules.find_matching_rules("I ate a apple today.", tokenizer);
+        let ids: Vec<_> = found.iter().map(|(rule, _)| rule.id()).collect();
+        assert_eq!(ids, vec!["CAPITAL_AFTER_DOT"]);
+        assert!(rules.suggest("I ate a apple today.", tokenizer).is_empty());
+
+        assert!(rules.find_matching_rules("", tokenizer).is_empty());
+    }
+
+    #[test]
+    fn finds_false_positives() {
+        let (tokenizer, rules) = &*EN;
+
+        let corpus = [
+            "She was here.",
+            "He knows alot.",
+            "",
+            "Thanks alot for the apple, a apple.",
+        ];
+
+        let found: Vec<_> = rules
+            .false_positives(&corpus, tokenizer)
+            .into_iter()
+            .map(|(rule, texts)| (rule.id(), texts))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("ALOT", vec![1, 3]), ("CAPITAL_AFTER_DOT", vec![3])]
+        );
+    }
+
+    #[test]
+    fn suggests_batches() {
+        let (tokenizer, rules) = &*EN;
+
+        let texts = [
+            "He dosn't know.",
+            "Fine.",
+            "",
+            "Über alles, she was not been here.",
+        ];
+
+        let mut buffer = String::new();
+        let mut offsets = vec![0];
+        for text in &texts {
+            buffer.push_str(text);
+            offsets.push(buffer.len());
+        }
+
+        let batch = rules.suggest_batch(&buffer, &offsets, tokenizer).unwrap();
+        let expected: Vec<_> = texts
+            .iter()
+            .enumerate()
+            .flat_map(|(i, text)| {
+                rules
+                    .suggest(text, tokenizer)
+                    .into_iter()
+                    .map(move |x| (i, x.start, x.end))
+            })
+            .collect();
+        let batch: Vec<_> = batch.iter().map(|(i, x)| (*i, x.start, x.end)).collect();
+        assert_eq!(batch, expected);
+        assert_eq!(batch.iter().map(|x| x.0).collect::<Vec<_>>(), vec![0, 3]);
+
+        // inside "Ü"
+        assert!(rules
+            .suggest_batch(&buffer, &[0, 15, 21], tokenizer)
+            .is_err());
+        assert!(rules.suggest_batch(&buffer, &[5, 2], tokenizer).is_err());
+        assert!(rules
+            .suggest_batch(&buffer, &[0], tokenizer)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn sets_short_messages() {
+        let (tokenizer, rules) = &*EN;
+
+        let short = |text: &str| rules.suggest(text, tokenizer)[0].short_message.clone();
+
+        // from the rule, from its rule group and truncated from the message
+        assert_eq!(short("He dosn't know."), "Typo");
+        assert_eq!(short("I can due it."), "Wrong word");
+        assert_eq!(
+            short("She was not been here."),
+            "Did you mean was not or has not…"
+        );
+        assert_eq!(short("He knows alot."), "Did you mean a lot?");
+
+        assert_eq!(
+            crate::types::shorten_message("Consider a stronger word than good."),
+            "Consider a stronger word than…"
+        );
+    }
+
+    #[test]
+    fn annotates_matched_text_and_tokens() {
+        let (tokenizer, rules) = &*EN;
+
+        let text = "Über uns: she was not been here.  The end.";
+
+        let tokens = crate::tokenizer::finalize(tokenizer.disambiguate(tokenizer.tokenize(text)));
+        let suggestions = rules.apply(&tokens, tokenizer);
+        assert!(!suggestions.is_empty());
+
+        for suggestion in &suggestions {
+            let expected: String = text
+                .chars()
+                .skip(suggestion.start)
+                .take(suggestion.end - suggestion.start)
+                .collect();
+            assert_eq!(suggestion.matched, expected);
+        }
+
+        let was_been = suggestions
+            .iter()
+            .find(|x| x.source.starts_with("WAS_BEEN"))
+            .unwrap();
+        assert_eq!(was_been.matched, "was not been");
+        let (first, last) = was_been.token_span;
+        let covered: Vec<_> = tokens[first..last]
+            .iter()
+            .map(|x| x.word.text.as_ref())
+            .collect();
+        assert_eq!(covered, ["was", "not", "been"]);
+    }
+
+    #[test]
+    fn merges_duplicate_suggestions() {
+        let tokenizer = &EN.0;
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        for id in ["LIST_A", "LIST_B"] {
+            rules.add_word_list(WordList::parse(id, "alot\ta lot").unwrap());
+        }
+
+        let text = "He knows alot.";
+        let suggestions = rules.suggest(text, tokenizer);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].source, "LIST_B");
+        assert!(suggestions[0].merged_sources.is_empty());
+
+        rules.set_check_options(CheckOptions {
+            merge_duplicates: true,
+            ..CheckOptions::default()
+        });
+        let merged = rules.suggest(text, tokenizer);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].source, "LIST_B");
+        assert_eq!(merged[0].merged_sources, ["LIST_A", "ALOT"]);
+    }
+}
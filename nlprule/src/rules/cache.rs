@@ -0,0 +1,181 @@
+//! The in-memory cache of suggestions by text, see [Rules::set_cache_size].
+
+use super::{persistent_cache, Rules};
+use crate::{
+    tokenizer::{finalize, Tokenizer},
+    types::*,
+    utils::cache::LruCache,
+};
+use log::warn;
+use std::sync::Mutex;
+
+pub(crate) type SuggestionCache = Mutex<LruCache<String, Vec<Suggestion>>>;
+
+// the texts in the suggestion cache with their suggestions
+pub(crate) type CacheEntries = Vec<(String, Vec<Suggestion>)>;
+
+/// How well the suggestion cache of a rule set works, see [Rules::set_cache_size].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheStats {
+    /// How often the suggestions for a text were found in the cache.
+    pub hits: u64,
+    /// How often the suggestions for a text had to be computed.
+    pub misses: u64,
+    /// The number of texts in the cache.
+    pub len: usize,
+    /// The maximum number of texts in the cache.
+    pub capacity: usize,
+}
+
+impl CacheStats {
+    pub(crate) fn of(cache: &SuggestionCache) -> Self {
+        let cache = cache.lock().unwrap();
+
+        CacheStats {
+            hits: cache.hits,
+            misses: cache.misses,
+            len: cache.len(),
+            capacity: cache.capacity(),
+        }
+    }
+
+    /// The share of lookups which were found in the cache.
+    pub fn hit_rate(&self) -> f32 {
+        self.hits as f32 / (self.hits + self.misses).max(1) as f32
+    }
+}
+
+// the capacity and the entries of a suggestion cache, from the least to the most recently used
+pub(crate) fn suggestion_cache_entries(cache: &SuggestionCache) -> (usize, CacheEntries) {
+    let cache = cache.lock().unwrap();
+    let entries = cache
+        .entries()
+        .map(|(text, suggestions)| (text.clone(), suggestions.clone()))
+        .collect();
+
+    (cache.capacity(), entries)
+}
+
+// inserts the entries in their order, so the last one is the most recently used
+pub(crate) fn fill_suggestion_cache(cache: &mut SuggestionCache, entries: CacheEntries) {
+    let cache = cache.get_mut().unwrap();
+    for (text, suggestions) in entries {
+        cache.insert(text, suggestions);
+    }
+}
+
+impl Rules {
+    /// Caches the suggestions for up to `size` texts passed to [suggest][Rules::suggest], evicting the least
+    /// recently used ones. Helps if the same texts are checked over and over again, so it works best to check
+    /// one sentence at a time. The cache assumes that the same tokenizer is used for all texts.
+    /// A size of zero turns the cache off.
+    pub fn set_cache_size(&mut self, size: usize) {
+        self.cache = if size > 0 {
+            Some(Mutex::new(LruCache::new(size)))
+        } else {
+            None
+        };
+    }
+
+    /// Hit and miss counts of the suggestion cache, `None` if the cache is turned off.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(CacheStats::of)
+    }
+
+    // the capacity and the entries of the suggestion cache, see `suggestion_cache_entries`
+    pub(crate) fn cache_entries(&self) -> Option<(usize, CacheEntries)> {
+        self.cache.as_ref().map(suggestion_cache_entries)
+    }
+
+    pub(crate) fn fill_cache(&mut self, entries: CacheEntries) {
+        if let Some(cache) = &mut self.cache {
+            fill_suggestion_cache(cache, entries);
+        }
+    }
+
+    pub(super) fn clear_cache(&mut self) {
+        if let Some(cache) = &mut self.cache {
+            cache.get_mut().unwrap().clear();
+        }
+        if self.persistent_cache.is_some() {
+            let config_hash = persistent_cache::config_hash(self);
+            if let Some((_, hash)) = &mut self.persistent_cache {
+                *hash = config_hash;
+            }
+        }
+    }
+
+    // `suggest` with another suggestion cache than the one of the rules, see `Pipeline::with_own_cache`
+    pub(crate) fn suggest_with_cache(
+        &self,
+        text: &str,
+        tokenizer: &Tokenizer,
+        cache: Option<&SuggestionCache>,
+    ) -> Vec<Suggestion> {
+        if let Some(cache) = cache {
+            if let Some(suggestions) = cache.lock().unwrap().get(text) {
+                return suggestions;
+            }
+        }
+
+        let persistent = self
+            .persistent_cache
+            .as_ref()
+            .map(|(cache, hash)| (cache, persistent_cache::key(*hash, text, tokenizer)));
+        let stored = persistent.as_ref().and_then(|(cache, key)| {
+            cache
+                .get(*key)
+                .map_err(|error| warn!("could not read from the persistent cache: {}", error))
+                .ok()
+                .flatten()
+        });
+
+        let suggestions = match stored {
+            Some(suggestions) => suggestions,
+            None => {
+                let tokens = tokenizer.disambiguate(tokenizer.tokenize(text));
+                let suggestions = self.apply(&finalize(tokens), tokenizer);
+
+                if let Some((cache, key)) = &persistent {
+                    if let Err(error) = cache.insert(*key, &suggestions) {
+                        warn!("could not write to the persistent cache: {}", error);
+                    }
+                }
+                suggestions
+            }
+        };
+
+        if let Some(cache) = cache {
+            cache
+                .lock()
+                .unwrap()
+                .insert(text.to_string(), suggestions.clone());
+        }
+
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::EN;
+
+    #[test]
+    fn caches_suggestions() {
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let tokenizer = &EN.0;
+        assert!(rules.cache_stats().is_none());
+
+        rules.set_cache_size(1);
+        let text = "The cat mat sit here.";
+        let suggestions = rules.suggest(text, tokenizer);
+        assert_eq!(rules.suggest(text, tokenizer), suggestions);
+        rules.suggest("She was not been here.", tokenizer);
+        assert_eq!(rules.suggest(text, tokenizer), suggestions);
+
+        let stats = rules.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.len), (1, 3, 1));
+        assert_eq!(stats.hit_rate(), 0.25);
+    }
+}
@@ -0,0 +1,88 @@
+//! Correcting a text until the corrections lead to no further suggestions, see [Rules::correct_iteratively].
+
+use super::{apply_suggestions, Rules, SpanMap};
+use crate::tokenizer::Tokenizer;
+use unicode_segmentation::UnicodeSegmentation;
+
+impl Rules {
+    /// Corrects a text like [correct][Rules::correct], then checks the sentences which changed again and applies
+    /// the new suggestions, since some corrections lead to further matches. Repeats until there are no new
+    /// suggestions or `max_rounds` rounds are done. Suggestions which would not change the text are ignored.
+    pub fn correct_iteratively(
+        &self,
+        text: &str,
+        tokenizer: &Tokenizer,
+        max_rounds: usize,
+    ) -> String {
+        let mut text = text.to_string();
+        // the char ranges which changed in the last round, `None` before the first round
+        let mut changed: Option<Vec<(usize, usize)>> = None;
+
+        for _ in 0..max_rounds {
+            let mut suggestions = match &changed {
+                None => self.suggest(&text, tokenizer),
+                Some(changed) => {
+                    let mut suggestions = Vec::new();
+                    let mut start = 0;
+
+                    for sentence in text.split_sentence_bounds() {
+                        let end = start + sentence.chars().count();
+                        if changed.iter().any(|(a, b)| *a <= end && start <= *b) {
+                            suggestions.extend(self.suggest(sentence, tokenizer).into_iter().map(
+                                |mut suggestion| {
+                                    suggestion.start += start;
+                                    suggestion.end += start;
+                                    suggestion
+                                },
+                            ));
+                        }
+                        start = end;
+                    }
+                    suggestions
+                }
+            };
+
+            let chars: Vec<_> = text.chars().collect();
+            suggestions.retain(|x| {
+                !x.replacements[0]
+                    .chars()
+                    .eq(chars[x.start..x.end].iter().copied())
+            });
+            if suggestions.is_empty() {
+                break;
+            }
+
+            changed = Some(
+                SpanMap::new(&suggestions)
+                    .edits
+                    .into_iter()
+                    .map(|(_, corrected)| corrected)
+                    .collect(),
+            );
+            text = apply_suggestions(&text, &suggestions);
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rules::tests::EN;
+
+    #[test]
+    fn corrects_until_no_suggestions() {
+        let (tokenizer, rules) = &*EN;
+
+        let text = "He dosn't know. I saw the the the cat.";
+
+        assert_eq!(
+            rules.correct_iteratively(text, tokenizer, 1),
+            "He doesn't know. I saw the the cat."
+        );
+        assert_eq!(
+            rules.correct_iteratively(text, tokenizer, 5),
+            "He doesn't know. I saw the cat."
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Tokens exempt from the grammar rules, see [CheckOptions::ignore_token][super::CheckOptions::ignore_token].
+
+use super::Rules;
+use crate::types::*;
+use std::fmt;
+
+/// A predicate on tokens, see [CheckOptions::ignore_token][super::CheckOptions::ignore_token].
+///
+/// The name identifies the predicate in the [persistent cache][Rules::set_persistent_cache], so a predicate
+/// which behaves differently must have another name. Predicates are equal if they are the same closure.
+#[derive(Clone)]
+pub struct TokenPredicate {
+    name: String,
+    predicate: std::sync::Arc<dyn Fn(&Token) -> bool + Send + Sync>,
+}
+
+impl TokenPredicate {
+    pub fn new(
+        name: impl Into<String>,
+        predicate: impl Fn(&Token) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        TokenPredicate {
+            name: name.into(),
+            predicate: std::sync::Arc::new(predicate),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_match(&self, token: &Token) -> bool {
+        (self.predicate)(token)
+    }
+}
+
+impl fmt::Debug for TokenPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TokenPredicate").field(&self.name).finish()
+    }
+}
+
+impl PartialEq for TokenPredicate {
+    fn eq(&self, other: &Self) -> bool {
+        std::sync::Arc::ptr_eq(&self.predicate, &other.predicate)
+    }
+}
+
+impl Rules {
+    // the tokens with the ones matching the predicate of the check options marked as immune, `None` if no token
+    // matches
+    pub(super) fn ignore_tokens<'t>(&self, tokens: &[Token<'t>]) -> Option<Vec<Token<'t>>> {
+        let predicate = self.check_options.ignore_token.as_ref()?;
+        if !tokens[1..].iter().any(|x| predicate.is_match(x)) {
+            return None;
+        }
+
+        let tokens = tokens
+            .iter()
+            .enumerate()
+            .map(|(i, token)| {
+                let mut token = token.clone();
+                // the sentence start token is never ignored
+                token.immune |= i > 0 && predicate.is_match(&token);
+                token
+            })
+            .collect();
+        Some(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{tests::EN, CheckOptions};
+
+    #[test]
+    fn ignores_tokens_by_predicate() {
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let tokenizer = &EN.0;
+        let text = "He knows alot. He dosn't know.";
+        assert_eq!(rules.suggest(text, tokenizer).len(), 2);
+
+        rules.set_check_options(CheckOptions {
+            ignore_token: Some(TokenPredicate::new("alot", |token| {
+                token.word.text.as_ref() == "alot"
+            })),
+            ..CheckOptions::default()
+        });
+        let suggestions = rules.suggest(text, tokenizer);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].source, "DOSNT");
+    }
+}
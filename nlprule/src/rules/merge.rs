@@ -0,0 +1,160 @@
+//! Merging rule sets compiled separately, see [Rules::merge].
+
+use super::{Rules, Segment};
+use crate::{rule::Rule, types::*, utils::StableHasher, Error};
+use std::hash::Hasher;
+
+/// How [Rules::merge] handles merged rules whose ID is already used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateRules {
+    /// Fails with [Error::DuplicateRule] without changing the rules.
+    Error,
+    /// Keeps the existing rule and drops the merged one.
+    Keep,
+    /// Replaces the existing rule with the merged one.
+    Replace,
+}
+
+impl Rules {
+    /// Adds the rules of another rule set compiled with the same tagger, e. g. an in-house rule pack compiled
+    /// separately from the rules of the language. The merged rules have a higher priority than the existing
+    /// ones. Rules with an ID which is already used are handled as given by `duplicates`, their IDs are
+    /// returned. All merged rules are loaded, settings of the other rule set like word lists are not merged.
+    /// The merged rules get a new checksum, so patches for the original rules do not apply anymore.
+    pub fn merge(
+        &mut self,
+        other: Rules,
+        duplicates: DuplicateRules,
+    ) -> Result<Vec<String>, Error> {
+        if other.fingerprint != self.fingerprint {
+            return Err(Error::IncompatibleRules {
+                expected: self.fingerprint,
+                found: other.fingerprint,
+            });
+        }
+
+        // everything which can fail happens before the rules are changed
+        let mut unloaded_ids = DefaultHashSet::default();
+        for segment in &self.unloaded {
+            unloaded_ids.extend(segment.deserialize()?.into_iter().map(|x| x.id));
+        }
+        let mut merged: Vec<_> = other.indices.into_iter().zip(other.rules).collect();
+        for segment in &other.unloaded {
+            merged.extend(segment.indices.iter().copied().zip(segment.deserialize()?));
+        }
+        merged.sort_by_key(|(index, _)| *index);
+
+        let ids: DefaultHashSet<_> = merged.iter().map(|(_, x)| x.id.as_str()).collect();
+        let duplicate_ids: Vec<String> = self
+            .rules
+            .iter()
+            .map(|x| x.id.as_str())
+            .chain(unloaded_ids.iter().map(String::as_str))
+            .filter(|x| ids.contains(x))
+            .map(String::from)
+            .collect();
+
+        let mut unloaded = self.unloaded.clone();
+        match duplicates {
+            DuplicateRules::Error => {
+                if let Some(id) = duplicate_ids.first() {
+                    return Err(Error::DuplicateRule(id.clone()));
+                }
+            }
+            DuplicateRules::Keep => merged.retain(|(_, x)| !duplicate_ids.contains(&x.id)),
+            DuplicateRules::Replace => {
+                for segment in &mut unloaded {
+                    let rules = segment.deserialize()?;
+                    if rules.iter().any(|x| duplicate_ids.contains(&x.id)) {
+                        let rules = segment
+                            .indices
+                            .iter()
+                            .copied()
+                            .zip(&rules)
+                            .filter(|(_, x)| !duplicate_ids.contains(&x.id));
+                        *segment = match Segment::from_rules(rules)?.pop() {
+                            Some(segment) => segment,
+                            None => Segment {
+                                category_id: segment.category_id.clone(),
+                                indices: Vec::new(),
+                                rules: bincode::serialize(&Vec::<Rule>::new())?,
+                            },
+                        };
+                    }
+                }
+            }
+        }
+
+        if duplicates == DuplicateRules::Replace {
+            let (indices, rules): (Vec<_>, Vec<_>) = self
+                .indices
+                .drain(..)
+                .zip(self.rules.drain(..))
+                .filter(|(_, x)| !duplicate_ids.contains(&x.id))
+                .unzip();
+            self.indices = indices;
+            self.rules = rules;
+        }
+        self.unloaded = unloaded;
+
+        // the merged rules come after all existing ones
+        let offset = self
+            .indices
+            .iter()
+            .chain(self.unloaded.iter().flat_map(|x| &x.indices))
+            .max()
+            .map_or(0, |x| x + 1);
+        let merged: Vec<_> = merged
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, rule))| (offset + i, rule))
+            .collect();
+
+        self.metadata.rule_count = self.indices.len()
+            + self.unloaded.iter().map(|x| x.indices.len()).sum::<usize>()
+            + merged.len();
+        let mut hasher = StableHasher::default();
+        hasher.write(&self.checksum.to_le_bytes());
+        hasher.write(&other.checksum.to_le_bytes());
+        self.checksum = hasher.finish();
+        self.insert_rules(merged);
+
+        Ok(duplicate_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::EN;
+
+    #[test]
+    fn merges_rule_sets() {
+        let upstream = &EN.1;
+        let pack = upstream.subset(&["DOSNT", "CAN_DUE"]).unwrap();
+
+        let mut rules = upstream.subset(&["WAS_BEEN", "DOSNT"]).unwrap();
+        assert!(matches!(
+            rules.merge(
+                upstream.subset(&["DOSNT"]).unwrap(),
+                DuplicateRules::Error
+            ),
+            Err(Error::DuplicateRule(id)) if id == "DOSNT"
+        ));
+        assert_eq!(rules.rules().len(), 2);
+
+        let checksum = rules.checksum();
+        let duplicates = rules.merge(pack, DuplicateRules::Replace).unwrap();
+        assert_eq!(duplicates, ["DOSNT"]);
+        let ids: Vec<_> = rules.rules().iter().map(|x| x.id()).collect();
+        assert_eq!(ids, ["WAS_BEEN", "CAN_DUE.0", "CAN_DUE.1", "DOSNT"]);
+        assert_eq!(rules.metadata().rule_count(), 4);
+        assert_ne!(rules.checksum(), checksum);
+
+        let de_rules = Rules::new("../storage/fixtures/de_rules.bin").unwrap();
+        assert!(matches!(
+            rules.merge(de_rules, DuplicateRules::Keep),
+            Err(Error::IncompatibleRules { .. })
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+//! Small updates of compiled rules. A [RulesPatch] only contains the categories which changed between two
+//! rules binaries, so deployments do not need to download the complete binary after every update.
+//!
+//! ```no_run
+//! use nlprule::{rules::patch::RulesPatch, Component, Rules};
+//! use std::fs::File;
+//!
+//! // when publishing an update
+//! let patch = RulesPatch::new(File::open("old/en_rules.bin")?, File::open("new/en_rules.bin")?)?;
+//! patch.write(File::create("en_rules_patch.bin")?, true)?;
+//!
+//! // in a deployment which has the old rules
+//! let mut rules = Rules::new("old/en_rules.bin")?;
+//! rules.apply_patch(RulesPatch::read(File::open("en_rules_patch.bin")?)?)?;
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use super::{Rules, RulesFields, Segment};
+use crate::{
+    component::Metadata, rule::engine::prefilter::Prefilter, types::DefaultHashMap, utils, Error,
+};
+use serde::{Deserialize, Serialize};
+use std::{hash::Hasher, io::Read};
+
+/// A hash of the segments of a rules binary which identifies the binary a patch can be applied to.
+pub(crate) fn checksum(segments: &[Segment], fingerprint: u64) -> u64 {
+    let mut segments: Vec<_> = segments.iter().collect();
+    segments.sort_by(|a, b| a.category_id.cmp(&b.category_id));
+
+    let mut hasher = utils::StableHasher::default();
+    hasher.write(&fingerprint.to_le_bytes());
+    for segment in segments {
+        hasher.write(segment.category_id.as_bytes());
+        hasher.write(&[0]);
+        for index in &segment.indices {
+            hasher.write(&(*index as u64).to_le_bytes());
+        }
+        hasher.write(&segment.rules);
+    }
+    hasher.finish()
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum SegmentPatch {
+    /// The rules of the category did not change, but their positions in the rule set may have.
+    Keep {
+        category_id: String,
+        indices: Vec<usize>,
+    },
+    /// The category changed or is new.
+    Replace(Segment),
+}
+
+impl SegmentPatch {
+    pub(crate) fn category_id(&self) -> &str {
+        match self {
+            SegmentPatch::Keep { category_id, .. } => category_id,
+            SegmentPatch::Replace(segment) => &segment.category_id,
+        }
+    }
+}
+
+/// The changes from one rules binary to another. Created with [RulesPatch::new] and applied with
+/// [Rules::apply_patch][crate::Rules::apply_patch]. Categories are the unit of change: a category
+/// with any changed rule is contained completely.
+#[derive(Serialize, Deserialize)]
+pub struct RulesPatch {
+    pub(crate) metadata: Metadata,
+    pub(crate) fingerprint: u64,
+    pub(crate) base_checksum: u64,
+    pub(crate) checksum: u64,
+    pub(crate) segments: Vec<SegmentPatch>,
+}
+
+impl RulesPatch {
+    /// Creates a patch which turns the rules binary read from `old` into the one read from `new`.
+    /// The data can optionally be gzip compressed.
+    pub fn new<R1: Read, R2: Read>(old: R1, new: R2) -> Result<Self, Error> {
+        let old = RulesFields::read(old)?;
+        let new = RulesFields::read(new)?;
+
+        let base_checksum = old.checksum();
+        let checksum = new.checksum();
+
+        let segments = new
+            .segments
+            .into_iter()
+            .map(|segment| {
+                let unchanged = old
+                    .segments
+                    .iter()
+                    .any(|x| x.category_id == segment.category_id && x.rules == segment.rules);
+
+                if unchanged {
+                    SegmentPatch::Keep {
+                        category_id: segment.category_id,
+                        indices: segment.indices,
+                    }
+                } else {
+                    SegmentPatch::Replace(segment)
+                }
+            })
+            .collect();
+
+        Ok(RulesPatch {
+            metadata: new.metadata,
+            fingerprint: new.fingerprint,
+            base_checksum,
+            checksum,
+            segments,
+        })
+    }
+
+    /// The checksum of the rules this patch applies to. See [Rules::checksum][crate::Rules::checksum].
+    pub fn base_checksum(&self) -> u64 {
+        self.base_checksum
+    }
+
+    /// The checksum of the rules after applying this patch.
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
+    /// IDs of the categories which are new or changed.
+    pub fn changed_categories(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|x| match x {
+            SegmentPatch::Replace(segment) => Some(segment.category_id.as_str()),
+            SegmentPatch::Keep { .. } => None,
+        })
+    }
+}
+
+impl Rules {
+    /// Updates the rules to the binary the patch was created from, see [patch][crate::rules::patch].
+    /// Rules in categories which did not change keep their state, so rules which were turned off stay off.
+    /// Categories which are not loaded stay unloaded, new categories are loaded.
+    /// The rules are not changed if the patch can not be applied.
+    pub fn apply_patch(&mut self, patch: RulesPatch) -> Result<(), Error> {
+        if patch.base_checksum != self.checksum {
+            return Err(Error::PatchMismatch {
+                expected: patch.base_checksum,
+                found: self.checksum,
+            });
+        }
+
+        // everything which can fail happens before the rules are changed
+        let mut rules = Vec::new();
+        let mut unloaded = Vec::new();
+        let mut kept: DefaultHashMap<String, std::vec::IntoIter<usize>> = DefaultHashMap::default();
+
+        for segment in patch.segments {
+            let old_unloaded = self
+                .unloaded
+                .iter()
+                .find(|x| x.category_id == segment.category_id());
+
+            match (segment, old_unloaded) {
+                (SegmentPatch::Keep { indices, .. }, Some(old)) => unloaded.push(Segment {
+                    indices,
+                    ..old.clone()
+                }),
+                (
+                    SegmentPatch::Keep {
+                        category_id,
+                        indices,
+                    },
+                    None,
+                ) => {
+                    let count = self
+                        .rules
+                        .iter()
+                        .filter(|x| x.category_id == category_id)
+                        .count();
+                    if count != indices.len() {
+                        return Err(Error::Unexpected(format!(
+                            "patch keeps {} rules in category {}, found {}",
+                            indices.len(),
+                            category_id,
+                            count
+                        )));
+                    }
+                    kept.insert(category_id, indices.into_iter());
+                }
+                (SegmentPatch::Replace(segment), Some(_)) => unloaded.push(segment),
+                (SegmentPatch::Replace(segment), None) => {
+                    rules.extend(segment.indices.iter().copied().zip(segment.deserialize()?));
+                }
+            }
+        }
+
+        // rules of a category are ordered by their index, so the new indices can be assigned in order
+        for rule in std::mem::take(&mut self.rules) {
+            if let Some(indices) = kept.get_mut(&rule.category_id) {
+                rules.push((indices.next().expect("counts were checked"), rule));
+            }
+        }
+        rules.sort_by_key(|(index, _)| *index);
+
+        let (indices, rules): (Vec<_>, Vec<_>) = rules.into_iter().unzip();
+        self.prefilter = Prefilter::new(rules.iter().map(|x| &x.engine));
+        self.indices = indices;
+        self.rules = rules;
+        self.unloaded = unloaded;
+        self.metadata = patch.metadata;
+        self.fingerprint = patch.fingerprint;
+        self.checksum = patch.checksum;
+        self.clear_cache();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{component::Component, rules::tests::EN};
+
+    #[test]
+    fn applies_patches() {
+        let ids = |rules: &Rules| {
+            rules
+                .rules()
+                .iter()
+                .map(|x| (x.id().to_string(), x.on()))
+                .collect::<Vec<_>>()
+        };
+
+        let mut base_bytes = Vec::new();
+        EN.1.write(&mut base_bytes, false).unwrap();
+
+        // turn off a rule in one category and remove another category
+        let mut target = Rules::new_from(&base_bytes[..]).unwrap();
+        let changed = target.rules[0].category_id.clone();
+        let removed = target
+            .rules
+            .iter()
+            .map(|x| x.category_id.clone())
+            .find(|x| *x != changed)
+            .unwrap();
+        target.rules[0].set_on(false);
+        let (indices, rules) = target
+            .indices
+            .drain(..)
+            .zip(target.rules.drain(..))
+            .filter(|(_, rule)| rule.category_id != removed)
+            .unzip();
+        target.indices = indices;
+        target.rules = rules;
+        let mut target_bytes = Vec::new();
+        target.write(&mut target_bytes, false).unwrap();
+        let target = Rules::new_from(&target_bytes[..]).unwrap();
+
+        let patch = RulesPatch::new(&base_bytes[..], &target_bytes[..]).unwrap();
+        assert_eq!(
+            patch.changed_categories().collect::<Vec<_>>(),
+            vec![changed]
+        );
+
+        let mut patched = Rules::new_from(&base_bytes[..]).unwrap();
+        assert_eq!(patched.checksum(), patch.base_checksum());
+        patched.apply_patch(patch).unwrap();
+        assert_eq!(ids(&patched), ids(&target));
+        assert_eq!(patched.checksum(), target.checksum());
+
+        let patch = RulesPatch::new(&base_bytes[..], &target_bytes[..]).unwrap();
+        assert!(matches!(
+            patched.apply_patch(patch),
+            Err(Error::PatchMismatch { .. })
+        ));
+        assert_eq!(ids(&patched), ids(&target));
+    }
+}
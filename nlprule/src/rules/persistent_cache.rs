@@ -0,0 +1,178 @@
+//! A cache of suggestions which persists between runs, so checking unchanged documents again (e. g. in a prose
+//! lint on CI) does not need to run the rules. Set with [Rules::set_persistent_cache][crate::Rules::set_persistent_cache].
+//!
+//! Suggestions are stored by a hash of the checked text together with the version of nlprule, the
+//! [checksum][crate::Rules::checksum] of the rules, the [checksum][crate::Tokenizer::checksum] of the tokenizer
+//! and the configuration of the rules like word lists. Storing and loading is behind the [PersistentCache] trait, [FileCache] stores
+//! one file per text in a directory.
+//!
+//! ```no_run
+//! use nlprule::{rules::persistent_cache::FileCache, Rules, Tokenizer};
+//!
+//! let tokenizer = Tokenizer::new("path/to/en_tokenizer.bin")?;
+//! let mut rules = Rules::new("path/to/en_rules.bin")?;
+//! rules.set_persistent_cache(Some(Box::new(FileCache::new(".nlprule-cache")?)));
+//!
+//! // only computed in the first run
+//! let suggestions = rules.suggest("She was not been here since Monday.", &tokenizer);
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use super::Rules;
+use crate::{tokenizer::Tokenizer, types::*, utils::StableHasher, Error};
+use std::{
+    fs,
+    hash::Hasher,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// Storage for suggestions by key. Errors are logged and treated like a missing entry, so a broken cache
+/// only makes checking slower.
+pub trait PersistentCache: Send + Sync {
+    /// The suggestions stored for the key, `None` if there are none.
+    fn get(&self, key: u64) -> Result<Option<Vec<Suggestion>>, Error>;
+
+    /// Stores the suggestions for the key, replacing the ones stored before.
+    fn insert(&self, key: u64, suggestions: &[Suggestion]) -> Result<(), Error>;
+}
+
+/// Stores the suggestions for each key in a file in a directory. Entries are never removed, the directory
+/// can be deleted at any time to clear the cache.
+#[derive(Debug, Clone)]
+pub struct FileCache {
+    dir: PathBuf,
+}
+
+impl FileCache {
+    /// Creates a cache in the directory, creating the directory if it does not exist.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileCache { dir })
+    }
+
+    /// The directory the entries are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+}
+
+impl PersistentCache for FileCache {
+    fn get(&self, key: u64) -> Result<Option<Vec<Suggestion>>, Error> {
+        match fs::read(self.path(key)) {
+            Ok(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn insert(&self, key: u64, suggestions: &[Suggestion]) -> Result<(), Error> {
+        // written to a temporary file first so concurrent runs never read a partial entry
+        let path = self.path(key);
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temporary, bincode::serialize(suggestions)?)?;
+        fs::rename(temporary, path)?;
+        Ok(())
+    }
+}
+
+/// A hash of everything besides the text which the suggestions depend on.
+pub(crate) fn config_hash(rules: &Rules) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.write(&rules.checksum.to_le_bytes());
+    for rule in &rules.rules {
+        hasher.write(rule.id.as_bytes());
+        hasher.write(&[rule.on as u8]);
+    }
+    for list in &rules.word_lists {
+        hasher.write(&bincode::serialize(list).expect("word lists can be serialized"));
+    }
+    if let Some(sensitive) = &rules.sensitive {
+        hasher.write(
+            &bincode::serialize(sensitive.options()).expect("sensitive options can be serialized"),
+        );
+    }
+    if let Some(statistics) = &rules.statistics {
+        hasher.write(statistics.to_text().as_bytes());
+    }
+    let options = &rules.check_options;
+    hasher.write(&bincode::serialize(options).expect("check options can be serialized"));
+    // not serialized, identified by its name
+    if let Some(predicate) = &options.ignore_token {
+        hasher.write(predicate.name().as_bytes());
+    }
+    hasher.finish()
+}
+
+/// The key of the suggestions for the text.
+pub(crate) fn key(config_hash: u64, text: &str, tokenizer: &Tokenizer) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(&config_hash.to_le_bytes());
+    hasher.write(&tokenizer.checksum().to_le_bytes());
+    hasher.write(&tokenizer.tagger().extension_checksum().to_le_bytes());
+    hasher.write(&[tokenizer.hard_wrapped as u8]);
+    hasher.write(text.as_bytes());
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::CheckOptions;
+
+    #[test]
+    fn serves_suggestions_from_files() {
+        let dir = std::env::temp_dir().join(format!("nlprule-cache-{}", std::process::id()));
+        let tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        rules.set_persistent_cache(Some(Box::new(FileCache::new(&dir).unwrap())));
+
+        let text = "She was not been here since Monday.";
+        let suggestions = rules.suggest(text, &tokenizer);
+        assert!(!suggestions.is_empty());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // a second run gets the stored suggestions without checking the text
+        let cache = FileCache::new(&dir).unwrap();
+        let key = key(config_hash(&rules), text, &tokenizer);
+        assert_eq!(cache.get(key).unwrap(), Some(suggestions));
+        cache.insert(key, &[]).unwrap();
+
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        rules.set_persistent_cache(Some(Box::new(cache)));
+        assert!(rules.suggest(text, &tokenizer).is_empty());
+
+        // other configurations have other keys
+        rules.set_sensitive(Some(Default::default()));
+        assert!(!rules.suggest(text, &tokenizer).is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keys_depend_on_tokenizer_and_options() {
+        let mut tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let text = "She was not been here since Monday.";
+        let before = key(config_hash(&rules), text, &tokenizer);
+
+        let id = tokenizer.rules()[0].id().to_owned();
+        tokenizer.disable_disambiguation(&[&id]);
+        let disabled = key(config_hash(&rules), text, &tokenizer);
+        assert_ne!(disabled, before);
+        tokenizer.enable_disambiguation(&[&id]);
+        assert_eq!(key(config_hash(&rules), text, &tokenizer), before);
+
+        rules.set_check_options(CheckOptions {
+            max_match_steps: Some(100),
+            ..CheckOptions::default()
+        });
+        assert_ne!(key(config_hash(&rules), text, &tokenizer), before);
+    }
+}
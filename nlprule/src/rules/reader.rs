@@ -0,0 +1,190 @@
+//! Checking text from a reader, e. g. a large file or a socket, without loading all of it into memory.
+
+use super::{Rules, SuggestionCache};
+use crate::{tokenizer::Tokenizer, types::*, Error};
+use std::io::Read;
+use unicode_segmentation::UnicodeSegmentation;
+
+impl Rules {
+    /// Checks UTF-8 text from the reader without loading all of it into memory and passes the suggestions to `sink`
+    /// as soon as the sentences they are in are read. The text is read in chunks of [STREAM_CHUNK_BYTES] and split
+    /// into sentences like the tokenizer does; a sentence is only checked once it ends or the reader is exhausted,
+    /// so sentences and chars which cross chunk boundaries are not split. Only sentences longer than
+    /// [STREAM_MAX_PENDING_BYTES] (e. g. text without punctuation) are checked in parts split at whitespace, which
+    /// bounds the memory used; matches across these splits are missed.
+    ///
+    /// `start` and `end` of the suggestions are char indices in the whole text. The `token_span` refers to the tokens
+    /// of the sentences checked together with the suggestion. The sentences checked together are looked up in and
+    /// stored in the caches like a text passed to [suggest][Rules::suggest].
+    ///
+    /// # Errors
+    /// Fails if the reader fails or the text is not valid UTF-8. The suggestions of the text before are still passed to `sink`.
+    pub fn suggest_from_reader<R: Read>(
+        &self,
+        reader: R,
+        tokenizer: &Tokenizer,
+        sink: impl FnMut(Suggestion),
+    ) -> Result<(), Error> {
+        self.suggest_from_reader_with_cache(reader, tokenizer, self.cache.as_ref(), sink)
+    }
+
+    // `suggest_from_reader` with another suggestion cache than the one of the rules, see `Pipeline::with_own_cache`
+    pub(crate) fn suggest_from_reader_with_cache<R: Read>(
+        &self,
+        reader: R,
+        tokenizer: &Tokenizer,
+        cache: Option<&SuggestionCache>,
+        mut sink: impl FnMut(Suggestion),
+    ) -> Result<(), Error> {
+        read_sentences(reader, STREAM_MAX_PENDING_BYTES, |text, offset| {
+            for mut suggestion in self.suggest_with_cache(text, tokenizer, cache) {
+                suggestion.start += offset;
+                suggestion.end += offset;
+                sink(suggestion);
+            }
+        })
+    }
+}
+
+/// The number of bytes [Rules::suggest_from_reader] reads at once.
+pub const STREAM_CHUNK_BYTES: usize = 64 * 1024;
+
+/// The maximum number of bytes of an unfinished sentence [Rules::suggest_from_reader] keeps in memory. A longer
+/// sentence is checked in parts, split after the last whitespace.
+pub const STREAM_MAX_PENDING_BYTES: usize = 1024 * 1024;
+
+// reads UTF-8 text from the reader and passes the text of complete sentences to `check` together with the number of
+// chars before it. An unfinished sentence of at least `max_pending` bytes is passed up to its last whitespace, or
+// completely if it has none
+fn read_sentences<R: Read>(
+    mut reader: R,
+    max_pending: usize,
+    mut check: impl FnMut(&str, usize),
+) -> Result<(), Error> {
+    let mut chunk = vec![0; STREAM_CHUNK_BYTES];
+    // bytes of a char which is not completely read yet
+    let mut bytes = Vec::new();
+    let mut pending = String::new();
+    // the number of chars before `pending`
+    let mut offset = 0;
+
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(n) => n,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error.into()),
+        };
+        let done = n == 0;
+        bytes.extend_from_slice(&chunk[..n]);
+
+        let valid = match std::str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            Err(error) if error.error_len().is_none() && !done => error.valid_up_to(),
+            Err(error) => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, error).into())
+            }
+        };
+        pending.push_str(std::str::from_utf8(&bytes[..valid]).expect("prefix is valid"));
+        bytes.drain(..valid);
+
+        // the last sentence may continue in the next chunk
+        let end = if done {
+            pending.len()
+        } else {
+            match pending.split_sentence_bound_indices().last() {
+                Some((i, _)) if i > 0 => i,
+                _ if pending.len() >= max_pending => pending
+                    .char_indices()
+                    .rev()
+                    .find(|(_, c)| c.is_whitespace())
+                    .map_or(pending.len(), |(i, c)| i + c.len_utf8()),
+                _ => 0,
+            }
+        };
+
+        if end > 0 {
+            let text = &pending[..end];
+            check(text, offset);
+
+            offset += text.chars().count();
+            pending.drain(..end);
+        }
+
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::EN;
+
+    // returns at most 3 bytes per read to split chars and sentences
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> std::io::Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn suggests_from_reader() {
+        let (tokenizer, rules) = &*EN;
+
+        let text = "Über uns. She was not been here. He dosn't know.\n\nThanks alot";
+        let spans = |suggestions: Vec<Suggestion>| -> Vec<_> {
+            suggestions
+                .into_iter()
+                .map(|x| (x.source, x.start, x.end, x.matched, x.replacements))
+                .collect()
+        };
+
+        let mut streamed = Vec::new();
+        rules
+            .suggest_from_reader(Trickle(text.as_bytes()), tokenizer, |x| streamed.push(x))
+            .unwrap();
+        assert_eq!(streamed.len(), 3);
+        assert_eq!(spans(streamed), spans(rules.suggest(text, tokenizer)));
+
+        let mut streamed = Vec::new();
+        let result = rules.suggest_from_reader(&b"He dosn't know. \xff"[..], tokenizer, |x| {
+            streamed.push(x)
+        });
+        assert!(matches!(result, Err(Error::Io(_))));
+        assert!(streamed.is_empty());
+    }
+
+    #[test]
+    fn splits_long_sentences_from_reader() {
+        let parts = |text: &str| {
+            let mut parts = Vec::new();
+            read_sentences(Trickle(text.as_bytes()), 8, |text, offset| {
+                parts.push((text.to_string(), offset))
+            })
+            .unwrap();
+            parts
+        };
+
+        assert_eq!(
+            parts("one two three four. Five six"),
+            vec![
+                ("one two ".to_string(), 0),
+                ("three ".to_string(), 8),
+                ("four. ".to_string(), 14),
+                ("Five ".to_string(), 20),
+                ("six".to_string(), 25)
+            ]
+        );
+        // without whitespace the text is split at a char boundary
+        assert_eq!(
+            parts("ääääää"),
+            vec![("ääää".to_string(), 0), ("ää".to_string(), 4)]
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Serialized rules are split in one segment per category, so categories can be loaded on their own, see
+//! [Rules::new_with_categories] and [Rules::load_category].
+
+use super::{patch, Rules};
+use crate::{
+    component::{self, Component, Metadata},
+    rule::{engine::prefilter::Prefilter, Rule},
+    Error,
+};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fs::File, io::Read, path::Path};
+
+/// The rules of one category in serialized form.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Segment {
+    pub(crate) category_id: String,
+    // positions of the rules in the complete rule set
+    pub(crate) indices: Vec<usize>,
+    pub(crate) rules: Vec<u8>,
+}
+
+impl RulesFields {
+    pub(crate) fn read<R: Read>(reader: R) -> Result<Self, Error> {
+        let (version, reader) = component::read_versioned::<Rules, _>(reader)?;
+
+        if version == Rules::format_version() {
+            Ok(bincode::deserialize_from(reader)?)
+        } else {
+            component::migrate::rules_fields(version, reader)
+        }
+    }
+
+    pub(crate) fn checksum(&self) -> u64 {
+        patch::checksum(&self.segments, self.fingerprint)
+    }
+}
+
+impl Segment {
+    pub(super) fn deserialize(&self) -> bincode::Result<Vec<Rule>> {
+        bincode::deserialize(&self.rules)
+    }
+
+    // one segment per category from the rules and their positions in the complete rule set
+    pub(crate) fn from_rules<'a>(
+        rules: impl IntoIterator<Item = (usize, &'a Rule)>,
+    ) -> bincode::Result<Vec<Segment>> {
+        let mut segments: Vec<(&str, Vec<usize>, Vec<&Rule>)> = Vec::new();
+
+        for (index, rule) in rules {
+            match segments.iter_mut().find(|x| x.0 == rule.category_id) {
+                Some((_, indices, rules)) => {
+                    indices.push(index);
+                    rules.push(rule);
+                }
+                None => segments.push((&rule.category_id, vec![index], vec![rule])),
+            }
+        }
+
+        segments
+            .into_iter()
+            .map(|(category_id, indices, rules)| {
+                Ok(Segment {
+                    category_id: category_id.to_string(),
+                    indices,
+                    rules: bincode::serialize(&rules)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RulesFields {
+    // must be the first field so it can be read on its own
+    pub(crate) metadata: Metadata,
+    pub(crate) segments: Vec<Segment>,
+    pub(crate) fingerprint: u64,
+}
+
+impl Serialize for Rules {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut fields = RulesFields {
+            metadata: self.metadata.clone(),
+            segments: Segment::from_rules(self.indices.iter().copied().zip(&self.rules))
+                .map_err(ser::Error::custom)?,
+            fingerprint: self.fingerprint,
+        };
+        fields.segments.extend(self.unloaded.iter().cloned());
+
+        fields.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rules {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let fields: RulesFields = Deserialize::deserialize(deserializer)?;
+        Rules::from_fields(fields).map_err(de::Error::custom)
+    }
+}
+
+impl Rules {
+    pub(crate) fn from_fields(fields: RulesFields) -> bincode::Result<Self> {
+        let mut rules = Rules {
+            checksum: fields.checksum(),
+            metadata: fields.metadata,
+            fingerprint: fields.fingerprint,
+            ..Rules::default()
+        };
+        rules.load_segments(fields.segments)?;
+
+        Ok(rules)
+    }
+
+    fn load_segments(&mut self, segments: Vec<Segment>) -> bincode::Result<()> {
+        let mut rules = Vec::new();
+        for segment in segments {
+            rules.extend(segment.indices.iter().copied().zip(segment.deserialize()?));
+        }
+        self.insert_rules(rules);
+
+        Ok(())
+    }
+
+    // adds rules with their positions in the complete rule set to the loaded rules
+    pub(super) fn insert_rules(&mut self, new_rules: Vec<(usize, Rule)>) {
+        let mut rules: Vec<_> = self.indices.drain(..).zip(self.rules.drain(..)).collect();
+        rules.extend(new_rules);
+        rules.sort_by_key(|(index, _)| *index);
+
+        let (indices, rules): (Vec<_>, Vec<_>) = rules.into_iter().unzip();
+        self.prefilter = Prefilter::new(rules.iter().map(|x| &x.engine));
+        self.indices = indices;
+        self.rules = rules;
+        self.clear_cache();
+    }
+
+    /// Creates a new rules set from a file, only loading the rules in the given categories.
+    /// The file can optionally be gzip compressed.
+    pub fn new_with_categories<P: AsRef<Path>>(p: P, category_ids: &[&str]) -> Result<Self, Error> {
+        Self::new_from_with_categories(File::open(p)?, category_ids)
+    }
+
+    /// Creates a new rules set from a reader, only loading the rules in the given categories.
+    /// The data can optionally be gzip compressed.
+    pub fn new_from_with_categories<R: Read>(
+        reader: R,
+        category_ids: &[&str],
+    ) -> Result<Self, Error> {
+        let fields = RulesFields::read(reader)?;
+        let checksum = fields.checksum();
+        let (segments, unloaded) = fields
+            .segments
+            .into_iter()
+            .partition(|x| category_ids.contains(&x.category_id.as_str()));
+
+        let mut rules = Rules {
+            metadata: fields.metadata,
+            unloaded,
+            fingerprint: fields.fingerprint,
+            checksum,
+            ..Rules::default()
+        };
+        rules.load_segments(segments)?;
+
+        Ok(rules)
+    }
+
+    /// Loads the rules in the category with the given ID if they have not been loaded yet.
+    /// Returns whether rules were loaded.
+    pub fn load_category(&mut self, category_id: &str) -> bincode::Result<bool> {
+        if let Some(index) = self
+            .unloaded
+            .iter()
+            .position(|x| x.category_id == category_id)
+        {
+            let segment = self.unloaded.remove(index);
+            self.load_segments(vec![segment])?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// IDs of the categories which have not been loaded yet.
+    pub fn unloaded_categories(&self) -> impl Iterator<Item = &str> {
+        self.unloaded.iter().map(|x| x.category_id.as_str())
+    }
+
+    /// Loads the categories which have not been loaded yet from the smallest to the largest as long as their rules
+    /// take at most `max_bytes` in total in their serialized form. Returns the IDs of the loaded categories.
+    pub fn load_smallest_categories(&mut self, max_bytes: usize) -> bincode::Result<Vec<String>> {
+        let mut sizes: Vec<_> = self
+            .unloaded
+            .iter()
+            .map(|x| (x.rules.len(), x.category_id.clone()))
+            .collect();
+        sizes.sort();
+
+        let mut total = 0;
+        let mut loaded = Vec::new();
+        for (size, category_id) in sizes {
+            if total + size > max_bytes {
+                break;
+            }
+            total += size;
+            self.load_category(&category_id)?;
+            loaded.push(category_id);
+        }
+
+        Ok(loaded)
+    }
+
+    /// Frees the categories which have not been loaded yet. They can not be loaded afterwards and are not part
+    /// of the binary if the rules are written. Returns the IDs of the freed categories.
+    pub fn discard_unloaded_categories(&mut self) -> Vec<String> {
+        self.unloaded.drain(..).map(|x| x.category_id).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::tests::EN;
+
+    #[test]
+    fn can_load_categories_separately() {
+        let rules = &EN.1;
+        let mut bytes = Vec::new();
+        rules.write(&mut bytes, false).unwrap();
+
+        let mut categories: Vec<_> = rules.rules().iter().map(|x| x.category_id()).collect();
+        categories.sort_unstable();
+        categories.dedup();
+        let (first, rest) = categories.split_first().unwrap();
+
+        let mut partial = Rules::new_from_with_categories(&bytes[..], &[first]).unwrap();
+        assert!(partial.rules().iter().all(|x| x.category_id() == *first));
+
+        for category in rest {
+            assert!(partial.load_category(category).unwrap());
+        }
+        assert_eq!(partial.unloaded_categories().count(), 0);
+
+        let ids = |rules: &Rules| {
+            rules
+                .rules()
+                .iter()
+                .map(|x| x.id().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&partial), ids(rules));
+    }
+}
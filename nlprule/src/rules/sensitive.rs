@@ -0,0 +1,225 @@
+//! Detection of profanity and personal information like email addresses and phone numbers, e. g. for
+//! moderation. Off by default, turned on with [Rules::set_sensitive][crate::Rules::set_sensitive].
+//! The suggestions are in the [CATEGORY_ID] category and replace the text with a redaction.
+//!
+//! ```no_run
+//! use nlprule::{rules::sensitive::SensitiveOptions, Rules, Tokenizer};
+//!
+//! let tokenizer = Tokenizer::new("path/to/en_tokenizer.bin")?;
+//! let mut rules = Rules::new("path/to/en_rules.bin")?;
+//! rules.set_sensitive(Some(SensitiveOptions::default()));
+//!
+//! assert_eq!(
+//!     rules.correct("Mail me at jane@example.com.", &tokenizer),
+//!     "Mail me at ****************."
+//! );
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use crate::types::*;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// The category of the suggestions. Their sources are the category followed by the kind, e. g. `SENSITIVE_EMAIL`.
+pub const CATEGORY_ID: &str = "SENSITIVE";
+
+lazy_static! {
+    static ref EMAIL: Regex =
+        Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b")
+            .unwrap();
+    static ref PHONE_NUMBER: Regex = Regex::new(r"(?:\+|\b)\d[\d ().\-/]{5,}\d\b").unwrap();
+    static ref DATE: Regex =
+        Regex::new(r"^\d{4}[-./]\d{1,2}[-./]\d{1,2}$|^\d{1,2}[-./]\d{1,2}[-./]\d{4}$").unwrap();
+}
+
+/// The kinds of sensitive text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensitiveKind {
+    Profanity,
+    Email,
+    PhoneNumber,
+}
+
+impl SensitiveKind {
+    /// The source of the suggestions for this kind.
+    pub fn source(&self) -> &'static str {
+        match self {
+            SensitiveKind::Profanity => "SENSITIVE_PROFANITY",
+            SensitiveKind::Email => "SENSITIVE_EMAIL",
+            SensitiveKind::PhoneNumber => "SENSITIVE_PHONE_NUMBER",
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        match self {
+            SensitiveKind::Profanity => "This word may be offensive.",
+            SensitiveKind::Email => "This looks like an email address.",
+            SensitiveKind::PhoneNumber => "This looks like a phone number.",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            SensitiveKind::Profanity => "[profanity]",
+            SensitiveKind::Email => "[email]",
+            SensitiveKind::PhoneNumber => "[phone number]",
+        }
+    }
+}
+
+/// How sensitive text is replaced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Redaction {
+    /// Replaces every char except whitespace with the given char.
+    Mask(char),
+    /// Replaces the text with a label of its kind like `[email]`.
+    Label,
+}
+
+/// Which kinds of sensitive text to detect and how to redact them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensitiveOptions {
+    /// Words which are flagged as profanity, matched ignoring case on whole tokens.
+    /// No list is shipped, so this is empty by default.
+    pub profanity: Vec<String>,
+    pub emails: bool,
+    /// Numbers with 7 to 15 digits, optionally grouped with spaces, dots, dashes, slashes or parentheses.
+    /// Dates are not flagged.
+    pub phone_numbers: bool,
+    pub redaction: Redaction,
+}
+
+impl Default for SensitiveOptions {
+    fn default() -> Self {
+        SensitiveOptions {
+            profanity: Vec::new(),
+            emails: true,
+            phone_numbers: true,
+            redaction: Redaction::Mask('*'),
+        }
+    }
+}
+
+/// Detects sensitive text with the given options.
+#[derive(Debug, Clone)]
+pub(crate) struct SensitiveDetector {
+    options: SensitiveOptions,
+    profanity: DefaultHashSet<String>,
+}
+
+impl SensitiveDetector {
+    pub(crate) fn new(options: SensitiveOptions) -> Self {
+        SensitiveDetector {
+            profanity: options.profanity.iter().map(|x| x.to_lowercase()).collect(),
+            options,
+        }
+    }
+
+    pub(crate) fn options(&self) -> &SensitiveOptions {
+        &self.options
+    }
+
+    fn suggestion(&self, kind: SensitiveKind, replaced: &str, start: usize) -> Suggestion {
+        let end = start + replaced.chars().count();
+        let replacement = match self.options.redaction {
+            Redaction::Mask(mask) => replaced
+                .chars()
+                .map(|c| if c.is_whitespace() { c } else { mask })
+                .collect(),
+            Redaction::Label => kind.label().to_string(),
+        };
+
+        Suggestion {
+            source: kind.source().to_string(),
+            message: kind.message().to_string(),
+            start,
+            end,
+            replacements: vec![replacement],
+            safety: FixSafety::NeedsReview,
+            confidence: None,
+            merged_sources: Vec::new(),
+            short_message: String::new(),
+            matched: String::new(),
+            token_span: (0, 0),
+            severity: Severity::default(),
+        }
+    }
+
+    /// The suggestions for sensitive text in the tokens. Only the text covered by the tokens is checked.
+    pub(crate) fn apply(&self, tokens: &[Token]) -> Vec<Suggestion> {
+        // the first token is the sentence start token
+        let (first, last) = match (tokens.get(1), tokens.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Vec::new(),
+        };
+        let text = first.text;
+        let (byte_start, char_start) = (first.byte_span.0, first.char_span.0);
+        let checked = &text[byte_start..last.byte_span.1];
+
+        let mut suggestions = Vec::new();
+
+        if !self.profanity.is_empty() {
+            for token in &tokens[1..] {
+                if self
+                    .profanity
+                    .contains(&token.word.text.as_ref().to_lowercase())
+                {
+                    suggestions.push(self.suggestion(
+                        SensitiveKind::Profanity,
+                        token.word.text.as_ref(),
+                        token.char_span.0,
+                    ));
+                }
+            }
+        }
+
+        let mut regexes = Vec::new();
+        if self.options.emails {
+            regexes.push((SensitiveKind::Email, &*EMAIL));
+        }
+        if self.options.phone_numbers {
+            regexes.push((SensitiveKind::PhoneNumber, &*PHONE_NUMBER));
+        }
+
+        for (kind, regex) in regexes {
+            for m in regex.find_iter(checked) {
+                let matched = m.as_str();
+                if kind == SensitiveKind::PhoneNumber {
+                    let digits = matched.chars().filter(char::is_ascii_digit).count();
+                    if !(7..=15).contains(&digits) || DATE.is_match(matched) {
+                        continue;
+                    }
+                }
+
+                let start = char_start + checked[..m.start()].chars().count();
+                suggestions.push(self.suggestion(kind, matched, start));
+            }
+        }
+
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_emails_and_phone_numbers() {
+        assert_eq!(
+            EMAIL
+                .find("Mail jane.doe+x@mail.example.com.")
+                .unwrap()
+                .as_str(),
+            "jane.doe+x@mail.example.com"
+        );
+
+        let phone_numbers: Vec<_> = PHONE_NUMBER
+            .find_iter("Call +43 (1) 234-5678 or 555 1234, not on 2021-03-04 or 12.")
+            .map(|x| x.as_str())
+            .filter(|x| !DATE.is_match(x))
+            .collect();
+        assert_eq!(phone_numbers, ["+43 (1) 234-5678", "555 1234"]);
+    }
+}
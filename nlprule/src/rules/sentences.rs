@@ -0,0 +1,137 @@
+//! Sentence level views of the suggestions: grouping them by sentence and limiting their number per sentence, see
+//! [CheckOptions::max_sentence_suggestions][super::CheckOptions::max_sentence_suggestions].
+
+use super::Rules;
+use crate::{tokenizer::Tokenizer, types::*};
+use unicode_segmentation::UnicodeSegmentation;
+
+impl Rules {
+    /// Computes the suggestions like [suggest][Rules::suggest] and groups them by sentence, see [group_by_sentence].
+    pub fn suggest_by_sentence(
+        &self,
+        text: &str,
+        tokenizer: &Tokenizer,
+    ) -> Vec<SentenceSuggestions> {
+        group_by_sentence(text, self.suggest(text, tokenizer))
+    }
+}
+
+// keeps the `max` suggestions with the highest priority in each sentence, in their original order
+pub(super) fn limit_per_sentence(
+    tokens: &[Token],
+    suggestions: Vec<(usize, Suggestion)>,
+    max: usize,
+) -> Vec<(usize, Suggestion)> {
+    // the char ends of the sentences, found like the tokenizer does
+    let sentence_ends: Vec<usize> = tokens[0]
+        .text
+        .split_sentence_bounds()
+        .scan(0, |end, sentence| {
+            *end += sentence.chars().count();
+            Some(*end)
+        })
+        .collect();
+
+    let mut ranked: Vec<_> = suggestions
+        .into_iter()
+        .enumerate()
+        .map(|(position, (priority, suggestion))| {
+            let sentence = sentence_ends
+                .iter()
+                .position(|end| suggestion.start < *end)
+                .unwrap_or(sentence_ends.len());
+            (sentence, position, priority, suggestion)
+        })
+        .collect();
+    ranked.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| b.2.cmp(&a.2)));
+
+    let mut counts = vec![0; sentence_ends.len() + 1];
+    ranked.retain(|(sentence, ..)| {
+        counts[*sentence] += 1;
+        counts[*sentence] <= max
+    });
+    ranked.sort_by_key(|(_, position, ..)| *position);
+
+    ranked
+        .into_iter()
+        .map(|(_, _, priority, suggestion)| (priority, suggestion))
+        .collect()
+}
+
+/// Groups the suggestions for a text by the sentence they start in. Sentences are found like the tokenizer does and
+/// cover the whole text, so there is one entry per sentence in order, also for sentences without suggestions.
+pub fn group_by_sentence(text: &str, suggestions: Vec<Suggestion>) -> Vec<SentenceSuggestions> {
+    let mut start = 0;
+    let mut sentences: Vec<_> = text
+        .split_sentence_bounds()
+        .map(|sentence| {
+            let end = start + sentence.chars().count();
+            let sentence = SentenceSuggestions {
+                start,
+                end,
+                suggestions: Vec::new(),
+            };
+            start = end;
+            sentence
+        })
+        .collect();
+
+    for suggestion in suggestions {
+        // a suggestion at the very end of the text belongs to the last sentence
+        let index = sentences
+            .iter()
+            .position(|x| suggestion.start < x.end)
+            .unwrap_or_else(|| sentences.len().saturating_sub(1));
+        if let Some(sentence) = sentences.get_mut(index) {
+            sentence.suggestions.push(suggestion);
+        }
+    }
+
+    sentences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{tests::EN, CheckOptions};
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn groups_suggestions_by_sentence() {
+        let (tokenizer, rules) = &*EN;
+
+        let text = "He knows alot. This is fine. I can due it.";
+
+        let sentences = rules.suggest_by_sentence(text, tokenizer);
+        let spans: Vec<_> = sentences.iter().map(|x| (x.start, x.end)).collect();
+        assert_eq!(spans, [(0, 15), (15, 29), (29, 42)]);
+
+        let counts: Vec<_> = sentences.iter().map(|x| x.suggestions.len()).collect();
+        assert_eq!(counts, [1, 0, 1]);
+        assert_eq!(
+            sentences
+                .into_iter()
+                .flat_map(|x| x.suggestions)
+                .collect::<Vec<_>>(),
+            rules.suggest(text, tokenizer)
+        );
+        assert!(group_by_sentence("", Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn limits_suggestions_per_sentence() {
+        let tokenizer = &EN.0;
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let text = "She was not been here. I saw the the cat. He dosn't know alot.";
+        let all = rules.suggest(text, tokenizer);
+        assert_eq!(all.len(), 4);
+
+        rules.set_check_options(CheckOptions {
+            max_sentence_suggestions: NonZeroUsize::new(1),
+            ..CheckOptions::default()
+        });
+        let limited = rules.suggest(text, tokenizer);
+        assert_eq!(limited.len(), 3);
+        assert_eq!(limited[..2], all[..2]);
+    }
+}
@@ -0,0 +1,92 @@
+//! The [severity][crate::types::Suggestion::severity] of suggestions, configured in
+//! [CheckOptions::severities][super::CheckOptions::severities].
+
+use super::Rules;
+use crate::{types::*, Error};
+use std::collections::BTreeMap;
+
+/// Parses severities for [CheckOptions::severities][super::CheckOptions::severities]. The text format has one line
+/// per ID with the ID and the severity separated by whitespace, e. g. `TYPOS error`. Empty lines and lines starting
+/// with `#` are skipped.
+pub fn parse_severities(text: &str) -> Result<BTreeMap<String, Severity>, Error> {
+    let mut severities = BTreeMap::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid =
+            || Error::Unexpected(format!("invalid severity on line {}: {:?}", i + 1, line));
+        let columns: Vec<_> = line.split_whitespace().collect();
+        match columns.as_slice() {
+            [id, severity] => {
+                severities.insert(id.to_string(), severity.parse().map_err(|_| invalid())?);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(severities)
+}
+
+impl Rules {
+    // the severity of a suggestion from the source with the priority `index`, see `apply_range`
+    pub(super) fn severity(&self, index: usize, source: &str) -> Severity {
+        let severities = &self.check_options.severities;
+        if severities.is_empty() {
+            return Severity::default();
+        }
+
+        let ids = match self.rules.get(index) {
+            Some(rule) => vec![
+                Some(rule.id()),
+                rule.group().map(|x| x.id()),
+                Some(rule.category_id()),
+            ],
+            None => vec![Some(source)],
+        };
+        ids.into_iter()
+            .flatten()
+            .find_map(|id| severities.get(id).copied())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{tests::EN, CheckOptions};
+
+    #[test]
+    fn maps_severities() {
+        let mut rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let tokenizer = &EN.0;
+        let text = "He knows alot. He dosn't know. I can due it.";
+        let severities = |rules: &Rules| -> Vec<_> {
+            rules
+                .suggest(text, tokenizer)
+                .into_iter()
+                .map(|x| x.severity)
+                .collect()
+        };
+        assert_eq!(severities(&rules), vec![Severity::Warning; 3]);
+
+        rules.set_check_options(CheckOptions {
+            severities: parse_severities(
+                "# blocks CI\nGRAMMAR error\n\nDOSNT hint\nCAN_DUE info\n",
+            )
+            .unwrap(),
+            ..CheckOptions::default()
+        });
+        // the rule and the rule group win over the category
+        assert_eq!(
+            severities(&rules),
+            vec![Severity::Error, Severity::Hint, Severity::Info]
+        );
+
+        assert!(parse_severities("DOSNT fatal").is_err());
+        assert!(parse_severities("DOSNT").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Mapping offsets between a text and the corrected text.
+
+use crate::types::*;
+
+/// Maps char offsets between a text and the text corrected with [apply_suggestions][super::apply_suggestions], e. g. to show both versions
+/// side by side. Offsets in a replaced part of the text map to the start of the replacement, offsets at the end
+/// of a replaced part map to the end of the replacement, all other offsets map to the same char in the other text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpanMap {
+    // the replaced char ranges in the original and in the corrected text
+    pub(super) edits: Vec<((usize, usize), (usize, usize))>,
+}
+
+impl SpanMap {
+    /// Creates the map for the suggestions. Like for [apply_suggestions][super::apply_suggestions] they must be sorted by start and
+    /// must not overlap, which is the case for the output of [Rules::suggest][super::Rules::suggest].
+    pub fn new(suggestions: &[Suggestion]) -> Self {
+        let mut offset: isize = 0;
+
+        let edits = suggestions
+            .iter()
+            .map(|x| {
+                let start = (x.start as isize + offset) as usize;
+                let length = x.replacements[0].chars().count();
+                offset += length as isize - (x.end - x.start) as isize;
+                ((x.start, x.end), (start, start + length))
+            })
+            .collect();
+
+        SpanMap { edits }
+    }
+
+    fn map(
+        &self,
+        offset: usize,
+        ranges: impl Fn(&((usize, usize), (usize, usize))) -> ((usize, usize), (usize, usize)),
+    ) -> usize {
+        let mut delta: isize = 0;
+
+        for edit in &self.edits {
+            let ((from_start, from_end), (to_start, to_end)) = ranges(edit);
+
+            if offset < from_start {
+                break;
+            } else if offset < from_end {
+                return to_start;
+            } else if offset == from_end {
+                return to_end;
+            }
+            delta = to_end as isize - from_end as isize;
+        }
+
+        (offset as isize + delta) as usize
+    }
+
+    /// Maps an offset in the original text to the corrected text.
+    pub fn to_corrected(&self, offset: usize) -> usize {
+        self.map(offset, |x| *x)
+    }
+
+    /// Maps an offset in the corrected text to the original text.
+    pub fn to_original(&self, offset: usize) -> usize {
+        self.map(offset, |(original, corrected)| (*corrected, *original))
+    }
+
+    /// Maps a span in the original text to the corrected text.
+    pub fn span_to_corrected(&self, (start, end): (usize, usize)) -> (usize, usize) {
+        (self.to_corrected(start), self.to_corrected(end))
+    }
+
+    /// Maps a span in the corrected text to the original text.
+    pub fn span_to_original(&self, (start, end): (usize, usize)) -> (usize, usize) {
+        (self.to_original(start), self.to_original(end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::apply_suggestions;
+
+    #[test]
+    fn maps_spans_between_texts() {
+        let suggestion = |start, end, replacement: &str| Suggestion {
+            source: "TEST".into(),
+            message: String::new(),
+            start,
+            end,
+            replacements: vec![replacement.into()],
+            safety: FixSafety::NeedsReview,
+            confidence: None,
+            merged_sources: Vec::new(),
+            short_message: String::new(),
+            matched: String::new(),
+            token_span: (0, 0),
+            severity: Severity::default(),
+        };
+        let text = "ab cd ef gh";
+        let suggestions = [
+            suggestion(0, 2, "x"),
+            suggestion(3, 3, "new "),
+            suggestion(6, 8, "longer"),
+        ];
+        let corrected = apply_suggestions(text, &suggestions);
+        assert_eq!(corrected, "x new cd longer gh");
+        let map = SpanMap::new(&suggestions);
+
+        // every char outside of the replaced parts maps to the same char in the other text
+        let original: Vec<_> = text.chars().collect();
+        let changed: Vec<_> = corrected.chars().collect();
+        for offset in [2, 3, 4, 5, 8, 9, 10] {
+            let mapped = map.to_corrected(offset);
+            assert_eq!(original[offset], changed[mapped], "{}", offset);
+            assert_eq!(map.to_original(mapped), offset);
+        }
+
+        assert_eq!(map.span_to_corrected((0, 2)), (0, 1));
+        assert_eq!(map.span_to_corrected((1, 2)), (0, 1));
+        assert_eq!(map.span_to_corrected((6, 8)), (9, 15));
+        assert_eq!(map.span_to_corrected((9, 11)), (16, 18));
+        assert_eq!(map.span_to_original((2, 6)), (3, 3));
+        assert_eq!(map.span_to_original((9, 15)), (6, 8));
+        assert_eq!(map.span_to_original((10, 12)), (6, 6));
+        assert_eq!(map.to_corrected(11), corrected.chars().count());
+        assert_eq!(SpanMap::new(&[]).to_original(4), 4);
+    }
+}
@@ -0,0 +1,132 @@
+//! Combining the suggestions of the rules with the suggestions of a spellchecker.
+
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+
+/// Which suggestion [merge_spelling] keeps when a spelling suggestion overlaps a grammar suggestion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SpellingMerge {
+    /// Keeps the grammar suggestion. Grammar rules see the context of the words, so their replacements usually
+    /// fix the spelling too.
+    #[default]
+    PreferGrammar,
+    /// Keeps the spelling suggestion and drops the grammar suggestions it overlaps.
+    PreferSpelling,
+    /// Keeps the suggestion covering more chars, the grammar suggestions if they cover as many.
+    PreferLonger,
+}
+
+/// Combines the suggestions of the rules with the suggestions of a spellchecker for the same text into one list
+/// which satisfies the guarantees of [Rules::suggest][super::Rules::suggest]: sorted by `start` and without overlaps.
+///
+/// A spelling suggestion with the same span and the same first replacement as a grammar suggestion is a duplicate,
+/// its source is added to the [merged_sources][Suggestion::merged_sources] of the grammar suggestion. Other overlaps
+/// are resolved with the policy. Spelling suggestions overlapping an earlier spelling suggestion are dropped.
+pub fn merge_spelling(
+    grammar: Vec<Suggestion>,
+    spelling: Vec<Suggestion>,
+    policy: SpellingMerge,
+) -> Vec<Suggestion> {
+    let overlaps = |a: &Suggestion, b: &Suggestion| a.start < b.end && b.start < a.end;
+    let len = |x: &Suggestion| x.end - x.start;
+
+    let mut spelling = spelling;
+    spelling.sort_by_key(|x| x.start);
+    let mut grammar: Vec<Option<Suggestion>> = grammar.into_iter().map(Some).collect();
+    let mut kept_spelling: Vec<Suggestion> = Vec::new();
+
+    for suggestion in spelling {
+        if kept_spelling.last().map(|x| overlaps(x, &suggestion)) == Some(true) {
+            continue;
+        }
+
+        let overlapped: Vec<usize> = (0..grammar.len())
+            .filter(|i| matches!(&grammar[*i], Some(x) if overlaps(x, &suggestion)))
+            .collect();
+        if overlapped.is_empty() {
+            kept_spelling.push(suggestion);
+            continue;
+        }
+
+        let duplicate = overlapped.iter().copied().find(|i| {
+            matches!(&grammar[*i], Some(x) if x.start == suggestion.start
+                && x.end == suggestion.end
+                && x.replacements.first() == suggestion.replacements.first())
+        });
+        if let Some(i) = duplicate {
+            let kept = grammar[i].as_mut().unwrap();
+            if kept.source != suggestion.source && !kept.merged_sources.contains(&suggestion.source)
+            {
+                kept.merged_sources.push(suggestion.source);
+            }
+            continue;
+        }
+
+        let prefer_spelling = match policy {
+            SpellingMerge::PreferGrammar => false,
+            SpellingMerge::PreferSpelling => true,
+            SpellingMerge::PreferLonger => overlapped
+                .iter()
+                .all(|i| len(grammar[*i].as_ref().unwrap()) < len(&suggestion)),
+        };
+        if prefer_spelling {
+            for i in overlapped {
+                grammar[i] = None;
+            }
+            kept_spelling.push(suggestion);
+        }
+    }
+
+    let mut output: Vec<_> = grammar.into_iter().flatten().chain(kept_spelling).collect();
+    output.sort_by_key(|x| x.start);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_spelling_suggestions() {
+        let suggestion = |source: &str, start: usize, end: usize, replacement: &str| Suggestion {
+            source: source.into(),
+            message: String::new(),
+            start,
+            end,
+            replacements: vec![replacement.into()],
+            safety: FixSafety::NeedsReview,
+            confidence: None,
+            merged_sources: Vec::new(),
+            short_message: String::new(),
+            matched: String::new(),
+            token_span: (0, 0),
+            severity: Severity::default(),
+        };
+        // "She was not been here, thier dog."
+        let grammar = vec![suggestion("WAS_BEEN", 4, 16, "was not")];
+        let spelling = vec![
+            suggestion("SPELLING", 12, 16, "bean"),
+            suggestion("SPELLING", 23, 28, "their"),
+        ];
+        let merged = |policy| -> Vec<_> {
+            merge_spelling(grammar.clone(), spelling.clone(), policy)
+                .into_iter()
+                .map(|x| (x.source, x.start))
+                .collect()
+        };
+
+        let grammar_first = vec![("WAS_BEEN".to_string(), 4), ("SPELLING".to_string(), 23)];
+        assert_eq!(merged(SpellingMerge::PreferGrammar), grammar_first);
+        assert_eq!(merged(SpellingMerge::PreferLonger), grammar_first);
+        assert_eq!(
+            merged(SpellingMerge::PreferSpelling),
+            vec![("SPELLING".to_string(), 12), ("SPELLING".to_string(), 23)]
+        );
+
+        let duplicate = vec![suggestion("SPELLING", 4, 16, "was not")];
+        let output = merge_spelling(grammar.clone(), duplicate, SpellingMerge::PreferSpelling);
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].source, "WAS_BEEN");
+        assert_eq!(output[0].merged_sources, vec!["SPELLING"]);
+    }
+}
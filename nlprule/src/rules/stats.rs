@@ -0,0 +1,192 @@
+//! Precision statistics of rules, used for the [confidence][crate::types::Suggestion::confidence] of suggestions.
+//! [Statistics::from_examples] collects them from the examples of the rules, [Statistics::record] from
+//! feedback on suggestions, e. g. whether users accepted them.
+//!
+//! ```no_run
+//! use nlprule::{rules::stats::Statistics, Rules, Tokenizer};
+//!
+//! let tokenizer = Tokenizer::new("path/to/en_tokenizer.bin")?;
+//! let mut rules = Rules::new("path/to/en_rules.bin")?;
+//!
+//! let mut statistics = Statistics::from_examples(&rules, &tokenizer);
+//! statistics.merge(&Statistics::new("path/to/feedback.tsv")?);
+//! rules.set_statistics(Some(statistics));
+//!
+//! for suggestion in rules.suggest("She was not been here since Monday.", &tokenizer) {
+//!     if suggestion.confidence.unwrap_or(0.) > 0.9 {
+//!         // apply automatically
+//!     }
+//! }
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use super::Rules;
+use crate::{
+    tokenizer::{finalize, Tokenizer},
+    types::*,
+    utils::parallelism::MaybeParallelRefIterator,
+    Error,
+};
+use std::{fmt::Write, fs, io::Read, path::Path};
+
+/// How often the suggestions of a rule were right and wrong.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleStats {
+    pub true_positives: usize,
+    pub false_positives: usize,
+}
+
+impl RuleStats {
+    /// The share of right suggestions, smoothed with one right and one wrong pseudo-suggestion so that
+    /// rules with few suggestions get a confidence close to 0.5 instead of 0 or 1.
+    pub fn precision(&self) -> f32 {
+        (self.true_positives + 1) as f32 / (self.true_positives + self.false_positives + 2) as f32
+    }
+}
+
+/// Precision statistics by the source of suggestions, i. e. rule ID, word list ID or kind of sensitive text.
+///
+/// The text format has one line per source with the tab-separated source, true positives and false positives.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Statistics {
+    stats: DefaultHashMap<String, RuleStats>,
+}
+
+impl Statistics {
+    /// Reads statistics in the text format from a file.
+    pub fn new<P: AsRef<Path>>(p: P) -> Result<Self, Error> {
+        Self::parse(&fs::read_to_string(p)?)
+    }
+
+    /// Reads statistics in the text format from a reader.
+    pub fn new_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Self::parse(&text)
+    }
+
+    /// Parses statistics in the text format.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut statistics = Statistics::default();
+
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let invalid =
+                || Error::Unexpected(format!("invalid statistics on line {}: {:?}", i + 1, line));
+            let columns: Vec<_> = line.split('\t').map(str::trim).collect();
+            let (source, true_positives, false_positives) = match columns.as_slice() {
+                [source, true_positives, false_positives] => (
+                    source,
+                    true_positives.parse().map_err(|_| invalid())?,
+                    false_positives.parse().map_err(|_| invalid())?,
+                ),
+                _ => return Err(invalid()),
+            };
+
+            statistics.stats.insert(
+                source.to_string(),
+                RuleStats {
+                    true_positives,
+                    false_positives,
+                },
+            );
+        }
+
+        Ok(statistics)
+    }
+
+    /// The statistics in the text format, sorted by source.
+    pub fn to_text(&self) -> String {
+        let mut sources: Vec<_> = self.stats.iter().collect();
+        sources.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut text = String::new();
+        for (source, stats) in sources {
+            writeln!(
+                text,
+                "{}\t{}\t{}",
+                source, stats.true_positives, stats.false_positives
+            )
+            .expect("writing to a string can not fail");
+        }
+        text
+    }
+
+    /// Collects statistics from the examples of the rules. A suggestion for an example where the rule should
+    /// trigger is right if it has the expected span and replacement, any other suggestion is wrong.
+    pub fn from_examples(rules: &Rules, tokenizer: &Tokenizer) -> Self {
+        let stats = rules
+            .rules()
+            .maybe_par_iter()
+            .map(|rule| {
+                let mut stats = RuleStats::default();
+
+                for example in rule.examples() {
+                    let tokens =
+                        finalize(tokenizer.disambiguate(tokenizer.tokenize(example.text())));
+
+                    for suggestion in rule.apply(&tokens, tokenizer) {
+                        if example.suggestion() == Some(&suggestion) {
+                            stats.true_positives += 1;
+                        } else {
+                            stats.false_positives += 1;
+                        }
+                    }
+                }
+
+                (rule.id().to_string(), stats)
+            })
+            .collect();
+
+        Statistics { stats }
+    }
+
+    /// Records whether a suggestion from the source was right.
+    pub fn record(&mut self, source: &str, right: bool) {
+        let stats = self.stats.entry(source.to_string()).or_default();
+        if right {
+            stats.true_positives += 1;
+        } else {
+            stats.false_positives += 1;
+        }
+    }
+
+    /// Adds the counts of the other statistics to these.
+    pub fn merge(&mut self, other: &Statistics) {
+        for (source, other) in &other.stats {
+            let stats = self.stats.entry(source.clone()).or_default();
+            stats.true_positives += other.true_positives;
+            stats.false_positives += other.false_positives;
+        }
+    }
+
+    /// The statistics of the source if there are any.
+    pub fn get(&self, source: &str) -> Option<&RuleStats> {
+        self.stats.get(source)
+    }
+
+    /// The confidence of suggestions from the source, the smoothed [precision][RuleStats::precision].
+    pub fn confidence(&self, source: &str) -> Option<f32> {
+        self.get(source).map(RuleStats::precision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_and_writes_text_format() {
+        let mut statistics = Statistics::parse("B\t3\t1\nA\t0\t0\n").unwrap();
+        statistics.record("A", true);
+        statistics.record("C", false);
+
+        assert_eq!(statistics.to_text(), "A\t1\t0\nB\t3\t1\nC\t0\t1\n");
+        assert_eq!(statistics.confidence("B"), Some(4. / 6.));
+        assert_eq!(statistics.confidence("D"), None);
+        assert!(Statistics::parse("A\t1").is_err());
+    }
+}
@@ -0,0 +1,79 @@
+//! Rule sets with only some of the rules, see [Rules::subset].
+
+use super::{Rules, RulesFields, Segment};
+use crate::{component::Metadata, rule::Rule, Error};
+
+impl Rules {
+    /// A rule set with only the rules whose ID, rule group ID or category ID is in `ids`, also from categories which
+    /// are not loaded. Written with [write][crate::Component::write] this gives a smaller binary for a specific purpose,
+    /// e. g. only punctuation rules, without compiling the rules again. Rules keep their priority, settings like
+    /// word lists and caches are not copied.
+    pub fn subset<S: AsRef<str>>(&self, ids: &[S]) -> Result<Rules, Error> {
+        let selected = |rule: &Rule| {
+            ids.iter().any(|id| {
+                let id = id.as_ref();
+                rule.id() == id
+                    || rule.category_id() == id
+                    || matches!(rule.group(), Some(group) if group.id() == id)
+            })
+        };
+
+        let unloaded = self
+            .unloaded
+            .iter()
+            .map(|segment| Ok(segment.indices.iter().copied().zip(segment.deserialize()?)))
+            .collect::<bincode::Result<Vec<_>>>()?;
+        let unloaded: Vec<_> = unloaded.into_iter().flatten().collect();
+
+        let rules: Vec<_> = self
+            .indices
+            .iter()
+            .copied()
+            .zip(&self.rules)
+            .chain(unloaded.iter().map(|(index, rule)| (*index, rule)))
+            .filter(|(_, rule)| selected(rule))
+            .collect();
+
+        let fields = RulesFields {
+            metadata: Metadata {
+                rule_count: rules.len(),
+                ..self.metadata.clone()
+            },
+            segments: Segment::from_rules(rules)?,
+            fingerprint: self.fingerprint,
+        };
+
+        Ok(Rules::from_fields(fields)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{component::Component, rules::tests::EN};
+
+    #[test]
+    fn writes_subsets() {
+        let rules = &EN.1;
+        let category_id = rules.rule("WAS_BEEN").unwrap().category_id().to_string();
+        let partial = Rules::new_with_categories("../storage/fixtures/en_rules.bin", &[]).unwrap();
+
+        let subset = partial.subset(&["CAN_DUE", "DOSNT"]).unwrap();
+        let ids: Vec<_> = subset.rules().iter().map(|x| x.id()).collect();
+        assert_eq!(ids, ["CAN_DUE.0", "CAN_DUE.1", "DOSNT"]);
+
+        let mut bytes = Vec::new();
+        rules
+            .subset(&[category_id.as_str()])
+            .unwrap()
+            .write(&mut bytes, false)
+            .unwrap();
+        let subset = Rules::new_from(&bytes[..]).unwrap();
+        assert_eq!(subset.metadata().rule_count(), subset.rules().len());
+        assert!(subset.rule("WAS_BEEN").is_some());
+        assert!(subset
+            .rules()
+            .iter()
+            .all(|x| x.category_id() == category_id));
+    }
+}
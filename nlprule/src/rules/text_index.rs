@@ -0,0 +1,113 @@
+//! Checking documents which are not stored as one contiguous string, e. g. ropes in editors.
+//! A [TextIndex] gives access to the lines of a document, [Rules::suggest_indexed][crate::Rules::suggest_indexed]
+//! only collects one paragraph at a time into a string and returns suggestions with char offsets into the
+//! whole document.
+//!
+//! For a [ropey](https://docs.rs/ropey) rope the implementation is `Box::new(self.lines().map(Cow::from))`.
+//!
+//! ```no_run
+//! use nlprule::{rules::text_index::TextIndex, Rules, Tokenizer};
+//! use std::borrow::Cow;
+//!
+//! // a document stored line by line
+//! struct Lines(Vec<String>);
+//!
+//! impl TextIndex for Lines {
+//!     fn lines(&self) -> Box<dyn Iterator<Item = Cow<'_, str>> + '_> {
+//!         Box::new(self.0.iter().map(|x| Cow::Borrowed(x.as_str())))
+//!     }
+//! }
+//!
+//! let tokenizer = Tokenizer::new("path/to/en_tokenizer.bin")?;
+//! let rules = Rules::new("path/to/en_rules.bin")?;
+//!
+//! let document = Lines(vec!["She was not been\n".into(), "here since Monday.\n".into()]);
+//! let suggestions = rules.suggest_indexed(&document, &tokenizer);
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use super::Rules;
+use crate::{tokenizer::Tokenizer, types::*};
+use std::borrow::Cow;
+
+/// A text which can be read line by line.
+pub trait TextIndex {
+    /// The lines of the text in order, including their line breaks. Joined they must give the whole text.
+    fn lines(&self) -> Box<dyn Iterator<Item = Cow<'_, str>> + '_>;
+}
+
+impl TextIndex for str {
+    fn lines(&self) -> Box<dyn Iterator<Item = Cow<'_, str>> + '_> {
+        Box::new(self.split_inclusive('\n').map(Cow::Borrowed))
+    }
+}
+
+impl TextIndex for String {
+    fn lines(&self) -> Box<dyn Iterator<Item = Cow<'_, str>> + '_> {
+        TextIndex::lines(self.as_str())
+    }
+}
+
+impl Rules {
+    /// Computes the suggestions for a text read through a [TextIndex]. The paragraphs, separated by blank lines,
+    /// are checked one after the other, with the same results as [suggest][Rules::suggest] for each paragraph.
+    /// The spans of the suggestions are char offsets into the whole text.
+    pub fn suggest_indexed<T: TextIndex + ?Sized>(
+        &self,
+        text: &T,
+        tokenizer: &Tokenizer,
+    ) -> Vec<Suggestion> {
+        let mut suggestions = Vec::new();
+        let mut paragraph = String::new();
+        // the char offset of the paragraph and of the end of the text read so far
+        let mut start = 0;
+        let mut end = 0;
+
+        let mut check = |paragraph: &mut String, start: usize| {
+            if !paragraph.trim().is_empty() {
+                suggestions.extend(self.suggest(paragraph, tokenizer).into_iter().map(
+                    |mut suggestion| {
+                        suggestion.start += start;
+                        suggestion.end += start;
+                        suggestion
+                    },
+                ));
+            }
+            paragraph.clear();
+        };
+
+        for line in text.lines() {
+            end += line.chars().count();
+
+            if line.trim().is_empty() {
+                check(&mut paragraph, start);
+                start = end;
+            } else {
+                paragraph.push_str(&line);
+            }
+        }
+        check(&mut paragraph, start);
+
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_with_document_offsets() {
+        let tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let rules = Rules::new("../storage/fixtures/en_rules.bin").unwrap();
+        let text = "She was not been\nhere since Monday.\n\n  \nI saw the the cat.\n";
+
+        let suggestions = rules.suggest_indexed(text, &tokenizer);
+        let chars: Vec<_> = text.chars().collect();
+        let replaced: Vec<String> = suggestions
+            .iter()
+            .map(|x| chars[x.start..x.end].iter().collect())
+            .collect();
+        assert_eq!(replaced, ["was not been", "the the"]);
+    }
+}
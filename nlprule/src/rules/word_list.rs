@@ -0,0 +1,273 @@
+//! Rule packs defined by word lists, e. g. for inclusive language. A [WordList] flags phrases with a message
+//! and suggested replacements. Word lists are loaded at runtime and checked together with the compiled rules,
+//! see [Rules::add_word_list][crate::Rules::add_word_list].
+//!
+//! In the text format each line is an entry with tab-separated columns: the phrase, the suggestions
+//! separated by `|`, optionally a message and optionally exceptions separated by `|`.
+//! Empty lines and lines starting with `#` are ignored.
+//!
+//! ```no_run
+//! use nlprule::{rules::word_list::WordList, Rules, Tokenizer};
+//!
+//! let tokenizer = Tokenizer::new("path/to/en_tokenizer.bin")?;
+//! let mut rules = Rules::new("path/to/en_rules.bin")?;
+//!
+//! let list = WordList::parse(
+//!     "INCLUSIVE_LANGUAGE",
+//!     "# phrase\tsuggestions\tmessage\texceptions\n\
+//!      chairman\tchair|chairperson\tConsider a gender-neutral term.\n\
+//!      master\tmain|primary\t\tmaster's degree|master class\n",
+//! )?;
+//! rules.add_word_list(list);
+//!
+//! let suggestions = rules.suggest("The chairman opened the meeting.", &tokenizer);
+//! assert_eq!(suggestions[0].replacements, vec!["chair", "chairperson"]);
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use crate::{types::*, Error};
+use serde::{Deserialize, Serialize};
+use std::{fs, io::Read, path::Path};
+
+/// A phrase flagged by a [WordList].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WordListEntry {
+    /// The words to flag. Matched ignoring case on token boundaries, any whitespace between words matches.
+    pub phrase: String,
+    /// The suggested replacements. Must not be empty. The first letter is capitalized if the match is capitalized.
+    pub suggestions: Vec<String>,
+    /// A human-readable message.
+    pub message: String,
+    /// Phrases containing the flagged words in which they are fine, e. g. "master's degree" for "master".
+    /// A match overlapping a match of an exception is not flagged. Exceptions only need to start on a token
+    /// boundary, so "master's degree" also covers "master's degrees".
+    #[serde(default)]
+    pub exceptions: Vec<String>,
+}
+
+/// A list of phrases to flag, see the [module docs][self].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WordList {
+    /// The ID of the list. Used as the source of its suggestions.
+    pub id: String,
+    pub entries: Vec<WordListEntry>,
+}
+
+// splits a column of alternatives separated by `|`
+fn alternatives(column: Option<&str>) -> Vec<String> {
+    column
+        .unwrap_or_default()
+        .split('|')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// the byte end of the phrase if the text starts with it, ignoring case and treating all whitespace as equal
+fn match_phrase(text: &str, phrase: &str) -> Option<usize> {
+    let mut chars = text.char_indices().peekable();
+
+    for (i, word) in phrase.split_whitespace().enumerate() {
+        if i > 0 {
+            if !matches!(chars.peek(), Some((_, c)) if c.is_whitespace()) {
+                return None;
+            }
+            while matches!(chars.peek(), Some((_, c)) if c.is_whitespace()) {
+                chars.next();
+            }
+        }
+
+        for expected in word.chars() {
+            match chars.next() {
+                Some((_, c)) if c == expected || c.to_lowercase().eq(expected.to_lowercase()) => {}
+                _ => return None,
+            }
+        }
+    }
+
+    Some(chars.peek().map_or(text.len(), |x| x.0))
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl WordList {
+    /// Creates a word list from a file in the text format.
+    pub fn new<P: AsRef<Path>>(id: &str, p: P) -> Result<Self, Error> {
+        Self::parse(id, &fs::read_to_string(p)?)
+    }
+
+    /// Creates a word list from a reader of the text format.
+    pub fn new_from<R: Read>(id: &str, mut reader: R) -> Result<Self, Error> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Self::parse(id, &text)
+    }
+
+    /// Parses a word list in the text format.
+    pub fn parse(id: &str, text: &str) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut columns = line.split('\t');
+            let phrase = columns.next().unwrap_or_default().trim().to_string();
+            let suggestions = alternatives(columns.next());
+            let message = columns
+                .next()
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map_or_else(
+                    || format!("Consider replacing \"{}\".", phrase),
+                    String::from,
+                );
+            let exceptions = alternatives(columns.next());
+
+            let entry = WordListEntry {
+                phrase,
+                suggestions,
+                message,
+                exceptions,
+            };
+            entry.validate().map_err(|reason| Error::InvalidWordList {
+                line: i + 1,
+                reason,
+            })?;
+            entries.push(entry);
+        }
+
+        Ok(WordList {
+            id: id.to_string(),
+            entries,
+        })
+    }
+
+    /// The suggestions for the phrases of this list in the tokens.
+    pub fn apply(&self, tokens: &[Token]) -> Vec<Suggestion> {
+        let text = match tokens.first() {
+            Some(token) => token.text,
+            None => return Vec::new(),
+        };
+        // the first token is the sentence start token
+        let words: Vec<&Token> = tokens
+            .iter()
+            .skip(1)
+            .filter(|x| x.byte_span.0 < x.byte_span.1)
+            .collect();
+        // the char index of the byte end of each token
+        let ends: DefaultHashMap<usize, usize> = words
+            .iter()
+            .map(|x| (x.byte_span.1, x.char_span.1))
+            .collect();
+        let lower_text = text.to_lowercase();
+
+        // the byte start, char start and char end of the matches of a phrase
+        let matches = |phrase: &str, whole_tokens: bool| -> Vec<(usize, usize, usize)> {
+            let first_word = phrase.split_whitespace().next().unwrap_or_default();
+            if !lower_text.contains(&first_word.to_lowercase()) {
+                return Vec::new();
+            }
+
+            words
+                .iter()
+                .filter_map(|token| {
+                    let start = token.byte_span.0;
+                    let length = match_phrase(&text[start..], phrase)?;
+                    let end = match ends.get(&(start + length)) {
+                        Some(end) => *end,
+                        None if !whole_tokens => {
+                            token.char_span.0 + text[start..start + length].chars().count()
+                        }
+                        None => return None,
+                    };
+                    Some((start, token.char_span.0, end))
+                })
+                .collect()
+        };
+
+        let mut suggestions = Vec::new();
+        for entry in &self.entries {
+            let exception_spans: Vec<_> = entry
+                .exceptions
+                .iter()
+                .flat_map(|exception| matches(exception, false))
+                .collect();
+
+            for (byte_start, start, end) in matches(&entry.phrase, true) {
+                if exception_spans
+                    .iter()
+                    .any(|(_, other_start, other_end)| *other_start < end && start < *other_end)
+                {
+                    continue;
+                }
+
+                let capitalized =
+                    matches!(text[byte_start..].chars().next(), Some(c) if c.is_uppercase());
+                suggestions.push(Suggestion {
+                    source: self.id.clone(),
+                    message: entry.message.clone(),
+                    start,
+                    end,
+                    replacements: entry
+                        .suggestions
+                        .iter()
+                        .map(|x| {
+                            if capitalized {
+                                capitalize(x)
+                            } else {
+                                x.clone()
+                            }
+                        })
+                        .collect(),
+                    safety: FixSafety::NeedsReview,
+                    confidence: None,
+                    merged_sources: Vec::new(),
+                    short_message: String::new(),
+                    matched: String::new(),
+                    token_span: (0, 0),
+                    severity: Severity::default(),
+                });
+            }
+        }
+
+        suggestions
+    }
+}
+
+impl WordListEntry {
+    fn validate(&self) -> Result<(), String> {
+        if self.phrase.split_whitespace().next().is_none() {
+            Err("the phrase is empty".into())
+        } else if self.suggestions.is_empty() {
+            Err(format!("\"{}\" has no suggestions", self.phrase))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_phrases_ignoring_case_and_whitespace() {
+        assert_eq!(match_phrase("Chairman of", "chairman"), Some(8));
+        assert_eq!(match_phrase("master\n class", "master class"), Some(13));
+        assert_eq!(match_phrase("masterclass", "master class"), None);
+
+        assert!(matches!(
+            WordList::parse("ID", "# comment\n\nchairman\t\tmessage"),
+            Err(Error::InvalidWordList { line: 3, .. })
+        ));
+    }
+}
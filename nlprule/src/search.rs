@@ -0,0 +1,165 @@
+//! Finding words in a text by their lemma or part-of-speech tag, e. g. to highlight all forms of "be" in a
+//! style tool. A [TokenIndex] is built once from the analyzed tokens of a text and can then be queried many times.
+//! Sequences of tokens are found with a [Query][query::Query].
+//!
+//! ```no_run
+//! use nlprule::{search::TokenIndex, Pipeline};
+//!
+//! let pipeline = Pipeline::builder()
+//!     .tokenizer_path("path/to/en_tokenizer.bin")
+//!     .rules_path("path/to/en_rules.bin")
+//!     .build()?;
+//!
+//! let text = "The cat was fed. The cats have been fed.";
+//! let index = TokenIndex::new(pipeline.analyze(text));
+//!
+//! let forms: Vec<_> = index.lemma("be").iter().map(|x| x.text(text)).collect();
+//! assert_eq!(forms, ["was", "been"]);
+//!
+//! // past participles
+//! let participles = index.pos("VBN")?;
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use crate::{types::*, Error};
+use query::{Query, QueryMatch};
+use regex::Regex;
+
+pub mod query;
+
+/// A token found in a [TokenIndex].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Occurrence {
+    /// The position of the token in [TokenIndex::tokens].
+    pub index: usize,
+    pub char_span: (usize, usize),
+    pub byte_span: (usize, usize),
+}
+
+impl Occurrence {
+    /// The text of the token, given the text the index was built from.
+    pub fn text<'a>(&self, text: &'a str) -> &'a str {
+        &text[self.byte_span.0..self.byte_span.1]
+    }
+}
+
+/// The positions of the tokens of a text by their lemmas and part-of-speech tags.
+/// All readings of a token which are left after disambiguation are indexed.
+pub struct TokenIndex<'t> {
+    tokens: Vec<Token<'t>>,
+    lemmas: DefaultHashMap<String, Vec<usize>>,
+    tags: DefaultHashMap<String, Vec<usize>>,
+}
+
+impl<'t> TokenIndex<'t> {
+    /// Indexes the tokens, typically from [Pipeline::analyze][crate::Pipeline::analyze].
+    pub fn new(tokens: Vec<Token<'t>>) -> Self {
+        let mut lemmas: DefaultHashMap<String, Vec<usize>> = DefaultHashMap::default();
+        let mut tags: DefaultHashMap<String, Vec<usize>> = DefaultHashMap::default();
+
+        for (i, token) in tokens.iter().enumerate() {
+            // the sentence start token has no text
+            if token.word.text.as_ref().is_empty() {
+                continue;
+            }
+
+            for data in &token.word.tags {
+                for (map, key) in [
+                    (&mut lemmas, data.lemma.as_ref()),
+                    (&mut tags, data.pos.as_ref()),
+                ] {
+                    if key.is_empty() {
+                        continue;
+                    }
+
+                    let positions = map.entry(key.to_string()).or_default();
+                    if positions.last() != Some(&i) {
+                        positions.push(i);
+                    }
+                }
+            }
+        }
+
+        TokenIndex {
+            tokens,
+            lemmas,
+            tags,
+        }
+    }
+
+    /// The indexed tokens.
+    pub fn tokens(&self) -> &[Token<'t>] {
+        &self.tokens
+    }
+
+    fn occurrences<'a>(&self, positions: impl Iterator<Item = &'a usize>) -> Vec<Occurrence> {
+        let mut positions: Vec<_> = positions.copied().collect();
+        positions.sort_unstable();
+        positions.dedup();
+
+        positions
+            .into_iter()
+            .map(|index| Occurrence {
+                index,
+                char_span: self.tokens[index].char_span,
+                byte_span: self.tokens[index].byte_span,
+            })
+            .collect()
+    }
+
+    /// The tokens with the lemma, in the order of the text. Lemmas are compared exactly.
+    pub fn lemma(&self, lemma: &str) -> Vec<Occurrence> {
+        self.occurrences(self.lemmas.get(lemma).into_iter().flatten())
+    }
+
+    /// The tokens with a part-of-speech tag which matches the regex pattern, in the order of the text.
+    /// The pattern must match the whole tag, e. g. `VB.*` matches all verb tags of the English tagset.
+    /// Fails with [Error::InvalidQuery] if the pattern is not a valid regex.
+    pub fn pos(&self, pattern: &str) -> Result<Vec<Occurrence>, Error> {
+        let regex = Regex::new(&format!("^(?:{})$", pattern))
+            .map_err(|x| Error::InvalidQuery(format!("invalid POS pattern {}: {}", pattern, x)))?;
+
+        Ok(self.occurrences(
+            self.tags
+                .iter()
+                .filter(|(tag, _)| regex.is_match(tag))
+                .flat_map(|(_, positions)| positions),
+        ))
+    }
+
+    /// The matches of the query in the tokens, see [Query::find].
+    pub fn query(&self, query: &Query) -> Vec<QueryMatch> {
+        query.find(&self.tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pipeline;
+
+    #[test]
+    fn finds_lemmas_and_tags() {
+        let pipeline = Pipeline::builder()
+            .tokenizer_path("../storage/fixtures/en_tokenizer.bin")
+            .rules_path("../storage/fixtures/en_rules.bin")
+            .build()
+            .unwrap();
+        let text = "She was not been here. This is good.";
+        let index = TokenIndex::new(pipeline.analyze(text));
+
+        let forms: Vec<_> = index.lemma("be").iter().map(|x| x.text(text)).collect();
+        assert_eq!(forms, ["was", "been", "is"]);
+
+        let verbs: Vec<_> = index
+            .pos("VB[DN]")
+            .unwrap()
+            .iter()
+            .map(|x| x.text(text))
+            .collect();
+        assert_eq!(verbs, ["was", "been"]);
+
+        assert!(index.lemma("cat").is_empty());
+        assert!(matches!(index.pos("VB("), Err(Error::InvalidQuery(_))));
+    }
+}
@@ -0,0 +1,441 @@
+//! A small query language over analyzed tokens in the style of the Corpus Query Language (CQL).
+//! Queries are compiled to the same compositions as the patterns of grammar rules.
+//!
+//! A query is a sequence of tokens. Each token is either `[...]` with conditions or a quoted word which is
+//! short for `[word="..."]`. `[]` matches any token. Conditions are `attribute="regex"` or
+//! `attribute!="regex"` with the attributes `word`, `lemma` and `pos`, combined with `&`, `|`, `!` and
+//! parentheses. Regexes must match the whole value, `(?i)` makes them case-insensitive. Tokens can be
+//! followed by a quantifier: `?`, `*`, `+`, `{n}`, `{n,}` or `{n,m}`.
+//!
+//! ```no_run
+//! use nlprule::{search::{query::Query, TokenIndex}, Pipeline};
+//!
+//! let pipeline = Pipeline::builder()
+//!     .tokenizer_path("path/to/en_tokenizer.bin")
+//!     .rules_path("path/to/en_rules.bin")
+//!     .build()?;
+//!
+//! // passive voice: a form of "be", optionally an adverb and a past participle
+//! let query = Query::new(r#"[lemma="be"] [pos="RB"]? [pos="VBN"]"#, pipeline.tokenizer().tagger())?;
+//!
+//! let text = "The cat was not fed.";
+//! let index = TokenIndex::new(pipeline.analyze(text));
+//! let passives: Vec<_> = index.query(&query).iter().map(|x| x.text(text)).collect();
+//! assert_eq!(passives, ["was not fed"]);
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use crate::{
+    rule::engine::{
+        composition::{
+            concrete::{TextAtom, WordDataAtom},
+            AndAtom, Atom, Composition, Matcher, NotAtom, OrAtom, Part, PosMatcher, Quantifier,
+            TextMatcher, TrueAtom, WordDataMatcher,
+        },
+        span,
+    },
+    tokenizer::tag::Tagger,
+    types::*,
+    utils::regex::SerializeRegex,
+    Error,
+};
+use std::str::Chars;
+
+/// A match of a [Query].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryMatch {
+    /// The positions of the first and one after the last matched token.
+    pub tokens: (usize, usize),
+    pub char_span: (usize, usize),
+    pub byte_span: (usize, usize),
+}
+
+impl QueryMatch {
+    /// The matched text, given the text the tokens are from.
+    pub fn text<'a>(&self, text: &'a str) -> &'a str {
+        &text[self.byte_span.0..self.byte_span.1]
+    }
+}
+
+/// A compiled query. Part-of-speech conditions are resolved against the tagset of the tagger, so the query
+/// must only be used with tokens from a tokenizer with the same tagger.
+pub struct Query {
+    composition: Composition,
+}
+
+impl Query {
+    /// Parses the query. Fails with [Error::InvalidQuery] if it is not valid.
+    pub fn new(query: &str, tagger: &Tagger) -> Result<Self, Error> {
+        let parts = Parser::new(query, tagger).parse()?;
+
+        Ok(Query {
+            composition: Composition {
+                parts,
+                group_ids_to_idx: DefaultHashMap::default(),
+            },
+        })
+    }
+
+    /// Finds the matches in the tokens, from left to right. Matches do not overlap and are never empty.
+    pub fn find(&self, tokens: &[Token]) -> Vec<QueryMatch> {
+        let refs: Vec<_> = tokens.iter().collect();
+        let mut matches = Vec::new();
+        let mut i = 0;
+
+        while i < refs.len() {
+            // the sentence start token can not be part of a match
+            let found = if refs[i].char_span.0 < refs[i].char_span.1 {
+                self.composition
+                    .apply(&refs, i)
+                    .map(|graph| span(&graph))
+                    .filter(|(start, end)| start < end)
+            } else {
+                None
+            };
+
+            match found {
+                Some((start, end)) => {
+                    let last = (i..refs.len())
+                        .take_while(|j| refs[*j].char_span.1 <= end)
+                        .last()
+                        .unwrap_or(i);
+
+                    matches.push(QueryMatch {
+                        tokens: (i, last + 1),
+                        char_span: (start, end),
+                        byte_span: (refs[i].byte_span.0, refs[last].byte_span.1),
+                    });
+                    i = last + 1;
+                }
+                None => i += 1,
+            }
+        }
+
+        matches
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::iter::Enumerate<Chars<'a>>>,
+    tagger: &'a Tagger,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(query: &'a str, tagger: &'a Tagger) -> Self {
+        Parser {
+            chars: query.chars().enumerate().peekable(),
+            tagger,
+            position: 0,
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, Error> {
+        Err(Error::InvalidQuery(format!(
+            "at char {}: {}",
+            self.position, message
+        )))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.chars.peek(), Some((_, c)) if c.is_whitespace()) {
+            self.chars.next();
+        }
+        self.position = self.chars.peek().map_or(self.position + 1, |(i, _)| *i);
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(&format!("expected `{}`", c))
+        }
+    }
+
+    fn parse(mut self) -> Result<Vec<Part>, Error> {
+        let mut parts = Vec::new();
+
+        while self.peek().is_some() {
+            let atom = match self.peek() {
+                Some('[') => {
+                    self.chars.next();
+                    let atom = if self.peek() == Some(']') {
+                        TrueAtom {}.into()
+                    } else {
+                        self.expression()?
+                    };
+                    self.expect(']')?;
+                    atom
+                }
+                Some('"') => self.condition("word", false)?,
+                _ => return self.error("expected `[` or a quoted word"),
+            };
+
+            parts.push(Part {
+                atom,
+                quantifier: self.quantifier()?,
+                visible: true,
+            });
+        }
+
+        if parts.is_empty() {
+            return self.error("the query is empty");
+        }
+        Ok(parts)
+    }
+
+    fn expression(&mut self) -> Result<Atom, Error> {
+        let mut atoms = vec![self.conjunction()?];
+        while self.eat('|') {
+            atoms.push(self.conjunction()?);
+        }
+
+        Ok(if atoms.len() == 1 {
+            atoms.pop().unwrap()
+        } else {
+            OrAtom { atoms }.into()
+        })
+    }
+
+    fn conjunction(&mut self) -> Result<Atom, Error> {
+        let mut atoms = vec![self.unary()?];
+        while self.eat('&') {
+            atoms.push(self.unary()?);
+        }
+
+        Ok(if atoms.len() == 1 {
+            atoms.pop().unwrap()
+        } else {
+            AndAtom { atoms }.into()
+        })
+    }
+
+    fn unary(&mut self) -> Result<Atom, Error> {
+        if self.eat('!') {
+            return Ok(NotAtom {
+                atom: Box::new(self.unary()?),
+            }
+            .into());
+        }
+
+        if self.eat('(') {
+            let atom = self.expression()?;
+            self.expect(')')?;
+            return Ok(atom);
+        }
+
+        let attribute = self.identifier()?;
+        let negate = if self.eat('!') {
+            true
+        } else if self.peek() == Some('=') {
+            false
+        } else {
+            return self.error("expected `=` or `!=`");
+        };
+        self.expect('=')?;
+
+        self.condition(&attribute, negate)
+    }
+
+    fn identifier(&mut self) -> Result<String, Error> {
+        self.skip_whitespace();
+        let mut identifier = String::new();
+        while let Some((_, c)) = self.chars.peek() {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+            identifier.push(*c);
+            self.chars.next();
+        }
+
+        if identifier.is_empty() {
+            self.error("expected an attribute")
+        } else {
+            Ok(identifier)
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(string),
+                Some((_, '\\')) if matches!(self.chars.peek(), Some((_, '"'))) => {
+                    string.push('"');
+                    self.chars.next();
+                }
+                Some((_, c)) => string.push(c),
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    fn condition(&mut self, attribute: &str, negate: bool) -> Result<Atom, Error> {
+        let value = self.string()?;
+        let regex = match SerializeRegex::new(&value, true, true) {
+            Ok(regex) => regex,
+            Err(error) => return self.error(&format!("invalid regex {}: {}", value, error)),
+        };
+
+        let atom: Atom = match attribute {
+            "word" => TextAtom {
+                matcher: text_matcher(regex),
+            }
+            .into(),
+            "lemma" => WordDataAtom {
+                matcher: WordDataMatcher {
+                    pos_matcher: None,
+                    inflect_matcher: Some(text_matcher(regex)),
+                },
+                case_sensitive: true,
+            }
+            .into(),
+            "pos" => {
+                let mut mask = vec![false; self.tagger.tag_store().len()];
+                for (tag, id) in self.tagger.tag_store() {
+                    mask[*id as usize] = regex.is_match(tag);
+                }
+
+                WordDataAtom {
+                    matcher: WordDataMatcher {
+                        pos_matcher: Some(PosMatcher::from(mask)),
+                        inflect_matcher: None,
+                    },
+                    case_sensitive: true,
+                }
+                .into()
+            }
+            _ => return self.error(&format!("unknown attribute `{}`", attribute)),
+        };
+
+        Ok(if negate {
+            NotAtom {
+                atom: Box::new(atom),
+            }
+            .into()
+        } else {
+            atom
+        })
+    }
+
+    fn number(&mut self) -> Result<usize, Error> {
+        self.skip_whitespace();
+        let mut digits = String::new();
+        while let Some((_, c)) = self.chars.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(*c);
+            self.chars.next();
+        }
+
+        match digits.parse() {
+            Ok(number) => Ok(number),
+            Err(_) => self.error("expected a number"),
+        }
+    }
+
+    fn quantifier(&mut self) -> Result<Quantifier, Error> {
+        let (min, max) = if self.eat('?') {
+            (0, 1)
+        } else if self.eat('*') {
+            (0, usize::MAX)
+        } else if self.eat('+') {
+            (1, usize::MAX)
+        } else if self.eat('{') {
+            let min = self.number()?;
+            let max = if self.eat(',') {
+                if self.peek() == Some('}') {
+                    usize::MAX
+                } else {
+                    self.number()?
+                }
+            } else {
+                min
+            };
+            self.expect('}')?;
+
+            if max < min || max == 0 {
+                return self.error("invalid quantifier");
+            }
+            (min, max)
+        } else {
+            (1, 1)
+        };
+
+        Ok(Quantifier { min, max })
+    }
+}
+
+fn text_matcher(regex: SerializeRegex) -> TextMatcher {
+    TextMatcher {
+        matcher: Matcher {
+            matcher: either::Right(regex),
+            negate: false,
+            case_sensitive: true,
+            empty_always_false: true,
+        },
+        set: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search::TokenIndex, Pipeline};
+
+    #[test]
+    fn finds_query_matches() {
+        let pipeline = Pipeline::builder()
+            .tokenizer_path("../storage/fixtures/en_tokenizer.bin")
+            .rules_path("../storage/fixtures/en_rules.bin")
+            .build()
+            .unwrap();
+        let tagger = pipeline.tokenizer().tagger();
+        let text = "She was not been here. This is very good.";
+        let index = TokenIndex::new(pipeline.analyze(text));
+
+        let find = |query: &str| -> Vec<&str> {
+            index
+                .query(&Query::new(query, tagger).unwrap())
+                .iter()
+                .map(|x| x.text(text))
+                .collect()
+        };
+
+        assert_eq!(
+            find(r#"[lemma="be"] [pos="RB"]? [pos="VBN"]"#),
+            ["was not been"]
+        );
+        assert_eq!(
+            find(r#"[lemma="be"] [pos="RB"]?"#),
+            ["was not", "been here", "is very"]
+        );
+        assert_eq!(find(r#""(?i)this" []"#), ["This is"]);
+        assert_eq!(find(r#"[pos="JJ" & !word="good"]"#), Vec::<&str>::new());
+        assert_eq!(find(r#"[pos="RB"]{2}"#), Vec::<&str>::new());
+
+        for invalid in &["", "[lemma=be]", r#"[tag="NN"]"#, r#"[pos="NN""#, "[]{3,1}"] {
+            assert!(matches!(
+                Query::new(invalid, tagger),
+                Err(Error::InvalidQuery(_))
+            ));
+        }
+    }
+}
@@ -4,28 +4,42 @@
 //! Tokens are *disambiguated* (i. e. information from the initial assignment is changed) in a rule-based way by
 //! [DisambiguationRule][crate::rule::DisambiguationRule]s.
 
-use crate::{types::*, utils::parallelism::MaybeParallelRefIterator};
+use crate::{
+    types::*,
+    utils::{self, parallelism::MaybeParallelRefIterator, StableHasher},
+};
 use lazy_static::lazy_static;
+use memchr::memmem;
+use once_cell::sync::OnceCell;
 use onig::Regex;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashSet,
-    fs::File,
-    io::{BufReader, Read},
-    path::Path,
+    borrow::Cow, collections::HashSet, fs::File, hash::Hasher, io::Read, ops::Range, path::Path,
     sync::Arc,
 };
 use unicode_segmentation::UnicodeSegmentation;
 
 pub mod chunk;
+pub mod lt_format;
+mod morfologik;
+pub mod multiword;
+pub mod pos;
+pub mod segment;
 pub mod tag;
 
 use chunk::Chunker;
+use multiword::MultiwordTagger;
+use segment::Segmentation;
 use tag::Tagger;
 
-use crate::rule::DisambiguationRule;
+use crate::{
+    component::{Component, Metadata},
+    rule::{engine::composition::Leaf, DisambiguationRule},
+    Error,
+};
 
 // see https://stackoverflow.com/a/40296745
+#[cfg(test)]
 fn split<F>(text: &str, split_func: F) -> Vec<&str>
 where
     F: Fn(char) -> bool,
@@ -46,7 +60,45 @@ where
     result
 }
 
-fn get_token_strs(text: &str) -> Vec<&str> {
+// Same as `split` with `utils::is_splitting_char`, but classifies ASCII bytes with a lookup table
+// and only decodes chars for non-ASCII bytes. Most text is mostly ASCII, so this is much faster.
+fn split_fast<'t>(text: &'t str, result: &mut Vec<&'t str>) {
+    let bytes = text.as_bytes();
+    let mut last = 0;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let len = if bytes[index].is_ascii() {
+            if utils::is_splitting_ascii(bytes[index]) {
+                1
+            } else {
+                index += 1;
+                continue;
+            }
+        } else {
+            let c = text[index..].chars().next().unwrap();
+            if utils::is_splitting_char(c) {
+                c.len_utf8()
+            } else {
+                index += c.len_utf8();
+                continue;
+            }
+        };
+
+        if last != index {
+            result.push(&text[last..index]);
+        }
+        result.push(&text[index..index + len]);
+        index += len;
+        last = index;
+    }
+
+    if last < text.len() {
+        result.push(&text[last..]);
+    }
+}
+
+pub(crate) fn get_token_strs(text: &str) -> Vec<&str> {
     let mut tokens = Vec::new();
 
     lazy_static! {
@@ -55,15 +107,19 @@ fn get_token_strs(text: &str) -> Vec<&str> {
     }
 
     let mut prev = 0;
-    let split_func = |c: char| c.is_whitespace() || crate::utils::splitting_chars().contains(c);
 
-    for (start, end) in URL_REGEX.find_iter(text) {
-        tokens.extend(split(&text[prev..start], split_func));
-        tokens.push(&text[start..end]);
-        prev = end;
+    // every URL contains "http" or "www", so the regex only needs to run if one of them is in the text
+    if memmem::find(text.as_bytes(), b"http").is_some()
+        || memmem::find(text.as_bytes(), b"www").is_some()
+    {
+        for (start, end) in URL_REGEX.find_iter(text) {
+            split_fast(&text[prev..start], &mut tokens);
+            tokens.push(&text[start..end]);
+            prev = end;
+        }
     }
 
-    tokens.extend(split(&text[prev..text.len()], split_func));
+    split_fast(&text[prev..text.len()], &mut tokens);
 
     tokens
 }
@@ -81,6 +137,112 @@ pub fn finalize(tokens: Vec<IncompleteToken>) -> Vec<Token> {
     finalized
 }
 
+/// Reconstructs text from tokens, e. g. after changing their words. The whitespace between two tokens which are
+/// next to each other in the text is taken from the text, otherwise there is a single space between them if the
+/// second token has a space before it. Tokens without text like the sentence start token are skipped.
+pub fn detokenize(tokens: &[Token]) -> String {
+    let mut output = String::new();
+    let mut previous: Option<&Token> = None;
+
+    for token in tokens.iter().filter(|x| !x.word.text.as_ref().is_empty()) {
+        if let Some(previous) = previous {
+            let between = (previous.byte_span.1 <= token.byte_span.0
+                && std::ptr::eq(previous.text, token.text))
+            .then(|| &token.text[previous.byte_span.1..token.byte_span.0])
+            .filter(|x| x.chars().all(char::is_whitespace));
+
+            match between {
+                Some(whitespace) => output.push_str(whitespace),
+                None if token.has_space_before => output.push(' '),
+                None => {}
+            }
+        }
+
+        output.push_str(token.word.text.as_ref());
+        previous = Some(token);
+    }
+
+    output
+}
+
+// replaces line breaks within paragraphs with spaces, keeping the byte offsets of all other characters
+fn unwrap_lines(text: &str) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let is_blank = |line: &str| line.trim().is_empty();
+
+    let mut unwrapped = String::with_capacity(text.len());
+    for (i, line) in lines.iter().enumerate() {
+        if i == 0 {
+            unwrapped.push_str(line);
+            continue;
+        }
+
+        if is_blank(lines[i - 1]) || is_blank(line) {
+            unwrapped.push('\n');
+        } else {
+            if unwrapped.ends_with('\r') {
+                unwrapped.pop();
+                unwrapped.push(' ');
+            }
+            unwrapped.push(' ');
+        }
+        unwrapped.push_str(line);
+    }
+    unwrapped
+}
+
+/// What happens to sentences with more tokens than [TokenizerOptions::max_sentence_tokens].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LongSentences {
+    /// Split the sentence into parts of the maximum length, which are checked independently.
+    #[default]
+    Split,
+    /// Do not disambiguate or check the sentence at all.
+    Skip,
+}
+
+/// Marks a token in a sentence with more tokens than [TokenizerOptions::max_sentence_tokens].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SentenceGuard {
+    /// The sentence was split after this token.
+    Break,
+    /// The sentence is skipped: its tokens are immune and not disambiguated or checked.
+    Skipped,
+}
+
+/// The ranges of tokens which are disambiguated and checked independently of each other.
+/// Sentences are split after [SentenceGuard::Break] tokens and [SentenceGuard::Skipped] tokens are left out.
+pub(crate) fn guard_ranges(
+    guards: impl Iterator<Item = Option<SentenceGuard>>,
+) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut len = 0;
+
+    for (i, guard) in guards.enumerate() {
+        len = i + 1;
+
+        match guard {
+            Some(SentenceGuard::Break) => {
+                ranges.push(start..i + 1);
+                start = i + 1;
+            }
+            Some(SentenceGuard::Skipped) => {
+                if start < i {
+                    ranges.push(start..i);
+                }
+                start = i + 1;
+            }
+            None => {}
+        }
+    }
+
+    if start < len {
+        ranges.push(start..len);
+    }
+    ranges
+}
+
 /// Options for a tokenizer.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TokenizerOptions {
@@ -105,6 +267,26 @@ pub struct TokenizerOptions {
     /// Used part-of-speech tags which are not in the tagger dictionary.
     #[serde(default)]
     pub extra_tags: Vec<String>,
+    /// Elided words which are joined with a following apostrophe into one token e. g. "l" in French "l'homme".
+    /// Matched case-insensitively.
+    #[serde(default)]
+    pub elisions: Vec<String>,
+    /// Contractions which are split into multiple tokens e. g. "al" into "a" and "el" in Spanish.
+    /// Each part consists of the text it covers in the contraction and the word it stands for,
+    /// so `"du": [["d", "de"], ["u", "le"]]` in French. Matched case-insensitively.
+    #[serde(default, serialize_with = "utils::serialize_sorted_map")]
+    pub contractions: DefaultHashMap<String, Vec<(String, String)>>,
+    /// How to split Chinese and Japanese text which is not separated by whitespace.
+    #[serde(default)]
+    pub segmentation: Segmentation,
+    /// The maximum number of tokens in a sentence. The cost of matching rules grows quadratically with the length
+    /// of a sentence, so very long "sentences" like minified code or logs can take extremely long to check.
+    /// Longer sentences are handled according to `long_sentences` and their tokens get a [SentenceGuard].
+    #[serde(default)]
+    pub max_sentence_tokens: Option<usize>,
+    /// What to do with sentences longer than `max_sentence_tokens`.
+    #[serde(default)]
+    pub long_sentences: LongSentences,
 }
 
 impl Default for TokenizerOptions {
@@ -118,51 +300,228 @@ impl Default for TokenizerOptions {
             ignore_ids: Vec::new(),
             known_failures: Vec::new(),
             extra_tags: Vec::new(),
+            elisions: Vec::new(),
+            contractions: DefaultHashMap::default(),
+            segmentation: Segmentation::default(),
+            max_sentence_tokens: None,
+            long_sentences: LongSentences::default(),
         }
     }
 }
 
+/// A change of a token by a disambiguation rule, recorded by [Tokenizer::disambiguate_traced].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisambiguationChange<'t> {
+    /// The ID of the disambiguation rule which made the change.
+    pub rule_id: &'t str,
+    /// The index of the changed token.
+    pub index: usize,
+    /// The word before the change.
+    pub before: Word<'t>,
+    /// The word after the change.
+    pub after: Word<'t>,
+}
+
 /// The complete Tokenizer doing tagging, chunking and disambiguation.
 #[derive(Serialize, Deserialize, Default)]
 pub struct Tokenizer {
+    // must be the first field so it can be read on its own
+    pub(crate) metadata: Metadata,
     pub(crate) rules: Vec<DisambiguationRule>,
     pub(crate) chunker: Option<Chunker>,
+    pub(crate) multiword_tagger: Option<MultiwordTagger>,
     pub(crate) tagger: Arc<Tagger>,
     pub(crate) options: TokenizerOptions,
+    // fingerprint of the tagger this tokenizer was compiled with
+    pub(crate) fingerprint: u64,
+    #[serde(skip)]
+    pub(crate) hard_wrapped: bool,
+    // computed on first use, must be reset when the fields it covers change
+    #[serde(skip)]
+    pub(crate) checksum: OnceCell<u64>,
 }
 
 impl Tokenizer {
-    /// Creates a new tokenizer from a file.
-    pub fn new<P: AsRef<Path>>(p: P) -> bincode::Result<Self> {
-        let reader = BufReader::new(File::open(p).unwrap());
-        bincode::deserialize_from(reader)
+    /// Creates a new tokenizer from a file. The file can optionally be gzip compressed.
+    pub fn new<P: AsRef<Path>>(p: P) -> Result<Self, Error> {
+        Self::new_from(File::open(p)?)
+    }
+
+    /// Creates a new tokenizer from a reader. The data can optionally be gzip compressed.
+    /// Binaries of older format versions are migrated if possible, see [Component::migrate].
+    pub fn new_from<R: Read>(reader: R) -> Result<Self, Error> {
+        Self::read(reader)
+    }
+
+    /// Creates a new tokenizer from a reader. Same as [new_from][Self::new_from].
+    /// Useful to load binaries embedded with [embed_nlprule][crate::embed_nlprule].
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Error> {
+        Self::new_from(reader)
     }
 
-    /// Creates a new tokenizer from a reader.
-    pub fn new_from<R: Read>(reader: R) -> bincode::Result<Self> {
-        bincode::deserialize_from(reader)
+    /// Compiles all regexes used by the disambiguation rules. Regexes are otherwise compiled lazily on first use,
+    /// so calling this is only needed if the cost should be paid upfront.
+    pub fn warm_up(&self) {
+        self.rules.maybe_par_iter().for_each(|x| x.warm_up());
+    }
+
+    /// The approximate memory used by the tagger, its extensions, the disambiguation rules, the chunker and
+    /// the multiword tagger. See [MemoryStats].
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        for rule in &self.rules {
+            rule.visit(&mut |leaf| {
+                if let Leaf::Regex(regex) = leaf {
+                    stats.count_regex(regex);
+                }
+            });
+        }
+
+        stats.parts = vec![
+            ("tagger", utils::serialized_size(self.tagger.as_ref())),
+            ("tagger_extension", self.tagger.extension_size()),
+            ("disambiguation_rules", utils::serialized_size(&self.rules)),
+            ("chunker", utils::serialized_size(&self.chunker)),
+            (
+                "multiword_tagger",
+                utils::serialized_size(&self.multiword_tagger),
+            ),
+        ];
+        stats
     }
 
     pub fn rules(&self) -> &Vec<DisambiguationRule> {
         &self.rules
     }
 
+    /// Disables the disambiguation rules with the given IDs. Returns how many rules were disabled.
+    pub fn disable_disambiguation<S: AsRef<str>>(&mut self, ids: &[S]) -> usize {
+        self.set_disambiguation_on(ids, false)
+    }
+
+    /// Enables the disambiguation rules with the given IDs. Returns how many rules were enabled.
+    pub fn enable_disambiguation<S: AsRef<str>>(&mut self, ids: &[S]) -> usize {
+        self.set_disambiguation_on(ids, true)
+    }
+
+    fn set_disambiguation_on<S: AsRef<str>>(&mut self, ids: &[S], on: bool) -> usize {
+        let mut count = 0;
+
+        for rule in self
+            .rules
+            .iter_mut()
+            .filter(|x| ids.iter().any(|id| id.as_ref() == x.id()))
+        {
+            rule.set_on(on);
+            count += 1;
+        }
+
+        self.checksum = OnceCell::new();
+        count
+    }
+
     pub fn tagger(&self) -> &Arc<Tagger> {
         &self.tagger
     }
 
+    /// Layers a [TaggerExtension][tag::TaggerExtension] over the tagger, see [Tagger::extend]. The tagger is
+    /// copied first if it is shared with other tokenizers.
+    pub fn extend_tagger(&mut self, extension: &tag::TaggerExtension) -> Result<(), Error> {
+        Arc::make_mut(&mut self.tagger).extend(extension)
+    }
+
+    /// Information about the source of this tokenizer.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// The fingerprint of the tagger this tokenizer was compiled with. See [Tagger::fingerprint].
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// A checksum of the contents of this tokenizer besides the tagger: the disambiguation rules and whether
+    /// they are enabled, the chunker, the multiword tagger and the options. The tagger is identified by its
+    /// [fingerprint][Tokenizer::fingerprint] and the extensions layered over it.
+    pub fn checksum(&self) -> u64 {
+        *self.checksum.get_or_init(|| {
+            let mut hasher = StableHasher::default();
+            let contents = (
+                &self.metadata,
+                &self.rules,
+                &self.chunker,
+                &self.multiword_tagger,
+                &self.options,
+                self.fingerprint,
+            );
+            hasher.write(&bincode::serialize(&contents).expect("tokenizers can be serialized"));
+            hasher.finish()
+        })
+    }
+
     pub fn chunker(&self) -> &Option<Chunker> {
         &self.chunker
     }
 
+    pub fn multiword_tagger(&self) -> &Option<MultiwordTagger> {
+        &self.multiword_tagger
+    }
+
     pub fn options(&self) -> &TokenizerOptions {
         &self.options
     }
 
+    /// Whether texts are treated as hard-wrapped, see [set_hard_wrapped][Tokenizer::set_hard_wrapped].
+    pub fn hard_wrapped(&self) -> bool {
+        self.hard_wrapped
+    }
+
+    /// Treats single line breaks as spaces when finding sentences, so lines of hard-wrapped text like commit
+    /// messages or LaTeX source are not taken for separate sentences. Empty lines still separate paragraphs.
+    /// [Rules::apply][crate::Rules::apply] then also keeps the line breaks in the replacements of suggestions
+    /// spanning multiple lines, see [Suggestion::reflow][crate::types::Suggestion::reflow].
+    /// Not stored in the binary.
+    pub fn set_hard_wrapped(&mut self, hard_wrapped: bool) {
+        self.hard_wrapped = hard_wrapped;
+    }
+
     pub(crate) fn disambiguate_up_to_id<'t>(
+        &'t self,
+        tokens: Vec<IncompleteToken<'t>>,
+        id: Option<&str>,
+    ) -> Vec<IncompleteToken<'t>> {
+        self.disambiguate_inner(tokens, id, None)
+    }
+
+    fn disambiguate_inner<'t>(
+        &'t self,
+        mut tokens: Vec<IncompleteToken<'t>>,
+        id: Option<&str>,
+        mut trace: Option<&mut Vec<DisambiguationChange<'t>>>,
+    ) -> Vec<IncompleteToken<'t>> {
+        if tokens.iter().all(|x| x.guard.is_none()) {
+            return self.disambiguate_range(tokens, id, trace, 0);
+        }
+
+        for range in guard_ranges(tokens.iter().map(|x| x.guard)) {
+            let part = tokens[range.clone()].to_vec();
+            let part = self.disambiguate_range(part, id, trace.as_deref_mut(), range.start);
+
+            for (token, disambiguated) in tokens[range].iter_mut().zip(part) {
+                *token = disambiguated;
+            }
+        }
+
+        tokens
+    }
+
+    // disambiguates tokens which start at index `offset` of all tokens
+    fn disambiguate_range<'t>(
         &'t self,
         mut tokens: Vec<IncompleteToken<'t>>,
         id: Option<&str>,
+        mut trace: Option<&mut Vec<DisambiguationChange<'t>>>,
+        offset: usize,
     ) -> Vec<IncompleteToken<'t>> {
         let n = id.map_or(self.rules.len(), |id| {
             self.rules.iter().position(|x| x.id == id).unwrap()
@@ -174,6 +533,7 @@ impl Tokenizer {
             let result = self.rules[i..n]
                 .maybe_par_iter()
                 .enumerate()
+                .filter(|(_, rule)| rule.on())
                 .filter_map(|(j, rule)| {
                     let changes = rule.apply(&finalized, &self);
                     if changes.is_empty() {
@@ -185,7 +545,28 @@ impl Tokenizer {
                 .find_first(|_| true);
 
             if let Some((index, changes)) = result {
-                self.rules[index].change(&mut tokens, &self, changes);
+                let rule = &self.rules[index];
+
+                if let Some(trace) = trace.as_mut() {
+                    let before: Vec<_> = tokens.iter().map(|x| x.word.clone()).collect();
+                    rule.change(&mut tokens, self, changes);
+
+                    trace.extend(
+                        before
+                            .into_iter()
+                            .zip(tokens.iter())
+                            .enumerate()
+                            .filter(|(_, (before, token))| *before != token.word)
+                            .map(|(index, (before, token))| DisambiguationChange {
+                                rule_id: rule.id(),
+                                index: offset + index,
+                                before,
+                                after: token.word.clone(),
+                            }),
+                    );
+                } else {
+                    rule.change(&mut tokens, self, changes);
+                }
                 i = index + 1;
             } else {
                 i = n;
@@ -204,12 +585,156 @@ impl Tokenizer {
         self.disambiguate_up_to_id(tokens, None)
     }
 
+    /// Apply rule-based disambiguation to the tokens like [disambiguate][Tokenizer::disambiguate], additionally
+    /// recording every change to a token in the order the changes were made.
+    /// Useful to find out whether wrong tags come from the tagger or from a disambiguation rule.
+    pub fn disambiguate_traced<'t>(
+        &'t self,
+        tokens: Vec<IncompleteToken<'t>>,
+    ) -> (Vec<IncompleteToken<'t>>, Vec<DisambiguationChange<'t>>) {
+        let mut trace = Vec::new();
+        let tokens = self.disambiguate_inner(tokens, None, Some(&mut trace));
+        (tokens, trace)
+    }
+
+    /// Tokenizes and disambiguates the text and formats the result with the changes of the disambiguation rules
+    /// like LanguageTool does, see [lt_format].
+    pub fn lt_log(&self, text: &str) -> String {
+        let (tokens, trace) = self.disambiguate_traced(self.tokenize(text));
+        lt_format::format_trace(&tokens, &trace)
+    }
+
+    /// Splits or skips the sentences with more than `max` tokens.
+    /// `sentence_starts` are the indices of the tokens at which a sentence starts.
+    fn guard_long_sentences(
+        &self,
+        tokens: &mut [IncompleteToken],
+        sentence_starts: &[usize],
+        max: usize,
+    ) {
+        let max = max.max(1);
+        let mut bounds = sentence_starts.to_vec();
+        bounds.push(tokens.len());
+        if bounds[0] != 0 {
+            bounds.insert(0, 0);
+        }
+
+        for (&start, &end) in bounds.iter().zip(&bounds[1..]) {
+            let sentence = &mut tokens[start..end];
+            if sentence.len() <= max {
+                continue;
+            }
+
+            match self.options.long_sentences {
+                LongSentences::Split => {
+                    for token in sentence.iter_mut().skip(max - 1).step_by(max) {
+                        token.is_sentence_end = true;
+                        token.guard = Some(SentenceGuard::Break);
+                    }
+                }
+                LongSentences::Skip => {
+                    for token in sentence.iter_mut() {
+                        token.immune = true;
+                        token.guard = Some(SentenceGuard::Skipped);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Joins elided words with the apostrophe directly following them.
+    fn join_elisions<'t>(&self, text: &'t str, token_strs: Vec<&'t str>) -> Vec<&'t str> {
+        if self.options.elisions.is_empty() {
+            return token_strs;
+        }
+
+        let mut joined: Vec<&'t str> = Vec::with_capacity(token_strs.len());
+
+        for x in token_strs {
+            if let Some(prev) = joined.last_mut() {
+                let prev_start = prev.as_ptr() as usize - text.as_ptr() as usize;
+                let is_elision = matches!(x, "'" | "’")
+                    && self
+                        .options
+                        .elisions
+                        .iter()
+                        .any(|elision| elision.eq_ignore_ascii_case(prev))
+                    // the apostrophe must follow the word without whitespace
+                    && prev_start + prev.len() == x.as_ptr() as usize - text.as_ptr() as usize;
+
+                if is_elision {
+                    *prev = &text[prev_start..prev_start + prev.len() + x.len()];
+                    continue;
+                }
+            }
+
+            joined.push(x);
+        }
+
+        joined
+    }
+
+    /// Splits a token into the parts of a contraction. Each part is returned together with the word it stands for.
+    /// Tokens which are not contractions consist of one part, with surrounding whitespace and
+    /// bidirectional formatting characters trimmed from the word.
+    fn split_contraction<'t>(&'t self, x: &'t str) -> Vec<(&'t str, Cow<'t, str>)> {
+        let trimmed = x.trim_matches(|c: char| c.is_whitespace() || utils::is_bidi_control(c));
+
+        let parts = if self.options.contractions.is_empty() {
+            None
+        } else {
+            self.options.contractions.get(&trimmed.to_lowercase())
+        };
+
+        match parts {
+            // contractions are their own tokens so the token can not contain whitespace
+            Some(parts)
+                if trimmed.len() == x.len()
+                    && parts.iter().map(|(part, _)| part.len()).sum::<usize>() == x.len() =>
+            {
+                let mut start = 0;
+
+                parts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (part, word))| {
+                        let text = &x[start..start + part.len()];
+                        start += part.len();
+
+                        // keep the casing of e. g. "Al" at the start of a sentence
+                        let word = if i == 0 && text != part {
+                            Cow::Owned(utils::apply_to_first(word, |c| c.to_uppercase().collect()))
+                        } else {
+                            Cow::Borrowed(word.as_str())
+                        };
+
+                        (text, word)
+                    })
+                    .collect()
+            }
+            _ => vec![(x, Cow::Borrowed(trimmed))],
+        }
+    }
+
     /// Tokenize the given text. This applies chunking and tagging, but does not do disambiguation.
+    /// Spans are in the logical order of the text, so right-to-left text needs no special treatment.
+    /// Bidirectional formatting characters separate tokens but are not part of any token.
+    /// Does not panic on any input, including the empty string.
     pub fn tokenize<'t>(&'t self, text: &'t str) -> Vec<IncompleteToken<'t>> {
-        let sentence_indices = text
+        let unwrapped;
+        let sentence_text = if self.hard_wrapped && text.contains('\n') {
+            unwrapped = unwrap_lines(text);
+            unwrapped.as_str()
+        } else {
+            text
+        };
+
+        // pointers into `text`, also if the sentences are found in the unwrapped text
+        let sentence_indices = sentence_text
             .unicode_sentences()
             .map(|sentence| {
-                let ptr = sentence.as_ptr() as usize;
+                let ptr = sentence.as_ptr() as usize - sentence_text.as_ptr() as usize
+                    + text.as_ptr() as usize;
                 (ptr, ptr + sentence.len())
             })
             .fold((HashSet::new(), HashSet::new()), |mut a, x| {
@@ -219,45 +744,75 @@ impl Tokenizer {
             });
 
         let mut current_char = 0;
-        let token_strs = get_token_strs(text);
-        let mut tokens: Vec<_> = token_strs
+        let token_strs = get_token_strs(text)
             .into_iter()
-            .map(|x| {
-                let char_start = current_char;
-                let ptr = x.as_ptr() as usize;
-                current_char += x.chars().count();
+            .flat_map(|x| {
+                self.options
+                    .segmentation
+                    .split(x, |word| self.tagger.has_word(word))
+            })
+            .collect();
+        let token_strs = self.join_elisions(text, token_strs);
+        let mut tokens = Vec::new();
+        let mut sentence_starts = Vec::new();
 
-                let byte_start = ptr - text.as_ptr() as usize;
-                let trimmed = x.trim();
+        for x in token_strs {
+            let ptr = x.as_ptr() as usize;
+            let is_sentence_start = sentence_indices.0.contains(&ptr);
+            let is_sentence_end = sentence_indices.1.contains(&(ptr + x.len()));
 
-                let is_sentence_start = sentence_indices.0.contains(&ptr);
-                let is_sentence_end = sentence_indices.1.contains(&(ptr + x.len()));
+            let parts = self.split_contraction(x);
+            let n_parts = parts.len();
 
-                IncompleteToken {
+            for (i, (part, word)) in parts.into_iter().enumerate() {
+                let char_start = current_char;
+                current_char += part.chars().count();
+
+                let byte_start = part.as_ptr() as usize - text.as_ptr() as usize;
+
+                let token = IncompleteToken {
                     word: Word::new_with_tags(
-                        self.tagger.id_word(trimmed.into()),
+                        self.tagger.id_word(word.clone()),
                         self.tagger.get_tags(
-                            trimmed,
-                            is_sentence_start || self.options.always_add_lower_tags,
+                            word.as_ref(),
+                            (is_sentence_start && i == 0) || self.options.always_add_lower_tags,
                             self.options.use_compound_split_heuristic,
                         ),
                     ),
                     char_span: (char_start, current_char),
-                    byte_span: (byte_start, byte_start + x.len()),
-                    is_sentence_end,
-                    has_space_before: text[..byte_start].ends_with(char::is_whitespace),
+                    byte_span: (byte_start, byte_start + part.len()),
+                    is_sentence_end: is_sentence_end && i + 1 == n_parts,
+                    has_space_before: text[..byte_start]
+                        .trim_end_matches(utils::is_bidi_control)
+                        .ends_with(char::is_whitespace),
                     chunks: Vec::new(),
+                    immune: false,
+                    guard: None,
                     text,
                     tagger: self.tagger.as_ref(),
+                };
+
+                if !token.word.text.as_ref().is_empty() {
+                    if is_sentence_start && i == 0 {
+                        sentence_starts.push(tokens.len());
+                    }
+                    tokens.push(token);
                 }
-            })
-            .filter(|token| !token.word.text.as_ref().is_empty())
-            .collect();
+            }
+        }
 
         if !tokens.is_empty() {
             let last_idx = tokens.len() - 1;
             tokens[last_idx].is_sentence_end = true;
 
+            if let Some(max) = self.options.max_sentence_tokens {
+                self.guard_long_sentences(&mut tokens, &sentence_starts, max);
+            }
+
+            if let Some(multiword_tagger) = &self.multiword_tagger {
+                multiword_tagger.apply(&mut tokens);
+            }
+
             if let Some(chunker) = &self.chunker {
                 chunker.apply(&mut tokens);
             }
@@ -269,22 +824,243 @@ impl Tokenizer {
 
 #[cfg(test)]
 mod tests {
-    use super::Tokenizer;
+    use super::{detokenize, finalize, tag::TaggerExtension, Tokenizer};
+    use crate::{Component, Error};
     use lazy_static::lazy_static;
     use quickcheck_macros::quickcheck;
-    use std::fs::File;
-    use std::io::BufReader;
+
+    #[quickcheck]
+    fn splits_like_char_predicate(text: String) -> bool {
+        let mut tokens = Vec::new();
+        super::split_fast(&text, &mut tokens);
+
+        tokens == super::split(&text, crate::utils::is_splitting_char)
+    }
 
     #[quickcheck]
     fn can_tokenize_anything(text: String) -> bool {
         lazy_static! {
-            static ref TOKENIZER: Tokenizer = {
-                let reader = BufReader::new(File::open("../storage/en_tokenizer.bin").unwrap());
-                bincode::deserialize_from(reader).unwrap()
-            };
+            static ref TOKENIZER: Tokenizer =
+                Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
         }
 
         TOKENIZER.tokenize(&text);
         true
     }
+
+    #[test]
+    fn detokenizes_tokens() {
+        let tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let text = "I  can\ndo it.";
+
+        let mut tokens = finalize(tokenizer.tokenize(text));
+        assert_eq!(detokenize(&tokens), text);
+
+        tokens.remove(2);
+        assert_eq!(detokenize(&tokens), "I do it.");
+    }
+
+    #[test]
+    fn traces_disambiguation_changes() {
+        let tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let text = "I can due his homework.";
+
+        let (tokens, trace) = tokenizer.disambiguate_traced(tokenizer.tokenize(text));
+        assert_eq!(tokens, tokenizer.disambiguate(tokenizer.tokenize(text)));
+
+        for change in &trace {
+            assert_ne!(change.before, change.after);
+            assert!(tokenizer.rules().iter().any(|x| x.id() == change.rule_id));
+        }
+        assert!(trace
+            .iter()
+            .any(|x| x.rule_id == "CAN_MD" && x.before.text.as_ref() == "can"));
+    }
+
+    #[test]
+    fn extends_tagger() {
+        let mut tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let path =
+            std::env::temp_dir().join(format!("nlprule-extension-{}.txt", std::process::id()));
+        std::fs::write(&path, "myocarditis\tmyocarditis\tNN\n").unwrap();
+        let extension = TaggerExtension::from_dumps(&[path.to_str().unwrap()]).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let mut bytes = Vec::new();
+        extension.write(&mut bytes, false).unwrap();
+        let extension = TaggerExtension::read(&bytes[..]).unwrap();
+
+        let fingerprint = tokenizer.fingerprint();
+        assert!(tokenizer
+            .tagger()
+            .get_tags("myocarditis", false, false)
+            .is_empty());
+        tokenizer.extend_tagger(&extension).unwrap();
+
+        let tags = tokenizer.tagger().get_tags("myocarditis", false, false);
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].pos.as_ref(), "NN");
+        assert_eq!(tags[0].lemma.as_ref(), "myocarditis");
+        assert_eq!(tokenizer.tagger().fingerprint(), fingerprint);
+
+        let mut unknown = TaggerExtension::default();
+        unknown
+            .entries
+            .insert("foo".into(), vec![("foo".into(), "NOT_A_TAG".into())]);
+        assert!(matches!(
+            tokenizer.extend_tagger(&unknown),
+            Err(Error::Unexpected(_))
+        ));
+    }
+
+    #[test]
+    fn ranks_word_frequencies() {
+        let tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+
+        assert_eq!(tokenizer.tagger().frequency("the"), Some(1));
+        assert_eq!(tokenizer.tagger().frequency("is"), Some(3));
+        assert_eq!(tokenizer.tagger().frequency("homework"), None);
+    }
+
+    #[test]
+    fn formats_trace_like_languagetool() {
+        let tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let log = tokenizer.lt_log("I can due his homework. He knows.");
+        let lines: Vec<_> = log.lines().collect();
+
+        assert_eq!(
+            lines[..4],
+            [
+                "<S> I[I/PRP] can[can/MD] due[due/JJ] his[his/PRP$] homework[homework/NN].[null/null,</S>]",
+                "Disambiguator log: ",
+                "CAN_MD: can[can/MD,can/NN] -> can[can/MD]",
+                "DUE_JJ: due[due/JJ,due/NN] -> due[due/JJ]",
+            ]
+        );
+        assert_eq!(lines.iter().filter(|x| x.starts_with("<S>")).count(), 2);
+    }
+
+    #[test]
+    fn tags_german_multiwords_and_compounds() {
+        let tokenizer = Tokenizer::new("../storage/fixtures/de_tokenizer.bin").unwrap();
+        let tokens = tokenizer.tokenize("Seit gestern ist der Haustürschlüssel z. B. alt.");
+
+        let tags = |text: &str| {
+            tokens
+                .iter()
+                .find(|x| x.word.text.as_ref() == text)
+                .unwrap()
+                .word
+                .tags
+                .iter()
+                .map(|x| (x.lemma.as_ref().to_string(), x.pos.as_ref().to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        // lowercase tags are added at the sentence start
+        assert!(tags("Seit").contains(&("seit".into(), "PRP:TMP+DAT".into())));
+        assert!(tags("Haustürschlüssel")
+            .contains(&("Haustürschlüssel".into(), "SUB:NOM:SIN:MAS".into())));
+        assert!(tags("z").contains(&("z. B.".into(), "ADV:MOD".into())));
+        assert!(tags("B").contains(&("z. B.".into(), "ADV:MOD".into())));
+    }
+
+    #[test]
+    fn can_disable_disambiguation_rules() {
+        let mut tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        let text = "I can due his homework.";
+
+        assert_eq!(tokenizer.disable_disambiguation(&["CAN_MD", "UNKNOWN"]), 1);
+        let (_, trace) = tokenizer.disambiguate_traced(tokenizer.tokenize(text));
+        assert!(trace.iter().all(|x| x.rule_id != "CAN_MD"));
+
+        assert_eq!(tokenizer.enable_disambiguation(&["CAN_MD"]), 1);
+        let (_, trace) = tokenizer.disambiguate_traced(tokenizer.tokenize(text));
+        assert!(trace.iter().any(|x| x.rule_id == "CAN_MD"));
+    }
+
+    #[test]
+    fn splits_contractions_and_joins_elisions() {
+        let mut tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+        tokenizer.options.elisions = vec!["l".into()];
+        tokenizer.options.contractions.insert(
+            "al".into(),
+            vec![("a".into(), "a".into()), ("l".into(), "el".into())],
+        );
+
+        let text = "Al parque con l'homme.";
+        let tokens = tokenizer.tokenize(text);
+        let words: Vec<_> = tokens
+            .iter()
+            .map(|x| (x.word.text.as_ref(), &text[x.byte_span.0..x.byte_span.1]))
+            .collect();
+
+        assert_eq!(
+            words,
+            vec![
+                ("A", "A"),
+                ("el", "l"),
+                ("parque", "parque"),
+                ("con", "con"),
+                ("l'", "l'"),
+                ("homme", "homme"),
+                (".", ".")
+            ]
+        );
+        assert!(!tokens[1].has_space_before);
+        assert_eq!(tokens[1].char_span, (1, 2));
+    }
+
+    #[test]
+    fn tokenizes_bidi_text() {
+        let tokenizer = Tokenizer::new("../storage/fixtures/en_tokenizer.bin").unwrap();
+
+        let text = "He said \u{200F}שלום עולם\u{200F}, then مرحبا؟";
+        let tokens = tokenizer.tokenize(text);
+        let char_indices: Vec<_> = text.char_indices().map(|(i, _)| i).collect();
+
+        let words: Vec<_> = tokens.iter().map(|x| x.word.text.as_ref()).collect();
+        assert_eq!(
+            words,
+            vec!["He", "said", "שלום", "עולם", ",", "then", "مرحبا", "؟"]
+        );
+
+        for token in &tokens {
+            assert_eq!(
+                &text[token.byte_span.0..token.byte_span.1],
+                token.word.text.as_ref()
+            );
+            assert_eq!(char_indices[token.char_span.0], token.byte_span.0);
+        }
+        assert!(tokens[2].has_space_before);
+        assert!(!tokens[4].has_space_before);
+    }
+
+    #[test]
+    fn unwraps_lines_within_paragraphs() {
+        let text = "a line\nwrapped\r\nhere\n\nnew paragraph\n";
+        let unwrapped = super::unwrap_lines(text);
+
+        assert_eq!(unwrapped, "a line wrapped  here\n\nnew paragraph\n");
+        assert_eq!(unwrapped.len(), text.len());
+    }
+
+    #[test]
+    fn serializes_deterministically() {
+        // every deserialized hash map has a different random iteration order
+        let bytes: Vec<_> = (0..2)
+            .map(|_| {
+                let tokenizer = Tokenizer::new("../storage/fixtures/de_tokenizer.bin").unwrap();
+                let mut bytes = Vec::new();
+                tokenizer.write(&mut bytes, false).unwrap();
+                bytes
+            })
+            .collect();
+
+        assert_eq!(bytes[0], bytes[1]);
+        assert_eq!(
+            bytes[0],
+            std::fs::read("../storage/fixtures/de_tokenizer.bin").unwrap()
+        );
+    }
 }
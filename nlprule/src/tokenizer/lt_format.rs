@@ -0,0 +1,129 @@
+//! Analyses and disambiguation traces in the textual format of LanguageTool, as printed by its command line
+//! tool with `--verbose` and by `testrules`. Diffing the output of both implementations sentence by sentence
+//! shows where the disambiguation of nlprule diverges.
+//!
+//! A sentence is printed as `<S> She[she/PRP] was[be/VBD] here[here/RB].[./.,</S>]`, each change by a
+//! disambiguation rule as `RULE_ID: can[can/MD,can/NN] -> can[can/MD]`.
+//!
+//! ```no_run
+//! use nlprule::Tokenizer;
+//!
+//! let tokenizer = Tokenizer::new("path/to/en_tokenizer.bin")?;
+//! print!("{}", tokenizer.lt_log("I can do his homework."));
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+use super::{pos, DisambiguationChange};
+use crate::types::*;
+use std::fmt::Write;
+use unicode_segmentation::UnicodeSegmentation;
+
+// LanguageTool prints missing lemmas and tags as `null`
+fn or_null(value: &str) -> &str {
+    if value.is_empty() {
+        "null"
+    } else {
+        value
+    }
+}
+
+/// Formats the word with its readings like `AnalyzedTokenReadings::toString` in LanguageTool.
+/// Chunk tags follow the readings separated by `|`, immune tokens are marked with `{!}`.
+pub fn readings(word: &Word, chunks: &[ChunkTag], immune: bool) -> String {
+    let mut readings: Vec<String> = word
+        .tags
+        .iter()
+        .filter(|x| x.pos.as_ref() != pos::SENT_END)
+        .map(|x| format!("{}/{}", or_null(x.lemma.as_ref()), or_null(x.pos.as_ref())))
+        .collect();
+    if readings.is_empty() {
+        readings.push("null/null".into());
+    }
+    if word.tags.iter().any(|x| x.pos.as_ref() == pos::SENT_END) {
+        readings.push("</S>".into());
+    }
+    if !chunks.is_empty() {
+        readings.push(
+            chunks
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join("|"),
+        );
+    }
+
+    let mut output = format!("{}[{}]", word.text.as_ref(), readings.join(","));
+    if immune {
+        output.push_str("{!}");
+    }
+    output
+}
+
+fn sentence(tokens: &[IncompleteToken]) -> String {
+    let mut output = String::from("<S>");
+
+    for (i, token) in tokens.iter().enumerate() {
+        if i == 0 || token.has_space_before {
+            output.push(' ');
+        }
+
+        let mut word = token.word.clone();
+        if i + 1 == tokens.len() {
+            word.tags.push(WordData::new(
+                word.text.clone(),
+                token.tagger.id_tag(pos::SENT_END),
+            ));
+        }
+        output.push_str(&readings(&word, &token.chunks, token.immune));
+    }
+
+    output
+}
+
+/// Formats disambiguated tokens and the changes recorded by
+/// [Tokenizer::disambiguate_traced][super::Tokenizer::disambiguate_traced]. Each sentence is followed by
+/// the changes to its tokens in the order they were made.
+pub fn format_trace(tokens: &[IncompleteToken], trace: &[DisambiguationChange]) -> String {
+    let mut output = String::new();
+    let text = match tokens.first() {
+        Some(token) => token.text,
+        None => return output,
+    };
+
+    // the char ends of the sentences, found like the tokenizer does
+    let mut sentence_ends = text.split_sentence_bounds().scan(0, |end, sentence| {
+        *end += sentence.chars().count();
+        Some(*end)
+    });
+    let mut start = 0;
+
+    while start < tokens.len() {
+        let sentence_end = sentence_ends.next().unwrap_or(usize::MAX);
+        let end = tokens[start..]
+            .iter()
+            .position(|x| x.char_span.0 >= sentence_end)
+            .map_or(tokens.len(), |x| start + x);
+        if end == start {
+            continue;
+        }
+
+        writeln!(output, "{}", sentence(&tokens[start..end])).unwrap();
+        writeln!(output, "Disambiguator log: ").unwrap();
+        for change in trace.iter().filter(|x| (start..end).contains(&x.index)) {
+            let chunks = &tokens[change.index].chunks;
+            writeln!(
+                output,
+                "{}: {} -> {}",
+                change.rule_id,
+                readings(&change.before, chunks, false),
+                readings(&change.after, chunks, false)
+            )
+            .unwrap();
+        }
+        output.push('\n');
+
+        start = end;
+    }
+
+    output
+}
@@ -0,0 +1,524 @@
+//! Reading of the binary Morfologik dictionaries LanguageTool uses for part-of-speech tagging (e. g. `english.dict`).
+//! A dictionary is a finite state automaton (in the FSA5 or CFSA2 format) accepting sequences of the form
+//! `{word}{separator}{encoded lemma}{separator}{tag}`, with the separator and the lemma encoding described
+//! in an `.info` file next to the dictionary.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+const FSA_MAGIC: &[u8] = b"\\fsa";
+const FSA5_VERSION: u8 = 5;
+const CFSA2_VERSION: u8 = 0xc6;
+// in an encoded lemma, this means that the whole word has to be removed
+const REMOVE_EVERYTHING: usize = 255;
+
+fn invalid<T>(message: impl Into<String>) -> io::Result<T> {
+    Err(io::Error::new(ErrorKind::InvalidData, message.into()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Fsa5 {
+        // length of node data and of the goto field of arcs, in bytes
+        node_data_length: usize,
+        gtl: usize,
+    },
+    Cfsa2 {
+        has_numbers: bool,
+    },
+}
+
+/// A finite state automaton in one of the Morfologik formats.
+struct Fsa {
+    format: Format,
+    arcs: Vec<u8>,
+    label_mapping: Vec<u8>,
+}
+
+impl Fsa {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < 8 || !bytes.starts_with(FSA_MAGIC) {
+            return invalid("not a Morfologik automaton");
+        }
+
+        match bytes[4] {
+            FSA5_VERSION => {
+                // bytes[5] and bytes[6] are the filler and annotation characters
+                let hgtl = bytes[7] as usize;
+
+                Ok(Fsa {
+                    format: Format::Fsa5 {
+                        node_data_length: (hgtl >> 4) & 0x0f,
+                        gtl: hgtl & 0x0f,
+                    },
+                    arcs: bytes[8..].to_vec(),
+                    label_mapping: Vec::new(),
+                })
+            }
+            CFSA2_VERSION => {
+                const NUMBERS: u16 = 1 << 8;
+
+                let flags = u16::from_be_bytes([bytes[5], bytes[6]]);
+                let label_mapping_size = bytes[7] as usize;
+                if bytes.len() < 8 + label_mapping_size {
+                    return invalid("truncated label mapping");
+                }
+
+                Ok(Fsa {
+                    format: Format::Cfsa2 {
+                        has_numbers: flags & NUMBERS != 0,
+                    },
+                    label_mapping: bytes[8..8 + label_mapping_size].to_vec(),
+                    arcs: bytes[8 + label_mapping_size..].to_vec(),
+                })
+            }
+            version => invalid(format!("unsupported automaton version {}", version)),
+        }
+    }
+
+    fn byte(&self, offset: usize) -> io::Result<u8> {
+        match self.arcs.get(offset) {
+            Some(byte) => Ok(*byte),
+            None => invalid("arc offset out of bounds"),
+        }
+    }
+
+    fn read_vint(&self, mut offset: usize) -> io::Result<(usize, usize)> {
+        let mut value = 0;
+        let mut shift = 0;
+
+        loop {
+            let byte = self.byte(offset)?;
+            value |= ((byte & 0x7f) as usize) << shift;
+            offset += 1;
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                return Ok((value, offset));
+            }
+        }
+    }
+
+    // (final, last, target next)
+    fn flags(&self, arc: usize) -> io::Result<(bool, bool, bool)> {
+        Ok(match self.format {
+            Format::Fsa5 { .. } => {
+                let flags = self.byte(arc + 1)?;
+                (flags & 1 != 0, flags & 2 != 0, flags & 4 != 0)
+            }
+            Format::Cfsa2 { .. } => {
+                let flags = self.byte(arc)?;
+                (flags & 0x20 != 0, flags & 0x40 != 0, flags & 0x80 != 0)
+            }
+        })
+    }
+
+    fn label(&self, arc: usize) -> io::Result<u8> {
+        match self.format {
+            Format::Fsa5 { .. } => self.byte(arc),
+            Format::Cfsa2 { .. } => match (self.byte(arc)? & 0x1f) as usize {
+                0 => self.byte(arc + 1),
+                index => match self.label_mapping.get(index) {
+                    Some(label) => Ok(*label),
+                    None => invalid("label index out of bounds"),
+                },
+            },
+        }
+    }
+
+    fn first_arc(&self, node: usize) -> io::Result<usize> {
+        match self.format {
+            Format::Fsa5 {
+                node_data_length, ..
+            } => Ok(node + node_data_length),
+            Format::Cfsa2 { has_numbers: true } => Ok(self.read_vint(node)?.1),
+            Format::Cfsa2 { has_numbers: false } => Ok(node),
+        }
+    }
+
+    fn skip_arc(&self, arc: usize) -> io::Result<usize> {
+        let (_, _, next) = self.flags(arc)?;
+
+        match self.format {
+            Format::Fsa5 { gtl, .. } => Ok(arc + if next { 2 } else { 1 + gtl }),
+            Format::Cfsa2 { .. } => {
+                let mut offset = arc + 1;
+                if self.byte(arc)? & 0x1f == 0 {
+                    offset += 1;
+                }
+                if !next {
+                    offset = self.read_vint(offset)?.1;
+                }
+                Ok(offset)
+            }
+        }
+    }
+
+    fn next_arc(&self, arc: usize) -> io::Result<Option<usize>> {
+        let (_, last, _) = self.flags(arc)?;
+        Ok(if last {
+            None
+        } else {
+            Some(self.skip_arc(arc)?)
+        })
+    }
+
+    // the target node of an arc, zero if the arc is terminal
+    fn target(&self, mut arc: usize) -> io::Result<usize> {
+        let (_, _, next) = self.flags(arc)?;
+
+        if next {
+            // the target is the node directly after the last arc of this node
+            while let Some(next_arc) = self.next_arc(arc)? {
+                arc = next_arc;
+            }
+            return self.skip_arc(arc);
+        }
+
+        match self.format {
+            Format::Fsa5 { gtl, .. } => {
+                let mut address = 0;
+                for i in (0..gtl).rev() {
+                    address = (address << 8) | self.byte(arc + 1 + i)? as usize;
+                }
+                Ok(address >> 3)
+            }
+            Format::Cfsa2 { .. } => {
+                let offset = if self.byte(arc)? & 0x1f == 0 { 2 } else { 1 };
+                Ok(self.read_vint(arc + offset)?.0)
+            }
+        }
+    }
+
+    fn root(&self) -> io::Result<usize> {
+        match self.format {
+            Format::Fsa5 { .. } => {
+                // the first node is a dummy, followed by an epsilon node with one arc to the root
+                let epsilon = self.skip_arc(self.first_arc(0)?)?;
+                self.target(self.first_arc(epsilon)?)
+            }
+            Format::Cfsa2 { .. } => self.target(self.first_arc(0)?),
+        }
+    }
+
+    /// Calls `f` with every sequence accepted by the automaton.
+    fn visit(&self, f: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        fn visit_node(
+            fsa: &Fsa,
+            node: usize,
+            buffer: &mut Vec<u8>,
+            f: &mut dyn FnMut(&[u8]) -> io::Result<()>,
+        ) -> io::Result<()> {
+            let mut arc = Some(fsa.first_arc(node)?);
+
+            while let Some(current) = arc {
+                buffer.push(fsa.label(current)?);
+
+                let (is_final, _, _) = fsa.flags(current)?;
+                if is_final {
+                    f(buffer)?;
+                }
+
+                let target = fsa.target(current)?;
+                if target != 0 {
+                    visit_node(fsa, target, buffer, f)?;
+                }
+
+                buffer.pop();
+                arc = fsa.next_arc(current)?;
+            }
+
+            Ok(())
+        }
+
+        visit_node(self, self.root()?, &mut Vec::new(), f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoder {
+    None,
+    Suffix,
+    Prefix,
+    Infix,
+}
+
+impl Encoder {
+    // decodes the lemma of a word from the encoded bytes, see the `ISequenceEncoder` implementations in Morfologik
+    fn decode(&self, word: &[u8], encoded: &[u8]) -> Option<Vec<u8>> {
+        let code = |i: usize| encoded.get(i).map(|x| x.wrapping_sub(b'A') as usize);
+
+        let (kept, rest): (Vec<&[u8]>, &[u8]) = match self {
+            Encoder::None => (Vec::new(), encoded),
+            Encoder::Suffix => {
+                let mut suffix = code(0)?;
+                if suffix == REMOVE_EVERYTHING {
+                    suffix = word.len();
+                }
+
+                (
+                    vec![word.get(..word.len().checked_sub(suffix)?)?],
+                    &encoded[1..],
+                )
+            }
+            Encoder::Prefix => {
+                let (mut prefix, mut suffix) = (code(0)?, code(1)?);
+                if prefix == REMOVE_EVERYTHING || suffix == REMOVE_EVERYTHING {
+                    prefix = word.len();
+                    suffix = 0;
+                }
+
+                let end = word.len().checked_sub(suffix)?;
+                (vec![word.get(prefix.min(end)..end)?], &encoded[2..])
+            }
+            Encoder::Infix => {
+                let (index, mut length, mut suffix) = (code(0)?, code(1)?, code(2)?);
+                if length == REMOVE_EVERYTHING || suffix == REMOVE_EVERYTHING {
+                    length = word.len();
+                    suffix = 0;
+                }
+
+                let end = word.len().checked_sub(suffix)?;
+                let infix_end = (index + length).min(end);
+                (
+                    vec![word.get(..index.min(end))?, word.get(infix_end..end)?],
+                    &encoded[3..],
+                )
+            }
+        };
+
+        Some(
+            kept.into_iter()
+                .chain(std::iter::once(rest))
+                .flatten()
+                .copied()
+                .collect(),
+        )
+    }
+}
+
+/// The metadata of a dictionary, read from its `.info` file.
+struct Info {
+    separator: u8,
+    latin1: bool,
+    encoder: Encoder,
+}
+
+impl Info {
+    fn from_properties(text: &str) -> io::Result<Self> {
+        let properties: HashMap<&str, &str> = text
+            .lines()
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty() && !x.starts_with('#'))
+            .filter_map(|x| {
+                let mut parts = x.splitn(2, '=');
+                Some((parts.next()?.trim(), parts.next()?.trim()))
+            })
+            .collect();
+
+        let latin1 = match properties
+            .get("fsa.dict.encoding")
+            .map(|x| x.to_lowercase())
+            .as_deref()
+        {
+            Some("utf-8") | Some("utf8") | None => false,
+            Some("iso-8859-1") | Some("iso8859-1") | Some("latin1") => true,
+            Some(encoding) => return invalid(format!("unsupported encoding {}", encoding)),
+        };
+
+        let separator = match properties.get("fsa.dict.separator") {
+            Some(separator) if separator.len() == 1 => separator.as_bytes()[0],
+            None => b'+',
+            Some(separator) => return invalid(format!("invalid separator {:?}", separator)),
+        };
+
+        let flag = |key: &str| properties.get(key) == Some(&"true");
+        let encoder = match properties.get("fsa.dict.encoder").map(|x| x.to_uppercase()) {
+            Some(x) if x == "NONE" => Encoder::None,
+            Some(x) if x == "SUFFIX" => Encoder::Suffix,
+            Some(x) if x == "PREFIX" => Encoder::Prefix,
+            Some(x) if x == "INFIX" => Encoder::Infix,
+            Some(x) => return invalid(format!("unsupported encoder {}", x)),
+            // older dictionaries use flags instead
+            None if flag("fsa.dict.uses-infixes") => Encoder::Infix,
+            None if flag("fsa.dict.uses-prefixes") => Encoder::Prefix,
+            None => Encoder::Suffix,
+        };
+
+        Ok(Info {
+            separator,
+            latin1,
+            encoder,
+        })
+    }
+
+    fn decode_string(&self, bytes: &[u8]) -> io::Result<String> {
+        if self.latin1 {
+            Ok(bytes.iter().map(|x| *x as char).collect())
+        } else {
+            String::from_utf8(bytes.to_vec())
+                .or_else(|_| invalid("dictionary entry is not valid UTF-8"))
+        }
+    }
+}
+
+fn read_entries(fsa: &Fsa, info: &Info) -> io::Result<Vec<(String, String, String)>> {
+    let mut entries = Vec::new();
+
+    fsa.visit(&mut |sequence| {
+        let mut parts = sequence.splitn(3, |x| *x == info.separator);
+
+        if let (Some(word), Some(encoded), Some(tag)) = (parts.next(), parts.next(), parts.next()) {
+            let lemma = match info.encoder.decode(word, encoded) {
+                Some(lemma) => lemma,
+                None => return invalid("invalid lemma encoding"),
+            };
+
+            entries.push((
+                info.decode_string(word)?,
+                info.decode_string(&lemma)?,
+                info.decode_string(tag)?,
+            ));
+        }
+
+        Ok(())
+    })?;
+
+    Ok(entries)
+}
+
+/// Reads all `(word, lemma, part-of-speech)` entries from a Morfologik dictionary.
+/// The `.info` file must be next to the dictionary e. g. `english.info` for `english.dict`.
+pub(crate) fn read_dictionary<P: AsRef<Path>>(
+    path: P,
+) -> io::Result<Vec<(String, String, String)>> {
+    let path = path.as_ref();
+
+    let fsa = Fsa::from_bytes(&fs::read(path)?)?;
+    let info = Info::from_properties(&fs::read_to_string(path.with_extension("info"))?)?;
+
+    read_entries(&fsa, &info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // builds an FSA5 automaton from sorted sequences as a trie, with four byte addresses and without
+    // the `target next` optimization
+    fn build_fsa5(sequences: &[&[u8]]) -> Vec<u8> {
+        #[derive(Default)]
+        struct Node {
+            children: Vec<(u8, bool, Node)>,
+        }
+
+        let mut root = Node::default();
+        for sequence in sequences {
+            let mut node = &mut root;
+            for (i, label) in sequence.iter().enumerate() {
+                let is_final = i + 1 == sequence.len();
+                let index = match node.children.iter().position(|x| x.0 == *label) {
+                    Some(index) => index,
+                    None => {
+                        node.children.push((*label, false, Node::default()));
+                        node.children.len() - 1
+                    }
+                };
+                node.children[index].1 |= is_final;
+                node = &mut node.children[index].2;
+            }
+        }
+
+        fn size(node: &Node) -> usize {
+            node.children.len() * 5 + node.children.iter().map(|x| size(&x.2)).sum::<usize>()
+        }
+
+        fn emit(node: &Node, offset: usize, out: &mut Vec<u8>) {
+            let mut child_offset = offset + node.children.len() * 5;
+            let mut targets = Vec::new();
+
+            for (i, (label, is_final, child)) in node.children.iter().enumerate() {
+                let target = if child.children.is_empty() {
+                    0
+                } else {
+                    child_offset
+                };
+                targets.push(target);
+                child_offset += size(child);
+
+                let flags = *is_final as u32 | (((i + 1 == node.children.len()) as u32) << 1);
+                out.push(*label);
+                out.extend(&(flags | (target as u32) << 3).to_le_bytes());
+            }
+
+            for ((_, _, child), target) in node.children.iter().zip(targets) {
+                if target != 0 {
+                    emit(child, target, out);
+                }
+            }
+        }
+
+        let mut out = b"\\fsa\x05_\x00\x04".to_vec();
+        // dummy node and epsilon node pointing to the root at offset 10
+        out.extend(&[0, 2, 0, 0, 0]);
+        out.extend(&[0, 2 | (10 << 3), 0, 0, 0]);
+        emit(&root, 10, &mut out);
+        out
+    }
+
+    #[test]
+    fn reads_fsa5_dictionary() {
+        let fsa = Fsa::from_bytes(&build_fsa5(&[
+            b"houses+B+NNS",
+            b"ran+Cun+VBD",
+            b"running+E+VBG",
+        ]))
+        .unwrap();
+        let info = Info::from_properties("fsa.dict.separator=+\nfsa.dict.encoding=utf-8").unwrap();
+
+        assert_eq!(
+            read_entries(&fsa, &info).unwrap(),
+            vec![
+                ("houses".into(), "house".into(), "NNS".into()),
+                ("ran".into(), "run".into(), "VBD".into()),
+                ("running".into(), "run".into(), "VBG".into()),
+            ]
+        );
+    }
+
+    // `cfsa2.dict` is a minimized CFSA2 automaton with a label index, explicit labels and arcs using the next bit
+    #[test]
+    fn reads_cfsa2_dictionary() {
+        let path = "tests/fixtures/morfologik/cfsa2.dict";
+        let fsa = Fsa::from_bytes(&fs::read(path).unwrap()).unwrap();
+        assert_eq!(fsa.format, Format::Cfsa2 { has_numbers: false });
+
+        let entries = read_dictionary(path).unwrap();
+        assert_eq!(entries.len(), 12);
+        assert!(entries.contains(&("Häuser".into(), "Haus".into(), "SUB:NOM:PLU".into())));
+        assert!(entries.contains(&("ran".into(), "run".into(), "VBD".into())));
+        assert!(entries.contains(&("wives".into(), "wife".into(), "NNS".into())));
+        assert!(entries.contains(&("1990s".into(), "1990s".into(), "CD".into())));
+    }
+
+    #[test]
+    fn decodes_lemmas() {
+        assert_eq!(
+            Encoder::Suffix.decode(b"houses", b"B"),
+            Some(b"house".to_vec())
+        );
+        assert_eq!(
+            Encoder::Prefix.decode(b"gelaufen", b"CA"),
+            Some(b"laufen".to_vec())
+        );
+        assert_eq!(
+            Encoder::Infix.decode(b"aufgelaufen", b"DCA"),
+            Some(b"auflaufen".to_vec())
+        );
+        assert_eq!(Encoder::Suffix.decode(b"a", b"C"), None);
+    }
+}
@@ -0,0 +1,66 @@
+//! Tagging of multi-word expressions such as "New York" or "z. B." which consist of multiple tokens
+//! but have a part-of-speech tag as a whole. Ported from the LanguageTool `MultiWordChunker`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::*;
+
+/// A multi-word expression split into its tokens.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Multiword {
+    // the tokens after the first one
+    pub(crate) rest: Vec<String>,
+    pub(crate) phrase: String,
+    pub(crate) tag: String,
+}
+
+/// Adds the tag of a multi-word expression to all of its tokens, with the whole expression as lemma.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct MultiwordTagger {
+    // maps the first token of an expression to the expressions starting with it, longest first
+    #[serde(serialize_with = "crate::utils::serialize_sorted_map")]
+    pub(crate) multiwords: DefaultHashMap<String, Vec<Multiword>>,
+}
+
+impl MultiwordTagger {
+    /// The part-of-speech tags assigned by this tagger.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.multiwords.values().flatten().map(|x| x.tag.as_str())
+    }
+
+    /// Tags the multi-word expressions in the tokens. Matching is case sensitive.
+    pub fn apply<'t>(&'t self, tokens: &mut [IncompleteToken<'t>]) {
+        let mut i = 0;
+
+        while i < tokens.len() {
+            let multiword =
+                self.multiwords
+                    .get(tokens[i].word.text.as_ref())
+                    .and_then(|candidates| {
+                        candidates.iter().find(|x| {
+                            tokens.len() > i + x.rest.len()
+                                && x.rest
+                                    .iter()
+                                    .zip(&tokens[i + 1..])
+                                    .all(|(text, token)| token.word.text.as_ref() == text)
+                        })
+                    });
+
+            if let Some(multiword) = multiword {
+                let n = multiword.rest.len() + 1;
+
+                for token in &mut tokens[i..i + n] {
+                    let data = WordData::new(
+                        token.tagger.id_word(multiword.phrase.as_str().into()),
+                        token.tagger.id_tag(multiword.tag.as_str()),
+                    );
+                    token.word.tags.push(data);
+                }
+
+                i += n;
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
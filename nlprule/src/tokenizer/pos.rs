@@ -0,0 +1,104 @@
+//! Constants for part-of-speech tags, so code matching on tags does not need to hardcode strings.
+//! The special tags are in every tagger, the tags in the language modules are the common ones of the
+//! LanguageTool tagset of the language. Use [Tagger::has_tag][super::tag::Tagger::has_tag] to check that a
+//! tagger knows a tag.
+//!
+//! ```no_run
+//! use nlprule::{tokenizer::{finalize, pos}, Tokenizer};
+//!
+//! let tokenizer = Tokenizer::new("path/to/en_tokenizer.bin")?;
+//! assert!(tokenizer.tagger().has_tag(pos::en::NNS));
+//!
+//! for token in finalize(tokenizer.disambiguate(tokenizer.tokenize("The houses are old."))) {
+//!     let plural = token.word.tags.iter().any(|x| x.pos.as_ref() == pos::en::NNS);
+//! }
+//! # Ok::<(), nlprule::Error>(())
+//! ```
+
+/// The tag of the special token at the start of each sentence.
+pub const SENT_START: &str = "SENT_START";
+/// Added to the last token of a sentence.
+pub const SENT_END: &str = "SENT_END";
+/// The tag of tokens which the tagger does not know.
+pub const UNKNOWN: &str = "UNKNOWN";
+
+/// Tags of the English tagset, which is based on the Penn Treebank tagset.
+pub mod en {
+    /// Coordinating conjunction.
+    pub const CC: &str = "CC";
+    /// Cardinal number.
+    pub const CD: &str = "CD";
+    /// Determiner.
+    pub const DT: &str = "DT";
+    /// Preposition or subordinating conjunction.
+    pub const IN: &str = "IN";
+    /// Adjective.
+    pub const JJ: &str = "JJ";
+    /// Comparative adjective.
+    pub const JJR: &str = "JJR";
+    /// Superlative adjective.
+    pub const JJS: &str = "JJS";
+    /// Modal verb.
+    pub const MD: &str = "MD";
+    /// Singular or mass noun.
+    pub const NN: &str = "NN";
+    /// Plural noun.
+    pub const NNS: &str = "NNS";
+    /// Singular proper noun.
+    pub const NNP: &str = "NNP";
+    /// Plural proper noun.
+    pub const NNPS: &str = "NNPS";
+    /// Personal pronoun.
+    pub const PRP: &str = "PRP";
+    /// Possessive pronoun.
+    pub const PRP_POSSESSIVE: &str = "PRP$";
+    /// Adverb.
+    pub const RB: &str = "RB";
+    /// "to".
+    pub const TO: &str = "TO";
+    /// Interjection.
+    pub const UH: &str = "UH";
+    /// Verb, base form.
+    pub const VB: &str = "VB";
+    /// Verb, past tense.
+    pub const VBD: &str = "VBD";
+    /// Verb, gerund or present participle.
+    pub const VBG: &str = "VBG";
+    /// Verb, past participle.
+    pub const VBN: &str = "VBN";
+    /// Verb, non-3rd person singular present.
+    pub const VBP: &str = "VBP";
+    /// Verb, 3rd person singular present.
+    pub const VBZ: &str = "VBZ";
+    /// Wh-determiner.
+    pub const WDT: &str = "WDT";
+    /// Wh-pronoun.
+    pub const WP: &str = "WP";
+    /// Wh-adverb.
+    pub const WRB: &str = "WRB";
+}
+
+/// Word classes of the German tagset. German tags combine the word class with features separated by `:`,
+/// e. g. `SUB:NOM:SIN:MAS`, so these are prefixes of the tags.
+pub mod de {
+    /// Adjective.
+    pub const ADJ: &str = "ADJ";
+    /// Adverb.
+    pub const ADV: &str = "ADV";
+    /// Article.
+    pub const ART: &str = "ART";
+    /// Conjunction.
+    pub const KON: &str = "KON";
+    /// Preposition.
+    pub const PRP: &str = "PRP";
+    /// Pronoun.
+    pub const PRO: &str = "PRO";
+    /// Noun.
+    pub const SUB: &str = "SUB";
+    /// Verb.
+    pub const VER: &str = "VER";
+    /// Proper noun.
+    pub const EIG: &str = "EIG";
+    /// Number.
+    pub const ZAL: &str = "ZAL";
+}
@@ -0,0 +1,101 @@
+//! Segmentation of Chinese and Japanese text, which is not separated by whitespace.
+//! Without segmentation a whole CJK sentence would be one token, so no rule could ever match inside of it.
+
+use serde::{Deserialize, Serialize};
+
+// the longest word which is looked up in dictionary segmentation, in characters
+const MAX_WORD_CHARS: usize = 8;
+
+/// How text in CJK scripts is split into tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Segmentation {
+    /// Split only at whitespace and punctuation, like any other text.
+    #[default]
+    Whitespace,
+    /// Split CJK text into single characters.
+    Character,
+    /// Split CJK text into the longest words known to the tagger, falling back to single characters.
+    Dictionary,
+}
+
+/// Whether the character belongs to a CJK script or is CJK punctuation.
+pub fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3000}'..='\u{303F}' // CJK symbols and punctuation
+            | '\u{3040}'..='\u{30FF}' // Hiragana and Katakana
+            | '\u{3400}'..='\u{4DBF}' // CJK unified ideographs extension A
+            | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+            | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+            | '\u{FF00}'..='\u{FFEF}' // halfwidth and fullwidth forms
+            | '\u{20000}'..='\u{2FA1F}' // supplementary ideographic planes
+    )
+}
+
+impl Segmentation {
+    /// Splits the CJK runs in a token. `is_known` decides whether a word is in the dictionary.
+    /// Text in other scripts is kept together.
+    pub(crate) fn split<'t, F: Fn(&str) -> bool>(
+        &self,
+        token: &'t str,
+        is_known: F,
+    ) -> Vec<&'t str> {
+        if *self == Segmentation::Whitespace || !token.chars().any(is_cjk) {
+            return vec![token];
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = token;
+
+        while let Some(first) = rest.chars().next() {
+            let len = if is_cjk(first) {
+                let fallback = first.len_utf8();
+
+                if *self == Segmentation::Dictionary {
+                    // greedy longest match
+                    rest.char_indices()
+                        .skip(1)
+                        .map(|(i, _)| i)
+                        .chain(std::iter::once(rest.len()))
+                        .take(MAX_WORD_CHARS)
+                        .take_while(|i| rest[..*i].chars().all(is_cjk))
+                        .filter(|i| is_known(&rest[..*i]))
+                        .last()
+                        .unwrap_or(fallback)
+                } else {
+                    fallback
+                }
+            } else {
+                rest.find(is_cjk).unwrap_or(rest.len())
+            };
+
+            parts.push(&rest[..len]);
+            rest = &rest[len..];
+        }
+
+        parts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Segmentation;
+
+    #[test]
+    fn segments_cjk_text() {
+        let is_known = |x: &str| ["北京", "北京大学", "大学"].contains(&x);
+
+        assert_eq!(
+            Segmentation::Whitespace.split("我在北京大学。", is_known),
+            vec!["我在北京大学。"]
+        );
+        assert_eq!(
+            Segmentation::Character.split("我在北京abc", is_known),
+            vec!["我", "在", "北", "京", "abc"]
+        );
+        assert_eq!(
+            Segmentation::Dictionary.split("我在北京大学。", is_known),
+            vec!["我", "在", "北京大学", "。"]
+        );
+    }
+}
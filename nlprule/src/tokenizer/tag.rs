@@ -1,21 +1,82 @@
 //! A dictionary-based tagger. The raw format is tuples of the form `(word, lemma, part-of-speech)`
 //! where each word typically has multiple entries with different part-of-speech tags.
 
-use crate::types::*;
+use super::pos;
+use crate::{types::*, utils, utils::parallelism::MaybeParallelIterator, Error};
 use bimap::BiMap;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::hash::Hasher;
 use std::io::BufRead;
 use std::{borrow::Cow, fs::File};
 
+/// Additional words with their lemmas and tags, e. g. the terminology of a domain. Compiled to a small binary
+/// of its own and layered over a loaded tagger with [Tokenizer::extend_tagger][super::Tokenizer::extend_tagger],
+/// so tagging can be improved without compiling the tokenizer again.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct TaggerExtension {
+    #[serde(serialize_with = "utils::serialize_sorted_map")]
+    pub(crate) entries: DefaultHashMap<String, Vec<(String, String)>>,
+}
+
+impl TaggerExtension {
+    /// Creates an extension from raw files in the same formats as [Tagger::from_dumps].
+    pub fn from_dumps<S: AsRef<str>>(paths: &[S]) -> std::io::Result<Self> {
+        let mut entries: DefaultHashMap<String, Vec<(String, String)>> = DefaultHashMap::default();
+
+        for (word, inflection, tag) in Tagger::get_lines(paths, &[] as &[&str])? {
+            let word_entries = entries.entry(word).or_default();
+            if !word_entries.contains(&(inflection.clone(), tag.clone())) {
+                word_entries.push((inflection, tag));
+            }
+        }
+
+        Ok(TaggerExtension { entries })
+    }
+
+    /// The number of words in this extension.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether this extension does not contain any words.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The tags used by this extension.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .values()
+            .flat_map(|x| x.iter().map(|(_, tag)| tag.as_str()))
+    }
+}
+
+// the entries of the extensions layered over a tagger, with tags resolved to the IDs of the tagger
+#[derive(Default, Clone)]
+pub(crate) struct ExtensionLayer {
+    tags: DefaultHashMap<String, Vec<(String, u16)>>,
+    groups: DefaultHashMap<String, Vec<String>>,
+    checksum: u64,
+}
+
 /// The lexical tagger.
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 pub struct Tagger {
-    tags: DefaultHashMap<u32, IndexMap<u32, Vec<u16>>>,
-    tag_store: BiMap<String, u16>,
-    word_store: BiMap<String, u32>,
-    groups: DefaultHashMap<u32, Vec<u32>>,
+    #[serde(serialize_with = "utils::serialize_sorted_map")]
+    pub(crate) tags: DefaultHashMap<u32, IndexMap<u32, Vec<u16>>>,
+    #[serde(serialize_with = "utils::serialize_sorted_map")]
+    pub(crate) tag_store: BiMap<String, u16>,
+    #[serde(serialize_with = "utils::serialize_sorted_map")]
+    pub(crate) word_store: BiMap<String, u32>,
+    #[serde(serialize_with = "utils::serialize_sorted_map")]
+    pub(crate) groups: DefaultHashMap<u32, Vec<u32>>,
+    // frequency ranks by word ID
+    #[serde(serialize_with = "utils::serialize_sorted_map")]
+    pub(crate) frequencies: DefaultHashMap<u32, u32>,
+    #[serde(skip)]
+    pub(crate) extension: ExtensionLayer,
 }
 
 impl Tagger {
@@ -23,8 +84,7 @@ impl Tagger {
         paths: &[S1],
         remove_paths: &[S2],
     ) -> std::io::Result<Vec<(String, String, String)>> {
-        let mut output = Vec::new();
-        let mut disallowed: Vec<String> = Vec::new();
+        let mut disallowed: DefaultHashSet<String> = DefaultHashSet::default();
 
         for path in remove_paths {
             let file = File::open(path.as_ref())?;
@@ -36,32 +96,54 @@ impl Tagger {
                     continue;
                 }
 
-                disallowed.push(line.to_string());
+                disallowed.insert(line.to_string());
             }
         }
 
-        for path in paths {
-            let file = File::open(path.as_ref())?;
-            let reader = std::io::BufReader::new(file);
+        // the dumps are read in parallel, the lines keep the order of the paths
+        let paths: Vec<_> = paths.iter().map(|x| x.as_ref()).collect();
+        let outputs = paths
+            .into_maybe_par_iter()
+            .map(|path| Tagger::read_dump(path, &disallowed))
+            .collect::<std::io::Result<Vec<_>>>()?;
 
-            for line in reader.lines() {
-                let line = line?;
-                if line.starts_with('#') {
-                    continue;
-                }
+        Ok(outputs.into_iter().flatten().collect())
+    }
 
-                let parts: Vec<_> = line.split('\t').collect();
+    fn read_dump(
+        path: &str,
+        disallowed: &DefaultHashSet<String>,
+    ) -> std::io::Result<Vec<(String, String, String)>> {
+        if path.ends_with(".dict") {
+            return Ok(super::morfologik::read_dictionary(path)?
+                .into_iter()
+                .filter(|(word, inflection, tag)| {
+                    !disallowed.contains(&format!("{}\t{}\t{}", word, inflection, tag))
+                })
+                .collect());
+        }
 
-                if disallowed.contains(&line) {
-                    continue;
-                }
+        let mut output = Vec::new();
+        let file = File::open(path)?;
+        let reader = std::io::BufReader::new(file);
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.starts_with('#') {
+                continue;
+            }
 
-                let word = parts[0].to_string();
-                let inflection = parts[1].to_string();
-                let tag = parts[2].to_string();
+            let parts: Vec<_> = line.split('\t').collect();
 
-                output.push((word, inflection, tag))
+            if disallowed.contains(&line) {
+                continue;
             }
+
+            let word = parts[0].to_string();
+            let inflection = parts[1].to_string();
+            let tag = parts[2].to_string();
+
+            output.push((word, inflection, tag))
         }
 
         Ok(output)
@@ -71,9 +153,10 @@ impl Tagger {
     ///
     /// # Arguments
     /// * `paths`: Paths to files where each line contains the word, lemma and tag, respectively,
-    /// separated by tabs, to be added to the tagger.
+    ///   separated by tabs, to be added to the tagger. Paths ending in `.dict` are read as binary Morfologik
+    ///   dictionaries as distributed with LanguageTool instead, with the `.info` file next to them.
     /// * `remove_paths`: Paths to files where each line contains the word, lemma and tag, respectively,
-    /// separated by tabs, to be removed from the tagger if present in the files from `paths`.
+    ///   separated by tabs, to be removed from the tagger if present in the files from `paths`.
     pub fn from_dumps<S1: AsRef<str>, S2: AsRef<str>, S3: AsRef<str>>(
         paths: &[S1],
         remove_paths: &[S2],
@@ -88,9 +171,9 @@ impl Tagger {
 
         // hardcoded special tags
         tag_store.insert("");
-        tag_store.insert("SENT_START");
-        tag_store.insert("SENT_END");
-        tag_store.insert("UNKNOWN");
+        tag_store.insert(pos::SENT_START);
+        tag_store.insert(pos::SENT_END);
+        tag_store.insert(pos::UNKNOWN);
 
         // add language specific special tags
         tag_store.extend(extra_tags.iter().map(|x| x.as_ref()));
@@ -151,18 +234,125 @@ impl Tagger {
             groups,
             word_store,
             tag_store,
+            frequencies: DefaultHashMap::default(),
+            extension: ExtensionLayer::default(),
         })
     }
 
+    /// Sets the frequency ranks of the words, given from most to least frequent. The first word has rank 1.
+    /// Words which are not in the word store of the tagger are skipped, but still take up their rank.
+    /// Replaces the ranks set before.
+    pub fn set_frequencies<S: AsRef<str>>(&mut self, words: &[S]) {
+        let mut frequencies = DefaultHashMap::default();
+
+        for (i, word) in words.iter().enumerate() {
+            if let Some(id) = self.word_store.get_by_left(word.as_ref()) {
+                frequencies.entry(*id).or_insert(i as u32 + 1);
+            }
+        }
+
+        self.frequencies = frequencies;
+    }
+
+    /// The frequency rank of the word, 1 for the most frequent word. `None` if the tagger has no frequency
+    /// information for the word, typically because it is rare.
+    pub fn frequency(&self, word: &str) -> Option<u32> {
+        self.word_store
+            .get_by_left(word)
+            .and_then(|x| self.frequencies.get(x))
+            .copied()
+    }
+
+    /// Adds the words of the extension to the tagger. Tags of words the tagger already knows are kept, the tags
+    /// from the extension are added to them. The word and tag IDs do not change, so the
+    /// [fingerprint][Tagger::fingerprint] stays the same. Fails without changing the tagger if the extension
+    /// uses a tag which is not in the tagset of this tagger.
+    pub fn extend(&mut self, extension: &TaggerExtension) -> Result<(), Error> {
+        if let Some(tag) = extension.tags().find(|x| !self.has_tag(x)) {
+            return Err(Error::Unexpected(format!(
+                "tagger extension uses unknown tag {}",
+                tag
+            )));
+        }
+
+        let mut words: Vec<_> = extension.entries.iter().collect();
+        words.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut hasher = utils::StableHasher::default();
+        hasher.write(&self.extension.checksum.to_le_bytes());
+
+        for (word, entries) in words {
+            for (inflection, tag) in entries {
+                hasher.write(word.as_bytes());
+                hasher.write(&[0xff]);
+                hasher.write(inflection.as_bytes());
+                hasher.write(&[0xff]);
+                hasher.write(tag.as_bytes());
+                hasher.write(&[0xff]);
+
+                let tag_id = *self.tag_store.get_by_left(tag).unwrap();
+                let known = self
+                    .get_raw(word)
+                    .iter()
+                    .any(|x| x.lemma.as_ref() == inflection && x.pos.as_ref() == tag);
+                if !known {
+                    self.extension
+                        .tags
+                        .entry(word.clone())
+                        .or_default()
+                        .push((inflection.clone(), tag_id));
+                }
+
+                let group = self.extension.groups.entry(inflection.clone()).or_default();
+                if !group.contains(word) {
+                    group.push(word.clone());
+                }
+            }
+        }
+        self.extension.checksum = hasher.finish();
+
+        Ok(())
+    }
+
+    // the approximate size of the extensions in bytes
+    pub(crate) fn extension_size(&self) -> usize {
+        let tags: usize = self
+            .extension
+            .tags
+            .iter()
+            .map(|(word, tags)| {
+                word.len() + tags.iter().map(|(lemma, _)| lemma.len() + 2).sum::<usize>()
+            })
+            .sum();
+        let groups: usize = self
+            .extension
+            .groups
+            .iter()
+            .map(|(word, members)| word.len() + members.iter().map(|x| x.len()).sum::<usize>())
+            .sum();
+
+        tags + groups
+    }
+
+    /// A checksum of the extensions layered over this tagger, 0 if there are none.
+    pub(crate) fn extension_checksum(&self) -> u64 {
+        self.extension.checksum
+    }
+
+    /// Whether the tagger knows the word, from its dictionary or from an extension.
+    pub(crate) fn has_word(&self, word: &str) -> bool {
+        self.word_store.contains_left(word) || self.extension.tags.contains_key(word)
+    }
+
     #[allow(clippy::clippy::ptr_arg)]
     fn get_raw(&self, word: &String) -> Vec<WordData> {
+        let mut output = Vec::new();
+
         if let Some(map) = self
             .word_store
             .get_by_left(word)
             .and_then(|x| self.tags.get(x))
         {
-            let mut output = Vec::new();
-
             for (key, value) in map.iter() {
                 for tag_id in value {
                     output.push(WordData::new(
@@ -171,11 +361,18 @@ impl Tagger {
                     ))
                 }
             }
+        }
 
-            output
-        } else {
-            Vec::new()
+        if let Some(entries) = self.extension.tags.get(word) {
+            for (inflection, tag_id) in entries {
+                output.push(WordData::new(
+                    self.id_word(inflection.as_str().into()),
+                    self.id_tag(self.tag_store.get_by_right(tag_id).unwrap().as_str()),
+                ))
+            }
         }
+
+        output
     }
 
     fn get_strict_tags(
@@ -205,13 +402,51 @@ impl Tagger {
         &self.word_store
     }
 
+    /// A hash of the tag and word ID spaces. Components compiled with the same tagger have the same fingerprint,
+    /// components with different fingerprints can not be used together.
+    pub fn fingerprint(&self) -> u64 {
+        fn write_store<T: Copy + Ord + std::hash::Hash + Into<u64>>(
+            hasher: &mut utils::StableHasher,
+            store: &BiMap<String, T>,
+        ) {
+            let mut entries: Vec<_> = store.iter().map(|(x, id)| (*id, x.as_str())).collect();
+            entries.sort_unstable();
+
+            hasher.write(&(entries.len() as u64).to_le_bytes());
+            for (id, x) in entries {
+                hasher.write(&id.into().to_le_bytes());
+                hasher.write(x.as_bytes());
+                hasher.write(&[0xff]);
+            }
+        }
+
+        let mut hasher = utils::StableHasher::default();
+        write_store(&mut hasher, &self.tag_store);
+        write_store(&mut hasher, &self.word_store);
+        hasher.finish()
+    }
+
+    /// Whether the tag is in the tagset of this tagger.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tag_store.contains_left(tag)
+    }
+
     pub fn id_tag<'a>(&self, tag: &'a str) -> PosId<'a> {
         PosId(tag, *self.tag_store.get_by_left(tag).unwrap())
     }
 
+    /// Identifies the word. The text of known words is borrowed from the tagger, so no owned copy of it is kept.
     pub fn id_word<'t>(&'t self, text: Cow<'t, str>) -> WordId<'t> {
-        let id = self.word_store.get_by_left(text.as_ref()).copied();
-        WordId(text, id)
+        match self.word_store.get_by_left(text.as_ref()) {
+            Some(id) => {
+                let text = match text {
+                    Cow::Borrowed(text) => text,
+                    Cow::Owned(_) => self.word_store.get_by_right(id).unwrap().as_str(),
+                };
+                WordId(Cow::Borrowed(text), Some(*id))
+            }
+            None => WordId(text, None),
+        }
     }
 
     /// Get the tags and lemmas (as [WordData][crate::types::WordData]) for the given word.
@@ -273,7 +508,8 @@ impl Tagger {
     /// Get the words with the same lemma as the given lemma.
     #[allow(clippy::clippy::ptr_arg)]
     pub fn get_group_members(&self, lemma: &String) -> Vec<&str> {
-        self.word_store
+        let mut members: Vec<&str> = self
+            .word_store
             .get_by_left(lemma)
             .and_then(|x| self.groups.get(x))
             .map(|vec| {
@@ -281,6 +517,16 @@ impl Tagger {
                     .map(|x| self.word_store.get_by_right(x).unwrap().as_str())
                     .collect()
             })
-            .unwrap_or_else(Vec::new)
+            .unwrap_or_else(Vec::new);
+
+        if let Some(group) = self.extension.groups.get(lemma) {
+            for word in group {
+                if !members.contains(&word.as_str()) {
+                    members.push(word);
+                }
+            }
+        }
+
+        members
     }
 }
@@ -5,20 +5,25 @@ use serde::{Deserialize, Serialize};
 use std::{
     borrow::Cow,
     collections::{hash_map, HashMap, HashSet},
+    fmt,
 };
 
-use crate::tokenizer::tag::Tagger;
+use crate::tokenizer::{pos, tag::Tagger, SentenceGuard};
 
 pub(crate) type DefaultHashMap<K, V> = HashMap<K, V>;
 pub(crate) type DefaultHashSet<T> = HashSet<T>;
 pub(crate) type DefaultHasher = hash_map::DefaultHasher;
 
+/// A string which stores up to 23 bytes inline instead of on the heap. Most words are shorter than that.
+pub type SmallString = smartstring::alias::String;
+
 /// Owned versions of the types for use in longer-living structures not bound to the `'t` lifetime e.g. rule tests.
 pub mod owned {
+    use super::SmallString;
     use serde::{Deserialize, Serialize};
 
     #[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
-    pub struct WordId(pub String, pub Option<u32>);
+    pub struct WordId(pub SmallString, pub Option<u32>);
 
     impl WordId {
         pub fn as_ref_id(&self) -> super::WordId {
@@ -71,7 +76,163 @@ pub mod owned {
         pub char_span: (usize, usize),
         pub byte_span: (usize, usize),
         pub has_space_before: bool,
-        pub chunks: Vec<String>,
+        pub chunks: Vec<super::ChunkTag>,
+    }
+}
+
+/// The position of a token in a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChunkPosition {
+    /// `B`, the first token of the chunk.
+    Begin,
+    /// `I`, a token inside the chunk.
+    Inside,
+    /// `E`, the last token of a noun phrase.
+    End,
+}
+
+/// The kind of a chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChunkLabel {
+    NounPhrase,
+    VerbPhrase,
+    PrepositionalPhrase,
+    AdjectivePhrase,
+    AdverbPhrase,
+    Subordinate,
+    Particle,
+    Interjection,
+    Conjunction,
+    ListMarker,
+    UnlikeCoordinated,
+    /// `O`, the token is not in a chunk.
+    Outside,
+    /// A label not known to nlprule.
+    Other(String),
+}
+
+impl ChunkLabel {
+    fn as_str(&self) -> &str {
+        match self {
+            ChunkLabel::NounPhrase => "NP",
+            ChunkLabel::VerbPhrase => "VP",
+            ChunkLabel::PrepositionalPhrase => "PP",
+            ChunkLabel::AdjectivePhrase => "ADJP",
+            ChunkLabel::AdverbPhrase => "ADVP",
+            ChunkLabel::Subordinate => "SBAR",
+            ChunkLabel::Particle => "PRT",
+            ChunkLabel::Interjection => "INTJ",
+            ChunkLabel::Conjunction => "CONJP",
+            ChunkLabel::ListMarker => "LST",
+            ChunkLabel::UnlikeCoordinated => "UCP",
+            ChunkLabel::Outside => "O",
+            ChunkLabel::Other(label) => label,
+        }
+    }
+
+    fn from_str(label: &str) -> Self {
+        match label {
+            "NP" => ChunkLabel::NounPhrase,
+            "VP" => ChunkLabel::VerbPhrase,
+            "PP" => ChunkLabel::PrepositionalPhrase,
+            "ADJP" => ChunkLabel::AdjectivePhrase,
+            "ADVP" => ChunkLabel::AdverbPhrase,
+            "SBAR" => ChunkLabel::Subordinate,
+            "PRT" => ChunkLabel::Particle,
+            "INTJ" => ChunkLabel::Interjection,
+            "CONJP" => ChunkLabel::Conjunction,
+            "LST" => ChunkLabel::ListMarker,
+            "UCP" => ChunkLabel::UnlikeCoordinated,
+            "O" => ChunkLabel::Outside,
+            label => ChunkLabel::Other(label.to_string()),
+        }
+    }
+}
+
+/// The grammatical number of a noun phrase, added to the chunk tags of nouns phrases in English.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChunkNumber {
+    Singular,
+    Plural,
+}
+
+/// A chunk tag like `B-NP-singular` or `I-VP`. Converts from and to the string used in the LanguageTool rules,
+/// which is also how it is serialized. Tags which do not have this form have an [Other][ChunkLabel::Other]
+/// label with the whole tag and no position.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct ChunkTag {
+    pub position: Option<ChunkPosition>,
+    pub label: ChunkLabel,
+    pub number: Option<ChunkNumber>,
+}
+
+impl From<&str> for ChunkTag {
+    fn from(tag: &str) -> Self {
+        let parts: Vec<_> = tag.split('-').collect();
+
+        let position = match parts[0] {
+            "B" => Some(ChunkPosition::Begin),
+            "I" => Some(ChunkPosition::Inside),
+            "E" => Some(ChunkPosition::End),
+            _ => None,
+        };
+        let number = match parts.get(2).copied() {
+            Some("singular") => Some(ChunkNumber::Singular),
+            Some("plural") => Some(ChunkNumber::Plural),
+            _ => None,
+        };
+
+        match (position, parts.len(), number) {
+            (None, 1, _) => ChunkTag {
+                position: None,
+                label: ChunkLabel::from_str(tag),
+                number: None,
+            },
+            (Some(position), 2, _) | (Some(position), 3, Some(_)) if !parts[1].is_empty() => {
+                ChunkTag {
+                    position: Some(position),
+                    label: ChunkLabel::from_str(parts[1]),
+                    number,
+                }
+            }
+            _ => ChunkTag {
+                position: None,
+                label: ChunkLabel::Other(tag.to_string()),
+                number: None,
+            },
+        }
+    }
+}
+
+impl From<String> for ChunkTag {
+    fn from(tag: String) -> Self {
+        ChunkTag::from(tag.as_str())
+    }
+}
+
+impl From<ChunkTag> for String {
+    fn from(tag: ChunkTag) -> Self {
+        tag.to_string()
+    }
+}
+
+impl fmt::Display for ChunkTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(position) = self.position {
+            let position = match position {
+                ChunkPosition::Begin => "B-",
+                ChunkPosition::Inside => "I-",
+                ChunkPosition::End => "E-",
+            };
+            f.write_str(position)?;
+        }
+        f.write_str(self.label.as_str())?;
+        match self.number {
+            Some(ChunkNumber::Singular) => f.write_str("-singular"),
+            Some(ChunkNumber::Plural) => f.write_str("-plural"),
+            None => Ok(()),
+        }
     }
 }
 
@@ -81,7 +242,7 @@ pub struct WordId<'t>(pub(crate) Cow<'t, str>, pub(crate) Option<u32>);
 
 impl<'t> WordId<'t> {
     pub(crate) fn to_owned_id(&self) -> owned::WordId {
-        owned::WordId(self.0.to_string(), self.1)
+        owned::WordId(self.0.as_ref().into(), self.1)
     }
 
     pub fn id(&self) -> &Option<u32> {
@@ -165,23 +326,39 @@ pub struct IncompleteToken<'t> {
     pub char_span: (usize, usize),
     pub is_sentence_end: bool,
     pub has_space_before: bool,
-    pub chunks: Vec<String>,
+    pub chunks: Vec<ChunkTag>,
+    /// Whether a disambiguation rule marked this token as immune. Grammar rules never match immune tokens.
+    pub immune: bool,
+    /// Set if the token is in a sentence with more tokens than allowed by
+    /// [max_sentence_tokens][crate::tokenizer::TokenizerOptions::max_sentence_tokens].
+    pub guard: Option<SentenceGuard>,
     pub text: &'t str,
-    #[derivative(PartialEq = "ignore", Debug = "ignore")]
+    // copies the reference instead of the tagger
+    #[derivative(
+        PartialEq = "ignore",
+        Debug = "ignore",
+        Clone(clone_with = "Clone::clone")
+    )]
     pub tagger: &'t Tagger,
 }
 
 /// A finished token with all information set.
 #[derive(Derivative)]
-#[derivative(Debug)]
+#[derivative(Debug, Clone)]
 pub struct Token<'t> {
     pub word: Word<'t>,
     pub char_span: (usize, usize),
     pub byte_span: (usize, usize),
     pub has_space_before: bool,
-    pub chunks: Vec<String>,
+    pub chunks: Vec<ChunkTag>,
+    /// Whether a disambiguation rule marked this token as immune. Grammar rules never match immune tokens.
+    pub immune: bool,
+    /// Set if the token is in a sentence with more tokens than allowed by
+    /// [max_sentence_tokens][crate::tokenizer::TokenizerOptions::max_sentence_tokens].
+    pub guard: Option<SentenceGuard>,
     pub text: &'t str,
-    #[derivative(Debug = "ignore")]
+    // copies the reference instead of the tagger
+    #[derivative(Debug = "ignore", Clone(clone_with = "Clone::clone"))]
     pub tagger: &'t Tagger,
 }
 
@@ -193,7 +370,7 @@ impl<'t> Token<'t> {
                 tagger.id_word("".into()),
                 vec![WordData::new(
                     tagger.id_word("".into()),
-                    tagger.id_tag("SENT_START"),
+                    tagger.id_tag(pos::SENT_START),
                 )]
                 .into_iter()
                 .collect(),
@@ -202,6 +379,8 @@ impl<'t> Token<'t> {
             byte_span: (0, 0),
             has_space_before: false,
             chunks: Vec::new(),
+            immune: false,
+            guard: None,
             text,
             tagger,
         }
@@ -230,14 +409,14 @@ impl<'t> From<IncompleteToken<'t>> for Token<'t> {
         if word.tags.iter().all(|x| x.pos.0.is_empty()) {
             word.tags.push(WordData::new(
                 data.word.text.clone(),
-                data.tagger.id_tag("UNKNOWN"),
+                data.tagger.id_tag(pos::UNKNOWN),
             ));
         }
 
         if data.is_sentence_end {
             word.tags.push(WordData::new(
                 data.word.text,
-                data.tagger.id_tag("SENT_END"),
+                data.tagger.id_tag(pos::SENT_END),
             ));
         }
 
@@ -247,13 +426,78 @@ impl<'t> From<IncompleteToken<'t>> for Token<'t> {
             char_span: data.char_span,
             has_space_before: data.has_space_before,
             chunks: data.chunks,
+            immune: data.immune,
+            guard: data.guard,
             text: data.text,
             tagger: data.tagger,
         }
     }
 }
 
+/// Whether a fix can be applied automatically, e. g. by a `--fix` mode of a linter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FixSafety {
+    /// The suggestion has a single replacement which does not change the meaning of the text.
+    Safe,
+    /// The suggestion should be reviewed before it is applied.
+    #[default]
+    NeedsReview,
+}
+
+/// How important a suggestion is, e. g. whether it should block CI. Maps to the levels of editors and report formats
+/// like LSP diagnostics. Set from [severities][crate::rules::CheckOptions::severities].
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    #[default]
+    Warning,
+    Info,
+    Hint,
+}
+
+impl Severity {
+    /// The lowercase name, e. g. `error`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+            Severity::Hint => "hint",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Severity {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(Severity::Error),
+            "warning" => Ok(Severity::Warning),
+            "info" => Ok(Severity::Info),
+            "hint" => Ok(Severity::Hint),
+            _ => Err(crate::Error::Unexpected(format!("unknown severity: {}", s))),
+        }
+    }
+}
+
 /// Suggestion for change in a text.
+///
+/// `start` and `end` are indices of Unicode scalar values (i. e. Rust `char`s) in the logical order the text is stored in,
+/// not in the order it is displayed. This also holds for right-to-left and mixed-direction text.
+/// Invisible bidirectional formatting characters count towards the indices like any other character.
+///
+/// The suggestions returned by [Rules::suggest][crate::Rules::suggest] satisfy `start <= end <= text.chars().count()`,
+/// have at least one replacement and are sorted by `start` without overlapping, so they can be applied one after another.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Suggestion {
     /// The ID of the rule this suggestion is from.
@@ -266,4 +510,176 @@ pub struct Suggestion {
     pub end: usize,
     /// The suggested replacement options for the text.
     pub replacements: Vec<String>,
+    /// Whether the suggestion can be applied without review, see [Rule::safety][crate::rule::Rule::safety].
+    #[serde(default)]
+    pub safety: FixSafety,
+    /// How likely the suggestion is right, between 0 and 1. Set from the precision statistics of its source
+    /// if the rules have them, see [Rules::set_statistics][crate::Rules::set_statistics].
+    #[serde(default)]
+    pub confidence: Option<f32>,
+    /// The IDs of other rules which made the same suggestion. Only set if
+    /// [merge_duplicates][crate::rules::CheckOptions::merge_duplicates] is enabled.
+    #[serde(default)]
+    pub merged_sources: Vec<String>,
+    /// A compact version of the message for inline hints, e. g. "Possible typo". Taken from the short message of
+    /// the rule if it has one, otherwise the message truncated to [SHORT_MESSAGE_CHARS] characters.
+    #[serde(default)]
+    pub short_message: String,
+    /// The text between `start` and `end`. Stays the same when the span is mapped to a marked-up source, see
+    /// [Extracted::map_suggestions][crate::markup::Extracted::map_suggestions].
+    #[serde(default)]
+    pub matched: String,
+    /// The indices of the tokens the suggestion covers (start inclusive, end exclusive) in the tokens passed to
+    /// [Rules::apply][crate::Rules::apply], i. e. in the tokens of the checked text, which start with the sentence
+    /// start token. Equal if the suggestion covers no token, e. g. only whitespace.
+    #[serde(default)]
+    pub token_span: (usize, usize),
+    /// How important the suggestion is, see [CheckOptions::severities][crate::rules::CheckOptions::severities].
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+/// The maximum length in characters of a short message derived from the message of a suggestion.
+pub const SHORT_MESSAGE_CHARS: usize = 32;
+
+/// Truncates the message to at most [SHORT_MESSAGE_CHARS] characters at a word boundary, marking the cut with `…`.
+pub fn shorten_message(message: &str) -> String {
+    if message.chars().count() <= SHORT_MESSAGE_CHARS {
+        return message.to_string();
+    }
+
+    // leave room for the ellipsis
+    let limit = message
+        .char_indices()
+        .nth(SHORT_MESSAGE_CHARS - 1)
+        .map_or(message.len(), |(i, _)| i);
+    let cut = message[..=limit]
+        .rfind(char::is_whitespace)
+        .filter(|x| *x > 0)
+        .unwrap_or(limit);
+
+    let mut short = message[..cut]
+        .trim_end_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation())
+        .to_string();
+    short.push('…');
+    short
+}
+
+impl Suggestion {
+    /// Keeps the line breaks of the text the suggestion replaces in its replacements. The whitespace between
+    /// the words of a replacement is taken from the replaced text in order. If the replacement has fewer words,
+    /// its last space keeps the line break of the dropped whitespace, so hard-wrapped lines do not get longer.
+    /// Does nothing if the replaced text has no line break.
+    pub fn reflow(&mut self, text: &str) {
+        let replaced: String = text
+            .chars()
+            .skip(self.start)
+            .take(self.end - self.start)
+            .collect();
+        if !replaced.contains('\n') {
+            return;
+        }
+
+        let gaps: Vec<&str> = whitespace_runs(&replaced).collect();
+
+        for replacement in self.replacements.iter_mut() {
+            let words: Vec<&str> = replacement.split_whitespace().collect();
+            let n_gaps = words.len().saturating_sub(1);
+            let mut new_gaps: Vec<&str> =
+                (0..n_gaps).map(|i| *gaps.get(i).unwrap_or(&" ")).collect();
+
+            if let (Some(last), Some(line_break)) = (
+                new_gaps.last_mut(),
+                gaps.iter().skip(n_gaps).rev().find(|x| x.contains('\n')),
+            ) {
+                if !last.contains('\n') {
+                    *last = line_break;
+                }
+            }
+
+            let trimmed = replacement.trim_start();
+            let mut reflowed = replacement[..replacement.len() - trimmed.len()].to_string();
+            for (i, word) in words.iter().enumerate() {
+                if i > 0 {
+                    reflowed.push_str(new_gaps[i - 1]);
+                }
+                reflowed.push_str(word);
+            }
+            if !words.is_empty() {
+                reflowed.push_str(&trimmed[trimmed.trim_end().len()..]);
+            }
+            *replacement = reflowed;
+        }
+    }
+}
+
+/// The suggestions in one sentence of a text, see [Rules::suggest_by_sentence][crate::Rules::suggest_by_sentence].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentenceSuggestions {
+    /// The start character index of the sentence in the text (inclusive).
+    pub start: usize,
+    /// The end character index of the sentence in the text (exclusive). Includes the whitespace after the sentence.
+    pub end: usize,
+    /// The suggestions which start in the sentence, with indices into the whole text.
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// The approximate memory used by the parts of a component, see [Tokenizer::memory_stats][crate::Tokenizer::memory_stats]
+/// and [Rules::memory_stats][crate::Rules::memory_stats]. Sizes are estimated from the size of the data when serialized,
+/// so they leave out the overhead of allocations and hash maps and should only be compared with each other.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The parts with their approximate size in bytes, e. g. `("tagger", 250_000)`.
+    pub parts: Vec<(&'static str, usize)>,
+    /// The number of regexes which are compiled. Regexes are compiled lazily on first use and their compiled
+    /// form is not part of the sizes, so the memory use grows with this number.
+    pub compiled_regexes: usize,
+    /// The number of regexes, compiled or not.
+    pub regexes: usize,
+}
+
+impl MemoryStats {
+    /// The approximate size of a part in bytes, `None` if there is no part with the name.
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.parts.iter().find(|(x, _)| *x == name).map(|(_, x)| *x)
+    }
+
+    /// The sum of the sizes of all parts in bytes.
+    pub fn total(&self) -> usize {
+        self.parts.iter().map(|(_, x)| x).sum()
+    }
+
+    pub(crate) fn count_regex(&mut self, regex: &crate::utils::regex::SerializeRegex) {
+        self.regexes += 1;
+        if regex.is_compiled() {
+            self.compiled_regexes += 1;
+        }
+    }
+}
+
+// the maximal runs of whitespace in the text
+fn whitespace_runs(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_whitespace())
+        .filter(|x| !x.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_chunk_tags() {
+        let tag = ChunkTag::from("E-NP-plural");
+        assert_eq!(tag.position, Some(ChunkPosition::End));
+        assert_eq!(tag.label, ChunkLabel::NounPhrase);
+        assert_eq!(tag.number, Some(ChunkNumber::Plural));
+
+        for tag in ["E-NP-plural", "B-VP", "O", "B-", "X-NP", "B-NP-many", ""] {
+            assert_eq!(ChunkTag::from(tag).to_string(), tag);
+        }
+        assert_eq!(
+            ChunkTag::from("B-NP-many").label,
+            ChunkLabel::Other("B-NP-many".into())
+        );
+    }
 }
@@ -0,0 +1,112 @@
+use crate::types::DefaultHashMap;
+use std::{borrow::Borrow, collections::BTreeMap, hash::Hash};
+
+/// A cache which evicts the least recently used entry once it is full.
+/// Counts hits and misses so the hit rate can be reported.
+#[derive(Debug)]
+pub struct LruCache<K, V> {
+    capacity: usize,
+    entries: DefaultHashMap<K, (V, u64)>,
+    // keys ordered by the time they were last used
+    recency: BTreeMap<u64, K>,
+    clock: u64,
+    pub(crate) hits: u64,
+    pub(crate) misses: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        LruCache {
+            capacity,
+            entries: DefaultHashMap::default(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.clock += 1;
+
+        match self.entries.get_mut(key) {
+            Some((value, last_used)) => {
+                let key = self
+                    .recency
+                    .remove(last_used)
+                    .expect("entries and recency match");
+                *last_used = self.clock;
+                self.recency.insert(self.clock, key);
+                self.hits += 1;
+
+                Some(value.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.clock += 1;
+
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, self.clock)) {
+            self.recency.remove(&last_used);
+        } else if self.entries.len() > self.capacity {
+            let oldest = *self.recency.keys().next().expect("cache is not empty");
+            let evicted = self.recency.remove(&oldest).unwrap();
+            self.entries.remove(&evicted);
+        }
+
+        self.recency.insert(self.clock, key);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    /// The entries from the least to the most recently used, so inserting them in order keeps their recency.
+    pub fn entries(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.recency
+            .values()
+            .map(move |key| (key, &self.entries[key].0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruCache;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        assert_eq!(cache.get(&"a"), Some(1));
+
+        cache.insert("c", 3);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"c"), Some(3));
+        assert_eq!((cache.hits, cache.misses), (3, 1));
+    }
+}
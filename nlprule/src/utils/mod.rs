@@ -1,9 +1,21 @@
+use flate2::read::GzDecoder;
 use lazy_static::lazy_static;
-use onig::{Captures, Regex};
+use onig::Regex;
+use serde::{Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, BufReader, Cursor, Read},
+};
 
+pub mod cache;
 pub mod parallelism;
 pub mod regex;
 
+/// The size of the value when serialized with bincode, zero if it can not be serialized.
+pub fn serialized_size<T: Serialize + ?Sized>(value: &T) -> usize {
+    bincode::serialized_size(value).map_or(0, |x| x as usize)
+}
+
 // see https://stackoverflow.com/questions/38406793/why-is-capitalizing-the-first-letter-of-a-string-so-convoluted-in-rust
 pub fn apply_to_first<F>(string: &str, func: F) -> String
 where
@@ -27,25 +39,153 @@ pub fn is_uppercase(string: &str) -> bool {
 }
 
 // see https://github.com/rust-onig/rust-onig/issues/59#issuecomment-340160520
-pub fn dollar_replace(mut replacement: String, caps: &Captures) -> String {
-    for i in 1..caps.len() {
+pub fn dollar_replace(mut replacement: String, caps: &self::regex::Captures) -> String {
+    // in reverse so that e. g. `$1` does not replace the start of `$10`
+    for i in (1..caps.len()).rev() {
         replacement = replacement.replace(&format!("${}", i), caps.at(i).unwrap_or(""));
     }
     replacement
 }
 
+/// Serializes a map (e. g. a hash map) with its entries sorted by key. Hash map iteration order is random,
+/// so this is needed for the binaries to be reproducible. Deserializes like the original map.
+pub fn serialize_sorted_map<'a, M, K, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Ord + Serialize + 'a,
+    V: Serialize + 'a,
+    S: Serializer,
+{
+    map.into_iter()
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
+/// Serializes an optional set with its elements sorted. See [serialize_sorted_map].
+pub fn serialize_sorted_set<'a, C, T, S>(
+    set: &'a Option<C>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    &'a C: IntoIterator<Item = &'a T>,
+    T: Ord + Serialize + 'a,
+    S: Serializer,
+{
+    set.as_ref()
+        .map(|x| x.into_iter().collect::<BTreeSet<_>>())
+        .serialize(serializer)
+}
+
+/// A 64-bit FNV-1a hasher. Unlike the std hashers, the resulting hash is stable across Rust versions and platforms
+/// as long as only byte slices are written, so it can be stored in binaries.
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl std::hash::Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Reads until `buf` is full or the reader is at its end. Returns the number of bytes read.
+pub(crate) fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(read)
+}
+
+/// Returns a reader over the decompressed data if the data read from `reader` is gzip compressed.
+/// Otherwise returns a reader over the data itself.
+pub fn maybe_decompress<'a, R: Read + 'a>(mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    // gzip magic bytes followed by the "deflate" compression method
+    const GZIP_HEADER: [u8; 3] = [0x1f, 0x8b, 0x08];
+
+    // a single read can return fewer bytes than the header, e. g. from a socket
+    let mut start = [0; GZIP_HEADER.len()];
+    let read = read_up_to(&mut reader, &mut start)?;
+    let is_gzip = read == start.len() && start == GZIP_HEADER;
+    let reader = BufReader::new(Cursor::new(start[..read].to_vec()).chain(reader));
+
+    Ok(if is_gzip {
+        Box::new(GzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    })
+}
+
 // remove duplicate whitespaces
 pub fn normalize_whitespace(string: &str) -> String {
     lazy_static! {
         static ref REGEX: Regex = Regex::new(r"(\s)\s+").unwrap();
     }
 
-    REGEX.replace_all(string, |caps: &Captures| caps.at(1).unwrap().to_string())
+    REGEX.replace_all(string, |caps: &onig::Captures| {
+        caps.at(1).unwrap().to_string()
+    })
 }
 
 #[inline]
 pub fn splitting_chars() -> &'static str {
-    r##"«»'’`´‘],.:;!?/\()<=>„“”"+#…*"##
+    r##"«»'’`´‘],.:;!?/\()<=>„“”"+#…*،؛؟"##
+}
+
+lazy_static! {
+    static ref SPLITTING_ASCII: [bool; 128] = {
+        let mut table = [false; 128];
+        for c in splitting_chars().chars().filter(char::is_ascii) {
+            table[c as usize] = true;
+        }
+        for c in (0..128u8).map(char::from).filter(|c| c.is_whitespace()) {
+            table[c as usize] = true;
+        }
+        table
+    };
+}
+
+/// Whether the ASCII byte is whitespace or one of the [splitting_chars].
+#[inline]
+pub fn is_splitting_ascii(byte: u8) -> bool {
+    SPLITTING_ASCII[byte as usize]
+}
+
+/// Whether the tokenizer splits at the character i. e. it is whitespace, a bidirectional formatting character
+/// or one of the [splitting_chars].
+#[inline]
+pub fn is_splitting_char(c: char) -> bool {
+    if c.is_ascii() {
+        is_splitting_ascii(c as u8)
+    } else {
+        c.is_whitespace() || is_bidi_control(c) || splitting_chars().contains(c)
+    }
+}
+
+/// Whether the character is an invisible bidirectional formatting character such as the right-to-left mark.
+/// These do not belong to any token but still count towards character indices.
+#[inline]
+pub fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+    )
 }
 
 #[inline]
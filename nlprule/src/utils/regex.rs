@@ -1,8 +1,98 @@
-use crate::Error;
-use onig::{Regex, RegexOptions};
+use crate::{types::DefaultHashMap, Error};
+use lazy_static::lazy_static;
+use once_cell::sync::OnceCell;
+use onig::RegexOptions;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::hash::{Hash, Hasher};
-use std::ops::Deref;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::{Arc, Mutex, Weak};
+
+type RegexKey = (String, bool, RegexEngine);
+
+// the number of entries at which the entries of dropped regexes are removed for the first time
+const MIN_PRUNE_LEN: usize = 1024;
+
+/// Regexes are shared between all `SerializeRegex`es with the same pattern, also across rule sets.
+/// The entries of dropped regexes are removed whenever the number of entries has doubled since the last time,
+/// so reloading rule sets does not grow the cache and inserting stays amortized constant time.
+struct RegexCache {
+    regexes: DefaultHashMap<RegexKey, Weak<Regex>>,
+    prune_len: usize,
+}
+
+impl RegexCache {
+    fn get(&self, key: &RegexKey) -> Option<Arc<Regex>> {
+        self.regexes.get(key).and_then(|x| x.upgrade())
+    }
+
+    fn insert(&mut self, key: RegexKey, regex: &Arc<Regex>) {
+        if self.regexes.len() >= self.prune_len {
+            self.regexes.retain(|_, x| x.strong_count() > 0);
+            self.prune_len = MIN_PRUNE_LEN.max(self.regexes.len() * 2);
+        }
+        self.regexes.insert(key, Arc::downgrade(regex));
+    }
+}
+
+lazy_static! {
+    static ref REGEX_CACHE: Mutex<RegexCache> = Mutex::new(RegexCache {
+        regexes: DefaultHashMap::default(),
+        prune_len: MIN_PRUNE_LEN,
+    });
+}
+
+/// The engine used to execute a regex.
+/// The `regex` crate is used whenever it supports the pattern and the pattern means the same there. Oniguruma is used
+/// for patterns which need features the `regex` crate does not have, like lookaround or backreferences.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegexEngine {
+    Regex,
+    Onig,
+}
+
+#[derive(Debug)]
+enum Regex {
+    Regex(::regex::Regex),
+    Onig(onig::Regex),
+}
+
+/// The capture groups of one regex match.
+pub enum Captures<'t> {
+    Regex(::regex::Captures<'t>),
+    Onig(onig::Captures<'t>),
+}
+
+impl<'t> Captures<'t> {
+    /// The number of groups, including the group of the whole match.
+    pub fn len(&self) -> usize {
+        match self {
+            Captures::Regex(captures) => captures.len(),
+            Captures::Onig(captures) => captures.len(),
+        }
+    }
+
+    /// The text of the group at index `i`, if it participated in the match.
+    pub fn at(&self, i: usize) -> Option<&'t str> {
+        match self {
+            Captures::Regex(captures) => captures.get(i).map(|x| x.as_str()),
+            Captures::Onig(captures) => captures.at(i),
+        }
+    }
+
+    /// The byte span of the group at index `i`, if it participated in the match.
+    pub fn pos(&self, i: usize) -> Option<(usize, usize)> {
+        match self {
+            Captures::Regex(captures) => captures.get(i).map(|x| (x.start(), x.end())),
+            Captures::Onig(captures) => captures.pos(i),
+        }
+    }
+
+    /// The byte spans of all groups.
+    pub fn iter_pos(&self) -> impl Iterator<Item = Option<(usize, usize)>> + '_ {
+        (0..self.len()).map(move |i| self.pos(i))
+    }
+}
 
 fn unescape<S: AsRef<str>>(string: S, c: &str) -> String {
     let placeholder = "###escaped_backslash###";
@@ -14,18 +104,98 @@ fn unescape<S: AsRef<str>>(string: S, c: &str) -> String {
         .replace(placeholder, r"\\")
 }
 
+// a char which matches itself case insensitively in both engines. Oniguruma also folds to and from several chars
+// (e. g. `ß` matches "ss"), the `regex` crate does not
+fn folds_simply(c: char) -> bool {
+    c.is_ascii() || (c.to_lowercase().count() == 1 && c.to_uppercase().count() == 1)
+}
+
+// whether the escape after a backslash is known to mean the same in both engines
+fn is_portable_escape(chars: &mut Peekable<Chars>, case_sensitive: bool) -> bool {
+    match chars.next() {
+        Some('d' | 'D' | 'w' | 'W' | 's' | 'S' | 'b' | 'B' | 'n' | 't' | 'r') => true,
+        // general categories like `\p{Lu}`, longer names are written differently in Java and Rust. The `regex` crate
+        // applies case insensitivity to them (so `\p{Lu}` also matches lowercase letters), Oniguruma does not
+        Some('p' | 'P') if case_sensitive => {
+            let name: String = chars.by_ref().take_while(|x| *x != '}').collect();
+            (2..=3).contains(&name.len())
+                && name.starts_with('{')
+                && name.chars().skip(1).all(|x| x.is_ascii_alphabetic())
+        }
+        Some(c) => c.is_ascii_punctuation(),
+        None => false,
+    }
+}
+
+/// Whether the (already preprocessed) pattern only uses syntax which means the same for Oniguruma with Java syntax
+/// and the `regex` crate. Only these patterns are run with the `regex` crate: some syntax it also accepts means
+/// something else there, e. g. `{,3}` is a literal in Java, nested classes and `&&` differ and inline flags and group
+/// names are written differently.
+fn is_portable(pattern: &str, case_sensitive: bool) -> bool {
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let portable = match c {
+            '\\' => is_portable_escape(&mut chars, case_sensitive),
+            // only non-capturing groups, no inline flags, named groups or lookaround
+            '(' => match chars.peek() {
+                Some('?') => chars.nth(1) == Some(':'),
+                _ => true,
+            },
+            '{' => {
+                let repetition: String = chars.by_ref().take_while(|x| *x != '}').collect();
+                let is_number = |x: &str| !x.is_empty() && x.chars().all(|x| x.is_ascii_digit());
+                match repetition.split_once(',') {
+                    Some((min, max)) => is_number(min) && (max.is_empty() || is_number(max)),
+                    None => is_number(&repetition),
+                }
+            }
+            '[' => {
+                if chars.peek() == Some(&'^') {
+                    chars.next();
+                }
+                // the engines disagree on whether a leading `]` is a literal
+                let mut portable = chars.peek() != Some(&']');
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some('\\') => portable &= is_portable_escape(&mut chars, case_sensitive),
+                        Some('[') | None => portable = false,
+                        Some('&') if chars.peek() == Some(&'&') => portable = false,
+                        Some(c) => portable &= case_sensitive || folds_simply(c),
+                    }
+                    if !portable {
+                        break;
+                    }
+                }
+                portable
+            }
+            c => case_sensitive || folds_simply(c),
+        };
+
+        if !portable {
+            return false;
+        }
+    }
+
+    true
+}
+
 #[derive(Serialize, Deserialize)]
 struct RegexFields {
     regex_str: String,
     case_sensitive: bool,
+    engine: RegexEngine,
 }
 
+/// A regex which is compiled lazily on first use.
 #[derive(Serialize, Debug)]
 pub struct SerializeRegex {
     regex_str: String,
     case_sensitive: bool,
+    engine: RegexEngine,
     #[serde(skip_serializing)]
-    regex: Regex,
+    regex: OnceCell<Arc<Regex>>,
 }
 
 impl Hash for SerializeRegex {
@@ -42,16 +212,24 @@ impl<'de> Deserialize<'de> for SerializeRegex {
     {
         let fields: RegexFields = Deserialize::deserialize(deserializer)?;
         Ok(SerializeRegex {
-            regex: SerializeRegex::compile(&fields.regex_str, fields.case_sensitive).unwrap(),
+            regex: OnceCell::new(),
             regex_str: fields.regex_str,
             case_sensitive: fields.case_sensitive,
+            engine: fields.engine,
         })
     }
 }
 
 impl SerializeRegex {
-    fn compile(regex_str: &str, case_sensitive: bool) -> Result<Regex, onig::Error> {
-        Regex::with_options(
+    fn compile_regex(regex_str: &str, case_sensitive: bool) -> Result<Regex, ::regex::Error> {
+        ::regex::RegexBuilder::new(regex_str)
+            .case_insensitive(!case_sensitive)
+            .build()
+            .map(Regex::Regex)
+    }
+
+    fn compile_onig(regex_str: &str, case_sensitive: bool) -> Result<Regex, onig::Error> {
+        onig::Regex::with_options(
             regex_str,
             if case_sensitive {
                 RegexOptions::REGEX_OPTION_NONE
@@ -60,6 +238,136 @@ impl SerializeRegex {
             },
             onig::Syntax::java(),
         )
+        .map(Regex::Onig)
+    }
+
+    fn compile(
+        regex_str: &str,
+        case_sensitive: bool,
+        engine: RegexEngine,
+    ) -> Result<Regex, String> {
+        match engine {
+            RegexEngine::Regex => SerializeRegex::compile_regex(regex_str, case_sensitive)
+                .map_err(|x| format!("{}", x)),
+            RegexEngine::Onig => SerializeRegex::compile_onig(regex_str, case_sensitive)
+                .map_err(|x| format!("{}", x)),
+        }
+    }
+
+    fn cached(
+        regex_str: &str,
+        case_sensitive: bool,
+        engine: RegexEngine,
+    ) -> Result<Arc<Regex>, String> {
+        let key = (regex_str.to_string(), case_sensitive, engine);
+
+        if let Some(regex) = REGEX_CACHE.lock().unwrap().get(&key) {
+            return Ok(regex);
+        }
+
+        // the lock is not held while compiling so other threads are not blocked
+        let regex = Arc::new(SerializeRegex::compile(regex_str, case_sensitive, engine)?);
+        REGEX_CACHE.lock().unwrap().insert(key, &regex);
+        Ok(regex)
+    }
+
+    fn regex(&self) -> &Regex {
+        self.try_regex()
+            .expect("regex was checked in `SerializeRegex::new`")
+    }
+
+    fn try_regex(&self) -> Result<&Regex, String> {
+        self.regex
+            .get_or_try_init(|| {
+                SerializeRegex::cached(&self.regex_str, self.case_sensitive, self.engine)
+            })
+            .map(|x| x.as_ref())
+    }
+
+    /// A regex which is executed with Oniguruma and was already preprocessed by [SerializeRegex::new],
+    /// e. g. a regex of a binary created before the `regex` crate was used.
+    pub(crate) fn onig(regex_str: String, case_sensitive: bool) -> Self {
+        SerializeRegex {
+            regex_str,
+            case_sensitive,
+            engine: RegexEngine::Onig,
+            regex: OnceCell::new(),
+        }
+    }
+
+    /// Compiles the regex if it has not been compiled yet. Returns an error message if it can not be compiled.
+    pub fn check(&self) -> Result<(), String> {
+        self.try_regex().map(|_| ())
+    }
+
+    /// The pattern of this regex.
+    pub fn as_str(&self) -> &str {
+        &self.regex_str
+    }
+
+    /// Compiles the regex now, if it has not been compiled yet.
+    pub fn warm_up(&self) {
+        self.regex();
+    }
+
+    /// Whether the regex has been compiled.
+    pub fn is_compiled(&self) -> bool {
+        self.regex.get().is_some()
+    }
+
+    /// Identifies the compiled regex. Regexes with the same pattern and case sensitivity share one compiled
+    /// regex and have the same ID.
+    pub fn id(&self) -> usize {
+        self.regex() as *const Regex as usize
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        match self.regex() {
+            Regex::Regex(regex) => regex.is_match(text),
+            Regex::Onig(regex) => regex.is_match(text),
+        }
+    }
+
+    pub fn captures<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+        match self.regex() {
+            Regex::Regex(regex) => regex.captures(text).map(Captures::Regex),
+            Regex::Onig(regex) => regex.captures(text).map(Captures::Onig),
+        }
+    }
+
+    pub fn captures_iter<'r, 't>(&'r self, text: &'t str) -> impl Iterator<Item = Captures<'t>> + 'r
+    where
+        't: 'r,
+    {
+        match self.regex() {
+            Regex::Regex(regex) => either::Left(regex.captures_iter(text).map(Captures::Regex)),
+            Regex::Onig(regex) => either::Right(regex.captures_iter(text).map(Captures::Onig)),
+        }
+    }
+
+    /// The number of capture groups, not including the group of the whole match.
+    #[cfg(feature = "compile")]
+    pub fn captures_len(&self) -> usize {
+        match self.regex() {
+            Regex::Regex(regex) => regex.captures_len() - 1,
+            Regex::Onig(regex) => regex.captures_len(),
+        }
+    }
+
+    /// Replaces all matches in the text with the output of `replacer`.
+    pub fn replace_all<F: Fn(&Captures) -> String>(&self, text: &str, replacer: F) -> String {
+        let mut output = String::new();
+        let mut last = 0;
+
+        for captures in self.captures_iter(text) {
+            let (start, end) = captures.pos(0).expect("whole match always exists");
+            output.push_str(&text[last..start]);
+            output.push_str(&replacer(&captures));
+            last = end;
+        }
+        output.push_str(&text[last..]);
+
+        output
     }
 
     pub fn new(
@@ -95,19 +403,154 @@ impl SerializeRegex {
             fixed
         };
 
+        let regex = if is_portable(&fixed, case_sensitive) {
+            SerializeRegex::compile_regex(&fixed, case_sensitive).ok()
+        } else {
+            None
+        };
+
+        let (regex, engine) = match regex {
+            Some(regex) => (regex, RegexEngine::Regex),
+            None => (
+                SerializeRegex::compile_onig(&fixed, case_sensitive)
+                    .map_err(|x| Error::Unexpected(format!("{}", x)))?,
+                RegexEngine::Onig,
+            ),
+        };
+
         Ok(SerializeRegex {
-            regex: SerializeRegex::compile(&fixed, case_sensitive)
-                .map_err(|x| Error::Unexpected(format!("{}", x)))?,
+            regex: OnceCell::from(Arc::new(regex)),
             regex_str: fixed,
             case_sensitive,
+            engine,
         })
     }
 }
 
-impl Deref for SerializeRegex {
-    type Target = Regex;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_onig() {
+        let simple = SerializeRegex::new("a(b+)", false, true).unwrap();
+        let lookbehind = SerializeRegex::new("(?<=a)(b+)", false, true).unwrap();
+
+        assert_eq!(simple.engine, RegexEngine::Regex);
+        assert_eq!(lookbehind.engine, RegexEngine::Onig);
+
+        for regex in &[simple, lookbehind] {
+            assert_eq!(
+                regex.replace_all("abb cab", |caps| caps.at(1).unwrap().to_uppercase()),
+                if regex.engine == RegexEngine::Regex {
+                    "BB cB"
+                } else {
+                    "aBB caB"
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn fast_path_means_the_same_in_both_engines() {
+        // patterns as they occur in the LanguageTool rules
+        let patterns = [
+            ("(?i)a(lot)", false),
+            ("could|would|should", true),
+            ("[A-Z].*", true),
+            ("\\p{Lu}\\p{Ll}+", true),
+            ("(?:is|was|were)n't", false),
+            ("\\d+[.,]\\d+", true),
+            ("[^a-zäöü]+", true),
+            ("(ein|eine)[rnms]?", false),
+            ("\\bthe\\b", false),
+            ("[.?!]", true),
+            ("[a-zäöüß]{2,}", true),
+            ("Ä(rger|pfel)", false),
+            ("\\w+-\\w+", true),
+            ("[\\p{L}\\d]+", true),
+            ("^[A-Z]{2,5}$", true),
+            ("\\(\\w+\\)", true),
+        ];
+        let texts = [
+            "A lot of things were done.",
+            "Die Äpfel sind groß, das ÄRGER ist größer.",
+            "He couldn't, but she would.",
+            "It costs 3,50 or 4.99 (approx.) EUR",
+            "NATO and the UNO; ÖBB 12 ٣٤",
+            "self-evident, an e-mail\nThe end",
+            "Straße STRASSE strasse Œuvre œuvre",
+        ];
+
+        for (pattern, must_fully_match) in &patterns {
+            for case_sensitive in &[true, false] {
+                let regex =
+                    SerializeRegex::new(pattern, *must_fully_match, *case_sensitive).unwrap();
+                // case insensitive general categories and multi char case folding are left to Oniguruma
+                if !case_sensitive && (pattern.contains("\\p") || pattern.contains('ß')) {
+                    assert_eq!(regex.engine, RegexEngine::Onig, "{}", pattern);
+                    continue;
+                }
+                assert_eq!(regex.engine, RegexEngine::Regex, "{}", pattern);
+
+                let onig =
+                    SerializeRegex::compile_onig(&regex.regex_str, regex.case_sensitive).unwrap();
+                let onig = SerializeRegex {
+                    regex_str: regex.regex_str.clone(),
+                    case_sensitive: regex.case_sensitive,
+                    engine: RegexEngine::Onig,
+                    regex: OnceCell::from(Arc::new(onig)),
+                };
+
+                for text in texts.iter().copied().chain(text_tokens(&texts)) {
+                    let spans = |regex: &SerializeRegex| {
+                        regex
+                            .captures_iter(text)
+                            .map(|x| x.iter_pos().collect::<Vec<_>>())
+                            .collect::<Vec<_>>()
+                    };
+                    assert_eq!(spans(&regex), spans(&onig), "{} on {:?}", pattern, text);
+                }
+            }
+        }
+
+        for pattern in &[
+            "(?<=a)b",
+            "a{,3}",
+            "[a-z&&[^e]]",
+            "[]a]",
+            "(?i)straße",
+            "\\p{Alpha}",
+            "(?s)a.b",
+            "\\Qa.b\\E",
+        ] {
+            let regex = SerializeRegex::new(pattern, false, true).unwrap();
+            assert_eq!(regex.engine, RegexEngine::Onig, "{}", pattern);
+        }
+    }
+
+    fn text_tokens<'a>(texts: &'a [&str]) -> impl Iterator<Item = &'a str> {
+        texts
+            .iter()
+            .flat_map(|x| x.split(|c: char| c.is_whitespace() || c == ','))
+    }
+
+    #[test]
+    fn removes_dropped_regexes() {
+        let pattern = format!("removed-{}", std::process::id());
+        let key = (pattern.clone(), true, RegexEngine::Regex);
+        let contains = |key: &RegexKey| REGEX_CACHE.lock().unwrap().regexes.contains_key(key);
+
+        let regex = SerializeRegex::cached(&pattern, true, RegexEngine::Regex).unwrap();
+        assert!(contains(&key));
+        drop(regex);
 
-    fn deref(&self) -> &Self::Target {
-        &self.regex
+        // other tests insert concurrently, so insert until the cache is pruned
+        let removed = (0..4 * MIN_PRUNE_LEN).any(|i| {
+            let other = format!("{}-{}", pattern, i);
+            SerializeRegex::cached(&other, true, RegexEngine::Regex).unwrap();
+            !contains(&key)
+        });
+        assert!(removed);
     }
 }